@@ -0,0 +1,41 @@
+//! 在 1 MB 负载中查找字节序列：逐位置比较与 `memchr::memmem` 的对比
+//!
+//! 模式位于负载末尾，两种方式都需要扫描整个负载。
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use someip_parser::MessageType;
+use someip_parser::filter::{Filter, PayloadPatternFilter};
+use someip_parser::testing::message::someip_message;
+use std::hint::black_box;
+
+const PAYLOAD_LEN: usize = 1024 * 1024;
+
+/// 逐个起始位置比较，作为对照
+fn naive_find(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn payload_pattern(c: &mut Criterion) {
+    let filter = PayloadPatternFilter::new("DEADBEEF").unwrap();
+    let mut msg = someip_message(0x1234, 0x8001, 1, MessageType::Notification);
+    // 与模式首字节相同的字节频繁出现，避免只比较首字节就能跳过
+    msg.payload = (0..PAYLOAD_LEN)
+        .map(|index| if index % 3 == 0 { 0xDE } else { index as u8 })
+        .collect();
+    msg.payload[PAYLOAD_LEN - 4..].copy_from_slice(&filter.pattern);
+    assert!(naive_find(&msg.payload, &filter.pattern));
+    assert!(filter.matches(&msg));
+
+    let mut group = c.benchmark_group("payload_pattern");
+    group.throughput(Throughput::Bytes(PAYLOAD_LEN as u64));
+    group.bench_function("naive", |b| {
+        b.iter(|| naive_find(black_box(&msg.payload), black_box(&filter.pattern)))
+    });
+    group.bench_function("memmem", |b| b.iter(|| filter.matches(black_box(&msg))));
+    group.finish();
+}
+
+criterion_group!(benches, payload_pattern);
+criterion_main!(benches);
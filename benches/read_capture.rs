@@ -0,0 +1,85 @@
+//! 读取经典 pcap 文件并逐层解析到传输层的吞吐量
+//!
+//! `zero_copy` 为当前读取路径；`copy_per_packet` 在每个数据包上额外复制一次，
+//! 对应改造前每个数据包都复制到新分配缓冲区的行为。
+
+use bytes::Bytes;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use someip_parser::MessageType;
+use someip_parser::parser::link_layer::{LinkLayer, LinkType, parse_link_layer};
+use someip_parser::parser::network_layer::{NetworkLayer, parse_network_layer};
+use someip_parser::parser::pcap_reader::{InputFormat, PCAPReader, RawPacket};
+use someip_parser::parser::transport_layer::{TransportLayer, parse_transport_layer};
+use someip_parser::testing::builder::{PacketBuilder, PcapFileBuilder};
+use std::hint::black_box;
+use std::time::{Duration, UNIX_EPOCH};
+
+const PACKETS: usize = 20_000;
+
+fn fixture() -> tempfile::NamedTempFile {
+    let mut capture = PcapFileBuilder::new(LinkType::Ethernet);
+    for index in 0..PACKETS {
+        let packet = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, 0x8001, 0, index as u16, MessageType::Notification)
+            .payload(vec![index as u8; 64 + index % 512]);
+        capture = capture.packet(UNIX_EPOCH + Duration::from_millis(index as u64), &packet);
+    }
+    let file = tempfile::NamedTempFile::new().unwrap();
+    capture.write_to(file.path()).unwrap();
+    file
+}
+
+/// 解析到传输层负载，返回负载长度
+fn parse(raw_packet: &RawPacket) -> usize {
+    let (rest, LinkLayer::Ethernet(frame)) =
+        parse_link_layer(&raw_packet.data, raw_packet.link_type).unwrap()
+    else {
+        unreachable!()
+    };
+    let network_payload = raw_packet.data.slice_ref(rest);
+    let (payload, NetworkLayer::IPv4(ip)) =
+        parse_network_layer(&network_payload, frame.ethertype).unwrap()
+    else {
+        unreachable!()
+    };
+    let payload = network_payload.slice_ref(payload);
+    match parse_transport_layer(&payload, ip.protocol).unwrap().1 {
+        TransportLayer::UDP(udp) => udp.payload.len(),
+        TransportLayer::TCP(tcp) => tcp.payload.len(),
+    }
+}
+
+fn read_capture(c: &mut Criterion) {
+    let file = fixture();
+    let path = file.path().to_str().unwrap();
+    let bytes = std::fs::metadata(path).unwrap().len();
+
+    let mut group = c.benchmark_group("read_capture");
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function("zero_copy", |b| {
+        b.iter(|| {
+            let mut reader = PCAPReader::new(path, InputFormat::Auto).unwrap();
+            while let Some(raw_packet) = reader.next_packet().unwrap() {
+                black_box(parse(&raw_packet));
+            }
+        })
+    });
+    group.bench_function("copy_per_packet", |b| {
+        b.iter(|| {
+            let mut reader = PCAPReader::new(path, InputFormat::Auto).unwrap();
+            while let Some(raw_packet) = reader.next_packet().unwrap() {
+                let copied = RawPacket {
+                    data: Bytes::copy_from_slice(&raw_packet.data),
+                    ..raw_packet
+                };
+                black_box(parse(&copied));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, read_capture);
+criterion_main!(benches);
@@ -0,0 +1,164 @@
+// src/analysis/event_timing.rs
+use crate::parser::someip::header::MessageType;
+use crate::parser::someip::matrix::Matrix;
+use crate::parser::someip::session::SomeIPMessage;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// 单个事件 (服务ID, 事件ID) 的通知周期统计
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventTiming {
+    pub service_id: u16,
+    pub event_id: u16,
+    pub count: usize,
+    pub mean_period_ms: f64,
+    pub min_period_ms: f64,
+    pub max_period_ms: f64,
+    /// 通知间隔的标准差
+    pub jitter_ms: f64,
+}
+
+/// 单个事件的累计统计，只保存最近一次时间戳与周期的运行统计量
+#[derive(Debug, Clone)]
+struct PeriodStats {
+    count: usize,
+    last: SystemTime,
+    mean_ms: f64,
+    m2: f64, // 周期与均值之差的平方和（Welford 算法）
+    min_ms: f64,
+    max_ms: f64,
+}
+
+impl PeriodStats {
+    fn new(timestamp: SystemTime) -> Self {
+        Self {
+            count: 1,
+            last: timestamp,
+            mean_ms: 0.0,
+            m2: 0.0,
+            min_ms: f64::INFINITY,
+            max_ms: 0.0,
+        }
+    }
+
+    fn record(&mut self, timestamp: SystemTime) {
+        let period = timestamp
+            .duration_since(self.last)
+            .map(|d| d.as_secs_f64() * 1000.0)
+            .unwrap_or(0.0);
+        self.last = self.last.max(timestamp);
+        self.count += 1;
+
+        let periods = (self.count - 1) as f64;
+        let delta = period - self.mean_ms;
+        self.mean_ms += delta / periods;
+        self.m2 += delta * (period - self.mean_ms);
+        self.min_ms = self.min_ms.min(period);
+        self.max_ms = self.max_ms.max(period);
+    }
+}
+
+/// 按到达顺序累计通知周期，内存占用只与事件数有关，可用于跟随模式
+pub struct EventTimingTracker {
+    events: BTreeMap<(u16, u16), PeriodStats>, // (服务ID, 事件ID) -> 周期统计
+}
+
+impl Default for EventTimingTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventTimingTracker {
+    pub fn new() -> Self {
+        Self {
+            events: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, message: &SomeIPMessage) {
+        if message.header.message_type != MessageType::Notification {
+            return;
+        }
+
+        self.events
+            .entry((message.header.service_id, message.header.method_id))
+            .and_modify(|stats| stats.record(message.timestamp))
+            .or_insert_with(|| PeriodStats::new(message.timestamp));
+    }
+
+    pub fn report(&self) -> Vec<EventTiming> {
+        self.events
+            .iter()
+            // 至少需要两个样本才能计算周期
+            .filter(|(_, stats)| stats.count >= 2)
+            .map(|(&(service_id, event_id), stats)| EventTiming {
+                service_id,
+                event_id,
+                count: stats.count,
+                mean_period_ms: stats.mean_ms,
+                min_period_ms: stats.min_ms,
+                max_period_ms: stats.max_ms,
+                jitter_ms: (stats.m2 / (stats.count - 1) as f64).sqrt(),
+            })
+            .collect()
+    }
+}
+
+pub fn format_event_timing(report: &[EventTiming], matrix: &Matrix) -> String {
+    let mut output = String::from("Event timing report\n");
+
+    for timing in report {
+        output.push_str(&format!(
+            "{service}:{event} | count={count} | mean={mean:.3}ms | min={min:.3}ms | max={max:.3}ms | jitter={jitter:.3}ms\n",
+            service = matrix
+                .get_service_name(timing.service_id)
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("0x{:04X}", timing.service_id)),
+            event = matrix
+                .get_method_name(timing.service_id, timing.event_id)
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("0x{:04X}", timing.event_id)),
+            count = timing.count,
+            mean = timing.mean_period_ms,
+            min = timing.min_period_ms,
+            max = timing.max_period_ms,
+            jitter = timing.jitter_ms,
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::message::someip_message;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn notification(event_id: u16, millis: u64) -> SomeIPMessage {
+        let mut message = someip_message(0x1234, event_id, 1, MessageType::Notification);
+        message.timestamp = UNIX_EPOCH + Duration::from_millis(millis);
+        message
+    }
+
+    #[test]
+    fn reports_period_and_jitter() {
+        let mut tracker = EventTimingTracker::new();
+        for millis in [0, 10, 30, 40] {
+            tracker.record(&notification(0x8001, millis));
+        }
+        tracker.record(&notification(0x8002, 0)); // 只有一个样本，不输出
+
+        let report = tracker.report();
+        assert_eq!(report.len(), 1);
+        let timing = &report[0];
+        assert_eq!(timing.count, 4);
+        assert!((timing.mean_period_ms - 40.0 / 3.0).abs() < 1e-9);
+        assert_eq!(timing.min_period_ms, 10.0);
+        assert_eq!(timing.max_period_ms, 20.0);
+        // 周期 10、20、10 的总体标准差
+        assert!((timing.jitter_ms - (200.0f64 / 9.0).sqrt()).abs() < 1e-9);
+    }
+}
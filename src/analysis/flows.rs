@@ -0,0 +1,111 @@
+// src/analysis/flows.rs
+use crate::error::Result;
+use crate::utils::timestamp::format_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// 解析流水线停止的协议层，低于 `Someip` 时不解析 SomeIP，只输出该层的流列表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseDepth {
+    /// 链路层：按 MAC 地址与以太网类型统计
+    Link,
+    /// 网络层：按 IP 地址与 IP 协议号统计
+    Network,
+    /// 传输层：按 UDP/TCP 四元组统计
+    Transport,
+    #[default]
+    Someip,
+}
+
+/// 单个流的统计，`src`/`dst` 的含义取决于解析深度（MAC、IP 或 IP:端口）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlowRecord {
+    pub protocol: String,
+    pub src: String,
+    pub dst: String,
+    pub packets: usize,
+    pub bytes: usize,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// 按 (协议, 源, 目的) 汇总数据包
+pub struct FlowTracker {
+    flows: BTreeMap<(String, String, String), FlowRecord>,
+}
+
+impl Default for FlowTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlowTracker {
+    pub fn new() -> Self {
+        Self {
+            flows: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        timestamp: SystemTime,
+        protocol: String,
+        src: String,
+        dst: String,
+        bytes: usize,
+    ) {
+        let seen = format_timestamp(&timestamp);
+        let flow = self
+            .flows
+            .entry((protocol.clone(), src.clone(), dst.clone()))
+            .or_insert_with(|| FlowRecord {
+                protocol,
+                src,
+                dst,
+                packets: 0,
+                bytes: 0,
+                first_seen: seen.clone(),
+                last_seen: seen.clone(),
+            });
+        flow.packets += 1;
+        flow.bytes += bytes;
+        // 格式化后的时间戳按字典序即按时间排序
+        if seen < flow.first_seen {
+            flow.first_seen = seen;
+        } else if seen > flow.last_seen {
+            flow.last_seen = seen;
+        }
+    }
+
+    pub fn flows(&self) -> impl Iterator<Item = &FlowRecord> {
+        self.flows.values()
+    }
+}
+
+/// 按输出格式输出流列表：json/yaml 输出结构化数据，其余格式每行一个流
+pub fn format_flows(tracker: &FlowTracker, output_format: &str) -> Result<String> {
+    let flows: Vec<&FlowRecord> = tracker.flows().collect();
+    match output_format {
+        "json" | "wireshark-json" => Ok(format!("{}\n", serde_json::to_string_pretty(&flows)?)),
+        "yaml" => Ok(serde_yaml::to_string(&flows)?),
+        _ => {
+            let mut output = String::new();
+            for flow in flows {
+                output.push_str(&format!(
+                    "{} {} -> {} | {} packets, {} bytes | {} - {}\n",
+                    flow.protocol,
+                    flow.src,
+                    flow.dst,
+                    flow.packets,
+                    flow.bytes,
+                    flow.first_seen,
+                    flow.last_seen
+                ));
+            }
+            Ok(output)
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! 分析模块，基于解析后的 SomeIP 消息生成统计与校验报告
+
+pub mod event_timing;
+pub mod flows;
+pub mod sd_consistency;
+pub mod summary;
@@ -0,0 +1,232 @@
+// src/analysis/sd_consistency.rs
+use crate::parser::someip::matrix::Matrix;
+use crate::parser::someip::sd_parser::{SDEntry, SDPacket};
+use crate::parser::someip::session::SomeIPMessage;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+const SD_SERVICE_ID: u16 = 0xFFFF;
+const ANY_INSTANCE: u16 = 0xFFFF;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SdConsistencyReport {
+    /// 已提供但从未被调用或订阅的服务 (服务ID, 实例ID)
+    pub unused_offers: Vec<(u16, u16)>,
+    /// 订阅了从未提供的服务 (服务ID, 实例ID, 事件组ID)
+    pub unoffered_subscriptions: Vec<(u16, u16, u16)>,
+    /// 从未收到确认的订阅 (服务ID, 实例ID, 事件组ID)
+    pub unacknowledged_subscriptions: Vec<(u16, u16, u16)>,
+}
+
+pub struct SdConsistencyChecker {
+    offers: BTreeSet<(u16, u16)>,
+    subscriptions: BTreeSet<(u16, u16, u16)>,
+    acks: BTreeSet<(u16, u16, u16)>,
+    traffic: BTreeSet<u16>,
+}
+
+impl Default for SdConsistencyChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SdConsistencyChecker {
+    pub fn new() -> Self {
+        Self {
+            offers: BTreeSet::new(),
+            subscriptions: BTreeSet::new(),
+            acks: BTreeSet::new(),
+            traffic: BTreeSet::new(),
+        }
+    }
+
+    pub fn record_sd_packet(&mut self, sd_packet: &SDPacket) {
+        for entry in &sd_packet.entries {
+            match entry {
+                SDEntry::OfferService(offer) if offer.ttl > 0 => {
+                    self.offers.insert((offer.service_id, offer.instance_id));
+                }
+                SDEntry::SubscribeEventgroup(sub) if sub.ttl > 0 => {
+                    self.subscriptions
+                        .insert((sub.service_id, sub.instance_id, sub.eventgroup_id));
+                }
+                SDEntry::SubscribeEventgroupAck(ack) if ack.ttl > 0 => {
+                    self.acks
+                        .insert((ack.service_id, ack.instance_id, ack.eventgroup_id));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn record_message(&mut self, message: &SomeIPMessage) {
+        if message.header.service_id != SD_SERVICE_ID {
+            self.traffic.insert(message.header.service_id);
+        }
+    }
+
+    pub fn report(&self) -> SdConsistencyReport {
+        let unused_offers = self
+            .offers
+            .iter()
+            .filter(|(service_id, instance_id)| {
+                !self.traffic.contains(service_id)
+                    && !self.subscriptions.iter().any(|&(s, i, _)| {
+                        s == *service_id && (i == *instance_id || i == ANY_INSTANCE)
+                    })
+            })
+            .cloned()
+            .collect();
+
+        let unoffered_subscriptions = self
+            .subscriptions
+            .iter()
+            .filter(|&&(service_id, instance_id, _)| {
+                !self.offers.iter().any(|&(s, i)| {
+                    s == service_id && (instance_id == ANY_INSTANCE || i == instance_id)
+                })
+            })
+            .cloned()
+            .collect();
+
+        let unacknowledged_subscriptions = self
+            .subscriptions
+            .iter()
+            .filter(|key| !self.acks.contains(key))
+            .cloned()
+            .collect();
+
+        SdConsistencyReport {
+            unused_offers,
+            unoffered_subscriptions,
+            unacknowledged_subscriptions,
+        }
+    }
+}
+
+pub fn format_sd_consistency(report: &SdConsistencyReport, matrix: &Matrix) -> String {
+    let service_name = |service_id: u16| {
+        matrix
+            .get_service_name(service_id)
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("0x{:04X}", service_id))
+    };
+
+    let mut output = String::from("SD consistency report\n");
+
+    for &(service_id, instance_id) in &report.unused_offers {
+        output.push_str(&format!(
+            "Unused offer: {}/0x{:04X} was offered but never called or subscribed\n",
+            service_name(service_id),
+            instance_id
+        ));
+    }
+
+    for &(service_id, instance_id, eventgroup_id) in &report.unoffered_subscriptions {
+        output.push_str(&format!(
+            "Unoffered subscription: {}/0x{:04X} eventgroup 0x{:04X} was subscribed but never offered\n",
+            service_name(service_id),
+            instance_id,
+            eventgroup_id
+        ));
+    }
+
+    for &(service_id, instance_id, eventgroup_id) in &report.unacknowledged_subscriptions {
+        output.push_str(&format!(
+            "Unacknowledged subscription: {}/0x{:04X} eventgroup 0x{:04X} never received an ack\n",
+            service_name(service_id),
+            instance_id,
+            eventgroup_id
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::someip::header::MessageType;
+    use crate::testing::message::someip_message;
+    use crate::testing::sd::{offer, sd_packet, subscribe, subscribe_ack, udp_endpoint};
+
+    fn checker_with(entries: Vec<SDEntry>) -> SdConsistencyChecker {
+        let mut checker = SdConsistencyChecker::new();
+        checker.record_sd_packet(&sd_packet(
+            1,
+            false,
+            true,
+            entries,
+            vec![udp_endpoint([10, 0, 0, 1], 30501)],
+        ));
+        checker
+    }
+
+    #[test]
+    fn offer_without_traffic_is_unused() {
+        let mut checker = checker_with(vec![offer(0x1234, 1, 3, 0), offer(0x5678, 1, 3, 0)]);
+        checker.record_message(&someip_message(0x5678, 0x0001, 1, MessageType::Request));
+        assert_eq!(checker.report().unused_offers, [(0x1234, 1)]);
+    }
+
+    #[test]
+    fn subscription_counts_as_use_of_offer() {
+        // 订阅任意实例 (0xFFFF) 同样视为使用了该服务
+        let checker = checker_with(vec![
+            offer(0x1234, 1, 3, 0),
+            subscribe(0x1234, ANY_INSTANCE, 0x0001, 3, 0),
+            subscribe_ack(0x1234, ANY_INSTANCE, 0x0001, 3),
+        ]);
+        assert_eq!(checker.report(), SdConsistencyReport::default());
+    }
+
+    #[test]
+    fn subscription_to_unoffered_instance_is_reported() {
+        let checker = checker_with(vec![
+            offer(0x1234, 1, 3, 0),
+            subscribe(0x1234, 2, 0x0001, 3, 0),
+        ]);
+        let report = checker.report();
+        assert_eq!(report.unoffered_subscriptions, [(0x1234, 2, 0x0001)]);
+        assert_eq!(report.unacknowledged_subscriptions, [(0x1234, 2, 0x0001)]);
+        // 实例 1 未被订阅也未被调用
+        assert_eq!(report.unused_offers, [(0x1234, 1)]);
+    }
+
+    #[test]
+    fn stop_entries_and_nacks_are_ignored() {
+        let checker = checker_with(vec![
+            offer(0x1234, 1, 0, 0),
+            subscribe(0x5678, 1, 0x0001, 3, 0),
+            subscribe_ack(0x5678, 1, 0x0001, 0),
+        ]);
+        let report = checker.report();
+        assert!(report.unused_offers.is_empty());
+        assert_eq!(report.unoffered_subscriptions, [(0x5678, 1, 0x0001)]);
+        assert_eq!(report.unacknowledged_subscriptions, [(0x5678, 1, 0x0001)]);
+    }
+
+    #[test]
+    fn sd_messages_do_not_count_as_traffic() {
+        let mut checker = checker_with(vec![offer(0xFFFF, 1, 3, 0)]);
+        checker.record_message(&someip_message(
+            0xFFFF,
+            0x8100,
+            1,
+            MessageType::Notification,
+        ));
+        assert_eq!(checker.report().unused_offers, [(0xFFFF, 1)]);
+    }
+
+    #[test]
+    fn report_falls_back_to_hex_service_ids() {
+        let checker = checker_with(vec![offer(0x1234, 1, 3, 0)]);
+        let output = format_sd_consistency(&checker.report(), &Matrix::new());
+        assert_eq!(
+            output,
+            "SD consistency report\n\
+             Unused offer: 0x1234/0x0001 was offered but never called or subscribed\n"
+        );
+    }
+}
@@ -0,0 +1,518 @@
+// src/analysis/summary.rs
+use crate::error::Result;
+use crate::parser::flow_control::{TcpClosedStats, TcpConnectionStats};
+use crate::parser::pcap_reader::{CaptureInfo, ChannelStats};
+use crate::parser::someip::header::{MessageType, ReturnCode, SomeIPHeader};
+use crate::parser::someip::matrix::Matrix;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 文本摘要中逐条列出的 TCP 连接方向数上限，其余只给出数量
+const MAX_CONNECTION_LINES: usize = 20;
+
+/// 单个抓包文件的头部信息
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CaptureFileSummary {
+    pub path: String,
+    pub link_type: Option<u16>,
+    pub snap_len: Option<u32>,
+}
+
+/// 单个服务的消息计数与平均响应延迟
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ServiceStats {
+    pub message_count: u64,
+    /// 错误消息与返回码不为 E_OK 的消息
+    pub error_count: u64,
+    pub notification_count: u64,
+    pub request_count: u64,
+    /// 已配对响应的平均往返时间，没有配对响应时为 0
+    pub avg_latency_ms: f64,
+    #[serde(skip)]
+    latency_samples: u64,
+}
+
+impl ServiceStats {
+    /// 错误消息占全部消息的比例
+    pub fn error_rate(&self) -> f64 {
+        if self.message_count == 0 {
+            0.0
+        } else {
+            self.error_count as f64 / self.message_count as f64
+        }
+    }
+}
+
+/// 整次运行的抓包与解析计数
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Stats {
+    pub files: Vec<CaptureFileSummary>,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub first_timestamp: Option<SystemTime>,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub last_timestamp: Option<SystemTime>,
+    pub total_packets: usize,
+    /// 解析失败的数据包
+    pub parse_errors: usize,
+    /// 抓包时间不在 `--start-time` / `--end-time` 范围内而跳过的数据包
+    pub skipped_time: usize,
+    /// 被 VLAN 过滤丢弃的数据包
+    pub skipped_vlan: usize,
+    /// 端口不属于已知 SomeIP 端口而跳过的数据包
+    pub skipped_port: usize,
+    /// 服务/方法 ID 不满足 `--service-id` / `--method-id` 而跳过的消息
+    pub skipped_id: usize,
+    /// 源/目的地址或端口不满足 `--src-ip` 等条件而跳过的数据包
+    pub skipped_endpoint: usize,
+    /// 位于 SomeIP 端口但头部不像 SomeIP（协议版本、消息类型或长度不符）而跳过的数据包
+    pub skipped_non_someip: usize,
+    /// 非首个 IPv4 分片（不含 UDP/TCP 头部，本工具不做 IP 重组）而跳过的数据包
+    pub skipped_fragments: usize,
+    /// 按消息类型统计的 SomeIP 消息数
+    pub messages_by_type: BTreeMap<String, usize>,
+    /// 按 (服务ID, 方法ID) 统计的 SomeIP 消息数
+    #[serde(skip)]
+    pub messages_by_method: BTreeMap<(u16, u16), usize>,
+    /// 按服务 ID 统计的通过输出过滤的消息
+    pub services: BTreeMap<u16, ServiceStats>,
+    pub sd_packets: usize,
+    pub tp_reassemblies: usize,
+    /// 超时未完成的 TP 重组
+    pub tp_failures: usize,
+    pub tcp_streams: usize,
+    /// 同一四元组上以新的初始序列号重新建立的 TCP 连接
+    pub tcp_restarts: usize,
+    /// 抓包中重复出现而被丢弃的 TCP 重传分段
+    pub tcp_retransmissions: u64,
+    /// 乱序缓存超过上限时放弃等待的 TCP 缺口
+    pub tcp_gaps_skipped: u64,
+    /// 抓包结束时仍在跟踪的各 TCP 连接方向的传输统计
+    pub tcp_connections: Vec<TcpConnectionStats>,
+    /// 抓包过程中已关闭或被移除的 TCP 连接方向的汇总
+    pub tcp_closed: TcpClosedStats,
+    /// TCP 字节流失去 SomeIP 同步后重新扫描头部的次数与跳过的字节数
+    pub tcp_resyncs: u64,
+    pub tcp_resync_skipped_bytes: u64,
+    /// 超时未收到响应的请求
+    pub timed_out_sessions: usize,
+    /// 未收到响应的请求（超时的请求与抓包结束时仍在等待的请求）
+    pub unmatched_requests: usize,
+    /// 开启 `--deduplicate` 时丢弃的重复消息
+    pub duplicate_messages: usize,
+    /// 读取器与处理循环之间的通道背压
+    pub channel_capacity: usize,
+    pub channel_high_water_mark: usize,
+    pub channel_blocked_ms: f64,
+    pub dropped_packets: usize,
+    #[serde(skip)]
+    tcp_stream_keys: HashSet<(IpAddr, u16, IpAddr, u16)>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, path: &str, info: Option<CaptureInfo>) {
+        self.files.push(CaptureFileSummary {
+            path: path.to_string(),
+            link_type: info.map(|info| info.link_type),
+            snap_len: info.map(|info| info.snap_len),
+        });
+    }
+
+    pub fn record_packet(&mut self, timestamp: SystemTime) {
+        self.total_packets += 1;
+        self.first_timestamp = Some(
+            self.first_timestamp
+                .map_or(timestamp, |first| first.min(timestamp)),
+        );
+        self.last_timestamp = self.last_timestamp.max(Some(timestamp));
+    }
+
+    pub fn record_message(&mut self, header: &SomeIPHeader) {
+        *self
+            .messages_by_type
+            .entry(header.message_type.to_string())
+            .or_default() += 1;
+        *self
+            .messages_by_method
+            .entry((header.service_id, header.method_id))
+            .or_default() += 1;
+    }
+
+    /// 记录一条通过输出过滤的消息到所属服务的统计
+    pub fn record_service(&mut self, header: &SomeIPHeader) {
+        let stats = self.services.entry(header.service_id).or_default();
+        stats.message_count += 1;
+        match header.message_type {
+            MessageType::Request | MessageType::RequestNoReturn => stats.request_count += 1,
+            MessageType::Notification => stats.notification_count += 1,
+            _ => {}
+        }
+        if matches!(
+            header.message_type,
+            MessageType::Error | MessageType::ErrorACK
+        ) || header.return_code != ReturnCode::Ok
+        {
+            stats.error_count += 1;
+        }
+    }
+
+    /// 记录一个已配对响应的往返时间
+    pub fn record_latency(&mut self, service_id: u16, latency_ms: f64) {
+        let stats = self.services.entry(service_id).or_default();
+        stats.latency_samples += 1;
+        stats.avg_latency_ms += (latency_ms - stats.avg_latency_ms) / stats.latency_samples as f64;
+    }
+
+    /// 按消息数降序排列的服务统计，消息数相同时按服务 ID 升序
+    pub fn services_by_count(&self) -> Vec<(u16, &ServiceStats)> {
+        let mut services: Vec<_> = self.services.iter().map(|(&id, s)| (id, s)).collect();
+        services.sort_by_key(|(id, stats)| (std::cmp::Reverse(stats.message_count), *id));
+        services
+    }
+
+    pub fn record_channel(&mut self, capacity: usize, channel: &ChannelStats) {
+        self.channel_capacity = capacity;
+        self.channel_high_water_mark = channel.high_water_mark;
+        self.channel_blocked_ms = channel.blocked.as_secs_f64() * 1000.0;
+        self.dropped_packets = channel.dropped;
+    }
+
+    /// 按无方向的四元组统计 TCP 流
+    pub fn record_tcp_stream(&mut self, a: (IpAddr, u16), b: (IpAddr, u16)) {
+        let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+        if self.tcp_stream_keys.insert((lo.0, lo.1, hi.0, hi.1)) {
+            self.tcp_streams += 1;
+        }
+    }
+}
+
+fn serialize_optional_timestamp<S>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match time.and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+        Some(duration) => serializer.serialize_f64(duration.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn service_name(matrix: &Matrix, service_id: u16) -> String {
+    matrix
+        .get_service_name(service_id)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("0x{:04X}", service_id))
+}
+
+pub fn format_summary(stats: &Stats, matrix: &Matrix) -> String {
+    let timestamp = |time: Option<SystemTime>| {
+        time.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| format!("{:.6}", d.as_secs_f64()))
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    let mut output = String::from("Capture summary\n");
+    for file in &stats.files {
+        output.push_str(&format!(
+            "File: {} linktype {} snaplen {}\n",
+            file.path,
+            file.link_type
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            file.snap_len
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+        ));
+    }
+    output.push_str(&format!(
+        "Packets: {} (first {}, last {}), {} failed to parse\n",
+        stats.total_packets,
+        timestamp(stats.first_timestamp),
+        timestamp(stats.last_timestamp),
+        stats.parse_errors
+    ));
+    output.push_str(&format!(
+        "Skipped: {} by time range, {} by VLAN filter, {} by port, {} by address/port filter, {} not SOME/IP, {} IP fragments, {} by service/method ID\n",
+        stats.skipped_time,
+        stats.skipped_vlan,
+        stats.skipped_port,
+        stats.skipped_endpoint,
+        stats.skipped_non_someip,
+        stats.skipped_fragments,
+        stats.skipped_id
+    ));
+    let total: usize = stats.messages_by_type.values().sum();
+    output.push_str(&format!("SOME/IP messages: {}\n", total));
+    for (message_type, count) in &stats.messages_by_type {
+        output.push_str(&format!("  {}: {}\n", message_type, count));
+    }
+    for method in stats.method_counts(matrix) {
+        output.push_str(&format!(
+            "  {}:{} (0x{:04X}:0x{:04X}): {}\n",
+            method.service, method.method, method.service_id, method.method_id, method.count
+        ));
+    }
+    if !stats.services.is_empty() {
+        output.push_str(&format!(
+            "  {:<32} {:>10} {:>10} {:>10} {:>10} {:>8} {:>12}\n",
+            "Service", "Messages", "Requests", "Notifs", "Errors", "Err%", "Avg latency"
+        ));
+    }
+    for (service_id, service) in stats.services_by_count() {
+        output.push_str(&format!(
+            "  {:<32} {:>10} {:>10} {:>10} {:>10} {:>7.2}% {:>10.3}ms\n",
+            match matrix.get_service_name(service_id) {
+                Some(name) => format!("{} (0x{:04X})", name, service_id),
+                None => format!("0x{:04X}", service_id),
+            },
+            service.message_count,
+            service.request_count,
+            service.notification_count,
+            service.error_count,
+            service.error_rate() * 100.0,
+            service.avg_latency_ms
+        ));
+    }
+    output.push_str(&format!(
+        "Unmatched requests: {} ({} timed out)\nSD packets: {}\nTP reassemblies: {} ({} failed)\nTCP streams: {} ({} restarts, {} retransmissions, {} gaps skipped, {} resyncs skipping {} bytes)\n",
+        stats.unmatched_requests,
+        stats.timed_out_sessions,
+        stats.sd_packets,
+        stats.tp_reassemblies,
+        stats.tp_failures,
+        stats.tcp_streams,
+        stats.tcp_restarts,
+        stats.tcp_retransmissions,
+        stats.tcp_gaps_skipped,
+        stats.tcp_resyncs,
+        stats.tcp_resync_skipped_bytes
+    ));
+    for connection in stats.tcp_connections.iter().take(MAX_CONNECTION_LINES) {
+        output.push_str(&format!(
+            "  {}:{} -> {}:{}: {} bytes delivered, {} segments buffered, {} retransmissions, {} gaps skipped, {:.3} ms, {}\n",
+            connection.src_ip,
+            connection.src_port,
+            connection.dst_ip,
+            connection.dst_port,
+            connection.bytes_delivered,
+            connection.segments_buffered,
+            connection.retransmissions,
+            connection.gaps_skipped,
+            connection.duration_ms,
+            connection.close
+        ));
+    }
+    if stats.tcp_connections.len() > MAX_CONNECTION_LINES {
+        output.push_str(&format!(
+            "  ... {} more connections\n",
+            stats.tcp_connections.len() - MAX_CONNECTION_LINES
+        ));
+    }
+    let closed = &stats.tcp_closed;
+    if closed.connections > 0 {
+        output.push_str(&format!(
+            "  {} closed connections ({} FIN, {} RST): {} bytes delivered, {} segments buffered, {} retransmissions, {} gaps skipped\n",
+            closed.connections,
+            closed.fin,
+            closed.reset,
+            closed.bytes_delivered,
+            closed.segments_buffered,
+            closed.retransmissions,
+            closed.gaps_skipped
+        ));
+    }
+    output.push_str(&format!(
+        "Duplicates dropped: {}\n",
+        stats.duplicate_messages
+    ));
+    output.push_str(&format!(
+        "Packet channel: capacity {}, high-water mark {}, blocked {:.3} ms, dropped {}\n",
+        stats.channel_capacity,
+        stats.channel_high_water_mark,
+        stats.channel_blocked_ms,
+        stats.dropped_packets
+    ));
+    output
+}
+
+/// `--stats-file` 输出的单个服务统计
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServiceStatsEntry<'a> {
+    pub service_id: u16,
+    pub service: String,
+    #[serde(flatten)]
+    pub stats: &'a ServiceStats,
+    pub error_rate: f64,
+}
+
+/// 以 JSON 数组输出各服务的统计，顺序与摘要中的服务表格一致
+pub fn service_stats_json(stats: &Stats, matrix: &Matrix) -> Result<String> {
+    let entries: Vec<ServiceStatsEntry> = stats
+        .services_by_count()
+        .into_iter()
+        .map(|(service_id, stats)| ServiceStatsEntry {
+            service_id,
+            service: service_name(matrix, service_id),
+            stats,
+            error_rate: stats.error_rate(),
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
+
+/// 单个服务/方法的消息数
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodCount {
+    pub service_id: u16,
+    pub method_id: u16,
+    pub service: String,
+    pub method: String,
+    pub count: usize,
+}
+
+/// `--summary` 以 json/yaml 输出的统计：全部计数加上带名称的按方法消息数
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryReport<'a> {
+    #[serde(flatten)]
+    pub stats: &'a Stats,
+    pub methods: Vec<MethodCount>,
+}
+
+impl Stats {
+    /// 按方法统计的消息数，服务与方法名称取自矩阵
+    pub fn method_counts(&self, matrix: &Matrix) -> Vec<MethodCount> {
+        self.messages_by_method
+            .iter()
+            .map(|(&(service_id, method_id), &count)| MethodCount {
+                service_id,
+                method_id,
+                service: service_name(matrix, service_id),
+                method: matrix
+                    .get_method_name(service_id, method_id)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("0x{:04X}", method_id)),
+                count,
+            })
+            .collect()
+    }
+}
+
+/// 按输出格式输出统计：json/yaml 输出结构化数据，其余格式输出文本
+pub fn format_summary_as(stats: &Stats, matrix: &Matrix, output_format: &str) -> Result<String> {
+    let report = SummaryReport {
+        stats,
+        methods: stats.method_counts(matrix),
+    };
+    match output_format {
+        "json" | "wireshark-json" => Ok(format!("{}\n", serde_json::to_string_pretty(&report)?)),
+        "yaml" => Ok(serde_yaml::to_string(&report)?),
+        _ => Ok(format_summary(stats, matrix)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::flow_control::TcpFlowController;
+    use crate::testing::message::{someip_message, tcp_segment};
+    use bytes::Bytes;
+    use std::time::Duration;
+
+    fn header(service_id: u16, message_type: MessageType) -> SomeIPHeader {
+        someip_message(service_id, 0x0001, 1, message_type).header
+    }
+
+    #[test]
+    fn record_service_counts_by_message_type() {
+        let mut stats = Stats::new();
+        stats.record_service(&header(0x1234, MessageType::Request));
+        stats.record_service(&header(0x1234, MessageType::RequestNoReturn));
+        stats.record_service(&header(0x1234, MessageType::Response));
+        stats.record_service(&header(0x1234, MessageType::Notification));
+        stats.record_service(&header(0x5678, MessageType::Notification));
+
+        let service = &stats.services[&0x1234];
+        assert_eq!(service.message_count, 4);
+        assert_eq!(service.request_count, 2);
+        assert_eq!(service.notification_count, 1);
+        assert_eq!(service.error_count, 0);
+        assert_eq!(stats.services[&0x5678].message_count, 1);
+    }
+
+    #[test]
+    fn record_service_counts_errors_and_failed_return_codes() {
+        let mut stats = Stats::new();
+        stats.record_service(&header(0x1234, MessageType::Error));
+        let mut response = header(0x1234, MessageType::Response);
+        response.return_code = ReturnCode::NotOk;
+        stats.record_service(&response);
+        stats.record_service(&header(0x1234, MessageType::Response));
+        stats.record_service(&header(0x1234, MessageType::Response));
+
+        let service = &stats.services[&0x1234];
+        assert_eq!(service.error_count, 2);
+        assert_eq!(service.error_rate(), 0.5);
+    }
+
+    #[test]
+    fn record_latency_keeps_running_average() {
+        let mut stats = Stats::new();
+        for latency_ms in [10.0, 20.0, 60.0] {
+            stats.record_latency(0x1234, latency_ms);
+        }
+        assert_eq!(stats.services[&0x1234].avg_latency_ms, 30.0);
+        assert_eq!(stats.services[&0x1234].message_count, 0);
+    }
+
+    #[test]
+    fn services_sorted_by_message_count() {
+        let mut stats = Stats::new();
+        stats.record_service(&header(0x0003, MessageType::Notification));
+        for _ in 0..2 {
+            stats.record_service(&header(0x0002, MessageType::Notification));
+        }
+        stats.record_service(&header(0x0001, MessageType::Notification));
+        let order: Vec<u16> = stats
+            .services_by_count()
+            .iter()
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(order, [0x0002, 0x0001, 0x0003]);
+
+        let summary = format_summary(&stats, &Matrix::new());
+        assert!(summary.contains("  0x0002 "));
+    }
+
+    #[test]
+    fn summary_caps_connection_lines() {
+        let mut flow = TcpFlowController::new(100, Duration::from_secs(30), Duration::ZERO, 1024);
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let server = IpAddr::from([10, 0, 0, 2]);
+        for src_port in 0..25 {
+            flow.process_tcp_packet(
+                UNIX_EPOCH,
+                &client,
+                &server,
+                &tcp_segment(40000 + src_port, 1000),
+                Bytes::from_static(b"abcd"),
+            )
+            .unwrap();
+        }
+        let mut stats = Stats::new();
+        stats.tcp_connections = flow.connection_stats();
+
+        let summary = format_summary(&stats, &Matrix::new());
+        assert_eq!(
+            summary.matches(" bytes delivered, ").count(),
+            MAX_CONNECTION_LINES
+        );
+        assert!(summary.contains("  ... 5 more connections\n"));
+    }
+}
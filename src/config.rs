@@ -1,34 +1,184 @@
-use clap::Parser;
-use std::path::PathBuf;
+use crate::analysis::flows::ParseDepth;
+use crate::filter::{
+    AndFilter, EndpointFilter, Filter, HeaderIdFilter, IpFilter, MessageTypeFilter, OrFilter,
+    PayloadPatternFilter, ReturnCodeFilter,
+};
+use crate::output::sort::SortKey;
+use crate::parser::flow_control::TcpGapPolicy;
+use crate::parser::pcap_reader::{InputFormat, STDIN_PATH};
+use crate::parser::someip::e2e::E2EProfile;
+use crate::parser::someip::header::{parse_message_type, parse_return_code};
+use crate::parser::someip::matrix::{FieldIdScheme, MatrixConflictPolicy};
+use crate::parser::someip::session::EvictionPolicy;
+use crate::utils::timestamp::parse_timestamp;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 /// SomeIP 协议解析工具，用于从 PCAP 文件中提取和分析 SomeIP 数据包
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Config {
-    /// 要解析的 PCAP 文件路径
-    #[arg(short, long, required = true)]
-    pub pcap_file: PathBuf,
+    /// 要解析的 PCAP 文件路径（可重复指定，多个文件按时间戳合并处理），`-` 表示从标准输入读取
+    #[arg(
+        short,
+        long,
+        num_args = 1..,
+        required_unless_present_any = ["config", "write_example_config", "reformat", "validate_only"]
+    )]
+    pub pcap_file: Vec<PathBuf>,
 
-    /// 矩阵文件（ARXML/JSON/YAML）路径，用于将 ID 映射为名称
+    /// TOML 配置文件路径，命令行参数优先于配置文件中的值
     #[arg(short, long)]
-    pub matrix_file: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+
+    /// 读取先前导出的 JSON 结果，按 --output-format 重新格式化输出（不解析 PCAP）
+    #[arg(long, value_name = "JSON")]
+    pub reformat: Option<PathBuf>,
+
+    /// 将默认配置写入指定的 TOML 文件后退出，可作为配置模板
+    #[arg(long, value_name = "PATH")]
+    pub write_example_config: Option<PathBuf>,
+
+    /// 只加载并检查矩阵文件，输出定义统计与无法解析的 ID 后退出（不解析 PCAP）
+    #[arg(long, requires = "matrix_file")]
+    pub validate_only: bool,
+
+    /// 矩阵文件（ARXML/JSON/YAML/CSV）路径，用于将 ID 映射为名称（可重复指定，按顺序合并）
+    #[arg(short, long)]
+    pub matrix_file: Vec<PathBuf>,
+
+    /// 多个矩阵文件中同一 ID 名称不同时的处理方式（默认：error）
+    #[arg(long, value_enum, default_value_t = MatrixConflictPolicy::Error)]
+    pub matrix_conflict_policy: MatrixConflictPolicy,
+
+    /// 矩阵中字段 GET/SET/NOTIFIER 方法 ID 的推导规则（默认：event-range）
+    #[arg(long, value_enum, default_value_t = FieldIdScheme::EventRange)]
+    pub field_id_scheme: FieldIdScheme,
 
     /// SomeIP-SD 服务发现端口（默认：30490）
     #[arg(short, long, default_value_t = 30490)]
     pub sd_port: u16,
 
+    /// BPF 过滤表达式，例如 "udp port 30490"（可选，也可写作 --bpf-filter）
+    #[arg(long, visible_alias = "bpf-filter")]
+    pub filter: Option<String>,
+
+    /// 只输出指定服务 ID 的消息（可重复指定，按十六进制解析，0x 前缀可选，如 1234 即 0x1234）
+    #[arg(long, value_parser = parse_hex_u16)]
+    pub service_id: Vec<u16>,
+
+    /// 只输出指定方法/事件 ID 的消息（可重复指定，按十六进制解析，0x 前缀可选）
+    #[arg(long, value_parser = parse_hex_u16)]
+    pub method_id: Vec<u16>,
+
+    /// 只输出源或目的地址属于指定网段的消息（可重复指定，如 192.168.1.0/24）
+    #[arg(long)]
+    pub ip: Vec<IpNet>,
+
+    /// 只处理源地址属于指定地址或网段的数据包（可重复指定，如 10.0.0.1 或 10.0.0.0/24）
+    #[arg(long, value_parser = parse_ip_net)]
+    pub src_ip: Vec<IpNet>,
+
+    /// 只处理目的地址属于指定地址或网段的数据包（可重复指定）
+    #[arg(long, value_parser = parse_ip_net)]
+    pub dst_ip: Vec<IpNet>,
+
+    /// 只处理指定源端口的数据包（可重复指定）
+    #[arg(long, value_parser = parse_u16)]
+    pub src_port: Vec<u16>,
+
+    /// 只处理指定目的端口的数据包（可重复指定）
+    #[arg(long, value_parser = parse_u16)]
+    pub dst_port: Vec<u16>,
+
+    /// 只输出指定消息类型的消息（可重复指定，如 0x02 表示 Notification）
+    #[arg(long, value_parser = parse_u8)]
+    pub message_type: Vec<u8>,
+
+    /// 只输出指定返回码的消息（可重复指定，如 0x00 表示 E_OK）
+    #[arg(long, value_parser = parse_u8)]
+    pub return_code: Vec<u8>,
+
+    /// 只输出负载中包含指定字节序列的消息（十六进制，如 DEADBEEF；可重复指定，任一匹配即可）
+    #[arg(long, value_name = "HEX")]
+    pub payload_pattern: Vec<String>,
+
+    /// 只处理抓包时间不早于该时间的数据包（RFC3339 或 Unix 秒数，包含边界）
+    #[arg(long, value_name = "TIME")]
+    pub start_time: Option<String>,
+
+    /// 只处理抓包时间早于该时间的数据包（RFC3339 或 Unix 秒数，不包含边界）
+    #[arg(long, value_name = "TIME")]
+    pub end_time: Option<String>,
+
     /// 过滤特定 VLAN ID 的数据包（可选）
     #[arg(short = 'v', long)] // 保持 vlan 参数简写为 v
     pub vlan: Option<u16>,
 
-    /// 输出格式（支持：text、json、yaml，默认：text）
+    /// 在输出中显示每条消息所属的 VLAN ID
+    #[arg(long)]
+    pub show_vlan: bool,
+
+    /// VXLAN 隧道的 UDP 端口，发往该端口的数据包会被解封装（默认：4789）
+    #[arg(long, default_value_t = crate::parser::tunnel::VXLAN_DEFAULT_PORT)]
+    pub vxlan_port: u16,
+
+    /// 在输出中显示隧道（VXLAN/GRE）外层的端点地址
+    #[arg(long)]
+    pub show_tunnel: bool,
+
+    /// 跟随模式：读到文件末尾后继续等待新追加的数据包并立即输出（类似 tail -f），Ctrl-C 结束
+    #[arg(long)]
+    pub follow: bool,
+
+    /// 跟随模式下处理跟不上时丢弃新数据包并计数，而不是阻塞读取
+    #[arg(long, requires = "follow")]
+    pub drop_when_full: bool,
+
+    /// 跟随模式下缓冲流式输出，每隔 --output-flush-interval 刷新一次，而不是每批消息都刷新
+    #[arg(long, requires = "follow")]
+    pub output_buffered: bool,
+
+    /// 缓冲输出的刷新间隔（毫秒，默认：1000）
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub output_flush_interval: u64,
+
+    /// 导出前按指定字段排序，字段相同时按时间戳排序（需要缓存全部结果，不能与 --follow 同时使用）
+    #[arg(long, value_enum, conflicts_with = "follow")]
+    pub sort_by: Option<SortKey>,
+
+    /// 按降序排序
+    #[arg(long, requires = "sort_by")]
+    pub sort_desc: bool,
+
+    /// 输出格式（支持：text、json、yaml、wireshark-json、html、sqlite，默认：text）
     #[arg(short = 'f', long, default_value_t = String::from("text"))]
     pub output_format: String,
 
-    /// 输出文件路径（默认：标准输出）
+    /// 输出文件路径（默认：标准输出；sqlite 格式时为数据库路径）
     #[arg(short, long)]
     pub output_file: Option<PathBuf>,
 
+    /// 输出中每条消息负载的最大字节数，超出部分截断（默认：不限制）
+    #[arg(long, value_name = "N")]
+    pub max_payload_bytes: Option<usize>,
+
+    /// text 输出中负载超过该字节数时输出为每行 16 字节的十六进制 + ASCII 转储（默认：始终输出单行十六进制；json/yaml 不受影响）
+    #[arg(long, value_name = "N")]
+    pub hexdump_threshold: Option<usize>,
+
+    /// json/yaml 输出中空负载输出为 null 而不是空字符串
+    #[arg(long)]
+    pub include_empty_payload_as_null: bool,
+
+    /// sqlite 输出时追加到已有数据库，不清空 messages 表
+    #[arg(long)]
+    pub sqlite_append: bool,
+
     /// 启用 verbose 模式（显示详细日志）
     #[arg(long, action = clap::ArgAction::Count)] // 将 verbose 参数简写改为 V
     pub verbose: u8,
@@ -37,6 +187,26 @@ pub struct Config {
     #[arg(long, default_value_t = 5)]
     pub request_timeout: u64,
 
+    /// 保留的最近完成的请求/响应对数量，用于关联迟到的响应（默认：1000）
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    pub session_history_size: usize,
+
+    /// 会话表已满时淘汰待响应请求的策略：fifo 淘汰最早的请求，lru 淘汰最久未访问的请求（默认：fifo）
+    #[arg(long, value_enum, default_value_t = EvictionPolicy::Fifo)]
+    pub session_eviction_policy: EvictionPolicy,
+
+    /// UDP 数据报中 SomeIP 长度字段的上限，超出时按格式错误丢弃（默认：65536）
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024)]
+    pub max_someip_length: u32,
+
+    /// TCP 字节流中 SomeIP 长度字段的上限，超出时视为失去同步并丢弃缓存（默认：16777216）
+    #[arg(long, value_name = "BYTES", default_value_t = 16 * 1024 * 1024)]
+    pub max_someip_stream_length: u32,
+
+    /// TCP 字节流失去同步后重新扫描头部时，候选头部长度字段的上限（默认：65536）
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024)]
+    pub tcp_resync_max_length: u32,
+
     /// TP 分段重组超时时间（秒，默认：30）
     #[arg(long, default_value_t = 30)]
     pub tp_timeout: u64,
@@ -44,27 +214,725 @@ pub struct Config {
     /// TCP 连接超时时间（秒，默认：60）
     #[arg(long, default_value_t = 60)]
     pub tcp_timeout: u64,
+
+    /// 按抓包时间每隔指定秒数检查一次请求超时，超时的请求立即输出而不是等到文件末尾（可选）
+    #[arg(long, value_name = "SECONDS")]
+    pub timeout_sweep_interval: Option<u64>,
+
+    /// 每个 TCP 连接缓存的乱序分段字节数上限，超出时按 `--tcp-gap-policy` 处理（默认：1048576）
+    #[arg(long, default_value_t = 1024 * 1024)]
+    pub tcp_max_buffer: usize,
+
+    /// 每个 TCP 连接缓存的乱序分段数上限（可选，默认只限制字节数）
+    #[arg(long, value_name = "SEGMENTS")]
+    pub tcp_max_segments: Option<usize>,
+
+    /// 乱序缓存超过上限时的处理方式：丢弃最旧的分段或跳过缺口（默认：drop-oldest）
+    #[arg(long, value_enum, default_value_t = TcpGapPolicy::DropOldest)]
+    pub tcp_gap_policy: TcpGapPolicy,
+
+    /// 读取器与处理循环之间的数据包通道容量（默认：1000）
+    #[arg(long, default_value_t = 1000)]
+    pub channel_capacity: usize,
+
+    /// 输入文件格式（auto、pcap、dlt；默认 auto：按扩展名与文件头识别）
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    pub input_format: InputFormat,
+
+    /// 解析停止的协议层（link、network、transport、someip；默认 someip），
+    /// 低于 someip 时输出该层的流列表而不是 SomeIP 消息
+    #[arg(long, value_enum, default_value_t = ParseDepth::Someip)]
+    pub parse_depth: ParseDepth,
+
+    /// 负载携带的 E2E 保护头部配置文件，设置后剥离头部并校验 CRC（p01、p02、p04、p05、p06、p07）
+    #[arg(long, value_enum)]
+    pub e2e_profile: Option<E2EProfile>,
+
+    /// E2E CRC 计算使用的数据 ID（P01/P02/P05/P06 的数据 ID 不随报文传输，默认：0）
+    #[arg(long, value_parser = parse_u32, default_value_t = 0, requires = "e2e_profile")]
+    pub e2e_data_id: u32,
+
+    /// 受 E2E 保护的消息（服务ID:方法ID，可重复指定，如 0x1234:0x8001），只校验这些消息
+    #[arg(long, value_parser = parse_message_id, requires = "e2e_profile")]
+    pub e2e_id: Vec<(u16, u16)>,
+
+    /// 校验 IPv4 头部及 UDP/TCP 校验和，丢弃校验失败的数据包
+    #[arg(long)]
+    pub verify_checksums: bool,
+
+    /// 宽松校验模式：校验失败的数据包只记录不丢弃（适用于校验和卸载的抓包）
+    #[arg(long, requires = "verify_checksums")]
+    pub checksum_lenient: bool,
+
+    /// 丢弃重复出现的 SomeIP 消息（镜像端口等导致同一消息被抓到两次）
+    #[arg(long)]
+    pub deduplicate: bool,
+
+    /// 输出每个事件的通知周期与抖动统计报告
+    #[arg(long)]
+    pub event_timing: bool,
+
+    /// 输出 SD 一致性报告（未被使用的服务提供、未被提供的订阅等）
+    #[arg(long)]
+    pub sd_consistency: bool,
+
+    /// 输出根据 SD 重启标志与会话 ID 检测到的 ECU 重启事件
+    #[arg(long)]
+    pub sd_reboots: bool,
+
+    /// 输出 SD 服务提供拓扑（各实例的端点与负载均衡优先级/权重）
+    #[arg(long)]
+    pub sd_offers: bool,
+
+    /// 运行结束时输出抓包与解析统计：按服务/方法与消息类型的消息数、未配对请求数、
+    /// TP 重组与 TCP 连接数等（json/yaml 输出格式下以相同格式输出；消息输出到标准输出时摘要输出到标准错误；
+    /// 也可写作 --stats）
+    #[arg(long, visible_alias = "stats")]
+    pub summary: bool,
+
+    /// 将各服务的消息数、错误率与平均延迟统计以 JSON 写入指定文件
+    #[arg(long, value_name = "PATH")]
+    pub stats_file: Option<PathBuf>,
+
+    /// 将包含通过过滤的 SomeIP 消息的原始帧写入新的 pcap 文件（保留时间戳与链路层类型）
+    #[arg(long, value_name = "PATH")]
+    pub write_pcap: Option<PathBuf>,
+
+    /// 运行结束时将生成的全部输出文件（路径、格式、记录数、大小）以 JSON 清单写入指定文件
+    #[arg(long, value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
+
+    /// 解析失败的数据包数超过该值时以 --error-exit-code 退出（可选）
+    #[arg(long, value_name = "N")]
+    pub max_errors: Option<usize>,
+
+    /// 严格模式：出现任何解析失败的数据包即以 --error-exit-code 退出
+    #[arg(long)]
+    pub strict: bool,
+
+    /// 解析失败超过容忍范围时的退出码（默认：2）
+    #[arg(long, default_value_t = 2)]
+    pub error_exit_code: u8,
+}
+
+/// 配置文件内容，字段与 `Config` 一一对应，未填写的字段保持命令行默认值
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub pcap_file: Option<Vec<PathBuf>>,
+    #[serde(default, deserialize_with = "deserialize_paths")]
+    pub matrix_file: Option<Vec<PathBuf>>,
+    pub matrix_conflict_policy: Option<MatrixConflictPolicy>,
+    pub field_id_scheme: Option<FieldIdScheme>,
+    pub sd_port: Option<u16>,
+    pub filter: Option<String>,
+    pub service_id: Option<Vec<u16>>,
+    pub method_id: Option<Vec<u16>>,
+    pub ip: Option<Vec<IpNet>>,
+    #[serde(default, deserialize_with = "deserialize_ip_nets")]
+    pub src_ip: Option<Vec<IpNet>>,
+    #[serde(default, deserialize_with = "deserialize_ip_nets")]
+    pub dst_ip: Option<Vec<IpNet>>,
+    pub src_port: Option<Vec<u16>>,
+    pub dst_port: Option<Vec<u16>>,
+    pub message_type: Option<Vec<u8>>,
+    pub return_code: Option<Vec<u8>>,
+    pub payload_pattern: Option<Vec<String>>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub vlan: Option<u16>,
+    pub show_vlan: Option<bool>,
+    pub vxlan_port: Option<u16>,
+    pub show_tunnel: Option<bool>,
+    pub follow: Option<bool>,
+    pub drop_when_full: Option<bool>,
+    pub output_buffered: Option<bool>,
+    pub output_flush_interval: Option<u64>,
+    pub sort_by: Option<SortKey>,
+    pub sort_desc: Option<bool>,
+    pub output_format: Option<String>,
+    pub output_file: Option<PathBuf>,
+    pub max_payload_bytes: Option<usize>,
+    pub hexdump_threshold: Option<usize>,
+    pub include_empty_payload_as_null: Option<bool>,
+    pub sqlite_append: Option<bool>,
+    pub verbose: Option<u8>,
+    pub request_timeout: Option<u64>,
+    pub session_history_size: Option<usize>,
+    pub session_eviction_policy: Option<EvictionPolicy>,
+    pub max_someip_length: Option<u32>,
+    pub max_someip_stream_length: Option<u32>,
+    pub tcp_resync_max_length: Option<u32>,
+    pub tp_timeout: Option<u64>,
+    pub tcp_timeout: Option<u64>,
+    pub timeout_sweep_interval: Option<u64>,
+    pub tcp_max_buffer: Option<usize>,
+    pub tcp_max_segments: Option<usize>,
+    pub tcp_gap_policy: Option<TcpGapPolicy>,
+    pub channel_capacity: Option<usize>,
+    pub input_format: Option<InputFormat>,
+    pub parse_depth: Option<ParseDepth>,
+    pub e2e_profile: Option<E2EProfile>,
+    pub e2e_data_id: Option<u32>,
+    pub e2e_id: Option<Vec<(u16, u16)>>,
+    pub verify_checksums: Option<bool>,
+    pub checksum_lenient: Option<bool>,
+    pub deduplicate: Option<bool>,
+    pub event_timing: Option<bool>,
+    pub sd_consistency: Option<bool>,
+    pub sd_reboots: Option<bool>,
+    pub sd_offers: Option<bool>,
+    pub summary: Option<bool>,
+    pub stats_file: Option<PathBuf>,
+    pub write_pcap: Option<PathBuf>,
+    pub manifest: Option<PathBuf>,
+    pub max_errors: Option<usize>,
+    pub strict: Option<bool>,
+    pub error_exit_code: Option<u8>,
+}
+
+impl ConfigFile {
+    /// 从 TOML 文件读取配置
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("无法读取配置文件 {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("配置文件格式错误 {}: {}", path.display(), e))
+    }
+}
+
+impl From<&Config> for ConfigFile {
+    fn from(config: &Config) -> Self {
+        Self {
+            pcap_file: Some(config.pcap_file.clone()),
+            matrix_file: Some(config.matrix_file.clone()),
+            matrix_conflict_policy: Some(config.matrix_conflict_policy),
+            field_id_scheme: Some(config.field_id_scheme),
+            sd_port: Some(config.sd_port),
+            filter: config.filter.clone(),
+            service_id: Some(config.service_id.clone()),
+            method_id: Some(config.method_id.clone()),
+            ip: Some(config.ip.clone()),
+            src_ip: Some(config.src_ip.clone()),
+            dst_ip: Some(config.dst_ip.clone()),
+            src_port: Some(config.src_port.clone()),
+            dst_port: Some(config.dst_port.clone()),
+            message_type: Some(config.message_type.clone()),
+            return_code: Some(config.return_code.clone()),
+            payload_pattern: Some(config.payload_pattern.clone()),
+            start_time: config.start_time.clone(),
+            end_time: config.end_time.clone(),
+            vlan: config.vlan,
+            show_vlan: Some(config.show_vlan),
+            vxlan_port: Some(config.vxlan_port),
+            show_tunnel: Some(config.show_tunnel),
+            follow: Some(config.follow),
+            drop_when_full: Some(config.drop_when_full),
+            output_buffered: Some(config.output_buffered),
+            output_flush_interval: Some(config.output_flush_interval),
+            sort_by: config.sort_by,
+            sort_desc: Some(config.sort_desc),
+            output_format: Some(config.output_format.clone()),
+            output_file: config.output_file.clone(),
+            max_payload_bytes: config.max_payload_bytes,
+            hexdump_threshold: config.hexdump_threshold,
+            include_empty_payload_as_null: Some(config.include_empty_payload_as_null),
+            sqlite_append: Some(config.sqlite_append),
+            verbose: Some(config.verbose),
+            request_timeout: Some(config.request_timeout),
+            session_history_size: Some(config.session_history_size),
+            session_eviction_policy: Some(config.session_eviction_policy),
+            max_someip_length: Some(config.max_someip_length),
+            max_someip_stream_length: Some(config.max_someip_stream_length),
+            tcp_resync_max_length: Some(config.tcp_resync_max_length),
+            tp_timeout: Some(config.tp_timeout),
+            tcp_timeout: Some(config.tcp_timeout),
+            timeout_sweep_interval: config.timeout_sweep_interval,
+            tcp_max_buffer: Some(config.tcp_max_buffer),
+            tcp_max_segments: config.tcp_max_segments,
+            tcp_gap_policy: Some(config.tcp_gap_policy),
+            channel_capacity: Some(config.channel_capacity),
+            input_format: Some(config.input_format),
+            parse_depth: Some(config.parse_depth),
+            e2e_profile: config.e2e_profile,
+            e2e_data_id: Some(config.e2e_data_id),
+            e2e_id: Some(config.e2e_id.clone()),
+            verify_checksums: Some(config.verify_checksums),
+            checksum_lenient: Some(config.checksum_lenient),
+            deduplicate: Some(config.deduplicate),
+            event_timing: Some(config.event_timing),
+            sd_consistency: Some(config.sd_consistency),
+            sd_reboots: Some(config.sd_reboots),
+            sd_offers: Some(config.sd_offers),
+            summary: Some(config.summary),
+            stats_file: config.stats_file.clone(),
+            write_pcap: config.write_pcap.clone(),
+            manifest: config.manifest.clone(),
+            max_errors: config.max_errors,
+            strict: Some(config.strict),
+            error_exit_code: Some(config.error_exit_code),
+        }
+    }
 }
 
-/// 验证命令行参数合法性
 impl Config {
+    /// 解析命令行参数，并合并 `--config` 指定的配置文件（命令行参数优先）
+    pub fn load() -> anyhow::Result<Self> {
+        let matches = Self::command().get_matches();
+        let mut config = Self::from_arg_matches(&matches)?;
+        if let Some(path) = config.config.clone() {
+            config.merge(ConfigFile::load(&path)?, &matches);
+        }
+        Ok(config)
+    }
+
+    /// 用配置文件中的值覆盖未在命令行显式指定的参数
+    pub fn merge(&mut self, file: ConfigFile, matches: &ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        macro_rules! merge_fields {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = file.$field {
+                        if !from_cli(stringify!($field)) {
+                            self.$field = value;
+                        }
+                    }
+                )*
+            };
+        }
+        macro_rules! merge_optional_fields {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if file.$field.is_some() && !from_cli(stringify!($field)) {
+                        self.$field = file.$field;
+                    }
+                )*
+            };
+        }
+
+        merge_fields!(
+            pcap_file,
+            matrix_file,
+            matrix_conflict_policy,
+            field_id_scheme,
+            sd_port,
+            service_id,
+            method_id,
+            ip,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+            message_type,
+            return_code,
+            payload_pattern,
+            show_vlan,
+            vxlan_port,
+            show_tunnel,
+            follow,
+            drop_when_full,
+            output_buffered,
+            output_flush_interval,
+            sort_desc,
+            output_format,
+            include_empty_payload_as_null,
+            sqlite_append,
+            verbose,
+            request_timeout,
+            session_history_size,
+            session_eviction_policy,
+            max_someip_length,
+            max_someip_stream_length,
+            tcp_resync_max_length,
+            tp_timeout,
+            tcp_timeout,
+            tcp_max_buffer,
+            tcp_gap_policy,
+            channel_capacity,
+            input_format,
+            parse_depth,
+            e2e_data_id,
+            e2e_id,
+            verify_checksums,
+            checksum_lenient,
+            deduplicate,
+            event_timing,
+            sd_consistency,
+            sd_reboots,
+            sd_offers,
+            summary,
+            strict,
+            error_exit_code,
+        );
+        merge_optional_fields!(
+            filter,
+            start_time,
+            end_time,
+            vlan,
+            output_file,
+            max_payload_bytes,
+            hexdump_threshold,
+            timeout_sweep_interval,
+            tcp_max_segments,
+            e2e_profile,
+            sort_by,
+            stats_file,
+            write_pcap,
+            manifest,
+            max_errors,
+        );
+    }
+
+    /// 将默认配置序列化为 TOML 写入文件，作为配置模板
+    pub fn write_example_config(path: &Path) -> anyhow::Result<()> {
+        // 忽略必填参数检查，仅取各参数的默认值
+        let matches = Self::command()
+            .ignore_errors(true)
+            .get_matches_from(["someip-parser"]);
+        let defaults = Self::from_arg_matches(&matches)?;
+        let content = toml::to_string_pretty(&ConfigFile::from(&defaults))?;
+        std::fs::write(path, content)
+            .map_err(|e| anyhow::anyhow!("无法写入配置文件 {}: {}", path.display(), e))
+    }
+
+    /// 根据地址/类型等过滤参数构造消息过滤器，各类条件之间为“与”关系，同类多个取值为“或”关系
+    ///
+    /// 服务/方法 ID 由 `build_header_filter` 在解析头部后提前过滤，不在此重复
+    pub fn build_filter(&self) -> Box<dyn Filter> {
+        let mut filters: Vec<Box<dyn Filter>> = Vec::new();
+        if !self.ip.is_empty() {
+            filters.push(Box::new(OrFilter(
+                self.ip
+                    .iter()
+                    .map(|net| Box::new(IpFilter(*net)) as Box<dyn Filter>)
+                    .collect(),
+            )));
+        }
+        if !self.message_type.is_empty() {
+            filters.push(Box::new(MessageTypeFilter(
+                self.message_type
+                    .iter()
+                    .map(|&value| parse_message_type(value))
+                    .collect(),
+            )));
+        }
+        if !self.return_code.is_empty() {
+            filters.push(Box::new(ReturnCodeFilter(
+                self.return_code
+                    .iter()
+                    .map(|&value| parse_return_code(value))
+                    .collect(),
+            )));
+        }
+        if !self.payload_pattern.is_empty() {
+            // 十六进制格式已在 validate 中校验
+            filters.push(Box::new(OrFilter(
+                self.payload_pattern
+                    .iter()
+                    .filter_map(|hex| PayloadPatternFilter::new(hex).ok())
+                    .map(|filter| Box::new(filter) as Box<dyn Filter>)
+                    .collect(),
+            )));
+        }
+        Box::new(AndFilter(filters))
+    }
+
+    /// 根据 `--service-id` / `--method-id` 构造头部 ID 过滤器
+    pub fn build_header_filter(&self) -> HeaderIdFilter {
+        HeaderIdFilter {
+            service_ids: self.service_id.clone(),
+            method_ids: self.method_id.clone(),
+        }
+    }
+
+    /// 根据 `--src-ip` / `--dst-ip` / `--src-port` / `--dst-port` 构造地址端口过滤器
+    pub fn build_endpoint_filter(&self) -> EndpointFilter {
+        EndpointFilter {
+            src_ips: self.src_ip.clone(),
+            dst_ips: self.dst_ip.clone(),
+            src_ports: self.src_port.clone(),
+            dst_ports: self.dst_port.clone(),
+        }
+    }
+
+    /// 解析 `--start-time` / `--end-time`，返回 [起始, 结束) 时间范围
+    pub fn time_range(&self) -> anyhow::Result<(Option<SystemTime>, Option<SystemTime>)> {
+        let parse = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(parse_timestamp)
+                .transpose()
+                .map_err(|e| anyhow::anyhow!(e))
+        };
+        Ok((parse(&self.start_time)?, parse(&self.end_time)?))
+    }
+
+    /// 验证命令行参数合法性
     pub fn validate(&self) -> anyhow::Result<()> {
+        if self.pcap_file.is_empty() && self.reformat.is_none() && !self.validate_only {
+            anyhow::bail!("未指定 PCAP 文件（通过 --pcap-file 或配置文件 pcap_file 设置）");
+        }
+
         // 检查 PCAP 文件是否存在
-        if !self.pcap_file.exists() {
-            anyhow::bail!("PCAP 文件不存在: {}", self.pcap_file.display());
+        let stdin_inputs = self
+            .pcap_file
+            .iter()
+            .filter(|path| path.as_os_str() == STDIN_PATH)
+            .count();
+        if stdin_inputs > 1 {
+            anyhow::bail!("标准输入 (-) 只能作为一个 PCAP 输入");
+        }
+        if stdin_inputs > 0 && self.follow {
+            anyhow::bail!("跟随模式需要可重新打开的文件，不能从标准输入 (-) 读取");
+        }
+        for pcap_file in &self.pcap_file {
+            if pcap_file.as_os_str() != STDIN_PATH && !pcap_file.exists() {
+                anyhow::bail!("PCAP 文件不存在: {}", pcap_file.display());
+            }
         }
 
         // 检查矩阵文件（如果提供）是否存在
-        if let Some(matrix_path) = &self.matrix_file {
+        for matrix_path in &self.matrix_file {
             if !matrix_path.exists() {
                 anyhow::bail!("矩阵文件不存在: {}", matrix_path.display());
             }
         }
 
+        // 配置文件中的值不经过 clap 的 requires 检查，合并后在此重新检查参数间的依赖
+        let dependencies = [
+            (
+                self.drop_when_full,
+                self.follow,
+                "--drop-when-full",
+                "--follow",
+            ),
+            (
+                self.output_buffered,
+                self.follow,
+                "--output-buffered",
+                "--follow",
+            ),
+            (
+                self.sort_desc,
+                self.sort_by.is_some(),
+                "--sort-desc",
+                "--sort-by",
+            ),
+            (
+                self.e2e_data_id != 0 || !self.e2e_id.is_empty(),
+                self.e2e_profile.is_some(),
+                "--e2e-data-id/--e2e-id",
+                "--e2e-profile",
+            ),
+            (
+                self.checksum_lenient,
+                self.verify_checksums,
+                "--checksum-lenient",
+                "--verify-checksums",
+            ),
+        ];
+        for (present, required, option, requirement) in dependencies {
+            if present && !required {
+                anyhow::bail!("{} 需要同时指定 {}", option, requirement);
+            }
+        }
+        if self.follow && self.sort_by.is_some() {
+            anyhow::bail!("--sort-by 需要缓存全部结果后排序，不能与 --follow 流式输出同时使用");
+        }
+        if let (Some(start), Some(end)) = self.time_range()?
+            && start >= end
+        {
+            anyhow::bail!("--start-time 必须早于 --end-time");
+        }
+        for pattern in &self.payload_pattern {
+            PayloadPatternFilter::new(pattern)
+                .map_err(|e| anyhow::anyhow!("--payload-pattern 格式错误: {}", e))?;
+        }
+        if self.e2e_profile.is_some() && self.e2e_id.is_empty() {
+            anyhow::bail!("--e2e-profile 需要通过 --e2e-id 指定受 E2E 保护的消息");
+        }
+        if self.error_exit_code == 0 {
+            anyhow::bail!("--error-exit-code 必须为非零值");
+        }
+        if self.output_buffered && self.output_flush_interval == 0 {
+            anyhow::bail!("--output-flush-interval 必须大于 0");
+        }
+        if self.channel_capacity == 0 {
+            anyhow::bail!("--channel-capacity 必须大于 0");
+        }
+        if self.parse_depth != ParseDepth::Someip && self.output_format == "sqlite" {
+            anyhow::bail!("--parse-depth 低于 someip 时输出流列表，不支持 sqlite 输出格式");
+        }
+        if self.follow && matches!(self.output_format.as_str(), "sqlite" | "html") {
+            anyhow::bail!(
+                "跟随模式需要流式输出，不支持 {} 输出格式",
+                self.output_format
+            );
+        }
+
         // 检查输出格式是否合法
         match self.output_format.as_str() {
-            "text" | "json" | "yaml" => Ok(()),
+            "text" | "json" | "yaml" | "wireshark-json" | "html" => Ok(()),
+            "sqlite" if self.output_file.is_none() => {
+                anyhow::bail!("sqlite 输出格式需要通过 --output-file 指定数据库路径")
+            }
+            "sqlite" => Ok(()),
             _ => anyhow::bail!("不支持的输出格式: {}", self.output_format),
         }
     }
 }
+
+/// 解析十进制或 0x 前缀的十六进制数
+fn parse_number(value: &str) -> Result<u64, String> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|e| format!("无效的数值 {}: {}", value, e))
+}
+
+/// 解析网段或单个 IP 地址，单个地址视为 /32（IPv6 为 /128）
+fn parse_ip_net(value: &str) -> Result<IpNet, String> {
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<std::net::IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("无效的 IP 地址或网段: {}", value))
+}
+
+/// 配置文件中的地址列表同样接受单个 IP 地址
+fn deserialize_ip_nets<'de, D>(deserializer: D) -> Result<Option<Vec<IpNet>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|values| {
+            values
+                .iter()
+                .map(|value| parse_ip_net(value).map_err(serde::de::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
+/// 配置文件中的路径列表，兼容只写一个路径字符串的旧格式
+fn deserialize_paths<'de, D>(deserializer: D) -> Result<Option<Vec<PathBuf>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PathBuf),
+        Many(Vec<PathBuf>),
+    }
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|paths| match paths {
+            OneOrMany::One(path) => vec![path],
+            OneOrMany::Many(paths) => paths,
+        }),
+    )
+}
+
+/// 解析 `服务ID:方法ID` 形式的消息标识
+fn parse_message_id(value: &str) -> Result<(u16, u16), String> {
+    let (service_id, method_id) = value
+        .split_once(':')
+        .ok_or_else(|| format!("消息标识应为 服务ID:方法ID: {}", value))?;
+    Ok((parse_u16(service_id)?, parse_u16(method_id)?))
+}
+
+fn parse_u16(value: &str) -> Result<u16, String> {
+    u16::try_from(parse_number(value)?).map_err(|_| format!("数值超出范围: {}", value))
+}
+
+/// 解析十六进制的 16 位 ID，0x 前缀可选，与矩阵文件和输出中的 ID 写法一致
+fn parse_hex_u16(value: &str) -> Result<u16, String> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u16::from_str_radix(digits, 16).map_err(|e| format!("无效的十六进制 ID {}: {}", value, e))
+}
+
+fn parse_u32(value: &str) -> Result<u32, String> {
+    u32::try_from(parse_number(value)?).map_err(|_| format!("数值超出范围: {}", value))
+}
+
+fn parse_u8(value: &str) -> Result<u8, String> {
+    u8::try_from(parse_number(value)?).map_err(|_| format!("数值超出范围: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以给定命令行参数与 TOML 配置文件内容构造合并后的配置
+    fn merged(args: &[&str], toml: &str) -> Config {
+        let matches = Config::command()
+            .try_get_matches_from(std::iter::once("someip-parser").chain(args.iter().copied()))
+            .unwrap();
+        let mut config = Config::from_arg_matches(&matches).unwrap();
+        config.merge(toml::from_str(toml).unwrap(), &matches);
+        config
+    }
+
+    #[test]
+    fn command_line_overrides_config_file() {
+        let config = merged(
+            &["-p", "-", "--sd-port", "30501"],
+            "sd_port = 30490\noutput_format = \"json\"\nservice_id = [4660]\n",
+        );
+        assert_eq!(config.sd_port, 30501);
+        // 命令行未指定的参数取配置文件中的值
+        assert_eq!(config.output_format, "json");
+        assert_eq!(config.service_id, [0x1234]);
+        // 两者都未指定的参数保持默认值
+        assert_eq!(config.request_timeout, 5);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn unknown_config_file_key_is_rejected() {
+        let error = toml::from_str::<ConfigFile>("sd_prot = 30490\n").unwrap_err();
+        assert!(error.to_string().contains("sd_prot"), "{error}");
+    }
+
+    #[test]
+    fn dependent_options_from_config_file_are_validated() {
+        let error = merged(&["-p", "-"], "sort_desc = true\n")
+            .validate()
+            .unwrap_err();
+        assert!(error.to_string().contains("--sort-by"), "{error}");
+        merged(&["-p", "-"], "sort_desc = true\nsort_by = \"service\"\n")
+            .validate()
+            .unwrap();
+        // 命令行指定的依赖同样满足配置文件中的参数
+        merged(
+            &["-p", "-", "--verify-checksums"],
+            "checksum_lenient = true\n",
+        )
+        .validate()
+        .unwrap();
+        assert!(
+            merged(&["-p", "-"], "checksum_lenient = true\n")
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn example_config_is_a_valid_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("example.toml");
+        Config::write_example_config(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let config = merged(&["-p", "-"], &content);
+        // 模板中的 pcap_file 为空，命令行指定的输入不被覆盖
+        assert_eq!(config.pcap_file, [PathBuf::from("-")]);
+        config.validate().unwrap();
+    }
+}
@@ -3,7 +3,7 @@ use anyhow::Error;
 
 pub type Result<T> = anyhow::Result<T, Error>;
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum SomeIPError {
     #[error("Invalid packet format: {0}")]
     InvalidPacketFormat(String),
@@ -22,4 +22,7 @@ pub enum SomeIPError {
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
-}
\ No newline at end of file
+
+    #[error("Capture file error: {0}")]
+    CaptureFileError(String),
+}
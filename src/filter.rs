@@ -0,0 +1,287 @@
+// src/filter.rs
+use crate::error::{Result, SomeIPError};
+use crate::parser::someip::header::{MessageType, ReturnCode, SomeIPHeader};
+use crate::parser::someip::session::SomeIPMessage;
+use ipnet::IpNet;
+use lru::LruCache;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+
+/// SomeIP 消息过滤条件，可通过 `AndFilter` / `OrFilter` 组合
+pub trait Filter {
+    fn matches(&self, msg: &SomeIPMessage) -> bool;
+}
+
+/// 源地址或目的地址落在指定网段内即匹配
+pub struct IpFilter(pub IpNet);
+
+impl Filter for IpFilter {
+    fn matches(&self, msg: &SomeIPMessage) -> bool {
+        self.0.contains(&msg.src_ip) || self.0.contains(&msg.dst_ip)
+    }
+}
+
+/// 按消息类型过滤
+pub struct MessageTypeFilter(pub Vec<MessageType>);
+
+impl Filter for MessageTypeFilter {
+    fn matches(&self, msg: &SomeIPMessage) -> bool {
+        self.0.contains(&msg.header.message_type)
+    }
+}
+
+/// 按返回码过滤
+pub struct ReturnCodeFilter(pub Vec<ReturnCode>);
+
+impl Filter for ReturnCodeFilter {
+    fn matches(&self, msg: &SomeIPMessage) -> bool {
+        self.0.contains(&msg.header.return_code)
+    }
+}
+
+/// 负载中包含指定字节序列即匹配，用于在全部负载中查找 VIN、错误码等特征
+#[derive(Debug, Clone)]
+pub struct PayloadPatternFilter {
+    pub pattern: Vec<u8>,
+}
+
+impl PayloadPatternFilter {
+    /// 从十六进制字符串（如 `DEADBEEF`，可带 `0x` 前缀）构造
+    pub fn new(hex_pattern: &str) -> Result<Self> {
+        let digits = hex_pattern
+            .strip_prefix("0x")
+            .or_else(|| hex_pattern.strip_prefix("0X"))
+            .unwrap_or(hex_pattern);
+        let pattern = hex::decode(digits).map_err(|e| {
+            SomeIPError::ConfigError(format!("invalid payload pattern {hex_pattern:?}: {e}"))
+        })?;
+        if pattern.is_empty() {
+            return Err(SomeIPError::ConfigError("payload pattern is empty".to_string()).into());
+        }
+        Ok(Self { pattern })
+    }
+}
+
+impl Filter for PayloadPatternFilter {
+    fn matches(&self, msg: &SomeIPMessage) -> bool {
+        memchr::memmem::find(&msg.payload, &self.pattern).is_some()
+    }
+}
+
+/// 按头部中的服务/方法 ID 过滤，在解析头部后立即使用，TP 重组与会话关联之前跳过不关心的消息
+///
+/// 两个列表均指定时要求同时匹配，为空的列表不做限制。
+#[derive(Debug, Clone, Default)]
+pub struct HeaderIdFilter {
+    pub service_ids: Vec<u16>,
+    pub method_ids: Vec<u16>,
+}
+
+impl HeaderIdFilter {
+    pub fn matches(&self, header: &SomeIPHeader) -> bool {
+        (self.service_ids.is_empty() || self.service_ids.contains(&header.service_id))
+            && (self.method_ids.is_empty() || self.method_ids.contains(&header.method_id))
+    }
+}
+
+/// 按源/目的地址与端口提前过滤，在解析 SomeIP 之前跳过不关心的数据包
+///
+/// 各条件之间为“与”关系，同一列表中的多个取值为“或”关系，为空的列表不做限制。
+#[derive(Debug, Clone, Default)]
+pub struct EndpointFilter {
+    pub src_ips: Vec<IpNet>,
+    pub dst_ips: Vec<IpNet>,
+    pub src_ports: Vec<u16>,
+    pub dst_ports: Vec<u16>,
+}
+
+impl EndpointFilter {
+    pub fn matches(&self, src_ip: &IpAddr, dst_ip: &IpAddr, src_port: u16, dst_port: u16) -> bool {
+        (self.src_ips.is_empty() || self.src_ips.iter().any(|net| net.contains(src_ip)))
+            && (self.dst_ips.is_empty() || self.dst_ips.iter().any(|net| net.contains(dst_ip)))
+            && (self.src_ports.is_empty() || self.src_ports.contains(&src_port))
+            && (self.dst_ports.is_empty() || self.dst_ports.contains(&dst_port))
+    }
+}
+
+/// 所有子条件均匹配时匹配（为空时匹配所有消息）
+pub struct AndFilter(pub Vec<Box<dyn Filter>>);
+
+impl Filter for AndFilter {
+    fn matches(&self, msg: &SomeIPMessage) -> bool {
+        self.0.iter().all(|filter| filter.matches(msg))
+    }
+}
+
+/// 任一子条件匹配时匹配（为空时不匹配任何消息）
+pub struct OrFilter(pub Vec<Box<dyn Filter>>);
+
+impl Filter for OrFilter {
+    fn matches(&self, msg: &SomeIPMessage) -> bool {
+        self.0.iter().any(|filter| filter.matches(msg))
+    }
+}
+
+/// 重复消息检测：按地址、端口、服务/方法/会话 ID 与负载的 Blake3 哈希识别
+///
+/// 只保留最近 `capacity` 条消息的哈希，镜像端口产生的重复消息通常紧随原消息出现。
+pub struct DeduplicateFilter {
+    seen: LruCache<[u8; 32], ()>,
+}
+
+impl DeduplicateFilter {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            seen: LruCache::new(capacity),
+        }
+    }
+
+    /// 同一消息第二次及之后出现时返回 true
+    pub fn is_duplicate(&mut self, msg: &SomeIPMessage) -> bool {
+        let mut hasher = blake3::Hasher::new();
+        for ip in [msg.src_ip, msg.dst_ip] {
+            match ip {
+                IpAddr::V4(ip) => hasher.update(&ip.octets()),
+                IpAddr::V6(ip) => hasher.update(&ip.octets()),
+            };
+        }
+        hasher.update(&msg.src_port.to_be_bytes());
+        hasher.update(&msg.dst_port.to_be_bytes());
+        hasher.update(&msg.header.service_id.to_be_bytes());
+        hasher.update(&msg.header.method_id.to_be_bytes());
+        hasher.update(&msg.header.session_id.to_be_bytes());
+        hasher.update(&msg.payload);
+
+        self.seen.put(hasher.finalize().into(), ()).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::message::someip_message;
+
+    fn with_payload(payload: &[u8]) -> SomeIPMessage {
+        let mut msg = someip_message(0x1234, 0x8001, 1, MessageType::Notification);
+        msg.payload = payload.to_vec();
+        msg
+    }
+
+    #[test]
+    fn payload_pattern_matches_anywhere_in_payload() {
+        let filter = PayloadPatternFilter::new("DEADBEEF").unwrap();
+        assert!(filter.matches(&with_payload(&[0xDE, 0xAD, 0xBE, 0xEF, 0x00, 0x01])));
+        assert!(filter.matches(&with_payload(&[0x00, 0xDE, 0xAD, 0xBE, 0xEF, 0x01])));
+        assert!(filter.matches(&with_payload(&[0x00, 0x01, 0xDE, 0xAD, 0xBE, 0xEF])));
+        assert!(filter.matches(&with_payload(&[0xDE, 0xAD, 0xBE, 0xEF])));
+    }
+
+    #[test]
+    fn payload_pattern_rejects_partial_matches() {
+        let filter = PayloadPatternFilter::new("0xDEADBEEF").unwrap();
+        // 模式被负载末尾截断
+        assert!(!filter.matches(&with_payload(&[0x00, 0xDE, 0xAD, 0xBE])));
+        // 模式比负载长
+        assert!(!filter.matches(&with_payload(&[0xAD, 0xBE])));
+        assert!(!filter.matches(&with_payload(&[])));
+        assert!(!filter.matches(&with_payload(&[0xDE, 0xAD, 0x00, 0xBE, 0xEF])));
+    }
+
+    #[test]
+    fn payload_pattern_requires_non_empty_hex() {
+        assert_eq!(
+            PayloadPatternFilter::new("0Xab01").unwrap().pattern,
+            [0xAB, 0x01]
+        );
+        assert!(PayloadPatternFilter::new("").is_err());
+        assert!(PayloadPatternFilter::new("0x").is_err());
+        assert!(PayloadPatternFilter::new("ABC").is_err());
+        assert!(PayloadPatternFilter::new("XYZW").is_err());
+    }
+
+    #[test]
+    fn header_id_filter_requires_both_lists() {
+        let header = |service_id, method_id| {
+            someip_message(service_id, method_id, 1, MessageType::Notification).header
+        };
+        let filter = HeaderIdFilter {
+            service_ids: vec![0x1234],
+            method_ids: vec![0x8001, 0x8002],
+        };
+        assert!(filter.matches(&header(0x1234, 0x8002)));
+        assert!(!filter.matches(&header(0x1234, 0x8003)));
+        assert!(!filter.matches(&header(0x5678, 0x8001)));
+        // 为空的列表不做限制
+        assert!(HeaderIdFilter::default().matches(&header(0x5678, 0x8003)));
+    }
+
+    #[test]
+    fn second_occurrence_is_duplicate() {
+        let mut filter = DeduplicateFilter::new(NonZeroUsize::new(2).unwrap());
+        let msg = with_payload(&[0x01]);
+        assert!(!filter.is_duplicate(&msg));
+        assert!(filter.is_duplicate(&msg));
+        assert!(filter.is_duplicate(&msg));
+
+        // 会话 ID 或负载不同的消息不是重复
+        let mut next_session = msg.clone();
+        next_session.header.session_id = 2;
+        assert!(!filter.is_duplicate(&next_session));
+        assert!(!filter.is_duplicate(&with_payload(&[0x02])));
+        // 只记住最近 2 条消息，最早的消息已被淘汰
+        assert!(!filter.is_duplicate(&msg));
+    }
+
+    #[test]
+    fn endpoint_filter_matches_ipv6_prefix() {
+        let filter = EndpointFilter {
+            src_ips: vec!["fd00:1::/64".parse().unwrap()],
+            ..EndpointFilter::default()
+        };
+        let dst: IpAddr = "fd00:2::1".parse().unwrap();
+        assert!(filter.matches(&"fd00:1::42".parse().unwrap(), &dst, 1, 2));
+        assert!(!filter.matches(&"fd00:1:0:1::42".parse().unwrap(), &dst, 1, 2));
+    }
+
+    #[test]
+    fn ip_filter_matches_source_or_destination() {
+        // 测试消息为 10.0.0.1 → 10.0.0.2
+        let msg = with_payload(&[]);
+        assert!(IpFilter("10.0.0.1/32".parse().unwrap()).matches(&msg));
+        assert!(IpFilter("10.0.0.2/32".parse().unwrap()).matches(&msg));
+        assert!(IpFilter("10.0.0.0/24".parse().unwrap()).matches(&msg));
+        assert!(!IpFilter("10.0.1.0/24".parse().unwrap()).matches(&msg));
+    }
+
+    #[test]
+    fn message_type_and_return_code_filters_match_listed_values() {
+        let mut msg = someip_message(0x1234, 0x0001, 1, MessageType::Response);
+        msg.header.return_code = ReturnCode::NotReady;
+
+        let by_type = MessageTypeFilter(vec![MessageType::Response, MessageType::Error]);
+        assert!(by_type.matches(&msg));
+        assert!(!MessageTypeFilter(vec![MessageType::Request]).matches(&msg));
+        assert!(!MessageTypeFilter(Vec::new()).matches(&msg));
+
+        assert!(ReturnCodeFilter(vec![ReturnCode::Ok, ReturnCode::NotReady]).matches(&msg));
+        assert!(!ReturnCodeFilter(vec![ReturnCode::Ok]).matches(&msg));
+        msg.header.return_code = ReturnCode::Unknown(0x2A);
+        assert!(ReturnCodeFilter(vec![ReturnCode::Unknown(0x2A)]).matches(&msg));
+    }
+
+    #[test]
+    fn and_or_combine_sub_filters() {
+        let msg = with_payload(&[0xAB]);
+        let matching = || -> Box<dyn Filter> { Box::new(PayloadPatternFilter::new("AB").unwrap()) };
+        let missing = || -> Box<dyn Filter> { Box::new(PayloadPatternFilter::new("CD").unwrap()) };
+
+        assert!(AndFilter(vec![matching(), matching()]).matches(&msg));
+        assert!(!AndFilter(vec![matching(), missing()]).matches(&msg));
+        assert!(OrFilter(vec![missing(), matching()]).matches(&msg));
+        assert!(!OrFilter(vec![missing(), missing()]).matches(&msg));
+
+        // 为空时 AndFilter 匹配所有消息，OrFilter 不匹配任何消息
+        assert!(AndFilter(Vec::new()).matches(&msg));
+        assert!(!OrFilter(Vec::new()).matches(&msg));
+    }
+}
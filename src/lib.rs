@@ -0,0 +1,37 @@
+//! SomeIP 协议解析库，可在其他 crate 中嵌入使用
+//!
+//! 命令行工具 `someip-parser` 基于本库实现；常用类型在 crate 根部重新导出。
+//!
+//! ```
+//! use someip_parser::parser::someip::header::parse_someip_header;
+//! use someip_parser::{MessageType, ReturnCode};
+//!
+//! let bytes = [
+//!     0x12, 0x34, 0x80, 0x01, // 服务 ID / 事件 ID
+//!     0x00, 0x00, 0x00, 0x08, // 长度
+//!     0x00, 0x01, 0x00, 0x02, // 客户端 ID / 会话 ID
+//!     0x01, 0x01, 0x02, 0x00, // 协议版本 / 接口版本 / 消息类型 / 返回码
+//! ];
+//! let (_, header) = parse_someip_header(&bytes).unwrap();
+//! assert_eq!(header.service_id, 0x1234);
+//! assert_eq!(header.method_id, 0x8001);
+//! assert_eq!(header.message_type, MessageType::Notification);
+//! assert_eq!(header.return_code, ReturnCode::Ok);
+//! ```
+
+pub mod analysis;
+pub mod config;
+pub mod error;
+pub mod filter;
+pub mod output;
+pub mod parser;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod utils;
+
+pub use error::SomeIPError;
+pub use output::formatter::{FormattedMessage, Formatter};
+pub use parser::someip::header::{MessageType, ReturnCode, SomeIPHeader};
+pub use parser::someip::matrix::Matrix;
+pub use parser::someip::sd_parser::SDPacket;
+pub use parser::someip::session::{SessionManager, SomeIPMessage};
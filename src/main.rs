@@ -1,41 +1,70 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use bytes::Bytes;
 use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
 use std::time::{Duration, SystemTime};
 use tokio::sync::mpsc;
 
 // 导入核心模块
-mod config;
-mod error;
-mod output;
-mod parser;
-mod utils;
+use someip_parser::{analysis, config, error, filter, output, parser};
 
+use analysis::event_timing::{EventTimingTracker, format_event_timing};
+use analysis::flows::{FlowTracker, ParseDepth, format_flows};
+use analysis::sd_consistency::{SdConsistencyChecker, format_sd_consistency};
+use analysis::summary::{Stats, format_summary_as, service_stats_json};
 use config::Config;
 use error::SomeIPError;
-use output::{exporter::Exporter, formatter::*};
+use filter::{DeduplicateFilter, EndpointFilter, Filter, HeaderIdFilter};
+use output::{
+    exporter::{Exporter, StreamingExporter},
+    formatter::*,
+    html::HtmlFormatter,
+    manifest::Manifest,
+    pcap_writer::{PcapWriter, PendingFrames},
+    sort::sort_messages,
+    sqlite::SqliteExporter,
+    wireshark::WiresharkJsonFormatter,
+};
 use parser::{
+    checksum::ChecksumVerifier,
     flow_control::TcpFlowController,
-    link_layer::parse_link_layer,
-    network_layer::parse_network_layer,
-    pcap_reader::{PCAPReader, RawPacket},
+    link_layer::{LinkLayer, LinkType, parse_link_layer},
+    network_layer::{NetworkLayer, parse_network_layer},
+    pcap_reader::{MergingPCAPReader, PCAPReader, RawPacket},
     someip::{
-        header::parse_someip_header,
-        matrix::Matrix,
+        e2e::{E2EProfile, E2EStatus, parse_e2e_header, verify_e2e_crc},
+        header::{SomeIPHeader, parse_someip_header},
+        matrix::{Matrix, format_matrix_summary},
         msi_parser::parse_msi_packet,
-        sd_parser::{SDPacket, parse_sd_packet},
+        sd_offers::{OfferTracker, format_sd_offers},
+        sd_parser::{SDEntry, SDPacket, decode_sd_packet},
+        sd_reboot::{RebootDetected, RebootTracker, format_sd_reboots},
+        sd_subscription::{
+            SubscriptionTracker, format_rejected_subscription, format_subscription_summary,
+        },
         session::{SessionManager, SomeIPMessage},
+        stream_decoder::{SomeIpStreamDecoder, StreamKey},
         tp_parser::{TPParser, parse_tp_segment},
     },
-    transport_layer::parse_transport_layer,
+    transport_layer::{TransportLayer, UDPPacketInfo, parse_transport_layer},
+    tunnel::{
+        GRE_PROTO_TRANSPARENT_ETHERNET, IP_PROTOCOL_GRE, MAX_TUNNEL_DEPTH, TunnelInfo, TunnelKind,
+        parse_gre, parse_vxlan,
+    },
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // 解析命令行参数try_parse
-    let cli = Config::parse();
+    // 解析命令行参数，并合并配置文件
+    let cli = Config::load()?;
+    if let Some(path) = &cli.write_example_config {
+        Config::write_example_config(path)?;
+        println!("已写入示例配置文件: {}", path.display());
+        return Ok(());
+    }
     cli.validate()?;
 
     // 初始化日志
@@ -43,287 +72,1362 @@ async fn main() -> Result<()> {
     info!("SomeIP 解析工具启动");
     debug!("命令行参数: {:?}", cli);
 
+    // 重新格式化先前导出的 JSON 结果
+    if let Some(path) = &cli.reformat {
+        info!("重新格式化结果文件: {}", path.display());
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取结果文件: {}", path.display()))?;
+        let mut formatted: Vec<FormattedMessage> =
+            serde_json::from_str(&content).context("结果文件不是有效的 JSON 输出")?;
+        if let Some(key) = cli.sort_by {
+            sort_messages(&mut formatted, key, cli.sort_desc);
+        }
+        export_results(&cli, &formatted)?;
+        return Ok(());
+    }
+
     // 加载矩阵文件（如果提供）
     let mut matrix = Matrix::new();
-    if let Some(matrix_path) = &cli.matrix_file {
+    matrix.set_field_id_scheme(cli.field_id_scheme);
+    matrix.set_conflict_policy(cli.matrix_conflict_policy);
+    for matrix_path in &cli.matrix_file {
         info!("加载矩阵文件: {}", matrix_path.display());
-        matrix.load_from_file(matrix_path)?;
+        let mut loaded = Matrix::new();
+        loaded.set_field_id_scheme(cli.field_id_scheme);
+        loaded.load_from_file(matrix_path)?;
+        matrix
+            .merge(loaded)
+            .with_context(|| format!("无法合并矩阵文件: {}", matrix_path.display()))?;
+    }
+    if cli.matrix_file.len() > 1 {
+        info!(
+            "已合并 {} 个矩阵文件，共 {} 个服务",
+            cli.matrix_file.len(),
+            matrix.len()
+        );
+    }
+
+    // 只检查矩阵文件
+    if cli.validate_only {
+        let summary = matrix.summary();
+        print!("{}", format_matrix_summary(&summary));
+        if !summary.invalid_ids.is_empty() {
+            anyhow::bail!("矩阵文件中有 {} 个无法解析的 ID", summary.invalid_ids.len());
+        }
+        return Ok(());
     }
 
     // 初始化核心组件
-    let (packet_tx, mut packet_rx) = mpsc::channel(1000);
-    let mut session_manager = SessionManager::new(
-        Duration::from_secs(cli.request_timeout),
-        10000, // 最大会话数
-    );
-    let mut tp_parser = TPParser::new(Duration::from_secs(cli.tp_timeout));
-    let mut tcp_flow = TcpFlowController::new(
-        100,                                  // 最大TCP连接数
-        Duration::from_secs(30),              // 分段超时
-        Duration::from_secs(cli.tcp_timeout), // 连接超时
-    );
-    let mut known_ports = std::collections::HashSet::new();
+    let (packet_tx, mut packet_rx) = mpsc::channel(cli.channel_capacity);
+    let mut known_ports = HashSet::new();
     known_ports.insert(cli.sd_port); // 初始已知端口：SD端口
+    let mut processor = PacketProcessor {
+        sd_port: cli.sd_port,
+        time_range: cli.time_range()?,
+        vlan_filter: cli.vlan,
+        vxlan_port: cli.vxlan_port,
+        known_ports,
+        offered_ports: HashMap::new(),
+        session_manager: SessionManager::new(
+            Duration::from_secs(cli.request_timeout),
+            10000, // 最大会话数
+            cli.session_history_size,
+        ),
+        tp_parser: TPParser::new(Duration::from_secs(cli.tp_timeout)),
+        tcp_flow: TcpFlowController::new(
+            100,                                  // 最大TCP连接数
+            Duration::from_secs(30),              // 分段超时
+            Duration::from_secs(cli.tcp_timeout), // 连接超时
+            cli.tcp_max_buffer,                   // 每个连接的乱序缓存上限
+        ),
+        stream_decoder: SomeIpStreamDecoder::new(cli.max_someip_stream_length),
+        max_someip_length: cli.max_someip_length,
+        sd_checker: SdConsistencyChecker::new(),
+        reboot_tracker: RebootTracker::new(),
+        reboot_events: Vec::new(),
+        subscription_tracker: SubscriptionTracker::new(),
+        offer_tracker: OfferTracker::new(),
+        parse_depth: cli.parse_depth,
+        flow_tracker: FlowTracker::new(),
+        stats: Stats::new(),
+        checksum_verifier: cli
+            .verify_checksums
+            .then(|| ChecksumVerifier::new(cli.checksum_lenient)),
+        filter: cli.build_filter(),
+        header_filter: cli.build_header_filter(),
+        endpoint_filter: cli.build_endpoint_filter(),
+        deduplicate: cli
+            .deduplicate
+            .then(|| DeduplicateFilter::new(DEDUPLICATE_CACHE_SIZE)),
+        e2e_profile: cli.e2e_profile,
+        e2e_data_id: cli.e2e_data_id,
+        e2e_ids: cli.e2e_id.iter().copied().collect(),
+        verbose: cli.verbose,
+        timeout_sweep_interval: cli.timeout_sweep_interval.map(Duration::from_secs),
+        next_sweep: None,
+        last_timestamp: None,
+        messages: Vec::new(),
+        pcap_writer: cli
+            .write_pcap
+            .as_deref()
+            .map(PcapWriter::create)
+            .transpose()
+            .context("无法创建 PCAP 输出文件")?,
+        packet_matched: false,
+        filtered_requests: HashSet::new(),
+        pending_frames: PendingFrames::new(),
+        message_frames: Vec::new(),
+        matched_frames: Vec::new(),
+        partial_sources: Vec::new(),
+    };
+    processor
+        .session_manager
+        .set_eviction_policy(cli.session_eviction_policy);
+    processor.tcp_flow.set_gap_policy(cli.tcp_gap_policy);
+    processor
+        .stream_decoder
+        .set_resync_max_length(cli.tcp_resync_max_length);
+    if let Some(max_segments) = cli.tcp_max_segments {
+        processor.tcp_flow.set_max_buffered_segments(max_segments);
+    }
 
     // 启动 PCAP 读取器
-    info!("开始读取 PCAP 文件: {}", cli.pcap_file.display());
-    let mut pcap_reader = PCAPReader::new(cli.pcap_file.to_str().context("无效的PCAP路径")?)?;
-    tokio::spawn(async move {
+    let mut readers = Vec::new();
+    for pcap_file in &cli.pcap_file {
+        info!("开始读取 PCAP 文件: {}", pcap_file.display());
+        let path = pcap_file.to_str().context("无效的PCAP路径")?;
+        let reader = PCAPReader::new(path, cli.input_format)?;
+        processor.stats.add_file(path, reader.capture_info());
+        readers.push(reader);
+    }
+    let mut pcap_reader = MergingPCAPReader::new(readers);
+    if let Some(filter) = &cli.filter {
+        info!("应用 BPF 过滤表达式: {}", filter);
+        pcap_reader.set_filter(filter)?;
+    }
+    if cli.follow {
+        info!("跟随模式：等待文件追加新的数据包，按 Ctrl-C 结束");
+        pcap_reader.set_follow(FOLLOW_POLL_INTERVAL);
+    }
+    pcap_reader.set_drop_when_full(cli.drop_when_full);
+    let reader_task = tokio::spawn(async move {
         if let Err(e) = pcap_reader.start(packet_tx).await {
             warn!("PCAP 读取器错误: {}", e);
         }
+        pcap_reader.channel_stats().clone()
     });
 
-    // 处理数据包
-    let mut messages = Vec::new();
-    while let Some(raw_packet) = packet_rx.recv().await {
-        let _ = process_raw_packet(
-            &raw_packet,
-            cli.sd_port,
-            &mut known_ports,
-            &mut session_manager,
-            &mut tp_parser,
-            &mut tcp_flow,
-            &matrix,
-            &mut messages,
+    // 跟随模式下消息产生后立即输出
+    let format_options = FormatOptions {
+        show_vlan: cli.show_vlan,
+        show_tunnel: cli.show_tunnel,
+        max_payload_bytes: cli.max_payload_bytes,
+        empty_payload_as_null: cli.include_empty_payload_as_null
+            && matches!(cli.output_format.as_str(), "json" | "yaml"),
+    };
+    let mut stream = if cli.follow && cli.parse_depth == ParseDepth::Someip {
+        Some(StreamingExporter::new(
+            create_formatter(&cli),
+            &cli.output_format,
+            cli.output_file.as_deref(),
+            cli.output_buffered
+                .then(|| Duration::from_millis(cli.output_flush_interval)),
+        )?)
+    } else {
+        None
+    };
+    // 已流式输出的消息不再保留，只计数并在输出时记入报告
+    let mut streamed = 0;
+    let mut event_timing = cli.event_timing.then(EventTimingTracker::new);
+
+    // 处理数据包，收到 Ctrl-C 时停止读取并照常输出结果
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    // 缓冲输出时即使没有新消息也定期刷新，保证已输出的消息及时落盘
+    let mut flush_timer =
+        tokio::time::interval(Duration::from_millis(cli.output_flush_interval.max(1)));
+    loop {
+        let raw_packet = tokio::select! {
+            packet = packet_rx.recv() => match packet {
+                Some(packet) => packet,
+                None => break,
+            },
+            _ = &mut ctrl_c => {
+                info!("收到中断信号，停止读取");
+                break;
+            }
+            _ = flush_timer.tick(), if cli.output_buffered => {
+                if let Some(stream) = &mut stream {
+                    stream.flush()?;
+                }
+                continue;
+            }
+        };
+        if let Err(e) = processor.process_raw_packet(&raw_packet) {
+            processor.stats.parse_errors += 1;
+            debug!(
+                "数据包处理失败 ({}): {}",
+                raw_packet.source_file.as_deref().unwrap_or("-"),
+                e
+            );
+        }
+
+        if let Some(stream) = &mut stream
+            && !processor.messages.is_empty()
+        {
+            let batch = std::mem::take(&mut processor.messages);
+            let formatted = batch
+                .iter()
+                .map(|msg| convert_to_formatted(msg, &matrix, &format_options))
+                .collect::<Vec<_>>();
+            stream.write(&formatted)?;
+            streamed += batch.len();
+            for msg in &batch {
+                record_reports(msg, &cli, &mut event_timing, &mut processor.sd_checker);
+            }
+        }
+    }
+    // 关闭通道让读取器退出，并取回通道背压统计
+    drop(packet_rx);
+    let channel_stats = reader_task.await?;
+    if channel_stats.dropped > 0 {
+        warn!(
+            "处理速度跟不上读取，已丢弃 {} 个数据包",
+            channel_stats.dropped
+        );
+    }
+    processor
+        .stats
+        .record_channel(cli.channel_capacity, &channel_stats);
+
+    let PacketProcessor {
+        mut session_manager,
+        mut sd_checker,
+        reboot_events,
+        mut subscription_tracker,
+        offer_tracker,
+        flow_tracker,
+        mut stats,
+        checksum_verifier,
+        tp_parser,
+        tcp_flow,
+        stream_decoder,
+        last_timestamp,
+        mut messages,
+        pcap_writer,
+        filtered_requests,
+        ..
+    } = processor;
+    let mut manifest = Manifest::new();
+    if let Some(writer) = pcap_writer {
+        let (written, skipped) = writer.finish().context("无法写入 PCAP 输出文件")?;
+        if let Some(path) = &cli.write_pcap {
+            manifest
+                .add(path, "pcap", written)
+                .context("无法读取 PCAP 输出文件")?;
+        }
+        info!("已导出 {} 个数据包到 PCAP 文件", written);
+        if skipped > 0 {
+            warn!("{} 个数据包的链路层类型与输出文件不同，未导出", skipped);
+        }
+    }
+    stats.tcp_restarts = tcp_flow.connection_restarts();
+    stats.tcp_retransmissions = tcp_flow.retransmission_count();
+    stats.tcp_gaps_skipped = tcp_flow.gaps_skipped();
+    stats.tcp_connections = tcp_flow.connection_stats();
+    stats.tcp_closed = tcp_flow.closed_connection_stats();
+    stats.tcp_resyncs = stream_decoder.resync_count();
+    stats.tcp_resync_skipped_bytes = stream_decoder.resync_skipped_bytes();
+
+    // 输出校验和失败统计
+    if let Some(verifier) = &checksum_verifier {
+        let total = verifier.ipv4_failures + verifier.udp_failures + verifier.tcp_failures;
+        let summary = format!(
+            "校验和失败统计: IPv4 {}，UDP {}，TCP {}",
+            verifier.ipv4_failures, verifier.udp_failures, verifier.tcp_failures
+        );
+        if total > 0 {
+            warn!("{}", summary);
+        } else {
+            info!("{}", summary);
+        }
+    }
+
+    // 输出 TP 重组统计
+    if cli.verbose >= 1 {
+        let stats = tp_parser.reassembly_stats();
+        info!(
+            "TP 重组统计: 共 {} 个消息，完成 {}，超时 {}，缺口事件 {}，迟到分段 {}",
+            stats.total_messages,
+            stats.complete,
+            stats.timeout,
+            stats.gap_events,
+            stats.late_segments
         );
     }
 
+    // 输出订阅生命周期摘要，抓包结束时 TTL 已到期的订阅计为过期；被拒绝的订阅总是输出
+    let end_time = last_timestamp.unwrap_or(SystemTime::UNIX_EPOCH);
+    subscription_tracker.expire(end_time);
+    if cli.verbose >= 1 {
+        info!(
+            "{}",
+            format_subscription_summary(&subscription_tracker, &matrix).trim_end()
+        );
+    } else {
+        for rejected in subscription_tracker.rejected_subscriptions() {
+            warn!("{}", format_rejected_subscription(rejected, &matrix));
+        }
+    }
+
     // 处理超时的会话
-    let timed_out = session_manager.cleanup_expired_sessions();
+    let timed_out = session_manager.cleanup_expired_sessions(end_time);
     info!("处理完成，共 {} 个超时会话", timed_out.len());
-    for pair in timed_out {
-        messages.push(pair.request);
-    }
+    stats.timed_out_sessions += timed_out.len();
+    stats.unmatched_requests =
+        stats.timed_out_sessions + session_manager.get_pending_requests(end_time).len();
+    stats.tp_failures = tp_parser.reassembly_stats().timeout;
+    messages.extend(
+        timed_out
+            .into_iter()
+            .map(|pair| pair.request)
+            .filter(|msg| !filtered_requests.contains(&request_key(msg))),
+    );
 
     // 格式化并导出结果
-    info!("解析完成，共处理 {} 个消息", messages.len());
-    let formatted = messages
+    info!("解析完成，共处理 {} 个消息", streamed + messages.len());
+    let mut formatted = messages
         .iter()
-        .map(|msg| convert_to_formatted(msg, &matrix))
+        .map(|msg| convert_to_formatted(msg, &matrix, &format_options))
         .collect::<Vec<_>>();
+    if let Some(key) = cli.sort_by {
+        sort_messages(&mut formatted, key, cli.sort_desc);
+    }
 
-    let formatter = match cli.output_format.as_str() {
-        "json" => Box::new(JsonFormatter::new(true)) as Box<dyn Formatter>,
-        "yaml" => Box::new(YamlFormatter::new()) as Box<dyn Formatter>,
-        _ => Box::new(TextFormatter::new()) as Box<dyn Formatter>,
-    };
+    if cli.parse_depth != ParseDepth::Someip {
+        // 解析深度低于 SomeIP 时只输出流列表
+        let flows = format_flows(&flow_tracker, &cli.output_format)?;
+        match &cli.output_file {
+            Some(path) => std::fs::write(path, flows)
+                .with_context(|| format!("无法写入输出文件: {}", path.display()))?,
+            None => print!("{}", flows),
+        }
+    } else if let Some(mut stream) = stream {
+        stream.write(&formatted)?;
+        stream.finish()?;
+    } else {
+        export_results(&cli, &formatted)?;
+    }
+    if let Some(path) = &cli.output_file {
+        let records = if cli.parse_depth != ParseDepth::Someip {
+            flow_tracker.flows().count()
+        } else {
+            streamed + messages.len()
+        };
+        manifest
+            .add(path, &cli.output_format, records)
+            .context("无法读取输出文件")?;
+    }
 
-    let exporter = Exporter::new(
-        formatter,
-        cli.output_file.map(|p| p.to_string_lossy().into_owned()),
-    );
-    exporter.export(&formatted)?;
+    for msg in &messages {
+        record_reports(msg, &cli, &mut event_timing, &mut sd_checker);
+    }
+
+    // 输出事件周期与抖动报告
+    if let Some(event_timing) = &event_timing {
+        print!("{}", format_event_timing(&event_timing.report(), &matrix));
+    }
+
+    // 输出 SD 一致性报告
+    if cli.sd_consistency {
+        print!("{}", format_sd_consistency(&sd_checker.report(), &matrix));
+    }
+
+    // 输出 ECU 重启事件
+    if cli.sd_reboots {
+        print!("{}", format_sd_reboots(&reboot_events));
+    }
+
+    // 输出 SD 服务提供拓扑
+    if cli.sd_offers {
+        print!("{}", format_sd_offers(&offer_tracker, &matrix));
+    }
+
+    // 输出抓包与解析统计；消息输出到标准输出时改为输出到标准错误，保持标准输出为单个 JSON/YAML 文档
+    if cli.summary {
+        let summary = format_summary_as(&stats, &matrix, &cli.output_format)?;
+        if cli.output_file.is_none() {
+            eprint!("{}", summary);
+        } else {
+            print!("{}", summary);
+        }
+    }
+    if let Some(path) = &cli.stats_file {
+        std::fs::write(path, service_stats_json(&stats, &matrix)?)
+            .with_context(|| format!("无法写入统计文件: {}", path.display()))?;
+        manifest
+            .add(path, "json", stats.services.len())
+            .context("无法读取统计文件")?;
+    }
+    if let Some(path) = &cli.manifest {
+        manifest
+            .write(path)
+            .with_context(|| format!("无法写入输出清单: {}", path.display()))?;
+    }
+
+    // 解析失败超过容忍范围时以非零退出码结束，供 CI 判断
+    let errors = stats.parse_errors;
+    if (cli.strict && errors > 0) || cli.max_errors.is_some_and(|max| errors > max) {
+        warn!(
+            "{} 个数据包解析失败，以退出码 {} 结束",
+            errors, cli.error_exit_code
+        );
+        std::io::stdout().flush()?;
+        std::process::exit(cli.error_exit_code as i32);
+    }
 
     info!("程序正常退出");
     Ok(())
 }
 
-/// 处理单个原始数据包
-fn process_raw_packet(
-    raw_packet: &RawPacket,
+/// 将输出的消息记入事件周期与 SD 一致性报告
+fn record_reports(
+    msg: &SomeIPMessage,
+    cli: &Config,
+    event_timing: &mut Option<EventTimingTracker>,
+    sd_checker: &mut SdConsistencyChecker,
+) {
+    if let Some(event_timing) = event_timing {
+        event_timing.record(msg);
+    }
+    if cli.sd_consistency {
+        sd_checker.record_message(msg);
+    }
+}
+
+/// 跟随模式下检查文件是否有新数据的间隔
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `--deduplicate` 记住的最近消息数
+const DEDUPLICATE_CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(4096).unwrap();
+
+/// 可能跨多个帧的消息来源：TCP 连接的一个方向，或一条 TP 分段消息 (服务ID, 客户端ID, 会话ID)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum FrameSource {
+    Tcp(StreamKey),
+    Tp((u16, u16, u16)),
+}
+
+/// 单个数据包在解析过程中携带的上下文
+#[derive(Debug, Clone)]
+struct PacketContext {
+    timestamp: SystemTime,
+    vlan_id: Option<u16>,
+    tunnel: Option<TunnelInfo>,
+    depth: usize,
+}
+
+/// 数据包处理器，持有跨数据包共享的解析状态
+struct PacketProcessor {
     sd_port: u16,
-    known_ports: &mut std::collections::HashSet<u16>,
-    session_manager: &mut SessionManager,
-    tp_parser: &mut TPParser,
-    tcp_flow: &mut TcpFlowController,
-    matrix: &Matrix,
-    messages: &mut Vec<SomeIPMessage>,
-) -> Result<()> {
-    // debug!("处理数据包: {:?}", raw_packet);
-
-    // 解析链路层
-    let (payload, link_layer) = parse_link_layer(&raw_packet.data)
-        .map_err(|e| SomeIPError::InvalidPacketFormat(format!("链路层解析失败: {}", e)))?;
-
-    // 解析网络层
-    let (link_payload, ethertype) = match &link_layer {
-        parser::link_layer::LinkLayer::Ethernet(eth) => (payload, eth.ethertype),
-        parser::link_layer::LinkLayer::SLL(sll) => (payload, sll.protocol),
-    };
-    let (network_payload, network_layer) = parse_network_layer(link_payload, ethertype)
-        .map_err(|e| SomeIPError::InvalidPacketFormat(format!("网络层解析失败: {}", e)))?;
-
-    // 提取 IP 地址
-    let (src_ip, dst_ip, protocol) = match &network_layer {
-        parser::network_layer::NetworkLayer::IPv4(ipv4) => (
-            IpAddr::V4(std::net::Ipv4Addr::from(ipv4.src_ip)),
-            IpAddr::V4(std::net::Ipv4Addr::from(ipv4.dst_ip)),
-            ipv4.protocol,
-        ),
-        parser::network_layer::NetworkLayer::IPv6(ipv6) => (
-            IpAddr::V6(std::net::Ipv6Addr::from(ipv6.src_ip)),
-            IpAddr::V6(std::net::Ipv6Addr::from(ipv6.dst_ip)),
-            ipv6.next_header,
-        ),
-    };
+    time_range: (Option<SystemTime>, Option<SystemTime>), // [起始, 结束)
+    vlan_filter: Option<u16>,
+    vxlan_port: u16,
+    known_ports: HashSet<u16>,
+    offered_ports: HashMap<(IpAddr, u16, u16), HashSet<u16>>, // (提供方地址, 服务ID, 实例ID) -> 提供的端口
+    session_manager: SessionManager,
+    tp_parser: TPParser,
+    tcp_flow: TcpFlowController,
+    stream_decoder: SomeIpStreamDecoder,
+    max_someip_length: u32, // UDP 数据报中 SomeIP 长度字段的上限
+    sd_checker: SdConsistencyChecker,
+    reboot_tracker: RebootTracker,
+    reboot_events: Vec<RebootDetected>,
+    subscription_tracker: SubscriptionTracker,
+    offer_tracker: OfferTracker,
+    parse_depth: ParseDepth,
+    flow_tracker: FlowTracker,
+    stats: Stats,
+    checksum_verifier: Option<ChecksumVerifier>,
+    filter: Box<dyn Filter>,
+    header_filter: HeaderIdFilter,
+    endpoint_filter: EndpointFilter,
+    deduplicate: Option<DeduplicateFilter>,
+    e2e_profile: Option<E2EProfile>,
+    e2e_data_id: u32,
+    e2e_ids: HashSet<(u16, u16)>, // 受 E2E 保护的 (服务ID, 方法ID)
+    verbose: u8,
+    timeout_sweep_interval: Option<Duration>,
+    next_sweep: Option<SystemTime>,
+    last_timestamp: Option<SystemTime>,
+    messages: Vec<SomeIPMessage>,
+    pcap_writer: Option<PcapWriter>, // --write-pcap：导出包含通过过滤消息的原始帧
+    packet_matched: bool,            // 当前数据包中是否有消息通过过滤
+    filtered_requests: HashSet<(u16, u16, u16)>, // 未通过过滤、只为配对保留的请求 (服务ID, 客户端ID, 会话ID)
+    pending_frames: PendingFrames<FrameSource>,  // 未完成消息此前收到的帧（仅 --write-pcap）
+    message_frames: Vec<RawPacket>, // 正在处理的消息此前收到的帧，消息通过过滤时移入 matched_frames
+    matched_frames: Vec<RawPacket>, // 需要在当前数据包之前导出的帧
+    partial_sources: Vec<FrameSource>, // 当前数据包中仍有未完成消息的来源
+}
+
+impl PacketProcessor {
+    /// 处理单个原始数据包
+    fn process_raw_packet(&mut self, raw_packet: &RawPacket) -> Result<()> {
+        // debug!("处理数据包: {:?}", raw_packet);
+        self.stats.record_packet(raw_packet.timestamp);
+
+        // 按抓包时间范围过滤，起始时间包含在内，结束时间不包含
+        let (start, end) = self.time_range;
+        if start.is_some_and(|start| raw_packet.timestamp < start)
+            || end.is_some_and(|end| raw_packet.timestamp >= end)
+        {
+            self.stats.skipped_time += 1;
+            return Ok(());
+        }
+        self.last_timestamp = self.last_timestamp.max(Some(raw_packet.timestamp));
+
+        // 抓包时间越过检查点时，先输出已超时的请求，保证输出顺序与实际一致
+        if let Some(interval) = self.timeout_sweep_interval {
+            let due = *self
+                .next_sweep
+                .get_or_insert(raw_packet.timestamp + interval);
+            if raw_packet.timestamp >= due {
+                self.sweep_timed_out_sessions(raw_packet.timestamp);
+                self.next_sweep = Some(raw_packet.timestamp + interval);
+            }
+        }
 
-    // debug!("解析数据包: {} -> {}, 协议: {}", src_ip, dst_ip, protocol);
+        let ctx = PacketContext {
+            timestamp: raw_packet.timestamp,
+            vlan_id: None,
+            tunnel: None,
+            depth: 0,
+        };
+        self.packet_matched = false;
+        self.matched_frames.clear();
+        self.partial_sources.clear();
+        self.process_frame(raw_packet.data.clone(), raw_packet.link_type, ctx)?;
 
-    // 解析传输层
-    let (_, transport_layer) = parse_transport_layer(network_payload, protocol)
-        .map_err(|e| SomeIPError::InvalidPacketFormat(format!("传输层解析失败: {}", e)))?;
+        // 跨多个帧的消息通过过滤时，先导出此前收到的帧，再导出完成该消息的数据包；
+        // 当前数据包已导出时，其中未完成消息的部分不再重复记录
+        if let Some(writer) = self.pcap_writer.as_mut() {
+            if self.packet_matched {
+                for frame in self.matched_frames.drain(..) {
+                    writer.write_packet(&frame)?;
+                }
+                writer.write_packet(raw_packet)?;
+            } else {
+                for source in self.partial_sources.drain(..) {
+                    self.pending_frames.push(source, raw_packet);
+                }
+            }
+        }
+        Ok(())
+    }
 
-    // debug!(
-    //     "处理数据包: {} -> {}, 协议: {}, 传输层: {:?}",
-    //     src_ip, dst_ip, protocol, transport_layer
-    // );
+    /// 解析链路层，并将负载交给网络层处理
+    fn process_frame(
+        &mut self,
+        data: Bytes,
+        link_type: LinkType,
+        mut ctx: PacketContext,
+    ) -> Result<()> {
+        // 根据 pcap 链路层类型解析链路层
+        let (payload, link_layer) = parse_link_layer(&data, link_type)
+            .map_err(|e| SomeIPError::InvalidPacketFormat(format!("链路层解析失败: {}", e)))?;
+        let payload = data.slice_ref(payload);
 
-    // 处理 UDP/TCP 数据包
-    match &transport_layer {
-        parser::transport_layer::TransportLayer::UDP(udp) => {
-            // 检查是否是已知端口（SD 端口或从 SD 学习到的端口）
-            if !known_ports.contains(&udp.src_port) && !known_ports.contains(&udp.dst_port) {
+        // 按 VLAN 过滤（仅针对最外层帧），并记录消息所属的 VLAN
+        let vlan_ids = match &link_layer {
+            LinkLayer::Ethernet(eth) => eth.vlan_ids.as_slice(),
+            _ => &[],
+        };
+        ctx.vlan_id = match self.vlan_filter {
+            Some(vlan) if ctx.depth == 0 && vlan_ids.contains(&vlan) => Some(vlan),
+            Some(_) if ctx.depth == 0 => {
+                self.stats.skipped_vlan += 1;
                 return Ok(());
             }
+            _ => ctx.vlan_id.or(vlan_ids.first().copied()),
+        };
 
-            // 解析 SomeIP 头部
-            if udp.payload.len() < 16 {
-                debug!("UDP 包长度不足，跳过: {} 字节", udp.payload.len());
+        let ethertype = match &link_layer {
+            LinkLayer::Ethernet(eth) => eth.ethertype,
+            LinkLayer::SLL(sll) => sll.protocol,
+            LinkLayer::SLL2(sll2) => sll2.protocol,
+            LinkLayer::Raw(raw) => raw.ethertype,
+            LinkLayer::Loopback(lo) => lo.ethertype,
+        };
+        if self.parse_depth == ParseDepth::Link {
+            let (src, dst) = match &link_layer {
+                LinkLayer::Ethernet(eth) => (format_mac(&eth.src_mac), format_mac(&eth.dst_mac)),
+                _ => ("-".to_string(), "-".to_string()),
+            };
+            let protocol = format!("0x{:04X}", ethertype);
+            self.flow_tracker
+                .record(ctx.timestamp, protocol, src, dst, data.len());
+            return Ok(());
+        }
+        self.process_network(payload, ethertype, ctx)
+    }
+
+    /// 解析网络层与传输层，处理隧道解封装与 SomeIP 消息
+    fn process_network(
+        &mut self,
+        link_payload: Bytes,
+        ethertype: u16,
+        ctx: PacketContext,
+    ) -> Result<()> {
+        // 解析网络层
+        let (network_payload, network_layer) = parse_network_layer(&link_payload, ethertype)
+            .map_err(|e| SomeIPError::InvalidPacketFormat(format!("网络层解析失败: {}", e)))?;
+        let network_payload = link_payload.slice_ref(network_payload);
+
+        // 提取 IP 地址
+        let (src_ip, dst_ip, protocol) = match &network_layer {
+            NetworkLayer::IPv4(ipv4) => (
+                IpAddr::V4(std::net::Ipv4Addr::from(ipv4.src_ip)),
+                IpAddr::V4(std::net::Ipv4Addr::from(ipv4.dst_ip)),
+                ipv4.protocol,
+            ),
+            NetworkLayer::IPv6(ipv6) => (
+                IpAddr::V6(std::net::Ipv6Addr::from(ipv6.src_ip)),
+                IpAddr::V6(std::net::Ipv6Addr::from(ipv6.dst_ip)),
+                ipv6.next_header,
+            ),
+        };
+
+        // debug!("解析数据包: {} -> {}, 协议: {}", src_ip, dst_ip, protocol);
+
+        // 校验 IP 头部与传输层校验和
+        if let Some(verifier) = self.checksum_verifier.as_mut() {
+            let (header_ok, segment) = match &network_layer {
+                NetworkLayer::IPv4(ipv4) => {
+                    let header_len = (ipv4.header_length as usize * 4).min(link_payload.len());
+                    (
+                        verifier.check_ipv4_header(&link_payload[..header_len]),
+                        &network_payload[..],
+                    )
+                }
+                NetworkLayer::IPv6(ipv6) => (
+                    true,
+                    &network_payload[..(ipv6.payload_length as usize).min(network_payload.len())],
+                ),
+            };
+            if !header_ok || !verifier.check_transport(&src_ip, &dst_ip, protocol, segment) {
+                debug!("校验和错误，丢弃数据包: {} -> {}", src_ip, dst_ip);
                 return Ok(());
             }
-            let (_, header) = parse_someip_header(&udp.payload).map_err(|e| {
-                SomeIPError::InvalidPacketFormat(format!("SomeIP 头部解析失败: {}", e))
+        }
+
+        if self.parse_depth == ParseDepth::Network {
+            self.flow_tracker.record(
+                ctx.timestamp,
+                ip_protocol_name(protocol),
+                src_ip.to_string(),
+                dst_ip.to_string(),
+                link_payload.len(),
+            );
+            return Ok(());
+        }
+
+        // GRE 隧道：解封装后重新进入链路层/网络层处理
+        if protocol == IP_PROTOCOL_GRE {
+            let (inner, gre) = parse_gre(&network_payload).map_err(|e| {
+                SomeIPError::InvalidPacketFormat(format!("GRE 头部解析失败: {}", e))
             })?;
+            let inner = network_payload.slice_ref(inner);
+            let Some(ctx) = enter_tunnel(ctx, TunnelKind::Gre, src_ip, dst_ip) else {
+                return Ok(());
+            };
+            return if gre.protocol_type == GRE_PROTO_TRANSPARENT_ETHERNET {
+                self.process_frame(inner, LinkType::Ethernet, ctx)
+            } else {
+                self.process_network(inner, gre.protocol_type, ctx)
+            };
+        }
 
-            // 处理 SD 包（服务发现）
-            if (udp.src_port == sd_port || udp.dst_port == sd_port)
-                && header.service_id == 0xFFFF
-                && header.method_id == 0x8100
-            {
-                let (_, sd_packet) =
-                    parse_sd_packet(&udp.payload[16..], header.clone()).map_err(|e| {
-                        SomeIPError::InvalidPacketFormat(format!("SD 包解析失败: {}", e))
+        // 非首个 IPv4 分片不含传输层头部，其负载不能按 UDP/TCP 解析
+        let more_fragments = match &network_layer {
+            NetworkLayer::IPv4(ipv4) if ipv4.fragment_offset != 0 => {
+                debug!(
+                    "跳过非首个 IPv4 分片: {} -> {}，偏移 {}",
+                    src_ip,
+                    dst_ip,
+                    ipv4.fragment_offset as usize * 8
+                );
+                self.stats.skipped_fragments += 1;
+                return Ok(());
+            }
+            NetworkLayer::IPv4(ipv4) => ipv4.flags & 0x1 != 0,
+            NetworkLayer::IPv6(_) => false,
+        };
+
+        // 解析传输层
+        let (_, transport_layer) = parse_transport_layer(&network_payload, protocol)
+            .map_err(|e| SomeIPError::InvalidPacketFormat(format!("传输层解析失败: {}", e)))?;
+
+        // debug!(
+        //     "处理数据包: {} -> {}, 协议: {}, 传输层: {:?}",
+        //     src_ip, dst_ip, protocol, transport_layer
+        // );
+
+        if self.parse_depth == ParseDepth::Transport {
+            let (protocol, src_port, dst_port, payload_len) = match &transport_layer {
+                TransportLayer::UDP(udp) => ("UDP", udp.src_port, udp.dst_port, udp.payload.len()),
+                TransportLayer::TCP(tcp) => ("TCP", tcp.src_port, tcp.dst_port, tcp.payload.len()),
+            };
+            self.flow_tracker.record(
+                ctx.timestamp,
+                protocol.to_string(),
+                SocketAddr::new(src_ip, src_port).to_string(),
+                SocketAddr::new(dst_ip, dst_port).to_string(),
+                payload_len,
+            );
+            return Ok(());
+        }
+
+        // 处理 UDP/TCP 数据包
+        match &transport_layer {
+            TransportLayer::UDP(udp) => {
+                // VXLAN 隧道：解封装内层以太网帧
+                if udp.dst_port == self.vxlan_port {
+                    let (inner, vxlan) = parse_vxlan(&udp.payload).map_err(|e| {
+                        SomeIPError::InvalidPacketFormat(format!("VXLAN 头部解析失败: {}", e))
                     })?;
-                learn_ports_from_sd(&sd_packet, known_ports);
-                info!("发现 SD 包，更新已知端口: {:?}", known_ports);
+                    let inner = udp.payload.slice_ref(inner);
+                    let kind = TunnelKind::Vxlan { vni: vxlan.vni };
+                    let Some(ctx) = enter_tunnel(ctx, kind, src_ip, dst_ip) else {
+                        return Ok(());
+                    };
+                    return self.process_frame(inner, LinkType::Ethernet, ctx);
+                }
+
+                if !self
+                    .endpoint_filter
+                    .matches(&src_ip, &dst_ip, udp.src_port, udp.dst_port)
+                {
+                    self.stats.skipped_endpoint += 1;
+                    return Ok(());
+                }
+
+                // 检查是否是已知端口（SD 端口或从 SD 学习到的端口）
+                if !self.known_ports.contains(&udp.src_port)
+                    && !self.known_ports.contains(&udp.dst_port)
+                {
+                    self.stats.skipped_port += 1;
+                    return Ok(());
+                }
+
+                // 只检查 SomeIP/SD 端口上的数据报；首个分片的长度字段包含后续分片，不与抓包数据比较
+                if !more_fragments && !udp.length_fits_capture() {
+                    return Err(SomeIPError::InvalidPacketFormat(format!(
+                        "UDP 长度字段 {} 与抓包数据不符（头部之后实际只有 {} 字节）",
+                        udp.length,
+                        udp.payload.len()
+                    ))
+                    .into());
+                }
+
+                if udp.payload.len() < 16 {
+                    debug!("UDP 包长度不足，跳过: {} 字节", udp.payload.len());
+                    return Ok(());
+                }
+
+                // 一个 UDP 数据报可以依次携带多条 SomeIP 消息，按 length 字段逐条切分
+                let mut offset = 0;
+                while udp.payload.len() - offset >= 16 {
+                    let data = &udp.payload[offset..];
+                    let (_, header) = parse_someip_header(data).map_err(|e| {
+                        SomeIPError::InvalidPacketFormat(format!("SomeIP 头部解析失败: {}", e))
+                    })?;
+                    // 同一端口上可能还有其他协议，头部明显不是 SomeIP 时跳过而不是按 SomeIP 解析
+                    if !header.is_plausible() {
+                        if offset == 0 {
+                            debug!(
+                                "非 SomeIP 数据包，跳过: {}:{} -> {}:{}，{} 字节",
+                                src_ip,
+                                udp.src_port,
+                                dst_ip,
+                                udp.dst_port,
+                                udp.payload.len()
+                            );
+                            self.stats.skipped_non_someip += 1;
+                        } else {
+                            debug!(
+                                "UDP 数据报末尾 {} 字节不是 SomeIP 消息，忽略: {} -> {}",
+                                data.len(),
+                                src_ip,
+                                dst_ip
+                            );
+                        }
+                        return Ok(());
+                    }
+                    if header.length > self.max_someip_length {
+                        return Err(SomeIPError::InvalidPacketFormat(format!(
+                            "SomeIP 长度字段 {} 超过上限 {}（UDP 负载 {} 字节）: {} -> {}",
+                            header.length,
+                            self.max_someip_length,
+                            data.len(),
+                            src_ip,
+                            dst_ip
+                        ))
+                        .into());
+                    }
+                    // length 字段从 client_id 开始计算，截断或伪造的长度不能用于切分负载
+                    if !header.length_fits(data.len()) {
+                        return Err(SomeIPError::InvalidPacketFormat(format!(
+                            "SomeIP 长度字段 {} 与 UDP 负载 {} 字节不符（应在 8 到 {} 之间）: {} -> {}",
+                            header.length,
+                            data.len(),
+                            data.len() - 8,
+                            src_ip,
+                            dst_ip
+                        ))
+                        .into());
+                    }
+
+                    let message = &data[..16 + header.payload_len()];
+                    offset += message.len();
+                    self.process_udp_message(&ctx, src_ip, dst_ip, udp, header, message)?;
+                }
+                if offset < udp.payload.len() {
+                    debug!(
+                        "UDP 数据报末尾 {} 字节不足一个 SomeIP 头部，忽略: {} -> {}",
+                        udp.payload.len() - offset,
+                        src_ip,
+                        dst_ip
+                    );
+                }
             }
 
-            // 处理 TP 分段包
-            let is_tp = (header.message_type.as_u8() & 0x20) != 0; // TP 标志位
-            if is_tp {
-                let segment = parse_tp_segment(&udp.payload[16..], header.clone())?;
-                if let Some(reassembled) = tp_parser.process_segment(segment)? {
-                    let msg = create_someip_message(
-                        &raw_packet.timestamp,
+            TransportLayer::TCP(tcp) => {
+                if !self
+                    .endpoint_filter
+                    .matches(&src_ip, &dst_ip, tcp.src_port, tcp.dst_port)
+                {
+                    self.stats.skipped_endpoint += 1;
+                    return Ok(());
+                }
+
+                // 仅处理已知端口的 TCP 包
+                if !self.known_ports.contains(&tcp.src_port)
+                    && !self.known_ports.contains(&tcp.dst_port)
+                {
+                    self.stats.skipped_port += 1;
+                    return Ok(());
+                }
+                self.stats
+                    .record_tcp_stream((src_ip, tcp.src_port), (dst_ip, tcp.dst_port));
+
+                // 连接建立或异常终止时丢弃未完成的消息，重新同步消息边界
+                let stream_key = (src_ip, tcp.src_port, dst_ip, tcp.dst_port);
+                if tcp.flags.syn || tcp.flags.rst {
+                    self.discard_partial_tcp_message(&stream_key);
+                }
+
+                // 处理 TCP 流控与重组，按序数据交给消息切分器，跨分段的消息缓存到下个分段
+                let gaps_skipped = self.tcp_flow.gaps_skipped();
+                let data = self.tcp_flow.process_tcp_packet(
+                    ctx.timestamp,
+                    &src_ip,
+                    &dst_ip,
+                    tcp,
+                    tcp.payload.clone(),
+                )?;
+                // 跳过缺口后缺口前未完成的消息已无法补全，缺口之后的数据可能从消息中间开始，重新扫描头部
+                if self.tcp_flow.gaps_skipped() != gaps_skipped {
+                    self.stream_decoder.resync(&stream_key);
+                }
+                let messages = data
+                    .map(|data| self.stream_decoder.push(stream_key, &data))
+                    .unwrap_or_default();
+                if tcp.flags.fin {
+                    self.discard_partial_tcp_message(&stream_key);
+                }
+
+                // 此前收到的帧只属于本分段中完成的第一条消息
+                let source = FrameSource::Tcp(stream_key);
+                if self.pcap_writer.is_some() {
+                    if !messages.is_empty() {
+                        self.message_frames = self.pending_frames.take(&source);
+                    }
+                    if self.stream_decoder.has_partial(&stream_key)
+                        || self.tcp_flow.has_buffered_segments(
+                            &src_ip,
+                            tcp.src_port,
+                            &dst_ip,
+                            tcp.dst_port,
+                        )
+                    {
+                        self.partial_sources.push(source);
+                    }
+                }
+
+                for message in messages {
+                    if self.skip_by_header_id(&message.header) {
+                        continue;
+                    }
+                    let mut msg = create_someip_message(
+                        &ctx,
                         &src_ip,
                         &dst_ip,
-                        udp.src_port,
-                        udp.dst_port,
-                        reassembled.header,
-                        reassembled.payload,
+                        tcp.src_port,
+                        tcp.dst_port,
+                        message.header,
+                        message.payload,
                     );
-                    handle_someip_message(msg, session_manager, messages)?;
+                    msg.resynced = message.resynced;
+                    self.handle_someip_message(msg)?;
+                    self.message_frames.clear();
                 }
             }
-            // 处理 MSI 多服务包
-            else if header.service_id == 0xFFFF && header.method_id == 0x8101 {
-                let msi_packet = parse_msi_packet(&udp.payload[16..])?;
-                info!("解析 MSI 包，包含 {} 个消息", msi_packet.messages.len());
-                for msi_msg in msi_packet.messages {
-                    let msg = create_someip_message(
-                        &raw_packet.timestamp,
-                        &src_ip,
-                        &dst_ip,
-                        udp.src_port,
-                        udp.dst_port,
-                        msi_msg.header,
-                        msi_msg.payload.to_vec(),
+        }
+
+        Ok(())
+    }
+
+    /// 处理 UDP 数据报中的单条 SomeIP 消息（`message` 为完整的头部与负载）：SD、TP 分段、MSI 或普通消息
+    fn process_udp_message(
+        &mut self,
+        ctx: &PacketContext,
+        src_ip: IpAddr,
+        dst_ip: IpAddr,
+        udp: &UDPPacketInfo,
+        header: SomeIPHeader,
+        message: &[u8],
+    ) -> Result<()> {
+        // 处理 SD 包（服务发现）
+        if (udp.src_port == self.sd_port || udp.dst_port == self.sd_port)
+            && header.service_id == 0xFFFF
+            && header.method_id == 0x8100
+        {
+            let sd_packet = decode_sd_packet(&message[16..], header.clone())?;
+            self.stats.sd_packets += 1;
+            if let Some(reboot) =
+                self.reboot_tracker
+                    .record(ctx.timestamp, src_ip, udp.src_port, dst_ip, &sd_packet)
+            {
+                warn!(
+                    "检测到 ECU 重启: {}:{}，SD 会话 ID 0x{:04X} -> 0x{:04X}",
+                    reboot.src_ip, reboot.src_port, reboot.previous_session_id, reboot.session_id
+                );
+                self.forget_rebooted_node(reboot.src_ip);
+                self.reboot_events.push(reboot);
+            }
+            self.learn_ports_from_sd(src_ip, &sd_packet);
+            self.sd_checker.record_sd_packet(&sd_packet);
+            for mismatch in self.offer_tracker.record(src_ip, &sd_packet) {
+                warn!(
+                    "服务 0x{:04X}/0x{:04X} 的端点 {}:{} 与 SD 报文源地址 {} 不一致",
+                    mismatch.service_id,
+                    mismatch.instance_id,
+                    mismatch.endpoint.ip,
+                    mismatch.endpoint.port,
+                    mismatch.provider
+                );
+            }
+            for rejected in self.subscription_tracker.record(
+                ctx.timestamp,
+                src_ip,
+                udp.src_port,
+                dst_ip,
+                &sd_packet,
+            ) {
+                let (service_id, instance_id, eventgroup_id) = rejected.key;
+                warn!(
+                    "订阅被拒绝: 服务 0x{:04X}/0x{:04X} 事件组 0x{:04X}，{} -> {}，返回码 {}，TTL {}",
+                    service_id,
+                    instance_id,
+                    eventgroup_id,
+                    rejected.subscriber_ip,
+                    rejected.server_ip,
+                    rejected.return_code,
+                    rejected.ttl
+                );
+            }
+            info!("发现 SD 包，更新已知端口: {:?}", self.known_ports);
+        }
+
+        // 按服务/方法 ID 提前过滤（SD 已在上面处理，只影响是否输出；MSI 容器按其中的消息逐条过滤）
+        if self.skip_by_header_id(&header) {
+            return Ok(());
+        }
+
+        // 处理 TP 分段包
+        if header.is_tp {
+            let segment = parse_tp_segment(&message[16..], header.clone())?;
+            let reassembled = self.tp_parser.process_segment(segment)?;
+            let tp_key = (header.service_id, header.client_id, header.session_id);
+            if self.pcap_writer.is_some() {
+                if reassembled.is_some() {
+                    self.message_frames = self.pending_frames.take(&FrameSource::Tp(tp_key));
+                } else if self.tp_parser.is_pending(&tp_key) {
+                    self.partial_sources.push(FrameSource::Tp(tp_key));
+                }
+                // 超时或被丢弃的 TP 消息不会再完成
+                let tp_parser = &self.tp_parser;
+                self.pending_frames.retain(|source| match source {
+                    FrameSource::Tp(key) => tp_parser.is_pending(key),
+                    FrameSource::Tcp(_) => true,
+                });
+            }
+            for gap in self.tp_parser.drain_gap_events() {
+                if self.verbose >= 2 {
+                    warn!(
+                        "TP 分段缺口: 服务 0x{:04X} 客户端 0x{:04X} 会话 0x{:04X}，期望偏移 {}，收到 {}",
+                        gap.key.0, gap.key.1, gap.key.2, gap.expected_offset, gap.received_offset
                     );
-                    handle_someip_message(msg, session_manager, messages)?;
                 }
             }
-            // 处理普通 SomeIP 包
-            else {
-                let payload = udp.payload[16..16 + header.length as usize].to_vec();
+            if let Some(reassembled) = reassembled {
+                self.stats.tp_reassemblies += 1;
                 let msg = create_someip_message(
-                    &raw_packet.timestamp,
+                    ctx,
                     &src_ip,
                     &dst_ip,
                     udp.src_port,
                     udp.dst_port,
-                    header,
-                    payload,
+                    reassembled.header,
+                    reassembled.payload,
                 );
-                handle_someip_message(msg, session_manager, messages)?;
+                self.handle_someip_message(msg)?;
+                self.message_frames.clear();
             }
         }
-
-        parser::transport_layer::TransportLayer::TCP(tcp) => {
-            // 仅处理已知端口的 TCP 包
-            if !known_ports.contains(&tcp.src_port) && !known_ports.contains(&tcp.dst_port) {
-                return Ok(());
+        // 处理 MSI 多服务包
+        else if header.service_id == 0xFFFF && header.method_id == 0x8101 {
+            let msi_packet = parse_msi_packet(&message[16..])?;
+            info!("解析 MSI 包，包含 {} 个消息", msi_packet.messages.len());
+            for msi_msg in msi_packet.messages {
+                if self.skip_by_header_id(&msi_msg.header) {
+                    continue;
+                }
+                let msg = create_someip_message(
+                    ctx,
+                    &src_ip,
+                    &dst_ip,
+                    udp.src_port,
+                    udp.dst_port,
+                    msi_msg.header,
+                    msi_msg.payload.to_vec(),
+                );
+                self.handle_someip_message(msg)?;
             }
-
-            // 处理 TCP 流控与重组
-            if let Some(data) = tcp_flow.process_tcp_packet(
+        }
+        // 处理普通 SomeIP 包
+        else {
+            let payload = message[16..].to_vec();
+            let msg = create_someip_message(
+                ctx,
                 &src_ip,
                 &dst_ip,
-                tcp,
-                bytes::Bytes::copy_from_slice(&tcp.payload),
-            )? {
-                // 解析重组后的 SomeIP 消息
-                let mut offset = 0;
-                while offset + 16 <= data.len() {
-                    let (_, header) = parse_someip_header(&data[offset..]).map_err(|e| {
-                        SomeIPError::InvalidPacketFormat(format!("TCP SomeIP 头部解析失败: {}", e))
-                    })?;
-                    let msg_len = 16 + header.length as usize;
-                    if offset + msg_len > data.len() {
-                        break;
+                udp.src_port,
+                udp.dst_port,
+                header,
+                payload,
+            );
+            self.handle_someip_message(msg)?;
+        }
+
+        Ok(())
+    }
+
+    /// 丢弃 TCP 连接某个方向上尚未完整的 SomeIP 消息
+    fn discard_partial_tcp_message(&mut self, key: &StreamKey) {
+        self.pending_frames.take(&FrameSource::Tcp(*key));
+        let discarded = self.stream_decoder.reset(key);
+        if discarded > 0 {
+            debug!(
+                "TCP 连接 {}:{} -> {}:{} 重新同步，丢弃 {} 字节不完整消息",
+                key.0, key.1, key.2, key.3, discarded
+            );
+        }
+    }
+
+    /// 头部中的服务/方法 ID 不满足 `--service-id` / `--method-id` 时返回 true，MSI 容器除外
+    fn skip_by_header_id(&mut self, header: &SomeIPHeader) -> bool {
+        let is_msi = header.service_id == 0xFFFF && header.method_id == 0x8101;
+        if is_msi || self.header_filter.matches(header) {
+            return false;
+        }
+        self.stats.skipped_id += 1;
+        true
+    }
+
+    /// 输出在抓包时间 `now` 之前已超时的请求，并移除已过期的订阅
+    fn sweep_timed_out_sessions(&mut self, now: SystemTime) {
+        self.subscription_tracker.expire(now);
+        let timed_out = self.session_manager.cleanup_expired_sessions(now);
+        self.stats.timed_out_sessions += timed_out.len();
+        if !timed_out.is_empty() {
+            debug!("{} 个请求在 {:?} 前超时", timed_out.len(), now);
+        }
+        for pair in timed_out {
+            self.emit_request(pair.request);
+        }
+    }
+
+    /// 从 SD 包中学习端口信息（按条目引用的选项关联服务与端口）
+    ///
+    /// TTL 为 0 的 OfferService 表示停止提供服务，移除该服务先前提供的端口
+    fn learn_ports_from_sd(&mut self, src_ip: IpAddr, sd_packet: &SDPacket) {
+        for entry in &sd_packet.entries {
+            let ports: Vec<u16> = sd_packet
+                .entry_options(entry)
+                .into_iter()
+                .filter_map(|option| {
+                    use parser::someip::sd_parser::SDOption::*;
+                    match option {
+                        Ipv4Endpoint(opt) => Some(opt.port),
+                        Ipv4Multicast(opt) => Some(opt.port),
+                        Ipv4SDEndpoint(opt) => Some(opt.port),
+                        Ipv6Endpoint(opt) => Some(opt.port),
+                        Ipv6Multicast(opt) => Some(opt.port),
+                        Ipv6SDEndpoint(opt) => Some(opt.port),
+                        _ => None,
                     }
+                })
+                .collect();
 
-                    let payload = data[offset + 16..offset + msg_len].to_vec();
-                    let msg = create_someip_message(
-                        &raw_packet.timestamp,
-                        &src_ip,
-                        &dst_ip,
-                        tcp.src_port,
-                        tcp.dst_port,
-                        header,
-                        payload,
+            if let SDEntry::OfferService(offer) = entry {
+                let key = (src_ip, offer.service_id, offer.instance_id);
+                if entry.is_stop() {
+                    if let Some(withdrawn) = self.offered_ports.remove(&key) {
+                        self.withdraw_ports(withdrawn, offer.service_id);
+                    }
+                    continue;
+                }
+                self.offered_ports.entry(key).or_default().extend(&ports);
+            }
+
+            for port in ports {
+                if self.known_ports.insert(port) {
+                    debug!(
+                        "服务 0x{:04X} 使用端口 {}",
+                        entry.service_id().unwrap_or(0xFFFF),
+                        port
                     );
-                    handle_someip_message(msg, session_manager, messages)?;
-                    offset += msg_len;
                 }
             }
         }
     }
 
-    Ok(())
-}
+    /// 从已知端口中移除不再被任何服务实例提供的端口（SD 端口始终保留）
+    fn withdraw_ports(&mut self, ports: HashSet<u16>, service_id: u16) {
+        for port in ports {
+            let still_offered = self.offered_ports.values().any(|p| p.contains(&port));
+            if port != self.sd_port && !still_offered && self.known_ports.remove(&port) {
+                debug!("服务 0x{:04X} 不再提供，移除端口 {}", service_id, port);
+            }
+        }
+    }
+
+    /// 节点重启后丢弃其未完成的会话与先前提供服务的端口，重启后的 SD 消息会重新通告端口
+    fn forget_rebooted_node(&mut self, ip: IpAddr) {
+        let evicted = self.session_manager.evict_node(ip);
+        if !evicted.is_empty() {
+            warn!("ECU {} 重启，丢弃 {} 个未完成的请求", ip, evicted.len());
+        }
+        // 未收到响应的请求仍然输出
+        for pair in evicted {
+            self.emit_request(pair.request);
+        }
 
-/// 从 SD 包中学习端口信息
-fn learn_ports_from_sd(sd_packet: &SDPacket, known_ports: &mut std::collections::HashSet<u16>) {
-    for option in &sd_packet.options {
-        use parser::someip::sd_parser::SDOption::*;
-        match option {
-            Ipv4Endpoint(opt) => {
-                known_ports.insert(opt.port);
+        let offers: Vec<_> = self
+            .offered_ports
+            .keys()
+            .filter(|(offerer, _, _)| *offerer == ip)
+            .copied()
+            .collect();
+        for key in offers {
+            if let Some(ports) = self.offered_ports.remove(&key) {
+                self.withdraw_ports(ports, key.1);
             }
-            Ipv4Multicast(opt) => {
-                known_ports.insert(opt.port);
+        }
+    }
+
+    /// 根据 SD 中提供服务的地址与端口推断消息所属的服务实例
+    ///
+    /// 不同 ECU 可能在同一端口上提供同一服务的不同实例，因此端口必须与提供方地址一起匹配
+    fn resolve_instance(&self, msg: &SomeIPMessage) -> Option<u16> {
+        self.offered_ports
+            .iter()
+            .find(|((provider, service_id, _), ports)| {
+                *service_id == msg.header.service_id
+                    && ((*provider == msg.src_ip && ports.contains(&msg.src_port))
+                        || (*provider == msg.dst_ip && ports.contains(&msg.dst_port)))
+            })
+            .map(|((_, _, instance_id), _)| *instance_id)
+    }
+
+    /// 处理 SomeIP 消息（区分请求/响应并关联会话）
+    fn handle_someip_message(&mut self, mut msg: SomeIPMessage) -> Result<()> {
+        if let Some(deduplicate) = self.deduplicate.as_mut()
+            && deduplicate.is_duplicate(&msg)
+        {
+            debug!(
+                "丢弃重复消息: 0x{:04X}:0x{:04X} 会话 0x{:04X}",
+                msg.header.service_id, msg.header.method_id, msg.header.session_id
+            );
+            self.stats.duplicate_messages += 1;
+            return Ok(());
+        }
+        self.stats.record_message(&msg.header);
+        // 只对配置为受 E2E 保护的消息剥离并校验 E2E 头部；先于输出过滤进行，
+        // 使负载过滤与输出的消息看到的是同一份剥离后的数据
+        if let Some(profile) = self.e2e_profile
+            && self
+                .e2e_ids
+                .contains(&(msg.header.service_id, msg.header.method_id))
+        {
+            match parse_e2e_header(profile, &msg.payload) {
+                Ok((e2e, data)) => {
+                    let crc_valid = verify_e2e_crc(profile, &e2e, &msg.payload, self.e2e_data_id);
+                    if !crc_valid {
+                        warn!(
+                            "E2E CRC 校验失败: 0x{:04X}:0x{:04X} 会话 0x{:04X} 计数器 {}",
+                            msg.header.service_id,
+                            msg.header.method_id,
+                            msg.header.session_id,
+                            e2e.counter
+                        );
+                    }
+                    msg.payload = data.to_vec();
+                    msg.e2e = Some(E2EStatus {
+                        crc_valid,
+                        counter: e2e.counter,
+                    });
+                }
+                Err(e) => debug!("E2E 头部解析失败: {}", e),
             }
-            Ipv4SDEndpoint(opt) => {
-                known_ports.insert(opt.port);
+        }
+
+        // 输出过滤在会话关联之后进行：被过滤掉的请求仍需参与配对，
+        // 否则只输出响应（如按消息类型或返回码过滤）时无法计算往返时间。
+        // 每条消息只判断一次，导出的帧、按服务统计与输出的消息保持一致
+        let matched = self.filter.matches(&msg);
+        if matched {
+            self.packet_matched = true;
+            self.matched_frames.append(&mut self.message_frames);
+            self.stats.record_service(&msg.header);
+        }
+        msg.instance_id = self.resolve_instance(&msg);
+
+        match msg.header.message_type {
+            // 处理请求类型消息
+            parser::someip::header::MessageType::Request
+            | parser::someip::header::MessageType::RequestNoReturn => {
+                // 同一会话的新请求覆盖旧请求，过滤结果也随之更新
+                let key = request_key(&msg);
+                if matched {
+                    self.filtered_requests.remove(&key);
+                } else {
+                    self.filtered_requests.insert(key);
+                }
+                self.session_manager.add_request(msg.clone())?;
             }
-            Ipv6Endpoint(opt) => {
-                known_ports.insert(opt.port);
+            // 处理响应类型消息
+            parser::someip::header::MessageType::Response
+            | parser::someip::header::MessageType::Error => {
+                if let Some(pair) = self.session_manager.add_response(msg.clone())? {
+                    self.emit_request(pair.request);
+                    if let Some(response) = pair.response {
+                        // 带有往返时间的响应
+                        if let Some(latency_ms) = response.latency_ms
+                            && matched
+                        {
+                            self.stats.record_latency(msg.header.service_id, latency_ms);
+                        }
+                        self.emit(response, matched);
+                    }
+                }
             }
-            Ipv6Multicast(opt) => {
-                known_ports.insert(opt.port);
+            // 通知消息：根据 SD 订阅表关联订阅者
+            parser::someip::header::MessageType::Notification => {
+                msg.subscription = self.subscription_tracker.resolve_notification(
+                    msg.timestamp,
+                    msg.header.service_id,
+                    msg.instance_id,
+                    msg.dst_ip,
+                    msg.dst_port,
+                );
+                self.emit(msg, matched);
             }
-            Ipv6SDEndpoint(opt) => {
-                known_ports.insert(opt.port);
+            // 处理其他单向消息
+            _ => {
+                self.emit(msg, matched);
             }
-            _ => {}
         }
+        Ok(())
+    }
+
+    /// 输出通过过滤条件的消息，`matched` 为该消息到达时的过滤结果
+    fn emit(&mut self, msg: SomeIPMessage, matched: bool) {
+        if matched {
+            self.messages.push(msg);
+        }
+    }
+
+    /// 输出会话表中取出的请求，沿用请求到达时的过滤结果
+    fn emit_request(&mut self, request: SomeIPMessage) {
+        let matched = !self.filtered_requests.contains(&request_key(&request));
+        self.emit(request, matched);
+    }
+}
+
+/// 请求在会话表中的键 (服务ID, 客户端ID, 会话ID)
+fn request_key(msg: &SomeIPMessage) -> (u16, u16, u16) {
+    (
+        msg.header.service_id,
+        msg.header.client_id,
+        msg.header.session_id,
+    )
+}
+
+/// 进入一层隧道：记录最外层隧道的端点，超过最大嵌套深度时返回 None
+fn enter_tunnel(
+    mut ctx: PacketContext,
+    kind: TunnelKind,
+    outer_src_ip: IpAddr,
+    outer_dst_ip: IpAddr,
+) -> Option<PacketContext> {
+    if ctx.depth >= MAX_TUNNEL_DEPTH {
+        debug!("隧道嵌套超过 {} 层，丢弃数据包", MAX_TUNNEL_DEPTH);
+        return None;
+    }
+    ctx.depth += 1;
+    ctx.tunnel.get_or_insert(TunnelInfo {
+        kind,
+        outer_src_ip,
+        outer_dst_ip,
+    });
+    Some(ctx)
+}
+
+/// 以冒号分隔的十六进制格式输出 MAC 地址
+fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// IP 协议号对应的协议名称
+fn ip_protocol_name(protocol: u8) -> String {
+    match protocol {
+        1 => "ICMP".to_string(),
+        6 => "TCP".to_string(),
+        17 => "UDP".to_string(),
+        IP_PROTOCOL_GRE => "GRE".to_string(),
+        58 => "ICMPv6".to_string(),
+        _ => format!("IP/{}", protocol),
     }
 }
 
 /// 创建 SomeIP 消息结构
 fn create_someip_message(
-    timestamp: &SystemTime,
+    ctx: &PacketContext,
     src_ip: &IpAddr,
     dst_ip: &IpAddr,
     src_port: u16,
@@ -332,42 +1436,51 @@ fn create_someip_message(
     payload: Vec<u8>,
 ) -> SomeIPMessage {
     SomeIPMessage {
-        timestamp: *timestamp,
+        timestamp: ctx.timestamp,
         header,
         payload,
         src_ip: *src_ip,
         dst_ip: *dst_ip,
         src_port,
         dst_port,
+        vlan_id: ctx.vlan_id,
+        tunnel: ctx.tunnel.clone(),
+        instance_id: None,
+        latency_ms: None,
+        subscription: None,
+        e2e: None,
+        resynced: false,
     }
 }
 
-/// 处理 SomeIP 消息（区分请求/响应并关联会话）
-fn handle_someip_message(
-    msg: SomeIPMessage,
-    session_manager: &mut SessionManager,
-    messages: &mut Vec<SomeIPMessage>,
-) -> Result<()> {
-    match msg.header.message_type {
-        // 处理请求类型消息
-        parser::someip::header::MessageType::Request
-        | parser::someip::header::MessageType::RequestNoReturn => {
-            session_manager.add_request(msg.clone())?;
-        }
-        // 处理响应类型消息
-        parser::someip::header::MessageType::Response
-        | parser::someip::header::MessageType::Error => {
-            if let Some(pair) = session_manager.add_response(msg.clone())? {
-                messages.push(pair.request);
-                messages.push(msg.clone());
-            }
-        }
-        // 处理单向消息（通知等）
-        _ => {
-            messages.push(msg.clone());
-        }
+/// 根据输出格式名称创建格式化器
+fn create_formatter(cli: &Config) -> Box<dyn Formatter> {
+    match cli.output_format.as_str() {
+        "json" => Box::new(JsonFormatter::new(true)),
+        "yaml" => Box::new(YamlFormatter::new()),
+        "wireshark-json" => Box::new(WiresharkJsonFormatter::new()),
+        "html" => Box::new(HtmlFormatter::new("SomeIP messages")),
+        _ => Box::new(TextFormatter::with_hexdump_threshold(cli.hexdump_threshold)),
     }
-    Ok(())
+}
+
+/// 按输出格式导出结果：sqlite 写入数据库，其余格式化为文本后输出
+fn export_results(cli: &Config, formatted: &[FormattedMessage]) -> Result<()> {
+    if cli.output_format == "sqlite" {
+        let path = cli
+            .output_file
+            .clone()
+            .context("sqlite 输出格式需要指定 --output-file")?;
+        return SqliteExporter::new(path, cli.sqlite_append).export(formatted);
+    }
+
+    let exporter = Exporter::new(
+        create_formatter(cli),
+        cli.output_file
+            .as_ref()
+            .map(|p| p.to_string_lossy().into_owned()),
+    );
+    exporter.export(formatted)
 }
 
 /// 初始化日志系统
@@ -2,7 +2,9 @@
 use super::formatter::*;
 use crate::error::Result;
 use std::fs::File;
-use std::io::{self, Write};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 pub struct Exporter {
     formatter: Box<dyn Formatter>,
@@ -22,14 +24,165 @@ impl Exporter {
 
         if let Some(path) = &self.output_path {
             // 输出到文件
-            let mut file = File::create(path)?;
+            let mut file = BufWriter::new(File::create(path)?);
             file.write_all(formatted.as_bytes())?;
+            file.flush()?;
             log::info!("Exported results to {}", path);
         } else {
             // 输出到标准输出
-            io::stdout().write_all(formatted.as_bytes())?;
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(formatted.as_bytes())?;
+            stdout.flush()?;
         }
 
         Ok(())
     }
 }
+
+/// 流式导出：每批消息产生后立即写出，结束时补全输出格式（如 JSON 数组的结尾）
+///
+/// 未设置刷新间隔时每批消息写出后立即刷新；设置后写入缓冲区，距上次刷新超过间隔时才刷新，
+/// 调用方应在空闲时定期调用 `flush`，并在结束时调用 `finish` 保证数据全部写出。
+pub struct StreamingExporter {
+    formatter: Box<dyn Formatter>,
+    writer: BufWriter<Box<dyn Write + Send>>,
+    json_array: bool, // 格式化结果为 JSON 数组，需要逐个元素拼接
+    yaml: bool,
+    written: usize,
+    flush_interval: Option<Duration>,
+    last_flush: Instant,
+}
+
+impl StreamingExporter {
+    pub fn new(
+        formatter: Box<dyn Formatter>,
+        output_format: &str,
+        output_path: Option<&Path>,
+        flush_interval: Option<Duration>,
+    ) -> Result<Self> {
+        let writer: Box<dyn Write + Send> = match output_path {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout()),
+        };
+        Ok(Self {
+            formatter,
+            writer: BufWriter::new(writer),
+            json_array: matches!(output_format, "json" | "wireshark-json"),
+            yaml: output_format == "yaml",
+            written: 0,
+            flush_interval,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// 将缓冲区中的数据写出到文件或标准输出
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    pub fn write(&mut self, messages: &[FormattedMessage]) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let formatted = self.formatter.format(messages)?;
+        if self.json_array {
+            let serde_json::Value::Array(items) = serde_json::from_str(&formatted)? else {
+                return Ok(());
+            };
+            for item in items {
+                let separator = if self.written == 0 { "[\n" } else { ",\n" };
+                self.writer.write_all(separator.as_bytes())?;
+                self.writer
+                    .write_all(serde_json::to_string_pretty(&item)?.as_bytes())?;
+                self.written += 1;
+            }
+        } else {
+            // YAML 序列与文本输出可以直接拼接
+            self.writer.write_all(formatted.as_bytes())?;
+            self.written += messages.len();
+        }
+        match self.flush_interval {
+            Some(interval) if self.last_flush.elapsed() < interval => Ok(()),
+            _ => self.flush(),
+        }
+    }
+
+    /// 写出格式结尾，保证输出文件完整有效
+    pub fn finish(mut self) -> Result<()> {
+        let tail = match (self.json_array, self.yaml, self.written) {
+            (true, _, 0) => "[]\n",
+            (true, _, _) => "\n]\n",
+            (_, true, 0) => "[]\n",
+            _ => "",
+        };
+        self.writer.write_all(tail.as_bytes())?;
+        self.flush()?;
+        log::info!("Streamed {} messages", self.written);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::someip::header::MessageType;
+    use crate::parser::someip::matrix::Matrix;
+    use crate::testing::message::someip_message;
+
+    fn message(session_id: u16) -> FormattedMessage {
+        let message = someip_message(0x1234, 0x8001, session_id, MessageType::Notification);
+        convert_to_formatted(&message, &Matrix::new(), &FormatOptions::default())
+    }
+
+    fn exporter(path: &Path, flush_interval: Option<Duration>) -> StreamingExporter {
+        StreamingExporter::new(
+            Box::new(JsonFormatter::new(true)),
+            "json",
+            Some(path),
+            flush_interval,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn only_flushed_data_survives_a_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.json");
+        let mut stream = exporter(&path, Some(Duration::from_secs(3600)));
+
+        stream.write(&[message(1)]).unwrap();
+        // 未到刷新间隔，数据仍在缓冲区中
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        stream.flush().unwrap();
+        let flushed = std::fs::read_to_string(&path).unwrap();
+        assert!(flushed.contains("\"session_id\": 1"), "{flushed}");
+
+        stream.write(&[message(2)]).unwrap();
+        // 模拟进程崩溃：不运行析构，缓冲区中的数据丢失
+        std::mem::forget(stream);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), flushed);
+    }
+
+    #[test]
+    fn unbuffered_writes_reach_disk_and_finish_completes_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.json");
+        let mut stream = exporter(&path, None);
+
+        stream.write(&[message(1)]).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        stream.write(&[message(2), message(3)]).unwrap();
+        stream.finish().unwrap();
+
+        let messages: Vec<FormattedMessage> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let sessions: Vec<_> = messages
+            .iter()
+            .map(|msg| msg.message.as_ref().unwrap().header.session_id)
+            .collect();
+        assert_eq!(sessions, [1, 2, 3]);
+    }
+}
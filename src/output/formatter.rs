@@ -1,21 +1,76 @@
 // src/output/formatter.rs
 use super::super::parser::someip::session::*;
+use super::super::parser::tunnel::TunnelKind;
 use crate::error::Result;
-use chrono::DateTime;
-use serde::{Serialize, ser::Serializer};
+use crate::utils::hexdump::hexdump;
+use crate::utils::timestamp::format_timestamp;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FormattedMessage {
-    #[serde(serialize_with = "serialize_timestamp")]
+    /// 以整数秒与纳秒输出，避免经过浮点数丢失精度
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub timestamp: SystemTime,
     pub sender: String,
     pub receiver: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tunnel: Option<FormattedTunnel>,
     pub service: String,
+    /// 服务实例名称（矩阵中未定义时为实例 ID），实例未知时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
     pub method: String,
     pub message_type: String,
     pub return_code: String,
-    pub payload: String,
+    /// 响应相对于对应请求的往返时间（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    /// 通知所属的事件组订阅，格式为 `事件组ID <- 订阅者:端口`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription: Option<String>,
+    /// E2E 头部 CRC 是否正确，未开启 `--e2e-profile` 或头部无法解析时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub e2e_crc_valid: Option<bool>,
+    /// E2E 头部中的计数器
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub e2e_counter: Option<u32>,
+    /// TCP 字节流失去同步后重新找到的第一条消息，之前可能有消息丢失
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resynced: bool,
+    /// 负载的原始字节数，`payload` 被截断时仍为完整长度
+    #[serde(default)]
+    pub payload_length: usize,
+    /// 十六进制负载；开启 `empty_payload_as_null` 时空负载为 None（序列化为 null）
+    pub payload: Option<String>,
+    /// 按矩阵中定义的参数解码的负载，未定义参数或解码失败时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoded: Option<serde_json::Value>,
+    /// 原始消息，供需要原始 ID 与负载的格式化器使用；随 JSON 输出保存，
+    /// 以便 `--reformat` 时转换为 wireshark-json 等依赖原始字段的格式
+    #[serde(default, rename = "raw", skip_serializing_if = "Option::is_none")]
+    pub message: Option<SomeIPMessage>,
+}
+
+/// 隧道外层端点信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormattedTunnel {
+    pub kind: String,
+    pub outer_sender: String,
+    pub outer_receiver: String,
+}
+
+/// 控制消息转换为输出格式时的可选行为
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    pub show_vlan: bool,
+    pub show_tunnel: bool,
+    /// 负载超过该字节数时截断输出
+    pub max_payload_bytes: Option<usize>,
+    /// 空负载输出为 null，仅用于结构化输出格式
+    pub empty_payload_as_null: bool,
 }
 
 pub trait Formatter {
@@ -44,6 +99,12 @@ impl Formatter for JsonFormatter {
 
 pub struct YamlFormatter;
 
+impl Default for YamlFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl YamlFormatter {
     pub fn new() -> Self {
         Self
@@ -56,11 +117,51 @@ impl Formatter for YamlFormatter {
     }
 }
 
-pub struct TextFormatter;
+/// 文本输出；负载超过 `hexdump_threshold` 字节时输出为多行十六进制 + ASCII 转储
+pub struct TextFormatter {
+    hexdump_threshold: Option<usize>,
+}
+
+impl Default for TextFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl TextFormatter {
+    /// 负载始终输出为单行十六进制
     pub fn new() -> Self {
-        Self
+        Self {
+            hexdump_threshold: None,
+        }
+    }
+
+    pub fn with_hexdump_threshold(threshold: Option<usize>) -> Self {
+        Self {
+            hexdump_threshold: threshold,
+        }
+    }
+
+    /// 单行十六进制，或以换行开始的多行转储（截断说明附在最后一行之后）
+    fn format_payload(&self, msg: &FormattedMessage) -> String {
+        let payload = msg.payload.as_deref().unwrap_or_default();
+        let Some(threshold) = self.hexdump_threshold else {
+            return payload.to_string();
+        };
+        if msg.payload_length <= threshold {
+            return payload.to_string();
+        }
+        // `format_payload` 截断时在十六进制之后附加说明
+        let hex_len = payload
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(payload.len());
+        let (hex, note) = payload.split_at(hex_len);
+        let Ok(bytes) = hex::decode(hex) else {
+            return payload.to_string();
+        };
+        format!("{} bytes\n{}{}", msg.payload_length, hexdump(&bytes), note)
+            .trim_end()
+            .to_string()
     }
 }
 
@@ -69,17 +170,66 @@ impl Formatter for TextFormatter {
         let mut output = String::new();
 
         for msg in messages {
+            let vlan = msg
+                .vlan
+                .map(|id| format!(" | VLAN {}", id))
+                .unwrap_or_default();
+            let tunnel = msg
+                .tunnel
+                .as_ref()
+                .map(|t| {
+                    format!(
+                        " | via {} {} -> {}",
+                        t.kind, t.outer_sender, t.outer_receiver
+                    )
+                })
+                .unwrap_or_default();
+            let instance = msg
+                .instance
+                .as_ref()
+                .map(|name| format!("@{}", name))
+                .unwrap_or_default();
+            let latency = msg
+                .latency_ms
+                .map(|ms| format!(" | {:.3} ms", ms))
+                .unwrap_or_default();
+            let subscription = msg
+                .subscription
+                .as_ref()
+                .map(|sub| format!(" | subscription {}", sub))
+                .unwrap_or_default();
+            let e2e = match (msg.e2e_counter, msg.e2e_crc_valid) {
+                (Some(counter), Some(valid)) => format!(
+                    " | E2E counter {} CRC {}",
+                    counter,
+                    if valid { "ok" } else { "FAILED" }
+                ),
+                _ => String::new(),
+            };
+            let decoded = msg
+                .decoded
+                .as_ref()
+                .map(|value| format!("\nDecoded: {}", value))
+                .unwrap_or_default();
             output.push_str(&format!(
-                "[{timestamp}] {sender} -> {receiver} | {service}:{method} | {type} | {return_code}\n\
-                 Payload: {payload}\n\n",
+                "[{timestamp}] {sender} -> {receiver}{vlan}{tunnel} | {service}{instance}:{method} | {type} | {return_code}{latency}{subscription}{e2e}{resync}\n\
+                 Payload: {payload}{decoded}\n\n",
                 timestamp = format_timestamp(&msg.timestamp),
                 sender = msg.sender,
                 receiver = msg.receiver,
+                vlan = vlan,
+                tunnel = tunnel,
                 service = msg.service,
+                instance = instance,
                 method = msg.method,
                 type = msg.message_type,
                 return_code = msg.return_code,
-                payload = hex::encode(&msg.payload)
+                latency = latency,
+                subscription = subscription,
+                e2e = e2e,
+                resync = if msg.resynced { " | RESYNC" } else { "" },
+                payload = self.format_payload(msg),
+                decoded = decoded
             ));
         }
 
@@ -87,36 +237,49 @@ impl Formatter for TextFormatter {
     }
 }
 
-fn serialize_timestamp<S>(time: &SystemTime, serializer: S) -> std::result::Result<S::Ok, S::Error>
+/// 时间戳的序列化形式：整数秒与纳秒，或旧版本输出的 Unix 秒数（浮点）
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TimestampRepr {
+    Exact(SystemTime),
+    Seconds(f64),
+}
+
+/// 读取时间戳，兼容旧版本以浮点秒数保存的结果文件
+fn deserialize_timestamp<'de, D>(deserializer: D) -> std::result::Result<SystemTime, D::Error>
 where
-    S: Serializer,
+    D: Deserializer<'de>,
 {
-    let timestamp = time
-        .duration_since(UNIX_EPOCH)
-        .map_err(|_| serde::ser::Error::custom("Invalid system time"))?
-        .as_secs_f64();
-
-    serializer.serialize_f64(timestamp)
+    match TimestampRepr::deserialize(deserializer)? {
+        TimestampRepr::Exact(time) => Ok(time),
+        TimestampRepr::Seconds(timestamp) => std::time::Duration::try_from_secs_f64(timestamp)
+            .map(|duration| UNIX_EPOCH + duration)
+            .map_err(|_| serde::de::Error::custom("Invalid timestamp")),
+    }
 }
 
-fn format_timestamp(time: &SystemTime) -> String {
-    let duration = time.duration_since(UNIX_EPOCH).unwrap();
-    let secs = duration.as_secs();
-    let millis = duration.subsec_millis();
-
-    let datetime = DateTime::from_timestamp(secs as i64, 0).expect("Invalid timestamp");
-    format!("{}.{:03}", datetime.format("%Y-%m-%d %H:%M:%S"), millis)
+/// 负载转为十六进制，超过上限时截断并注明原始长度
+fn format_payload(payload: &[u8], max_bytes: Option<usize>) -> String {
+    match max_bytes {
+        Some(max) if payload.len() > max => format!(
+            "{}...(truncated, total={} bytes)",
+            hex::encode(&payload[..max]),
+            payload.len()
+        ),
+        _ => hex::encode(payload),
+    }
 }
 
 pub fn convert_to_formatted(
     message: &SomeIPMessage,
     matrix: &super::super::parser::someip::matrix::Matrix,
+    options: &FormatOptions,
 ) -> FormattedMessage {
     let service_id = message.header.service_id;
     let method_id = message.header.method_id;
 
     FormattedMessage {
-        timestamp: message.timestamp.into(),
+        timestamp: message.timestamp,
         sender: matrix
             .get_ip_name(&message.src_ip)
             .unwrap_or(&message.src_ip.to_string())
@@ -125,16 +288,209 @@ pub fn convert_to_formatted(
             .get_ip_name(&message.dst_ip)
             .unwrap_or(&message.dst_ip.to_string())
             .to_string(),
+        vlan: if options.show_vlan {
+            message.vlan_id
+        } else {
+            None
+        },
+        tunnel: message
+            .tunnel
+            .as_ref()
+            .filter(|_| options.show_tunnel)
+            .map(|tunnel| FormattedTunnel {
+                kind: match tunnel.kind {
+                    TunnelKind::Vxlan { vni } => format!("VXLAN(vni {})", vni),
+                    TunnelKind::Gre => "GRE".to_string(),
+                },
+                outer_sender: matrix
+                    .get_ip_name(&tunnel.outer_src_ip)
+                    .unwrap_or(&tunnel.outer_src_ip.to_string())
+                    .to_string(),
+                outer_receiver: matrix
+                    .get_ip_name(&tunnel.outer_dst_ip)
+                    .unwrap_or(&tunnel.outer_dst_ip.to_string())
+                    .to_string(),
+            }),
         service: matrix
             .get_service_name(service_id)
             .unwrap_or(&format!("0x{:04X}", service_id))
             .to_string(),
+        instance: message.instance_id.map(|instance_id| {
+            matrix
+                .get_instance_name(service_id, instance_id)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("0x{:04X}", instance_id))
+        }),
         method: matrix
             .get_method_name(service_id, method_id)
             .unwrap_or(&format!("0x{:04X}", method_id))
             .to_string(),
-        message_type: format!("{:?}", message.header.message_type),
-        return_code: format!("{:?}", message.header.return_code),
-        payload: hex::encode(&message.payload),
+        message_type: message.header.message_type.to_string(),
+        return_code: message.header.return_code.to_string(),
+        latency_ms: message.latency_ms,
+        subscription: message.subscription.as_ref().map(|sub| {
+            format!(
+                "0x{:04X} <- {}:{}",
+                sub.eventgroup_id,
+                matrix
+                    .get_ip_name(&sub.subscriber_ip)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| sub.subscriber_ip.to_string()),
+                sub.subscriber_port
+            )
+        }),
+        e2e_crc_valid: message.e2e.as_ref().map(|e2e| e2e.crc_valid),
+        e2e_counter: message.e2e.as_ref().map(|e2e| e2e.counter),
+        resynced: message.resynced,
+        payload_length: message.payload.len(),
+        payload: if message.payload.is_empty() && options.empty_payload_as_null {
+            None
+        } else {
+            Some(format_payload(&message.payload, options.max_payload_bytes))
+        },
+        decoded: matrix.decode_payload(service_id, method_id, &message.payload),
+        message: Some(message.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::wireshark::WiresharkJsonFormatter;
+    use crate::parser::someip::header::MessageType;
+    use crate::parser::someip::matrix::Matrix;
+    use crate::testing::message::someip_message;
+    use std::time::Duration;
+
+    fn formatted() -> FormattedMessage {
+        let mut message = someip_message(0x1234, 0x8001, 1, MessageType::Notification);
+        message.timestamp = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        message.payload = vec![0xDE, 0xAD];
+        message.header.length = 10;
+        convert_to_formatted(&message, &Matrix::new(), &FormatOptions::default())
+    }
+
+    #[test]
+    fn json_round_trip_keeps_nanoseconds_and_raw_message() {
+        let message = formatted();
+        let json = serde_json::to_string(&message).unwrap();
+        let parsed: FormattedMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(parsed.payload_length, 2);
+    }
+
+    #[test]
+    fn truncated_payload_keeps_full_length() {
+        let mut message = someip_message(0x1234, 0x8001, 1, MessageType::Notification);
+        message.payload = (0..40).collect();
+        message.header.length = 48;
+        let options = FormatOptions {
+            max_payload_bytes: Some(4),
+            ..FormatOptions::default()
+        };
+        let formatted = convert_to_formatted(&message, &Matrix::new(), &options);
+        assert_eq!(
+            formatted.payload.as_deref(),
+            Some("00010203...(truncated, total=40 bytes)")
+        );
+        assert_eq!(formatted.payload_length, 40);
+
+        let messages = [formatted];
+        let json: serde_json::Value =
+            serde_json::from_str(&JsonFormatter::new(false).format(&messages).unwrap()).unwrap();
+        assert_eq!(json[0]["payload_length"], 40);
+        let yaml = YamlFormatter::new().format(&messages).unwrap();
+        assert!(yaml.contains("payload_length: 40\n"), "{yaml}");
+        let text = TextFormatter::with_hexdump_threshold(Some(2))
+            .format(&messages)
+            .unwrap();
+        assert!(text.contains("40 bytes\n00000000  00 01 02 03"), "{text}");
+        assert!(text.contains("...(truncated, total=40 bytes)"), "{text}");
+
+        // 未超过上限时不截断
+        let full = convert_to_formatted(&message, &Matrix::new(), &FormatOptions::default());
+        assert_eq!(full.payload.unwrap().len(), 80);
+        assert_eq!(full.payload_length, 40);
+    }
+
+    #[test]
+    fn message_type_and_return_code_use_display_names() {
+        let message = formatted();
+        assert_eq!(message.message_type, "NOTIFICATION");
+        assert_eq!(message.return_code, "E_OK");
+    }
+
+    #[test]
+    fn show_vlan_controls_vlan_in_text_output() {
+        let mut message = someip_message(0x1234, 0x8001, 1, MessageType::Notification);
+        message.vlan_id = Some(42);
+        let text = |show_vlan| {
+            let options = FormatOptions {
+                show_vlan,
+                ..FormatOptions::default()
+            };
+            let formatted = convert_to_formatted(&message, &Matrix::new(), &options);
+            TextFormatter::new().format(&[formatted]).unwrap()
+        };
+        assert!(text(true).contains(" | VLAN 42"), "{}", text(true));
+        assert!(!text(false).contains("VLAN"), "{}", text(false));
+    }
+
+    #[test]
+    fn empty_payload_serializes_as_null_only_when_enabled() {
+        let message = someip_message(0x1234, 0x8001, 1, MessageType::Notification);
+        let json = |empty_payload_as_null| {
+            let options = FormatOptions {
+                empty_payload_as_null,
+                ..FormatOptions::default()
+            };
+            let formatted = convert_to_formatted(&message, &Matrix::new(), &options);
+            serde_json::to_value(formatted).unwrap()
+        };
+        assert_eq!(json(true)["payload"], serde_json::Value::Null);
+        assert_eq!(json(false)["payload"], "");
+        assert_eq!(json(true)["payload_length"], 0);
+    }
+
+    #[test]
+    fn reads_timestamp_saved_as_float_seconds() {
+        let mut value = serde_json::to_value(formatted()).unwrap();
+        value["timestamp"] = serde_json::json!(1.5);
+        let parsed: FormattedMessage = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.timestamp, UNIX_EPOCH + Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn reformatted_json_converts_to_wireshark_json() {
+        let json = serde_json::to_string(&[formatted()]).unwrap();
+        let parsed: Vec<FormattedMessage> = serde_json::from_str(&json).unwrap();
+        let output = WiresharkJsonFormatter::new().format(&parsed).unwrap();
+        let packets: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let layers = &packets[0]["_source"]["layers"];
+        assert_eq!(layers["frame"]["frame.time_epoch"], "1700000000.123456789");
+        assert_eq!(layers["someip"]["someip.serviceid"], "0x1234");
+        assert_eq!(layers["someip"]["someip.payload"], "de:ad");
+    }
+
+    #[test]
+    fn text_payload_dumped_only_above_threshold() {
+        let mut message = formatted();
+        message.payload = Some("41".repeat(20));
+        message.payload_length = 20;
+
+        let single_line = TextFormatter::new().format(&[message.clone()]).unwrap();
+        assert!(single_line.contains(&"41".repeat(20)));
+        assert!(!single_line.contains("00000010"));
+
+        let dump = TextFormatter::with_hexdump_threshold(Some(16))
+            .format(&[message.clone()])
+            .unwrap();
+        assert!(dump.contains("20 bytes\n00000000  41 41"));
+        assert!(dump.contains("00000010  41 41 41 41"));
+
+        let below = TextFormatter::with_hexdump_threshold(Some(20))
+            .format(&[message])
+            .unwrap();
+        assert!(!below.contains("00000000"));
     }
 }
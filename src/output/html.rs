@@ -0,0 +1,186 @@
+// src/output/html.rs
+use super::formatter::{FormattedMessage, Formatter};
+use crate::error::Result;
+use crate::utils::hexdump::hexdump;
+use crate::utils::timestamp::format_timestamp;
+use std::time::UNIX_EPOCH;
+
+/// 输出单个自包含的 HTML 报告：可排序、可过滤的消息表格，脚本与样式全部内联，无需联网
+pub struct HtmlFormatter {
+    title: String,
+}
+
+impl HtmlFormatter {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+        }
+    }
+}
+
+impl Formatter for HtmlFormatter {
+    fn format(&self, messages: &[FormattedMessage]) -> Result<String> {
+        let mut rows = String::new();
+        for msg in messages {
+            let epoch = msg
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or_default();
+            let latency = msg
+                .latency_ms
+                .map(|ms| format!("{:.3}", ms))
+                .unwrap_or_default();
+            rows.push_str(&format!(
+                "<tr><td data-sort=\"{epoch}\">{time}</td><td>{sender}</td><td>{receiver}</td>\
+                 <td>{service}</td><td>{method}</td>\
+                 <td><span class=\"badge {badge}\">{message_type}</span></td>\
+                 <td>{return_code}</td><td data-sort=\"{latency_sort}\">{latency}</td>\
+                 <td data-sort=\"{length}\">{payload}</td></tr>\n",
+                epoch = epoch,
+                time = escape(&format_timestamp(&msg.timestamp)),
+                sender = escape(&msg.sender),
+                receiver = escape(&msg.receiver),
+                service = escape(&msg.service),
+                method = escape(&msg.method),
+                badge = badge_class(&msg.message_type),
+                message_type = escape(&msg.message_type),
+                return_code = escape(&msg.return_code),
+                latency_sort = msg.latency_ms.unwrap_or(-1.0),
+                latency = latency,
+                length = msg.payload_length,
+                payload = payload_cell(msg),
+            ));
+        }
+
+        Ok(format!(
+            include_str!("html_template.html"),
+            title = escape(&self.title),
+            count = messages.len(),
+            rows = rows
+        ))
+    }
+}
+
+/// 消息类型对应的徽章样式类，如 `REQUEST_NO_RETURN` -> `request-no-return`
+fn badge_class(message_type: &str) -> String {
+    message_type
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// 负载单元格：摘要显示前 16 字节，点击展开为每行 16 字节的十六进制 + ASCII 转储
+fn payload_cell(msg: &FormattedMessage) -> String {
+    let payload = msg.payload.as_deref().unwrap_or_default();
+    if payload.is_empty() {
+        return String::new();
+    }
+    // 截断的负载在十六进制之后带有说明
+    let hex_len = payload
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(payload.len());
+    let (hex, note) = payload.split_at(hex_len);
+
+    let dump = match hex::decode(hex) {
+        Ok(bytes) => format!("{}{}", hexdump(&bytes), note),
+        Err(_) => payload.to_string(),
+    };
+
+    let summary = &hex[..hex.len().min(32)];
+    format!(
+        "<details><summary>{}{} ({} bytes)</summary><pre>{}</pre></details>",
+        summary,
+        if hex.len() > 32 { "…" } else { "" },
+        msg.payload_length,
+        escape(dump.trim_end())
+    )
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::formatter::{FormatOptions, convert_to_formatted};
+    use crate::parser::someip::header::MessageType;
+    use crate::parser::someip::matrix::Matrix;
+    use crate::testing::message::someip_message;
+    use scraper::{Html, Selector};
+
+    fn messages(count: u16) -> Vec<FormattedMessage> {
+        (0..count)
+            .map(|session_id| {
+                let mut message =
+                    someip_message(0x1234, 0x0001, session_id, MessageType::RequestNoReturn);
+                message.payload = vec![0x41; 20];
+                message.header.length = 28;
+                convert_to_formatted(&message, &Matrix::new(), &FormatOptions::default())
+            })
+            .collect()
+    }
+
+    fn select<'a>(document: &'a Html, selector: &str) -> Vec<scraper::ElementRef<'a>> {
+        document
+            .select(&Selector::parse(selector).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn one_table_row_per_message() {
+        for count in [0, 1, 5] {
+            let output = HtmlFormatter::new("Report")
+                .format(&messages(count))
+                .unwrap();
+            let document = Html::parse_document(&output);
+            assert_eq!(
+                select(&document, "#messages tbody tr").len(),
+                count as usize
+            );
+            assert_eq!(select(&document, "#messages thead th").len(), 9);
+        }
+    }
+
+    #[test]
+    fn rows_carry_badge_and_expandable_payload() {
+        let output = HtmlFormatter::new("A <b>&</b> report")
+            .format(&messages(1))
+            .unwrap();
+        let document = Html::parse_document(&output);
+
+        let title = select(&document, "title")[0].text().collect::<String>();
+        assert_eq!(title, "A <b>&</b> report");
+        let badge = select(&document, "td span.badge.request-no-return")[0];
+        assert_eq!(badge.text().collect::<String>(), "REQUEST_NO_RETURN");
+        let summary = select(&document, "td details summary")[0];
+        assert_eq!(
+            summary.text().collect::<String>(),
+            format!("{}… (20 bytes)", "41".repeat(16))
+        );
+        let dump = select(&document, "td details pre")[0]
+            .text()
+            .collect::<String>();
+        assert!(dump.starts_with("00000000  41 41"), "{dump}");
+        // 不引用任何外部脚本或样式
+        assert!(select(&document, "script[src], link[href]").is_empty());
+    }
+}
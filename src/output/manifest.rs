@@ -0,0 +1,45 @@
+// src/output/manifest.rs
+use crate::error::Result;
+use serde::Serialize;
+use std::path::Path;
+
+/// 本次运行生成的单个输出文件
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub format: String,
+    /// 写入的记录数（消息、数据包、流或服务统计条目）
+    pub records: usize,
+    /// 写入完成后的文件大小
+    pub bytes: u64,
+}
+
+/// `--manifest` 输出的清单，列出本次运行生成的全部输出文件，供下游自动化查找
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个已写入完成的输出文件，文件大小从磁盘读取
+    pub fn add(&mut self, path: &Path, format: &str, records: usize) -> Result<()> {
+        let bytes = std::fs::metadata(path)?.len();
+        self.files.push(ManifestEntry {
+            path: path.display().to_string(),
+            format: format.to_string(),
+            records,
+            bytes,
+        });
+        Ok(())
+    }
+
+    /// 以 JSON 写入清单文件
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+}
@@ -2,3 +2,9 @@
 
 pub mod exporter;
 pub mod formatter;
+pub mod html;
+pub mod manifest;
+pub mod pcap_writer;
+pub mod sort;
+pub mod sqlite;
+pub mod wireshark;
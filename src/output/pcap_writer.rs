@@ -0,0 +1,196 @@
+// src/output/pcap_writer.rs
+use crate::error::Result;
+use crate::parser::link_layer::LinkType;
+use crate::parser::pcap_reader::RawPacket;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// 纳秒精度时间戳的经典 pcap 魔数
+const PCAP_NANOSECOND_MAGIC: u32 = 0xA1B2_3C4D;
+const SNAP_LEN: u32 = 262_144;
+
+/// 将原始帧按原时间戳写入经典 pcap 文件（纳秒精度）
+///
+/// pcap 文件只能有一种链路层类型，全局头部在写入第一个数据包时按其链路层类型生成；
+/// 多文件输入时链路层类型不同的数据包会被跳过。
+pub struct PcapWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    link_type: Option<LinkType>,
+    packets_written: usize,
+    packets_skipped: usize,
+}
+
+impl PcapWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(File::create(path)?),
+            link_type: None,
+            packets_written: 0,
+            packets_skipped: 0,
+        })
+    }
+
+    pub fn write_packet(&mut self, packet: &RawPacket) -> Result<()> {
+        match self.link_type {
+            None => {
+                self.write_header(packet.link_type)?;
+                self.link_type = Some(packet.link_type);
+            }
+            Some(link_type) if link_type != packet.link_type => {
+                self.packets_skipped += 1;
+                return Ok(());
+            }
+            Some(_) => {}
+        }
+
+        let since_epoch = packet
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let len = packet.data.len() as u32;
+        self.writer
+            .write_all(&(since_epoch.as_secs() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&since_epoch.subsec_nanos().to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?; // 捕获长度
+        self.writer
+            .write_all(&packet.orig_len.max(len).to_le_bytes())?; // 原始长度
+        self.writer.write_all(&packet.data)?;
+        self.packets_written += 1;
+        Ok(())
+    }
+
+    /// 刷新缓冲并返回 (写入的数据包数, 因链路层类型不同而跳过的数据包数)
+    ///
+    /// 没有写入任何数据包时仍生成以太网类型的空文件，保证输出可被其他工具打开。
+    pub fn finish(mut self) -> Result<(usize, usize)> {
+        if self.link_type.is_none() {
+            self.write_header(LinkType::Ethernet)?;
+        }
+        self.writer.flush()?;
+        log::debug!(
+            "Wrote {} packets to {}",
+            self.packets_written,
+            self.path.display()
+        );
+        Ok((self.packets_written, self.packets_skipped))
+    }
+
+    fn write_header(&mut self, link_type: LinkType) -> Result<()> {
+        self.writer
+            .write_all(&PCAP_NANOSECOND_MAGIC.to_le_bytes())?;
+        self.writer.write_all(&2u16.to_le_bytes())?; // 主版本号
+        self.writer.write_all(&4u16.to_le_bytes())?; // 次版本号
+        self.writer.write_all(&0i32.to_le_bytes())?; // 时区偏移
+        self.writer.write_all(&0u32.to_le_bytes())?; // 时间戳精度
+        self.writer.write_all(&SNAP_LEN.to_le_bytes())?;
+        self.writer
+            .write_all(&link_type.to_linktype().to_le_bytes())?;
+        Ok(())
+    }
+}
+
+/// 尚未完成的消息（TCP 字节流中跨分段的消息、TP 分段消息）已收到的帧
+///
+/// 消息完成且通过过滤时取出这些帧与完成消息的帧一起导出，未通过过滤或消息被丢弃时一并丢弃。
+pub struct PendingFrames<K> {
+    frames: HashMap<K, Vec<RawPacket>>,
+}
+
+impl<K: Hash + Eq> Default for PendingFrames<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq> PendingFrames<K> {
+    pub fn new() -> Self {
+        Self {
+            frames: HashMap::new(),
+        }
+    }
+
+    /// 记录属于 `key` 的未完成消息的帧
+    ///
+    /// 帧数据单独复制一份，避免长时间未完成的消息占住读取器的整块缓冲区。
+    pub fn push(&mut self, key: K, frame: &RawPacket) {
+        self.frames.entry(key).or_default().push(RawPacket {
+            data: Bytes::copy_from_slice(&frame.data),
+            ..frame.clone()
+        });
+    }
+
+    /// 取出 `key` 已收到的帧，按到达顺序排列
+    pub fn take(&mut self, key: &K) -> Vec<RawPacket> {
+        self.frames.remove(key).unwrap_or_default()
+    }
+
+    /// 只保留 `keep` 返回 true 的消息的帧
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.frames.retain(|key, _| keep(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::pcap_reader::{InputFormat, PCAPReader};
+    use std::time::Duration;
+
+    fn frame(byte: u8, nanos: u32) -> RawPacket {
+        RawPacket {
+            timestamp: UNIX_EPOCH + Duration::new(1_700_000_000, nanos),
+            data: Bytes::from(vec![byte; 60]),
+            orig_len: 1514,
+            link_type: LinkType::Ethernet,
+            source_file: None,
+        }
+    }
+
+    #[test]
+    fn written_file_reads_back() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = PcapWriter::create(file.path()).unwrap();
+        writer.write_packet(&frame(1, 123_456_789)).unwrap();
+        writer.write_packet(&frame(2, 999_999_999)).unwrap();
+        writer
+            .write_packet(&RawPacket {
+                link_type: LinkType::LinuxSLL,
+                ..frame(3, 0)
+            })
+            .unwrap();
+        assert_eq!(writer.finish().unwrap(), (2, 1));
+
+        let mut reader = PCAPReader::new(file.path().to_str().unwrap(), InputFormat::Auto).unwrap();
+        for expected in [frame(1, 123_456_789), frame(2, 999_999_999)] {
+            let packet = reader.next_packet().unwrap().unwrap();
+            assert_eq!(packet.timestamp, expected.timestamp);
+            assert_eq!(packet.data, expected.data);
+            assert_eq!(packet.orig_len, 1514);
+            assert_eq!(packet.link_type, LinkType::Ethernet);
+        }
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn pending_frames_are_taken_once() {
+        let mut pending = PendingFrames::new();
+        pending.push("a", &frame(1, 0));
+        pending.push("a", &frame(2, 0));
+        pending.push("b", &frame(3, 0));
+        pending.retain(|key| *key == "a");
+
+        let frames = pending.take(&"a");
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].data[0], 2);
+        assert!(pending.take(&"a").is_empty());
+        assert!(pending.take(&"b").is_empty());
+    }
+}
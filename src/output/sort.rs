@@ -0,0 +1,126 @@
+// src/output/sort.rs
+use super::formatter::FormattedMessage;
+use crate::parser::someip::header::SomeIPHeader;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// 输出排序字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    Timestamp,
+    Service,
+    Method,
+    ReturnCode,
+    /// 响应相对于对应请求的延迟，非响应消息排在最后
+    Latency,
+}
+
+/// 按指定字段排序，字段相同时按时间戳升序（稳定排序）
+pub fn sort_messages(messages: &mut [FormattedMessage], key: SortKey, descending: bool) {
+    messages.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Timestamp => Ordering::Equal,
+            SortKey::Service => compare_id(a, b, |h| h.service_id, |msg| &msg.service),
+            SortKey::Method => compare_id(a, b, |h| h.method_id, |msg| &msg.method),
+            SortKey::ReturnCode => compare_id(
+                a,
+                b,
+                |h| h.return_code.as_u8() as u16,
+                |msg| &msg.return_code,
+            ),
+            SortKey::Latency => match (a.latency_ms, b.latency_ms) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                // 没有延迟的消息无论升序降序都排在最后
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        let ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then(a.timestamp.cmp(&b.timestamp))
+    });
+}
+
+/// 优先比较原始数值 ID，重新格式化的结果没有原始消息时退回到名称比较
+fn compare_id(
+    a: &FormattedMessage,
+    b: &FormattedMessage,
+    id: impl Fn(&SomeIPHeader) -> u16,
+    name: impl Fn(&FormattedMessage) -> &String,
+) -> Ordering {
+    match (&a.message, &b.message) {
+        (Some(a_msg), Some(b_msg)) => id(&a_msg.header).cmp(&id(&b_msg.header)),
+        _ => name(a).cmp(name(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::formatter::{FormatOptions, convert_to_formatted};
+    use crate::parser::someip::header::MessageType;
+    use crate::parser::someip::matrix::Matrix;
+    use crate::testing::message::someip_message;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// 构造指定服务与时间戳（秒）的格式化消息
+    fn formatted(service_id: u16, seconds: u64, latency_ms: Option<f64>) -> FormattedMessage {
+        let mut message = someip_message(service_id, 0x0001, 1, MessageType::Response);
+        message.timestamp = UNIX_EPOCH + Duration::from_secs(seconds);
+        message.latency_ms = latency_ms;
+        convert_to_formatted(&message, &Matrix::new(), &FormatOptions::default())
+    }
+
+    fn order(messages: &[FormattedMessage]) -> Vec<(u16, u64)> {
+        messages
+            .iter()
+            .map(|msg| {
+                let seconds = msg.timestamp.duration_since(UNIX_EPOCH).unwrap();
+                (
+                    msg.message.as_ref().unwrap().header.service_id,
+                    seconds.as_secs(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_service_id_then_timestamp() {
+        let mut messages = vec![
+            formatted(0x2000, 1, None),
+            formatted(0x0100, 4, None),
+            formatted(0x1000, 2, None),
+            formatted(0x0100, 3, None),
+        ];
+        sort_messages(&mut messages, SortKey::Service, false);
+        assert_eq!(
+            order(&messages),
+            [(0x0100, 3), (0x0100, 4), (0x1000, 2), (0x2000, 1)]
+        );
+
+        // 降序只反转服务 ID，相同服务内仍按时间戳升序
+        sort_messages(&mut messages, SortKey::Service, true);
+        assert_eq!(
+            order(&messages),
+            [(0x2000, 1), (0x1000, 2), (0x0100, 3), (0x0100, 4)]
+        );
+    }
+
+    #[test]
+    fn messages_without_latency_sort_last() {
+        let mut messages = vec![
+            formatted(0x0001, 1, None),
+            formatted(0x0002, 2, Some(5.0)),
+            formatted(0x0003, 3, Some(1.0)),
+        ];
+        sort_messages(&mut messages, SortKey::Latency, false);
+        assert_eq!(order(&messages), [(0x0003, 3), (0x0002, 2), (0x0001, 1)]);
+        sort_messages(&mut messages, SortKey::Latency, true);
+        assert_eq!(order(&messages), [(0x0002, 2), (0x0003, 3), (0x0001, 1)]);
+    }
+}
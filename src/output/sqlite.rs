@@ -0,0 +1,189 @@
+// src/output/sqlite.rs
+use super::formatter::FormattedMessage;
+use crate::error::Result;
+use rusqlite::{Connection, params};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// 将解析结果写入 SQLite 数据库的 `messages` 表，便于用 SQL 查询
+pub struct SqliteExporter {
+    path: PathBuf,
+    append: bool,
+}
+
+impl SqliteExporter {
+    /// `append` 为 true 时在已有数据库上追加，否则先清空 `messages` 表
+    pub fn new(path: PathBuf, append: bool) -> Self {
+        Self { path, append }
+    }
+
+    pub fn export(&self, messages: &[FormattedMessage]) -> Result<()> {
+        let mut conn = Connection::open(&self.path)?;
+        if !self.append {
+            conn.execute("DROP TABLE IF EXISTS messages", [])?;
+        }
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY,
+                timestamp REAL NOT NULL,
+                sender TEXT NOT NULL,
+                receiver TEXT NOT NULL,
+                vlan INTEGER,
+                tunnel_kind TEXT,
+                tunnel_outer_sender TEXT,
+                tunnel_outer_receiver TEXT,
+                service TEXT NOT NULL,
+                instance TEXT,
+                method TEXT NOT NULL,
+                message_type TEXT NOT NULL,
+                return_code TEXT NOT NULL,
+                latency_ms REAL,
+                subscription TEXT,
+                payload_length INTEGER NOT NULL,
+                payload TEXT NOT NULL,
+                service_id_hex INTEGER,
+                method_id_hex INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_messages_service_id ON messages (service_id_hex);
+            CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages (timestamp);",
+        )?;
+
+        // 所有行在同一事务中插入
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO messages (
+                    timestamp, sender, receiver, vlan,
+                    tunnel_kind, tunnel_outer_sender, tunnel_outer_receiver,
+                    service, instance, method, message_type, return_code, latency_ms, subscription,
+                    payload_length, payload, service_id_hex, method_id_hex
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+            )?;
+            for msg in messages {
+                let timestamp = msg
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs_f64())
+                    .unwrap_or_default();
+                // 重新格式化的结果不含原始消息，数值 ID 留空
+                let header = msg.message.as_ref().map(|m| &m.header);
+                stmt.execute(params![
+                    timestamp,
+                    msg.sender,
+                    msg.receiver,
+                    msg.vlan,
+                    msg.tunnel.as_ref().map(|t| &t.kind),
+                    msg.tunnel.as_ref().map(|t| &t.outer_sender),
+                    msg.tunnel.as_ref().map(|t| &t.outer_receiver),
+                    msg.service,
+                    msg.instance,
+                    msg.method,
+                    msg.message_type,
+                    msg.return_code,
+                    msg.latency_ms,
+                    msg.subscription,
+                    msg.payload_length,
+                    msg.payload.as_deref().unwrap_or_default(),
+                    header.map(|h| h.service_id),
+                    header.map(|h| h.method_id),
+                ])?;
+            }
+        }
+        tx.commit()?;
+
+        log::info!(
+            "Exported {} messages to {}",
+            messages.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::formatter::{FormatOptions, convert_to_formatted};
+    use crate::parser::someip::header::MessageType;
+    use crate::parser::someip::matrix::Matrix;
+    use crate::testing::message::someip_message;
+    use std::time::Duration;
+
+    fn messages(count: u16) -> Vec<FormattedMessage> {
+        (0..count)
+            .map(|session_id| {
+                let mut message =
+                    someip_message(0x1234, 0x8001, session_id, MessageType::Notification);
+                message.timestamp = UNIX_EPOCH + Duration::from_millis(session_id as u64 * 10);
+                message.payload = session_id.to_be_bytes().to_vec();
+                message.header.length = 10;
+                convert_to_formatted(&message, &Matrix::new(), &FormatOptions::default())
+            })
+            .collect()
+    }
+
+    fn row_count(path: &std::path::Path) -> i64 {
+        Connection::open(path)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn writes_one_row_per_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messages.db");
+        SqliteExporter::new(path.clone(), false)
+            .export(&messages(100))
+            .unwrap();
+
+        assert_eq!(row_count(&path), 100);
+        let conn = Connection::open(&path).unwrap();
+        let (timestamp, service, message_type, payload, service_id, method_id): (
+            f64,
+            String,
+            String,
+            String,
+            u16,
+            u16,
+        ) = conn
+            .query_row(
+                "SELECT timestamp, service, message_type, payload, service_id_hex, method_id_hex
+                 FROM messages ORDER BY timestamp DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(timestamp, 0.99);
+        assert_eq!(service, "0x1234");
+        assert_eq!(message_type, "NOTIFICATION");
+        assert_eq!(payload, "0063");
+        assert_eq!((service_id, method_id), (0x1234, 0x8001));
+    }
+
+    #[test]
+    fn append_keeps_existing_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messages.db");
+        SqliteExporter::new(path.clone(), false)
+            .export(&messages(3))
+            .unwrap();
+        SqliteExporter::new(path.clone(), true)
+            .export(&messages(2))
+            .unwrap();
+        assert_eq!(row_count(&path), 5);
+        SqliteExporter::new(path.clone(), false)
+            .export(&messages(1))
+            .unwrap();
+        assert_eq!(row_count(&path), 1);
+    }
+}
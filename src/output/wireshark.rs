@@ -0,0 +1,224 @@
+// src/output/wireshark.rs
+use super::formatter::{FormattedMessage, Formatter};
+use crate::error::Result;
+use crate::parser::someip::header::SomeIPHeader;
+use crate::parser::someip::sd_parser::{SDEntry, SDOption, TransportProtocol, decode_sd_packet};
+use serde_json::{Map, Value, json};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::UNIX_EPOCH;
+
+/// 按 Wireshark someip/someipsd 解析器的字段命名输出 JSON（结构与 `tshark -T json` 一致）
+pub struct WiresharkJsonFormatter;
+
+impl Default for WiresharkJsonFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WiresharkJsonFormatter {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Formatter for WiresharkJsonFormatter {
+    fn format(&self, messages: &[FormattedMessage]) -> Result<String> {
+        let packets = messages
+            .iter()
+            .filter_map(|msg| msg.message.as_ref())
+            .map(|message| {
+                let mut layers = Map::new();
+
+                let time_epoch = message
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| format!("{}.{:09}", d.as_secs(), d.subsec_nanos()))
+                    .unwrap_or_default();
+                layers.insert("frame".into(), json!({ "frame.time_epoch": time_epoch }));
+
+                let ip_layer = match (message.src_ip, message.dst_ip) {
+                    (IpAddr::V6(src), IpAddr::V6(dst)) => (
+                        "ipv6",
+                        json!({ "ipv6.src": src.to_string(), "ipv6.dst": dst.to_string() }),
+                    ),
+                    (src, dst) => (
+                        "ip",
+                        json!({ "ip.src": src.to_string(), "ip.dst": dst.to_string() }),
+                    ),
+                };
+                layers.insert(ip_layer.0.into(), ip_layer.1);
+
+                layers.insert(
+                    "someip".into(),
+                    someip_layer(&message.header, &message.payload),
+                );
+
+                if message.header.service_id == 0xFFFF
+                    && message.header.method_id == 0x8100
+                    && let Ok(sd) = decode_sd_packet(&message.payload, message.header.clone())
+                {
+                    let flags = (sd.flags.reboot as u8) << 7
+                        | (sd.flags.unicast as u8) << 6
+                        | (sd.flags.explicit_initial_data_control as u8) << 5;
+                    let entries: Vec<Value> = sd.entries.iter().map(sd_entry).collect();
+                    let options: Vec<Value> = sd.options.iter().map(sd_option).collect();
+                    layers.insert(
+                        "someipsd".into(),
+                        json!({
+                            "someipsd.flags": format!("0x{:02x}", flags),
+                            "someipsd.flags.reboot": (sd.flags.reboot as u8).to_string(),
+                            "someipsd.flags.unicast": (sd.flags.unicast as u8).to_string(),
+                            "someipsd.entries": entries,
+                            "someipsd.options": options,
+                        }),
+                    );
+                }
+
+                json!({ "_source": { "layers": layers } })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(serde_json::to_string_pretty(&packets)?)
+    }
+}
+
+fn someip_layer(header: &SomeIPHeader, payload: &[u8]) -> Value {
+    json!({
+        "someip.serviceid": format!("0x{:04x}", header.service_id),
+        "someip.methodid": format!("0x{:04x}", header.method_id),
+        "someip.length": header.length.to_string(),
+        "someip.clientid": format!("0x{:04x}", header.client_id),
+        "someip.sessionid": format!("0x{:04x}", header.session_id),
+        "someip.protoversion": format!("0x{:02x}", header.protocol_version),
+        "someip.interfaceversion": format!("0x{:02x}", header.interface_version),
+        "someip.messagetype": format!("0x{:02x}", header.message_type.as_u8()),
+        "someip.returncode": format!("0x{:02x}", header.return_code.as_u8()),
+        "someip.payload": colon_hex(payload),
+    })
+}
+
+fn sd_entry(entry: &SDEntry) -> Value {
+    macro_rules! common {
+        ($entry_type:expr, $e:expr) => {
+            json!({
+                "someipsd.entry.type": format!("0x{:02x}", $entry_type),
+                "someipsd.entry.index1": $e.first_options_index.to_string(),
+                "someipsd.entry.index2": $e.second_options_index.to_string(),
+                "someipsd.entry.numopt1": $e.number_of_first_options.to_string(),
+                "someipsd.entry.numopt2": $e.number_of_second_options.to_string(),
+                "someipsd.entry.serviceid": format!("0x{:04x}", $e.service_id),
+                "someipsd.entry.instanceid": format!("0x{:04x}", $e.instance_id),
+                "someipsd.entry.majorver": $e.major_version.to_string(),
+                "someipsd.entry.ttl": $e.ttl.to_string(),
+            })
+        };
+    }
+
+    let (mut value, extra) = match entry {
+        SDEntry::FindService(e) => (
+            common!(0x00, e),
+            json!({ "someipsd.entry.minorver": e.minor_version.to_string() }),
+        ),
+        SDEntry::OfferService(e) => (
+            common!(0x01, e),
+            json!({ "someipsd.entry.minorver": e.minor_version.to_string() }),
+        ),
+        SDEntry::SubscribeEventgroup(e) => (
+            common!(0x06, e),
+            json!({ "someipsd.entry.eventgroupid": format!("0x{:04x}", e.eventgroup_id) }),
+        ),
+        SDEntry::SubscribeEventgroupAck(e) => (
+            common!(0x07, e),
+            json!({ "someipsd.entry.eventgroupid": format!("0x{:04x}", e.eventgroup_id) }),
+        ),
+        SDEntry::Unknown { entry_type, .. } => (
+            json!({ "someipsd.entry.type": format!("0x{:02x}", entry_type) }),
+            json!({}),
+        ),
+    };
+    if let (Value::Object(map), Value::Object(extra)) = (&mut value, extra) {
+        map.extend(extra);
+    }
+    value
+}
+
+fn sd_option(option: &SDOption) -> Value {
+    fn endpoint(option_type: u8, ip: IpAddr, protocol: &TransportProtocol, port: u16) -> Value {
+        let ip_field = match ip {
+            IpAddr::V4(_) => "someipsd.option.ipv4",
+            IpAddr::V6(_) => "someipsd.option.ipv6",
+        };
+        let protocol = match protocol {
+            TransportProtocol::TCP => 0x06,
+            TransportProtocol::UDP => 0x11,
+            TransportProtocol::Unknown(value) => *value,
+        };
+        json!({
+            "someipsd.option.type": format!("0x{:02x}", option_type),
+            ip_field: ip.to_string(),
+            "someipsd.option.proto": format!("0x{:02x}", protocol),
+            "someipsd.option.port": port.to_string(),
+        })
+    }
+
+    match option {
+        SDOption::Configuration(opt) => json!({
+            "someipsd.option.type": "0x01",
+            "someipsd.option.config_string": opt.raw_items(),
+        }),
+        SDOption::LoadBalancing(opt) => json!({
+            "someipsd.option.type": "0x02",
+            "someipsd.option.lb.priority": opt.priority.to_string(),
+            "someipsd.option.lb.weight": opt.weight.to_string(),
+        }),
+        SDOption::Ipv4Endpoint(opt) => endpoint(
+            0x04,
+            Ipv4Addr::from(opt.ip_address).into(),
+            &opt.transport_protocol,
+            opt.port,
+        ),
+        SDOption::Ipv6Endpoint(opt) => endpoint(
+            0x06,
+            Ipv6Addr::from(opt.ip_address).into(),
+            &opt.transport_protocol,
+            opt.port,
+        ),
+        SDOption::Ipv4Multicast(opt) => endpoint(
+            0x14,
+            Ipv4Addr::from(opt.ip_address).into(),
+            &opt.transport_protocol,
+            opt.port,
+        ),
+        SDOption::Ipv6Multicast(opt) => endpoint(
+            0x16,
+            Ipv6Addr::from(opt.ip_address).into(),
+            &opt.transport_protocol,
+            opt.port,
+        ),
+        SDOption::Ipv4SDEndpoint(opt) => endpoint(
+            0x24,
+            Ipv4Addr::from(opt.ip_address).into(),
+            &opt.transport_protocol,
+            opt.port,
+        ),
+        SDOption::Ipv6SDEndpoint(opt) => endpoint(
+            0x26,
+            Ipv6Addr::from(opt.ip_address).into(),
+            &opt.transport_protocol,
+            opt.port,
+        ),
+        SDOption::Unknown { option_type, .. } => json!({
+            "someipsd.option.type": format!("0x{:02x}", option_type),
+        }),
+    }
+}
+
+/// Wireshark 字节字段的显示格式：以冒号分隔的十六进制
+fn colon_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
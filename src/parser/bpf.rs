@@ -0,0 +1,97 @@
+// src/parser/bpf.rs
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+thread_local! {
+    /// 当前线程上已编译的程序：表达式 -> 链路层类型 -> 程序，无法编译时为 None
+    static PROGRAMS: RefCell<HashMap<String, HashMap<u16, Option<pcap::BpfProgram>>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// BPF 过滤表达式
+///
+/// libpcap 编译出的程序没有承诺可以跨线程使用，因此过滤器只保存表达式，
+/// 在执行过滤的线程上按链路层类型编译并缓存，过滤器本身可以随读取器在线程间移动。
+#[derive(Debug, Clone, PartialEq)]
+pub struct BpfFilter {
+    expr: String,
+}
+
+impl BpfFilter {
+    /// 先以以太网类型编译一次，尽早发现语法错误
+    pub fn new(expr: &str) -> Result<Self, pcap::Error> {
+        compile(expr, 1)?;
+        Ok(Self {
+            expr: expr.to_string(),
+        })
+    }
+
+    pub fn expr(&self) -> &str {
+        &self.expr
+    }
+
+    /// 数据包是否匹配；表达式无法用于该链路层类型时放行所有数据包
+    pub fn matches(&self, link_type: u16, data: &[u8]) -> bool {
+        PROGRAMS.with_borrow_mut(|programs| {
+            if !programs.contains_key(&self.expr) {
+                programs.insert(self.expr.clone(), HashMap::new());
+            }
+            let by_link_type = programs.get_mut(&self.expr).unwrap();
+            let program = by_link_type.entry(link_type).or_insert_with(|| {
+                compile(&self.expr, link_type)
+                    .map_err(|e| {
+                        log::warn!(
+                            "Cannot compile BPF filter for link type {}, passing all packets: {}",
+                            link_type,
+                            e
+                        )
+                    })
+                    .ok()
+            });
+            program.as_ref().is_none_or(|program| program.filter(data))
+        })
+    }
+}
+
+fn compile(expr: &str, link_type: u16) -> Result<pcap::BpfProgram, pcap::Error> {
+    let capture = pcap::Capture::dead(pcap::Linktype(link_type as i32))?;
+    capture.compile(expr, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::builder::PacketBuilder;
+
+    #[test]
+    fn accepts_known_good_expression() {
+        let mut capture = pcap::Capture::dead(pcap::Linktype(1)).unwrap();
+        capture.filter("udp port 30490", true).unwrap();
+        let filter = BpfFilter::new("udp port 30490").unwrap();
+        assert_eq!(filter.expr(), "udp port 30490");
+    }
+
+    #[test]
+    fn rejects_invalid_expression() {
+        assert!(BpfFilter::new("udp port bogus").is_err());
+    }
+
+    #[test]
+    fn matches_on_another_thread() {
+        let filter = BpfFilter::new("udp").unwrap();
+        let udp = PacketBuilder::new().udp(30490, 30490).payload(b"x").build();
+        let tcp = PacketBuilder::new()
+            .tcp(30509, 40000, 1)
+            .payload(b"x")
+            .build();
+        assert!(filter.matches(1, &udp));
+        assert!(!filter.matches(1, &tcp));
+
+        // 另一个线程上重新编译，不共享已编译的程序
+        let moved = filter.clone();
+        let result = std::thread::spawn(move || (moved.matches(1, &udp), moved.matches(1, &tcp)))
+            .join()
+            .unwrap();
+        assert_eq!(result, (true, false));
+    }
+}
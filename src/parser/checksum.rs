@@ -0,0 +1,113 @@
+// src/parser/checksum.rs
+use std::net::IpAddr;
+
+/// 校验 IPv4 头部以及 UDP/TCP（含伪首部）的校验和，并按层统计失败次数
+#[derive(Debug, Clone, Default)]
+pub struct ChecksumVerifier {
+    lenient: bool,
+    pub ipv4_failures: u64,
+    pub udp_failures: u64,
+    pub tcp_failures: u64,
+}
+
+impl ChecksumVerifier {
+    pub fn new(lenient: bool) -> Self {
+        Self {
+            lenient,
+            ..Default::default()
+        }
+    }
+
+    /// 校验 IPv4 头部，返回该数据包是否应继续处理
+    pub fn check_ipv4_header(&mut self, header: &[u8]) -> bool {
+        // 校验和为 0 通常意味着由网卡卸载计算
+        if header.len() < 20 || header[10..12] == [0, 0] {
+            return true;
+        }
+
+        if internet_checksum(&[header]) == 0 {
+            return true;
+        }
+
+        self.ipv4_failures += 1;
+        log::debug!("IPv4 header checksum mismatch");
+        self.lenient
+    }
+
+    /// 校验 UDP/TCP 校验和（含伪首部），返回该数据包是否应继续处理
+    pub fn check_transport(
+        &mut self,
+        src_ip: &IpAddr,
+        dst_ip: &IpAddr,
+        protocol: u8,
+        segment: &[u8],
+    ) -> bool {
+        let checksum_offset = match protocol {
+            17 => 6,
+            6 => 16,
+            _ => return true,
+        };
+        if segment.len() < checksum_offset + 2
+            || segment[checksum_offset..checksum_offset + 2] == [0, 0]
+        {
+            return true;
+        }
+
+        let length = segment.len() as u32;
+        let pseudo_header = match (src_ip, dst_ip) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                let mut buf = Vec::with_capacity(12);
+                buf.extend_from_slice(&src.octets());
+                buf.extend_from_slice(&dst.octets());
+                buf.extend_from_slice(&[0, protocol]);
+                buf.extend_from_slice(&(length as u16).to_be_bytes());
+                buf
+            }
+            (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                let mut buf = Vec::with_capacity(40);
+                buf.extend_from_slice(&src.octets());
+                buf.extend_from_slice(&dst.octets());
+                buf.extend_from_slice(&length.to_be_bytes());
+                buf.extend_from_slice(&[0, 0, 0, protocol]);
+                buf
+            }
+            _ => return true,
+        };
+
+        if internet_checksum(&[&pseudo_header, segment]) == 0 {
+            return true;
+        }
+
+        if protocol == 17 {
+            self.udp_failures += 1;
+            log::debug!("UDP checksum mismatch: {} -> {}", src_ip, dst_ip);
+        } else {
+            self.tcp_failures += 1;
+            log::debug!("TCP checksum mismatch: {} -> {}", src_ip, dst_ip);
+        }
+        self.lenient
+    }
+}
+
+/// RFC 1071 反码和校验，数据（含校验和字段）正确时结果为 0
+pub fn internet_checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut odd_byte: Option<u8> = None;
+
+    for chunk in chunks {
+        for &byte in chunk.iter() {
+            match odd_byte.take() {
+                Some(high) => sum += u16::from_be_bytes([high, byte]) as u32,
+                None => odd_byte = Some(byte),
+            }
+        }
+    }
+    if let Some(high) = odd_byte {
+        sum += u16::from_be_bytes([high, 0]) as u32;
+    }
+
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
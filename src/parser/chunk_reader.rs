@@ -0,0 +1,121 @@
+// src/parser/chunk_reader.rs
+use bytes::{Bytes, BytesMut};
+use std::io::{ErrorKind, Read};
+
+/// 抓包数据来源：文件或标准输入
+pub type CaptureInput = Box<dyn Read + Send>;
+
+/// 每次从底层读取的最小字节数
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// 按大块读取抓包数据，记录以共享同一块缓冲区的 `Bytes` 切片输出
+///
+/// 每读入一块才分配一次内存，而不是每个数据包分配并复制一次；
+/// 输出的切片在后续各层解析中继续零拷贝传递。
+/// 数据不足时已缓冲的部分保留，底层数据增长（跟随模式）后可以继续读取。
+pub struct ChunkReader<R> {
+    inner: R,
+    buffer: BytesMut,
+    position: u64, // 已输出（或跳过）的字节数，即下一条记录在输入中的偏移
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buffer: BytesMut::new(),
+            position: 0,
+        }
+    }
+
+    /// 保证缓冲区中至少有 `len` 字节，输入结束时返回 false
+    pub fn fill(&mut self, len: usize) -> std::io::Result<bool> {
+        while self.buffer.len() < len {
+            let start = self.buffer.len();
+            self.buffer.reserve(CHUNK_SIZE.max(len - start));
+            // 在已分配的容量内读取，不再额外分配
+            let capacity = self.buffer.capacity();
+            self.buffer.resize(capacity, 0);
+            let read = loop {
+                match self.inner.read(&mut self.buffer[start..]) {
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    result => break result,
+                }
+            };
+            let read = match read {
+                Ok(read) => read,
+                Err(e) => {
+                    self.buffer.truncate(start);
+                    return Err(e);
+                }
+            };
+            self.buffer.truncate(start + read);
+            if read == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// 当前已缓冲、尚未输出的数据
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// 输出缓冲区开头的 `len` 字节，调用前需通过 `fill` 保证数据足够
+    pub fn take(&mut self, len: usize) -> Bytes {
+        self.position += len as u64;
+        self.buffer.split_to(len).freeze()
+    }
+
+    /// 下一条记录在输入中的偏移
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slices_share_one_chunk() {
+        let mut reader = ChunkReader::new(&[1u8, 2, 3, 4, 5, 6][..]);
+        assert!(reader.fill(4).unwrap());
+        let first = reader.take(2);
+        let second = reader.take(2);
+        assert_eq!(&first[..], [1, 2]);
+        assert_eq!(&second[..], [3, 4]);
+        // 两个切片来自同一次读取的缓冲区
+        assert_eq!(first.as_ptr().wrapping_add(2), second.as_ptr());
+        assert_eq!(reader.position(), 4);
+    }
+
+    #[test]
+    fn keeps_partial_data_at_end_of_input() {
+        let mut reader = ChunkReader::new(&[1u8, 2, 3][..]);
+        assert!(!reader.fill(4).unwrap());
+        assert_eq!(reader.buffered(), [1, 2, 3]);
+        assert_eq!(reader.position(), 0);
+    }
+
+    #[test]
+    fn continues_after_input_grows() {
+        // 模拟跟随模式下被追加的文件：第一次读到末尾，之后又有新数据
+        struct Growing(Vec<Vec<u8>>);
+        impl Read for Growing {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() {
+                    return Ok(0);
+                }
+                let chunk = self.0.remove(0);
+                buf[..chunk.len()].copy_from_slice(&chunk);
+                Ok(chunk.len())
+            }
+        }
+        let mut reader = ChunkReader::new(Growing(vec![vec![1, 2], vec![], vec![3, 4]]));
+        assert!(!reader.fill(4).unwrap());
+        assert!(reader.fill(4).unwrap());
+        assert_eq!(&reader.take(4)[..], [1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,245 @@
+// src/parser/classic_pcap_reader.rs
+use super::bpf::BpfFilter;
+use super::chunk_reader::{CaptureInput, ChunkReader};
+use super::link_layer::LinkType;
+use super::pcap_reader::{CaptureInfo, RawPacket};
+use crate::error::{Result, SomeIPError};
+use std::time::{Duration, SystemTime};
+
+const MAGIC_MICROS: u32 = 0xA1B2C3D4;
+const MAGIC_NANOS: u32 = 0xA1B23C4D;
+const MAGIC_MODIFIED: u32 = 0xA1B2CD34; // Kuznetzov 补丁格式，记录头部多出 8 字节
+
+const GLOBAL_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/// 单条记录的长度上限，长度字段来自不可信的文件，超出时视为文件损坏而不是按其分配内存
+const MAX_RECORD_LENGTH: usize = 16 * 1024 * 1024;
+
+/// 文件开头是否为经典 pcap 魔数（任意字节序）
+pub fn is_classic_pcap(magic: &[u8]) -> bool {
+    let Ok(magic) = <[u8; 4]>::try_from(magic) else {
+        return false;
+    };
+    [u32::from_le_bytes(magic), u32::from_be_bytes(magic)]
+        .iter()
+        .any(|value| matches!(*value, MAGIC_MICROS | MAGIC_NANOS | MAGIC_MODIFIED))
+}
+
+/// 经典 pcap 文件读取器
+///
+/// 不经过 libpcap，直接按块读取文件，数据包是读取缓冲区的零拷贝切片。
+pub struct ClassicPcapReader {
+    reader: ChunkReader<CaptureInput>,
+    big_endian: bool,
+    nanos: bool,              // 时间戳小数部分为纳秒
+    record_header_len: usize, // 记录头部长度，补丁格式为 24
+    info: CaptureInfo,
+    filter: Option<BpfFilter>,
+}
+
+impl ClassicPcapReader {
+    /// 读取并校验全局头部
+    pub fn new(mut reader: ChunkReader<CaptureInput>) -> Result<Self> {
+        if !reader.fill(GLOBAL_HEADER_LEN)? {
+            return Err(
+                SomeIPError::CaptureFileError("Truncated pcap global header".to_string()).into(),
+            );
+        }
+        let header = reader.take(GLOBAL_HEADER_LEN);
+        let magic: [u8; 4] = header[..4].try_into().unwrap();
+        let (magic, big_endian) = match u32::from_le_bytes(magic) {
+            value @ (MAGIC_MICROS | MAGIC_NANOS | MAGIC_MODIFIED) => (value, false),
+            _ => match u32::from_be_bytes(magic) {
+                value @ (MAGIC_MICROS | MAGIC_NANOS | MAGIC_MODIFIED) => (value, true),
+                _ => {
+                    return Err(SomeIPError::CaptureFileError(
+                        "Unknown capture file format".to_string(),
+                    )
+                    .into());
+                }
+            },
+        };
+
+        let mut this = Self {
+            reader,
+            big_endian,
+            nanos: magic == MAGIC_NANOS,
+            record_header_len: if magic == MAGIC_MODIFIED {
+                RECORD_HEADER_LEN + 8
+            } else {
+                RECORD_HEADER_LEN
+            },
+            info: CaptureInfo {
+                link_type: 0,
+                snap_len: 0,
+            },
+            filter: None,
+        };
+        // 链路层类型字段的高 16 位携带 FCS 等附加信息
+        this.info = CaptureInfo {
+            link_type: this.u32_at(&header, 20) as u16,
+            snap_len: this.u32_at(&header, 16),
+        };
+        Ok(this)
+    }
+
+    pub fn capture_info(&self) -> CaptureInfo {
+        self.info
+    }
+
+    /// 已读取到的输入偏移
+    pub fn position(&self) -> u64 {
+        self.reader.position()
+    }
+
+    pub fn set_filter(&mut self, expr: &str) -> std::result::Result<(), pcap::Error> {
+        self.filter = Some(BpfFilter::new(expr)?);
+        Ok(())
+    }
+
+    /// 读取下一个数据包，文件结束时返回 None；最后一条记录不完整时返回错误，
+    /// 已读取的部分保留在缓冲区中，文件继续增长后可以再次读取
+    pub fn next_packet(&mut self) -> Result<Option<RawPacket>> {
+        loop {
+            if !self.reader.fill(self.record_header_len)? {
+                return self.end_of_input();
+            }
+            let header = &self.reader.buffered()[..self.record_header_len];
+            let seconds = self.u32_at(header, 0);
+            let fraction = self.u32_at(header, 4);
+            let cap_len = self.u32_at(header, 8) as usize;
+            let orig_len = self.u32_at(header, 12);
+            if cap_len > MAX_RECORD_LENGTH {
+                return Err(SomeIPError::CaptureFileError(format!(
+                    "Invalid pcap record length: {}",
+                    cap_len
+                ))
+                .into());
+            }
+
+            let record_len = self.record_header_len + cap_len;
+            if !self.reader.fill(record_len)? {
+                return self.end_of_input();
+            }
+            let record = self.reader.take(record_len);
+            let data = record.slice(self.record_header_len..);
+            if let Some(filter) = &self.filter
+                && !filter.matches(self.info.link_type, &data)
+            {
+                continue;
+            }
+
+            let nanos = if self.nanos {
+                fraction.min(999_999_999)
+            } else {
+                fraction.min(999_999) * 1000
+            };
+            return Ok(Some(RawPacket {
+                timestamp: SystemTime::UNIX_EPOCH + Duration::new(seconds as u64, nanos),
+                data,
+                orig_len,
+                link_type: LinkType::from_dlt(self.info.link_type),
+                source_file: None,
+            }));
+        }
+    }
+
+    fn end_of_input(&self) -> Result<Option<RawPacket>> {
+        if self.reader.buffered().is_empty() {
+            Ok(None)
+        } else {
+            Err(SomeIPError::CaptureFileError("Truncated pcap record".to_string()).into())
+        }
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> u32 {
+        let bytes: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::builder::{PacketBuilder, PcapFileBuilder};
+    use std::time::UNIX_EPOCH;
+
+    fn reader(bytes: Vec<u8>) -> Result<ClassicPcapReader> {
+        ClassicPcapReader::new(ChunkReader::new(Box::new(std::io::Cursor::new(bytes))))
+    }
+
+    #[test]
+    fn reads_builder_capture() {
+        let packet = PacketBuilder::new().udp(30490, 30490).payload(b"abc");
+        let timestamp = UNIX_EPOCH + Duration::new(7, 123_456_000);
+        let bytes = PcapFileBuilder::new(LinkType::Ethernet)
+            .packet(timestamp, &packet)
+            .packet(timestamp, &packet)
+            .build();
+
+        let mut reader = reader(bytes).unwrap();
+        assert_eq!(
+            reader.capture_info(),
+            CaptureInfo {
+                link_type: 1,
+                snap_len: 65535
+            }
+        );
+        for _ in 0..2 {
+            let raw_packet = reader.next_packet().unwrap().unwrap();
+            assert_eq!(&raw_packet.data[..], &packet.build()[..]);
+            assert_eq!(raw_packet.timestamp, timestamp);
+            assert_eq!(raw_packet.link_type, LinkType::Ethernet);
+        }
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn reads_big_endian_nanosecond_capture() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC_NANOS.to_be_bytes());
+        bytes.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&65535u32.to_be_bytes());
+        bytes.extend_from_slice(&101u32.to_be_bytes());
+        bytes.extend_from_slice(&1u32.to_be_bytes());
+        bytes.extend_from_slice(&5u32.to_be_bytes());
+        bytes.extend_from_slice(&2u32.to_be_bytes());
+        bytes.extend_from_slice(&2u32.to_be_bytes());
+        bytes.extend_from_slice(&[0x45, 0]);
+
+        let mut reader = reader(bytes).unwrap();
+        let raw_packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(raw_packet.link_type, LinkType::Raw);
+        assert_eq!(raw_packet.timestamp, UNIX_EPOCH + Duration::new(1, 5));
+    }
+
+    #[test]
+    fn reports_truncated_and_oversized_records() {
+        let packet = PacketBuilder::new().udp(1, 2).payload(b"abc");
+        let mut bytes = PcapFileBuilder::new(LinkType::Ethernet)
+            .packet(UNIX_EPOCH, &packet)
+            .build();
+        bytes.truncate(bytes.len() - 1);
+        let error = reader(bytes).unwrap().next_packet().unwrap_err();
+        assert!(error.to_string().contains("Truncated pcap record"));
+
+        let mut bytes = PcapFileBuilder::new(LinkType::Ethernet).build();
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        let error = reader(bytes).unwrap().next_packet().unwrap_err();
+        assert!(error.to_string().contains("Invalid pcap record length"));
+    }
+
+    #[test]
+    fn rejects_unknown_magic() {
+        assert!(reader(vec![0; 24]).is_err());
+        assert!(is_classic_pcap(&MAGIC_MICROS.to_be_bytes()));
+        assert!(!is_classic_pcap(b"DLT\x01"));
+    }
+}
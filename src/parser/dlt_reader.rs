@@ -0,0 +1,276 @@
+// src/parser/dlt_reader.rs
+use super::bpf::BpfFilter;
+use super::chunk_reader::{CaptureInput, ChunkReader};
+use super::link_layer::LinkType;
+use super::pcap_reader::RawPacket;
+use crate::error::{Result, SomeIPError};
+use bytes::Bytes;
+use std::time::{Duration, SystemTime};
+
+pub const DLT_STORAGE_MAGIC: [u8; 4] = *b"DLT\x01";
+
+const STORAGE_HEADER_LEN: usize = 16;
+const STANDARD_HEADER_LEN: usize = 4;
+const EXTENDED_HEADER_LEN: usize = 10;
+
+// 标准头部 HTYP 标志位
+const HTYP_UEH: u8 = 0x01; // 带扩展头部
+const HTYP_MSBF: u8 = 0x02; // 负载为大端字节序
+const HTYP_WEID: u8 = 0x04; // 带 ECU ID
+const HTYP_WSID: u8 = 0x08; // 带会话 ID
+const HTYP_WTMS: u8 = 0x10; // 带时间戳
+
+// 扩展头部 MSIN 字段
+const MSIN_VERBOSE: u8 = 0x01;
+const MSTP_NW_TRACE: u8 = 0x2;
+const MTIN_NW_TRACE_IPC: u8 = 0x1;
+const MTIN_NW_TRACE_ETHERNET: u8 = 0x5;
+
+// verbose 参数类型信息中的 RAWD 位
+const TYPE_INFO_RAWD: u32 = 0x0400;
+
+/// AUTOSAR DLT 存储文件读取器，只输出 IPC/以太网总线的网络跟踪消息
+///
+/// 以太网跟踪的数据按以太网帧处理，IPC 跟踪的数据按原始 IP 包处理；
+/// verbose 消息的各个原始数据参数（通常为头部与负载）按顺序拼接。
+pub struct DltReader {
+    reader: ChunkReader<CaptureInput>,
+    filter: Option<BpfFilter>,
+    skipped: usize,
+    reported: usize, // 已输出到日志的跳过数
+}
+
+impl DltReader {
+    pub fn new(reader: ChunkReader<CaptureInput>) -> Result<Self> {
+        Ok(Self {
+            reader,
+            filter: None,
+            skipped: 0,
+            reported: 0,
+        })
+    }
+
+    /// 已读取到的输入偏移
+    pub fn position(&self) -> u64 {
+        self.reader.position()
+    }
+
+    pub fn set_filter(&mut self, expr: &str) -> std::result::Result<(), pcap::Error> {
+        self.filter = Some(BpfFilter::new(expr)?);
+        Ok(())
+    }
+
+    /// 跳过的非网络跟踪消息数自上次报告以来有变化时返回当前跳过数
+    pub fn take_skipped_report(&mut self) -> Option<usize> {
+        if self.skipped == self.reported {
+            return None;
+        }
+        self.reported = self.skipped;
+        Some(self.skipped)
+    }
+
+    /// 读取下一个网络跟踪消息，文件结束时返回 None
+    pub fn next_packet(&mut self) -> Result<Option<RawPacket>> {
+        loop {
+            if !self.reader.fill(STORAGE_HEADER_LEN + STANDARD_HEADER_LEN)? {
+                return self.end_of_input();
+            }
+            let storage = &self.reader.buffered()[..STORAGE_HEADER_LEN + STANDARD_HEADER_LEN];
+            if storage[..4] != DLT_STORAGE_MAGIC {
+                return Err(SomeIPError::CaptureFileError(
+                    "Invalid DLT storage header".to_string(),
+                )
+                .into());
+            }
+            let seconds = u32::from_le_bytes(storage[4..8].try_into().unwrap());
+            let micros = u32::from_le_bytes(storage[8..12].try_into().unwrap());
+
+            // 标准头部中的长度包含标准头部本身
+            let htyp = storage[STORAGE_HEADER_LEN];
+            let length = u16::from_be_bytes([
+                storage[STORAGE_HEADER_LEN + 2],
+                storage[STORAGE_HEADER_LEN + 3],
+            ]) as usize;
+            if length < STANDARD_HEADER_LEN {
+                return Err(SomeIPError::CaptureFileError(format!(
+                    "Invalid DLT message length: {}",
+                    length
+                ))
+                .into());
+            }
+            if !self.reader.fill(STORAGE_HEADER_LEN + length)? {
+                return self.end_of_input();
+            }
+            let record = self.reader.take(STORAGE_HEADER_LEN + length);
+            let message = &record[STORAGE_HEADER_LEN + STANDARD_HEADER_LEN..];
+
+            let Some((link_type, data)) = parse_network_trace(htyp, message) else {
+                self.skipped += 1;
+                continue;
+            };
+            if !self.matches_filter(&link_type, &data) {
+                continue;
+            }
+
+            return Ok(Some(RawPacket {
+                orig_len: data.len() as u32,
+                timestamp: SystemTime::UNIX_EPOCH
+                    + Duration::new(seconds as u64, micros.min(999_999) * 1000),
+                data: Bytes::from(data),
+                link_type,
+                source_file: None,
+            }));
+        }
+    }
+
+    fn end_of_input(&self) -> Result<Option<RawPacket>> {
+        if self.reader.buffered().is_empty() {
+            Ok(None)
+        } else {
+            Err(SomeIPError::CaptureFileError("Truncated DLT message".to_string()).into())
+        }
+    }
+
+    fn matches_filter(&self, link_type: &LinkType, data: &[u8]) -> bool {
+        // 以太网跟踪按以太网帧过滤，IPC 跟踪按原始 IP 包过滤
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(link_type.to_linktype() as u16, data))
+    }
+}
+
+/// 解析标准头部之后的内容，非 IPC/以太网网络跟踪消息返回 None
+fn parse_network_trace(htyp: u8, message: &[u8]) -> Option<(LinkType, Vec<u8>)> {
+    if htyp & HTYP_UEH == 0 {
+        return None;
+    }
+    let mut offset = 0;
+    for (flag, len) in [(HTYP_WEID, 4), (HTYP_WSID, 4), (HTYP_WTMS, 4)] {
+        if htyp & flag != 0 {
+            offset += len;
+        }
+    }
+    let extended = message.get(offset..offset + EXTENDED_HEADER_LEN)?;
+    let payload = &message[offset + EXTENDED_HEADER_LEN..];
+
+    let msin = extended[0];
+    let link_type = match ((msin >> 1) & 0x07, msin >> 4) {
+        (MSTP_NW_TRACE, MTIN_NW_TRACE_ETHERNET) => LinkType::Ethernet,
+        (MSTP_NW_TRACE, MTIN_NW_TRACE_IPC) => LinkType::Raw,
+        _ => return None,
+    };
+
+    if msin & MSIN_VERBOSE == 0 {
+        // 非 verbose 消息：4 字节消息 ID 之后即为数据
+        return Some((link_type, payload.get(4..)?.to_vec()));
+    }
+
+    // verbose 消息：拼接全部原始数据参数
+    let big_endian = htyp & HTYP_MSBF != 0;
+    let argument_count = extended[1];
+    let mut data = Vec::new();
+    let mut rest = payload;
+    for _ in 0..argument_count {
+        let type_info = rest.get(..4)?;
+        let type_info = u32::from_le_bytes(type_info.try_into().unwrap());
+        let type_info = if big_endian {
+            type_info.swap_bytes()
+        } else {
+            type_info
+        };
+        if type_info & TYPE_INFO_RAWD == 0 {
+            return None;
+        }
+        let len = rest.get(4..6)?;
+        let len = if big_endian {
+            u16::from_be_bytes([len[0], len[1]])
+        } else {
+            u16::from_le_bytes([len[0], len[1]])
+        } as usize;
+        data.extend_from_slice(rest.get(6..6 + len)?);
+        rest = &rest[6 + len..];
+    }
+    Some((link_type, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// 存储头部 + 标准头部（带扩展头部）+ 扩展头部 + 负载组成的一条 DLT 记录
+    fn record(seconds: u32, micros: u32, msin: u8, argument_count: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = DLT_STORAGE_MAGIC.to_vec();
+        bytes.extend_from_slice(&seconds.to_le_bytes());
+        bytes.extend_from_slice(&micros.to_le_bytes());
+        bytes.extend_from_slice(b"ECU1");
+        let length = STANDARD_HEADER_LEN + EXTENDED_HEADER_LEN + payload.len();
+        bytes.extend_from_slice(&[0x20 | HTYP_UEH, 0]);
+        bytes.extend_from_slice(&(length as u16).to_be_bytes());
+        bytes.extend_from_slice(&[msin, argument_count]);
+        bytes.extend_from_slice(b"APP1CTX1");
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// verbose 消息的原始数据参数（小端）
+    fn raw_argument(data: &[u8]) -> Vec<u8> {
+        let mut bytes = TYPE_INFO_RAWD.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn reader(bytes: Vec<u8>) -> DltReader {
+        DltReader::new(ChunkReader::new(Box::new(Cursor::new(bytes)))).unwrap()
+    }
+
+    const ETHERNET_NON_VERBOSE: u8 = MTIN_NW_TRACE_ETHERNET << 4 | MSTP_NW_TRACE << 1;
+    const IPC_VERBOSE: u8 = MTIN_NW_TRACE_IPC << 4 | MSTP_NW_TRACE << 1 | MSIN_VERBOSE;
+    const LOG_INFO_VERBOSE: u8 = 0x4 << 4 | MSIN_VERBOSE;
+
+    #[test]
+    fn reads_network_traces_and_skips_other_messages() {
+        let mut bytes = record(
+            1_700_000_000,
+            250_000,
+            ETHERNET_NON_VERBOSE,
+            0,
+            &[0, 0, 0, 7, 0xAA, 0xBB],
+        );
+        bytes.extend(record(1, 0, LOG_INFO_VERBOSE, 1, &raw_argument(b"log")));
+        let mut arguments = raw_argument(&[0x45, 0x00]);
+        arguments.extend(raw_argument(&[0x01, 0x02, 0x03]));
+        bytes.extend(record(1_700_000_001, 0, IPC_VERBOSE, 2, &arguments));
+        let mut reader = reader(bytes);
+
+        let ethernet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(ethernet.link_type, LinkType::Ethernet);
+        assert_eq!(&ethernet.data[..], [0xAA, 0xBB]);
+        assert_eq!(
+            ethernet.timestamp,
+            SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 250_000_000)
+        );
+
+        // 日志消息被跳过，IPC 跟踪的各个原始数据参数按顺序拼接
+        let ipc = reader.next_packet().unwrap().unwrap();
+        assert_eq!(ipc.link_type, LinkType::Raw);
+        assert_eq!(&ipc.data[..], [0x45, 0x00, 0x01, 0x02, 0x03]);
+        assert_eq!(ipc.orig_len, 5);
+
+        assert!(reader.next_packet().unwrap().is_none());
+        assert_eq!(reader.take_skipped_report(), Some(1));
+        assert_eq!(reader.take_skipped_report(), None);
+    }
+
+    #[test]
+    fn rejects_truncated_message_and_bad_magic() {
+        let mut bytes = record(1, 0, ETHERNET_NON_VERBOSE, 0, &[0, 0, 0, 7, 0xAA]);
+        bytes.pop();
+        assert!(reader(bytes).next_packet().is_err());
+
+        let mut bytes = record(1, 0, ETHERNET_NON_VERBOSE, 0, &[0, 0, 0, 7, 0xAA]);
+        bytes[3] = 0x02;
+        assert!(reader(bytes).next_packet().is_err());
+    }
+}
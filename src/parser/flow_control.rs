@@ -1,10 +1,101 @@
 // src/utils/flow_control.rs
 use super::super::parser::transport_layer::*;
 use crate::error::Result;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::net::IpAddr;
-use std::time::{Duration, Instant};
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant, SystemTime};
+
+/// 每个方向保留的最近已交付分段数，用于比较重传分段的内容
+const RECENT_SEGMENTS: usize = 64;
+
+/// 乱序缓存超过上限时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TcpGapPolicy {
+    /// 丢弃最早缓存的分段，继续等待缺失的数据
+    #[default]
+    DropOldest,
+    /// 放弃等待缺失的数据，从第一个缓存的分段继续交付
+    SkipGap,
+}
+
+/// TCP 连接（单个方向）的结束方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TcpClose {
+    /// 抓包结束时仍未关闭
+    Open,
+    /// 收到 FIN，正常关闭
+    Fin,
+    /// 收到 RST，异常终止
+    Reset,
+}
+
+impl fmt::Display for TcpClose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TcpClose::Open => "open",
+            TcpClose::Fin => "closed (FIN)",
+            TcpClose::Reset => "reset (RST)",
+        })
+    }
+}
+
+/// 单个方向 TCP 连接的传输统计，用于区分抓包丢包与解析问题
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TcpConnectionStats {
+    pub src_ip: String,
+    pub src_port: u16,
+    pub dst_ip: String,
+    pub dst_port: u16,
+    /// 按序交付给消息切分器的字节数
+    pub bytes_delivered: u64,
+    /// 乱序到达而进入缓存的分段数
+    pub segments_buffered: u64,
+    pub retransmissions: u64,
+    pub gaps_skipped: u64,
+    /// 第一个与最后一个分段之间的抓包时间跨度
+    pub duration_ms: f64,
+    pub close: TcpClose,
+    #[serde(skip)]
+    first_seen: SystemTime,
+    #[serde(skip)]
+    last_seen: SystemTime,
+}
+
+/// 已关闭或被移除的 TCP 连接方向的汇总统计，移除后不再单独保留每个连接的统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TcpClosedStats {
+    pub connections: u64,
+    /// 以 FIN 正常关闭的连接数
+    pub fin: u64,
+    /// 以 RST 终止的连接数
+    pub reset: u64,
+    pub bytes_delivered: u64,
+    pub segments_buffered: u64,
+    pub retransmissions: u64,
+    pub gaps_skipped: u64,
+}
+
+impl TcpClosedStats {
+    fn add(&mut self, stats: &TcpConnectionStats) {
+        self.connections += 1;
+        match stats.close {
+            TcpClose::Fin => self.fin += 1,
+            TcpClose::Reset => self.reset += 1,
+            TcpClose::Open => {}
+        }
+        self.bytes_delivered += stats.bytes_delivered;
+        self.segments_buffered += stats.segments_buffered;
+        self.retransmissions += stats.retransmissions;
+        self.gaps_skipped += stats.gaps_skipped;
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct TcpConnectionKey {
@@ -23,10 +114,22 @@ struct TcpSegment {
 struct TcpStream {
     segments: VecDeque<TcpSegment>,
     expected_seq: u32,
-    window_size: u16,
+    window_scale: u8,
+    window_size: u32, // 应用窗口缩放后的有效窗口
     last_activity: Instant,
     closed: bool,
     fin_seq: Option<u32>,
+    isn: Option<u32>, // 最近一次 SYN 的初始序列号，连接建立前开始抓包时为 None
+    delivered: VecDeque<(u32, Bytes)>, // 最近已交付的分段 (序列号, 数据)
+}
+
+/// 序列号比较采用 RFC 793 的模 2^32 算术，以正确处理序列号回绕
+fn seq_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+fn seq_before(a: u32, b: u32) -> bool {
+    seq_after(b, a)
 }
 
 pub struct TcpFlowController {
@@ -34,6 +137,15 @@ pub struct TcpFlowController {
     max_connections: usize,
     segment_timeout: Duration,
     connection_timeout: Duration,
+    max_buffered_bytes: usize,    // 每个连接缓存的乱序分段字节数上限
+    max_buffered_segments: usize, // 每个连接缓存的乱序分段数上限
+    gap_policy: TcpGapPolicy,
+    gaps_skipped: u64,    // 按 `SkipGap` 放弃等待的缺口数
+    restarts: usize,      // 同一四元组上以新的初始序列号重新建立的连接数
+    retransmissions: u64, // 数据已交付或已缓存而被丢弃的重传分段数
+    stats: HashMap<TcpConnectionKey, TcpConnectionStats>, // 仍在跟踪的连接方向的统计
+    closed: TcpClosedStats, // 连接被移除后其统计并入汇总
+    closed_isns: LruCache<TcpConnectionKey, u32>, // 被移除连接的初始序列号，用于识别 RST 之后的重建
 }
 
 impl TcpFlowController {
@@ -41,17 +153,40 @@ impl TcpFlowController {
         max_connections: usize,
         segment_timeout: Duration,
         connection_timeout: Duration,
+        max_buffered_bytes: usize,
     ) -> Self {
         Self {
             connections: HashMap::new(),
             max_connections,
             segment_timeout,
             connection_timeout,
+            max_buffered_bytes,
+            restarts: 0,
+            retransmissions: 0,
+            max_buffered_segments: usize::MAX,
+            gap_policy: TcpGapPolicy::default(),
+            gaps_skipped: 0,
+            stats: HashMap::new(),
+            closed: TcpClosedStats::default(),
+            closed_isns: LruCache::new(
+                NonZeroUsize::new(max_connections).unwrap_or(NonZeroUsize::MIN),
+            ),
         }
     }
 
+    /// 设置每个连接缓存的乱序分段数上限（默认不限制，只受字节数上限约束）
+    pub fn set_max_buffered_segments(&mut self, max_segments: usize) {
+        self.max_buffered_segments = max_segments;
+    }
+
+    /// 设置乱序缓存超过上限时的处理方式
+    pub fn set_gap_policy(&mut self, policy: TcpGapPolicy) {
+        self.gap_policy = policy;
+    }
+
     pub fn process_tcp_packet(
         &mut self,
+        timestamp: SystemTime,
         src_ip: &IpAddr,
         dst_ip: &IpAddr,
         tcp_packet: &TCPPacketInfo,
@@ -67,42 +202,90 @@ impl TcpFlowController {
         // 清理超时的连接
         self.cleanup_expired_connections();
 
-        // 如果达到最大连接数，移除最旧的连接
-        if self.connections.len() >= self.max_connections {
-            if let Some(oldest_key) = self
+        // 处理RST包：连接立即终止，丢弃缓存的分段
+        if tcp_packet.flags.rst {
+            Self::stats_entry(&mut self.stats, &key, timestamp).close = TcpClose::Reset;
+            self.remove_connection(&key);
+            return Ok(None);
+        }
+
+        // 新连接达到最大连接数时，移除最旧的连接
+        if !self.connections.contains_key(&key)
+            && self.connections.len() >= self.max_connections
+            && let Some(oldest_key) = self
                 .connections
                 .iter()
                 .min_by_key(|(_, stream)| stream.last_activity)
                 .map(|(key, _)| key.clone())
-            {
-                self.connections.remove(&oldest_key);
-            }
+        {
+            self.remove_connection(&oldest_key);
         }
+        let stats = Self::stats_entry(&mut self.stats, &key, timestamp);
 
         // 获取或创建TCP流
+        let existing = self.connections.contains_key(&key);
         let stream = self
             .connections
             .entry(key.clone())
             .or_insert_with(|| TcpStream {
                 segments: VecDeque::new(),
                 expected_seq: tcp_packet.seq_num,
-                window_size: tcp_packet.window_size,
+                window_scale: 0,
+                window_size: tcp_packet.window_size as u32,
                 last_activity: Instant::now(),
                 closed: false,
                 fin_seq: None,
+                isn: None,
+                delivered: VecDeque::new(),
             });
-        let stream = stream;
-        let stream = stream as *mut TcpStream;
-        // SAFETY: We have exclusive access to self, so this is safe.
-        let mut stream = unsafe { &mut *stream };
-
-        // 更新流状态
+        // 新建的流（新连接或 RST 之后中途接续的连接）重新计为未关闭
+        if !existing || tcp_packet.flags.syn {
+            stats.close = TcpClose::Open;
+        }
+        // 更新流状态：窗口缩放选项只在 SYN 中有效，其他分段中出现的忽略；
+        // SYN 自身的窗口不缩放（RFC 7323，缩放因子最大为14）
         stream.last_activity = Instant::now();
-        stream.window_size = tcp_packet.window_size;
+        if tcp_packet.flags.syn {
+            stream.window_scale = tcp_packet
+                .options
+                .window_scale
+                .map_or(0, |window_scale| window_scale.min(14));
+            stream.window_size = tcp_packet.window_size as u32;
+        } else {
+            stream.window_size = (tcp_packet.window_size as u32) << stream.window_scale;
+        }
 
-        // 处理SYN包
+        // 处理SYN包：新连接（可能复用了四元组），重置流状态；SYN 占用一个序列号
+        // 已有连接收到不同初始序列号的 SYN（无论之前是否收到 FIN）视为连接重建，
+        // 相同初始序列号的 SYN 只是重传
+        let mut seq_num = tcp_packet.seq_num;
         if tcp_packet.flags.syn {
-            stream.expected_seq = tcp_packet.seq_num + 1;
+            // 连接已因 RST 或超时被移除时，与移除前记录的初始序列号比较
+            if !existing {
+                stream.isn = self.closed_isns.pop(&key);
+            }
+            if stream.isn.is_some_and(|isn| isn != tcp_packet.seq_num)
+                || (existing && stream.isn.is_none())
+            {
+                self.restarts += 1;
+                log::debug!(
+                    "TCP connection {}:{} -> {}:{} restarted with ISN {} (previous {:?}), dropped {} buffered segments",
+                    key.src_ip,
+                    key.src_port,
+                    key.dst_ip,
+                    key.dst_port,
+                    tcp_packet.seq_num,
+                    stream.isn,
+                    stream.segments.len()
+                );
+            }
+            stream.isn = Some(tcp_packet.seq_num);
+            seq_num = seq_num.wrapping_add(1);
+            stream.expected_seq = seq_num;
+            stream.segments.clear();
+            stream.closed = false;
+            stream.fin_seq = None;
+            stream.delivered.clear();
             if payload.is_empty() {
                 return Ok(None);
             }
@@ -110,40 +293,118 @@ impl TcpFlowController {
 
         // 处理FIN包
         if tcp_packet.flags.fin {
-            stream.fin_seq = Some(tcp_packet.seq_num + payload.len() as u32);
+            stream.fin_seq = Some(tcp_packet.seq_num.wrapping_add(payload.len() as u32));
             stream.closed = true;
-        }
-
-        // 处理RST包
-        if tcp_packet.flags.rst {
-            stream.closed = true;
-            return Ok(None);
+            stats.close = TcpClose::Fin;
         }
 
         // 如果有数据，处理数据段
         if !payload.is_empty() {
+            let mut payload = payload;
+
+            // 起点早于期望序列号的分段是重传（抓包点同时看到原始分段与重传分段），
+            // 已交付的部分不再输出；内容与已交付的数据不同时给出警告
+            if seq_before(seq_num, stream.expected_seq) {
+                self.retransmissions += 1;
+                stats.retransmissions += 1;
+                if !Self::matches_delivered(stream, seq_num, &payload) {
+                    log::warn!(
+                        "TCP stream {}:{} -> {}:{} retransmitted seq {} with different payload, keeping the first copy",
+                        key.src_ip,
+                        key.src_port,
+                        key.dst_ip,
+                        key.dst_port,
+                        seq_num
+                    );
+                }
+
+                // 部分重传：分段跨越期望序列号，裁掉已接收的前缀，只保留新数据
+                let overlap = stream.expected_seq.wrapping_sub(seq_num) as usize;
+                if overlap < payload.len() {
+                    log::trace!(
+                        "Trimming {} overlapping bytes from TCP segment with seq num {}",
+                        overlap,
+                        seq_num
+                    );
+                    payload = payload.slice(overlap..);
+                    seq_num = stream.expected_seq;
+                }
+            }
+
             // 检查是否是期望的序列号
-            if tcp_packet.seq_num == stream.expected_seq {
+            if seq_num == stream.expected_seq {
                 // 按序到达的数据
-                stream.expected_seq += payload.len() as u32;
+                stream.expected_seq = stream.expected_seq.wrapping_add(payload.len() as u32);
+                Self::remember_delivered(stream, seq_num, payload.clone());
 
                 // 检查是否有积压的分段可以合并
-                let mut reassembled = payload;
-                self.process_out_of_order_segments(key.clone(), &mut stream, &mut reassembled)?;
+                let reassembled =
+                    Self::merge_out_of_order_segments(stream, self.segment_timeout, payload);
 
+                stats.bytes_delivered += reassembled.len() as u64;
                 return Ok(Some(reassembled));
-            } else if tcp_packet.seq_num > stream.expected_seq {
+            } else if seq_after(seq_num, stream.expected_seq) {
+                // 尚未交付但已缓存的分段再次到达，保留先到的一份
+                if stream
+                    .segments
+                    .iter()
+                    .any(|seg| seg.seq_num == seq_num && seg.data.len() >= payload.len())
+                {
+                    self.retransmissions += 1;
+                    stats.retransmissions += 1;
+                    return Ok(None);
+                }
+                stats.segments_buffered += 1;
                 stream.segments.push_back(TcpSegment {
-                    seq_num: tcp_packet.seq_num,
+                    seq_num,
                     data: payload,
                     timestamp: Instant::now(),
                 });
 
-                // 排序分段
+                // 按相对于期望序列号的偏移排序分段
+                let expected_seq = stream.expected_seq;
                 stream
                     .segments
                     .make_contiguous()
-                    .sort_by_key(|seg| seg.seq_num);
+                    .sort_by_key(|seg| seg.seq_num.wrapping_sub(expected_seq));
+
+                if Self::over_buffer_limit(
+                    stream,
+                    self.max_buffered_bytes,
+                    self.max_buffered_segments,
+                ) {
+                    match self.gap_policy {
+                        TcpGapPolicy::DropOldest => Self::drop_oldest_segments(
+                            &key,
+                            stream,
+                            self.max_buffered_bytes,
+                            self.max_buffered_segments,
+                        ),
+                        TcpGapPolicy::SkipGap => {
+                            // 分段已按偏移排序，跳到第一个缓存的分段并交付其后连续的数据
+                            let resume_seq = stream.segments[0].seq_num;
+                            log::warn!(
+                                "TCP stream {}:{} -> {}:{} gave up on {} missing bytes at seq {}",
+                                key.src_ip,
+                                key.src_port,
+                                key.dst_ip,
+                                key.dst_port,
+                                resume_seq.wrapping_sub(stream.expected_seq),
+                                stream.expected_seq
+                            );
+                            self.gaps_skipped += 1;
+                            stats.gaps_skipped += 1;
+                            stream.expected_seq = resume_seq;
+                            let reassembled = Self::merge_out_of_order_segments(
+                                stream,
+                                self.segment_timeout,
+                                Bytes::new(),
+                            );
+                            stats.bytes_delivered += reassembled.len() as u64;
+                            return Ok(Some(reassembled));
+                        }
+                    }
+                }
             } else {
                 // 重复的数据，丢弃
                 log::trace!(
@@ -156,46 +417,620 @@ impl TcpFlowController {
         Ok(None)
     }
 
-    fn process_out_of_order_segments(
-        &mut self,
-        key: TcpConnectionKey,
+    /// 取得连接方向的统计并更新其抓包时间范围，不存在时新建
+    fn stats_entry<'a>(
+        stats: &'a mut HashMap<TcpConnectionKey, TcpConnectionStats>,
+        key: &TcpConnectionKey,
+        timestamp: SystemTime,
+    ) -> &'a mut TcpConnectionStats {
+        let stats = stats
+            .entry(key.clone())
+            .or_insert_with(|| TcpConnectionStats {
+                src_ip: key.src_ip.clone(),
+                src_port: key.src_port,
+                dst_ip: key.dst_ip.clone(),
+                dst_port: key.dst_port,
+                bytes_delivered: 0,
+                segments_buffered: 0,
+                retransmissions: 0,
+                gaps_skipped: 0,
+                duration_ms: 0.0,
+                close: TcpClose::Open,
+                first_seen: timestamp,
+                last_seen: timestamp,
+            });
+        stats.first_seen = stats.first_seen.min(timestamp);
+        stats.last_seen = stats.last_seen.max(timestamp);
+        stats
+    }
+
+    /// 移除连接方向，其统计并入已关闭连接的汇总，初始序列号保留到该四元组下次出现 SYN
+    fn remove_connection(&mut self, key: &TcpConnectionKey) {
+        if let Some(isn) = self.connections.remove(key).and_then(|stream| stream.isn) {
+            self.closed_isns.put(key.clone(), isn);
+        }
+        if let Some(stats) = self.stats.remove(key) {
+            self.closed.add(&stats);
+        }
+    }
+
+    /// 将已与期望序列号衔接的缓存分段追加到 `head` 之后返回，并丢弃超时的分段
+    ///
+    /// 所有分段追加到同一个 `BytesMut` 中，没有可合并的分段时直接返回 `head`，不复制数据
+    fn merge_out_of_order_segments(
         stream: &mut TcpStream,
-        reassembled: &mut Bytes,
-    ) -> Result<()> {
+        segment_timeout: Duration,
+        head: Bytes,
+    ) -> Bytes {
+        let mut merged: Option<BytesMut> = None;
         // 检查是否有积压的分段可以合并
         while let Some(next_segment) = stream.segments.front() {
-            if next_segment.seq_num == stream.expected_seq {
-                // 下一个分段按序到达
-                let segment = stream.segments.pop_front().unwrap();
-                let mut buf = reassembled.clone().to_vec();
-                buf.extend_from_slice(&segment.data);
-                *reassembled = Bytes::from(buf);
-                stream.expected_seq += segment.data.len() as u32;
-            } else if next_segment.seq_num < stream.expected_seq {
-                // 重复的分段，丢弃
-                stream.segments.pop_front();
-            } else {
+            if seq_after(next_segment.seq_num, stream.expected_seq) {
                 // 后续分段还未准备好
                 break;
             }
+
+            // 分段起点不晚于期望序列号，跳过与已重组数据重叠的部分
+            let segment = stream.segments.pop_front().unwrap();
+            let overlap = stream.expected_seq.wrapping_sub(segment.seq_num) as usize;
+            if overlap >= segment.data.len() {
+                // 完全重复的分段，丢弃
+                continue;
+            }
+
+            Self::remember_delivered(stream, stream.expected_seq, segment.data.slice(overlap..));
+            merged
+                .get_or_insert_with(|| BytesMut::from(&head[..]))
+                .extend_from_slice(&segment.data[overlap..]);
+            stream.expected_seq = stream
+                .expected_seq
+                .wrapping_add((segment.data.len() - overlap) as u32);
         }
 
         // 清理超时的分段
         stream
             .segments
-            .retain(|seg| Instant::now().duration_since(seg.timestamp) <= self.segment_timeout);
+            .retain(|seg| Instant::now().duration_since(seg.timestamp) <= segment_timeout);
 
-        Ok(())
+        merged.map_or(head, BytesMut::freeze)
+    }
+
+    /// 记录已交付的分段，只保留最近的 `RECENT_SEGMENTS` 个
+    fn remember_delivered(stream: &mut TcpStream, seq_num: u32, data: Bytes) {
+        if stream.delivered.len() == RECENT_SEGMENTS {
+            stream.delivered.pop_front();
+        }
+        stream.delivered.push_back((seq_num, data));
+    }
+
+    /// 重传分段与最近已交付数据重叠的部分是否一致；重叠部分已不在记录中时视为一致
+    fn matches_delivered(stream: &TcpStream, seq_num: u32, data: &[u8]) -> bool {
+        stream.delivered.iter().all(|(delivered_seq, delivered)| {
+            // 以重传分段起点为原点，计算已交付分段在其中的区间
+            let start = delivered_seq.wrapping_sub(seq_num) as i32 as i64;
+            let end = start + delivered.len() as i64;
+            let from = start.max(0);
+            let to = end.min(data.len() as i64);
+            from >= to
+                || data[from as usize..to as usize]
+                    == delivered[(from - start) as usize..(to - start) as usize]
+        })
+    }
+
+    /// 缓存的乱序分段是否超过字节数或分段数上限
+    fn over_buffer_limit(stream: &TcpStream, max_bytes: usize, max_segments: usize) -> bool {
+        stream.segments.len() > max_segments
+            || stream
+                .segments
+                .iter()
+                .map(|seg| seg.data.len())
+                .sum::<usize>()
+                > max_bytes
+    }
+
+    /// 缓存的乱序分段超过上限时（例如永久性缺口），按到达顺序丢弃最旧的分段
+    fn drop_oldest_segments(
+        key: &TcpConnectionKey,
+        stream: &mut TcpStream,
+        max_bytes: usize,
+        max_segments: usize,
+    ) {
+        let mut buffered: usize = stream.segments.iter().map(|seg| seg.data.len()).sum();
+        let mut dropped = 0;
+        while buffered > max_bytes || stream.segments.len() > max_segments {
+            let Some(oldest) = stream
+                .segments
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, seg)| seg.timestamp)
+                .map(|(index, _)| index)
+            else {
+                break;
+            };
+            if let Some(segment) = stream.segments.remove(oldest) {
+                buffered -= segment.data.len();
+                dropped += segment.data.len();
+            }
+        }
+
+        log::warn!(
+            "TCP stream {}:{} -> {}:{} exceeded {} buffered bytes or {} segments, dropped {} bytes of out-of-order segments",
+            key.src_ip,
+            key.src_port,
+            key.dst_ip,
+            key.dst_port,
+            max_bytes,
+            max_segments,
+            dropped
+        );
     }
 
     fn cleanup_expired_connections(&mut self) {
         let now = Instant::now();
-        self.connections.retain(|_, stream| {
-            !stream.closed || now.duration_since(stream.last_activity) <= self.connection_timeout
-        });
+        let expired: Vec<_> = self
+            .connections
+            .iter()
+            .filter(|(_, stream)| {
+                stream.closed && now.duration_since(stream.last_activity) > self.connection_timeout
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            self.remove_connection(&key);
+        }
+    }
+
+    /// 某个方向是否缓存着乱序到达、尚未交付的分段
+    pub fn has_buffered_segments(
+        &self,
+        src_ip: &IpAddr,
+        src_port: u16,
+        dst_ip: &IpAddr,
+        dst_port: u16,
+    ) -> bool {
+        let key = TcpConnectionKey {
+            src_ip: src_ip.to_string(),
+            src_port,
+            dst_ip: dst_ip.to_string(),
+            dst_port,
+        };
+        self.connections
+            .get(&key)
+            .is_some_and(|stream| !stream.segments.is_empty())
     }
 
     pub fn get_connections_count(&self) -> usize {
         self.connections.len()
     }
+
+    /// 同一四元组上重新建立的连接数
+    pub fn connection_restarts(&self) -> usize {
+        self.restarts
+    }
+
+    /// 被识别为重传而丢弃（或裁掉已交付部分）的分段数
+    pub fn retransmission_count(&self) -> u64 {
+        self.retransmissions
+    }
+
+    /// 因乱序缓存超过上限而放弃等待的缺口数（仅 `SkipGap`）
+    pub fn gaps_skipped(&self) -> u64 {
+        self.gaps_skipped
+    }
+
+    /// 已关闭或被移除的连接方向的汇总统计
+    pub fn closed_connection_stats(&self) -> TcpClosedStats {
+        self.closed.clone()
+    }
+
+    /// 仍在跟踪的各连接方向的传输统计，按首个分段的抓包时间排序
+    pub fn connection_stats(&self) -> Vec<TcpConnectionStats> {
+        let mut connections: Vec<_> = self
+            .stats
+            .values()
+            .map(|stats| TcpConnectionStats {
+                duration_ms: stats
+                    .last_seen
+                    .duration_since(stats.first_seen)
+                    .unwrap_or_default()
+                    .as_secs_f64()
+                    * 1000.0,
+                ..stats.clone()
+            })
+            .collect();
+        connections.sort_by_key(|stats| stats.first_seen);
+        connections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::message::tcp_segment;
+    use std::net::Ipv4Addr;
+    use std::time::UNIX_EPOCH;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    fn controller(max_connections: usize) -> TcpFlowController {
+        TcpFlowController::new(
+            max_connections,
+            Duration::from_secs(30),
+            Duration::ZERO,
+            1 << 20,
+        )
+    }
+
+    fn send(
+        flow: &mut TcpFlowController,
+        segment: &TCPPacketInfo,
+        payload: &[u8],
+    ) -> Option<Bytes> {
+        flow.process_tcp_packet(
+            UNIX_EPOCH,
+            &CLIENT,
+            &SERVER,
+            segment,
+            Bytes::copy_from_slice(payload),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reset_connection_folded_into_closed_totals() {
+        let mut flow = controller(100);
+        send(&mut flow, &tcp_segment(40000, 1000), b"abcd");
+        let mut rst = tcp_segment(40000, 1004);
+        rst.flags.rst = true;
+        send(&mut flow, &rst, b"");
+
+        assert!(flow.connection_stats().is_empty());
+        let closed = flow.closed_connection_stats();
+        assert_eq!(closed.connections, 1);
+        assert_eq!(closed.reset, 1);
+        assert_eq!(closed.bytes_delivered, 4);
+    }
+
+    #[test]
+    fn finished_connection_pruned_after_timeout() {
+        let mut flow = controller(100);
+        let mut fin = tcp_segment(40000, 1000);
+        fin.flags.fin = true;
+        send(&mut flow, &fin, b"abcd");
+        assert_eq!(flow.connection_stats()[0].close, TcpClose::Fin);
+
+        std::thread::sleep(Duration::from_millis(1));
+        send(&mut flow, &tcp_segment(40001, 1000), b"ef");
+        let connections = flow.connection_stats();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].src_port, 40001);
+        assert_eq!(flow.get_connections_count(), 1);
+        let closed = flow.closed_connection_stats();
+        assert_eq!((closed.connections, closed.fin), (1, 1));
+        assert_eq!(closed.bytes_delivered, 4);
+    }
+
+    #[test]
+    fn evicted_connection_counted_as_closed() {
+        let mut flow = controller(1);
+        send(&mut flow, &tcp_segment(40000, 1000), b"abcd");
+        send(&mut flow, &tcp_segment(40001, 1000), b"ef");
+        // 已跟踪的连接继续收到数据时不会挤掉自己
+        send(&mut flow, &tcp_segment(40001, 1002), b"gh");
+
+        let connections = flow.connection_stats();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].bytes_delivered, 4);
+        let closed = flow.closed_connection_stats();
+        assert_eq!((closed.connections, closed.fin, closed.reset), (1, 0, 0));
+    }
+
+    fn syn(src_port: u16, isn: u32) -> TCPPacketInfo {
+        let mut segment = tcp_segment(src_port, isn);
+        segment.flags.syn = true;
+        segment
+    }
+
+    #[test]
+    fn syn_with_new_isn_restarts_stream() {
+        let mut flow = controller(100);
+        send(&mut flow, &syn(40000, 1000), b"");
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1001), b"ab").unwrap(),
+            "ab"
+        );
+        // 没有 FIN/RST 的情况下以新的初始序列号重新连接，旧的期望序列号不再适用
+        send(&mut flow, &syn(40000, 5000), b"");
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 5001), b"cd").unwrap(),
+            "cd"
+        );
+        assert_eq!(flow.connection_restarts(), 1);
+    }
+
+    #[test]
+    fn retransmitted_syn_is_not_a_restart() {
+        let mut flow = controller(100);
+        send(&mut flow, &syn(40000, 1000), b"");
+        send(&mut flow, &syn(40000, 1000), b"");
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1001), b"ab").unwrap(),
+            "ab"
+        );
+        assert_eq!(flow.connection_restarts(), 0);
+    }
+
+    #[test]
+    fn syn_after_reset_counts_as_restart() {
+        let mut flow = controller(100);
+        send(&mut flow, &syn(40000, 1000), b"");
+        send(&mut flow, &tcp_segment(40000, 1001), b"ab");
+        let mut rst = tcp_segment(40000, 1003);
+        rst.flags.rst = true;
+        send(&mut flow, &rst, b"");
+        assert_eq!(flow.get_connections_count(), 0);
+
+        send(&mut flow, &syn(40000, 9000), b"");
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 9001), b"cd").unwrap(),
+            "cd"
+        );
+        assert_eq!(flow.connection_restarts(), 1);
+        // 其他四元组上的第一个 SYN 不算重建
+        send(&mut flow, &syn(40001, 1000), b"");
+        assert_eq!(flow.connection_restarts(), 1);
+    }
+
+    fn window_size(flow: &TcpFlowController, src_port: u16) -> u32 {
+        let key = TcpConnectionKey {
+            src_ip: CLIENT.to_string(),
+            src_port,
+            dst_ip: SERVER.to_string(),
+            dst_port: 30502,
+        };
+        flow.connections[&key].window_size
+    }
+
+    #[test]
+    fn window_scale_taken_only_from_syn() {
+        let mut flow = controller(100);
+        let mut handshake = syn(40000, 1000);
+        handshake.options.window_scale = Some(3);
+        handshake.window_size = 1000;
+        send(&mut flow, &handshake, b"");
+        assert_eq!(window_size(&flow, 40000), 1000);
+
+        let mut data = tcp_segment(40000, 1001);
+        data.window_size = 1000;
+        send(&mut flow, &data, b"ab");
+        assert_eq!(window_size(&flow, 40000), 8000);
+
+        // 非 SYN 分段中的缩放选项被忽略
+        data.seq_num = 1003;
+        data.options.window_scale = Some(7);
+        send(&mut flow, &data, b"cd");
+        assert_eq!(window_size(&flow, 40000), 8000);
+    }
+
+    #[test]
+    fn syn_discards_buffered_segments() {
+        let mut flow = controller(100);
+        send(&mut flow, &syn(40000, 1000), b"");
+        // 1001 之前的数据丢失，1003 处的分段进入乱序缓存
+        assert!(send(&mut flow, &tcp_segment(40000, 1003), b"cd").is_none());
+        assert!(flow.has_buffered_segments(&CLIENT, 40000, &SERVER, 30502));
+
+        send(&mut flow, &syn(40000, 2000), b"");
+        assert!(!flow.has_buffered_segments(&CLIENT, 40000, &SERVER, 30502));
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 2001), b"ef").unwrap(),
+            "ef"
+        );
+    }
+
+    #[test]
+    fn fin_and_rst_close_connection() {
+        // 连接超时足够长，已收到 FIN 的连接在检查前不会被清理
+        let mut flow = TcpFlowController::new(
+            100,
+            Duration::from_secs(30),
+            Duration::from_secs(30),
+            1 << 20,
+        );
+        send(&mut flow, &syn(40000, 1000), b"");
+        let mut fin = tcp_segment(40000, 1001);
+        fin.flags.fin = true;
+        assert_eq!(send(&mut flow, &fin, b"ab").unwrap(), "ab");
+        assert_eq!(flow.connection_stats()[0].close, TcpClose::Fin);
+
+        send(&mut flow, &syn(40001, 1000), b"");
+        let mut rst = tcp_segment(40001, 1001);
+        rst.flags.rst = true;
+        send(&mut flow, &rst, b"");
+        let connections = flow.connection_stats();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].src_port, 40000);
+        assert_eq!(flow.closed_connection_stats().reset, 1);
+    }
+
+    #[test]
+    fn reassembles_across_sequence_wraparound() {
+        let mut flow = controller(100);
+        let data: Vec<u8> = (0..1024).map(|i| (i % 251) as u8).collect();
+        // 第一个数据字节位于 0xFFFF_FF00，第二个分段起点回绕到 0
+        send(&mut flow, &syn(40000, 0xFFFF_FEFF), b"");
+        let chunk = |index: usize| {
+            let seq_num = 0xFFFF_FF00u32.wrapping_add(index as u32 * 256);
+            (
+                tcp_segment(40000, seq_num),
+                &data[index * 256..(index + 1) * 256],
+            )
+        };
+
+        let mut delivered = Vec::new();
+        for index in [0, 2, 3, 1] {
+            let (segment, payload) = chunk(index);
+            if let Some(bytes) = send(&mut flow, &segment, payload) {
+                delivered.extend_from_slice(&bytes);
+            }
+        }
+        assert_eq!(delivered, data);
+        assert!(!flow.has_buffered_segments(&CLIENT, 40000, &SERVER, 30502));
+    }
+
+    #[test]
+    fn exact_duplicate_is_dropped() {
+        let mut flow = controller(100);
+        send(&mut flow, &syn(40000, 1000), b"");
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1001), b"abcd").unwrap(),
+            "abcd"
+        );
+        assert!(send(&mut flow, &tcp_segment(40000, 1001), b"abcd").is_none());
+        assert_eq!(flow.retransmission_count(), 1);
+    }
+
+    #[test]
+    fn repeated_first_segment_of_two_is_suppressed() {
+        for repeated in [&b"abcd"[..], b"XXXX"] {
+            let mut flow = controller(100);
+            send(&mut flow, &syn(40000, 1000), b"");
+            let mut delivered = Vec::new();
+            for (seq_num, payload) in [(1001, &b"abcd"[..]), (1001, repeated), (1005, b"efgh")] {
+                if let Some(bytes) = send(&mut flow, &tcp_segment(40000, seq_num), payload) {
+                    delivered.extend_from_slice(&bytes);
+                }
+            }
+            // 内容不同的重传同样丢弃，保留先到的一份
+            assert_eq!(delivered, b"abcdefgh");
+            assert_eq!(flow.retransmission_count(), 1);
+            assert_eq!(flow.connection_stats()[0].retransmissions, 1);
+        }
+    }
+
+    #[test]
+    fn segment_cap_drops_oldest_buffered_segment() {
+        let mut flow = controller(100);
+        flow.set_max_buffered_segments(2);
+        send(&mut flow, &syn(40000, 1000), b"");
+        // 按到达顺序，偏移最大的分段最先缓存，超过上限时被丢弃
+        for (seq_num, payload) in [(1013, b"mnop"), (1005, b"efgh"), (1009, b"ijkl")] {
+            assert!(send(&mut flow, &tcp_segment(40000, seq_num), payload).is_none());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1001), b"abcd").unwrap(),
+            "abcdefghijkl"
+        );
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1013), b"mnop").unwrap(),
+            "mnop"
+        );
+        assert_eq!(flow.gaps_skipped(), 0);
+    }
+
+    #[test]
+    fn byte_cap_with_skip_gap_resumes_after_missing_data() {
+        let mut flow = TcpFlowController::new(100, Duration::from_secs(30), Duration::ZERO, 8);
+        flow.set_gap_policy(TcpGapPolicy::SkipGap);
+        send(&mut flow, &syn(40000, 1000), b"");
+        assert!(send(&mut flow, &tcp_segment(40000, 1005), b"efgh").is_none());
+        assert!(send(&mut flow, &tcp_segment(40000, 1009), b"ijkl").is_none());
+        // 第三个分段使缓存超过 8 字节，放弃等待 1001 处缺失的 4 字节
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1013), b"mnop").unwrap(),
+            "efghijklmnop"
+        );
+        assert_eq!(flow.gaps_skipped(), 1);
+        assert_eq!(flow.connection_stats()[0].gaps_skipped, 1);
+        assert!(!flow.has_buffered_segments(&CLIENT, 40000, &SERVER, 30502));
+        // 缺失的数据迟到时视为重传，不再交付
+        assert!(send(&mut flow, &tcp_segment(40000, 1001), b"abcd").is_none());
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1017), b"qr").unwrap(),
+            "qr"
+        );
+    }
+
+    #[test]
+    fn partial_overlap_delivers_only_new_bytes() {
+        let mut flow = controller(100);
+        send(&mut flow, &syn(40000, 1000), b"");
+        send(&mut flow, &tcp_segment(40000, 1001), b"abcd");
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1003), b"cdef").unwrap(),
+            "ef"
+        );
+        assert_eq!(flow.retransmission_count(), 1);
+    }
+
+    #[test]
+    fn buffered_segments_overlapping_in_order_data_are_trimmed() {
+        let mut flow = controller(100);
+        send(&mut flow, &syn(40000, 1000), b"");
+        // 两个相互重叠的乱序分段，都与随后到达的按序分段重叠
+        assert!(send(&mut flow, &tcp_segment(40000, 1005), b"efgh").is_none());
+        assert!(send(&mut flow, &tcp_segment(40000, 1003), b"cdef").is_none());
+        assert_eq!(
+            send(&mut flow, &tcp_segment(40000, 1001), b"abcd").unwrap(),
+            "abcdefgh"
+        );
+        assert!(!flow.has_buffered_segments(&CLIENT, 40000, &SERVER, 30502));
+    }
+
+    /// 期望序列号为 100、按顺序缓存了给定分段的流
+    fn buffered_stream(segments: &[(u32, &'static [u8])]) -> TcpStream {
+        TcpStream {
+            segments: segments
+                .iter()
+                .map(|&(seq_num, data)| TcpSegment {
+                    seq_num,
+                    data: Bytes::from_static(data),
+                    timestamp: Instant::now(),
+                })
+                .collect(),
+            expected_seq: 100,
+            window_scale: 0,
+            window_size: 0,
+            last_activity: Instant::now(),
+            closed: false,
+            fin_seq: None,
+            isn: None,
+            delivered: VecDeque::new(),
+        }
+    }
+
+    fn merge(stream: &mut TcpStream) -> Bytes {
+        TcpFlowController::merge_out_of_order_segments(
+            stream,
+            Duration::from_secs(30),
+            Bytes::from_static(b">"),
+        )
+    }
+
+    #[test]
+    fn merge_appends_contiguous_segments() {
+        let mut stream = buffered_stream(&[(100, b"ab"), (102, b"cd")]);
+        assert_eq!(merge(&mut stream), ">abcd");
+        assert_eq!(stream.expected_seq, 104);
+        assert!(stream.segments.is_empty());
+    }
+
+    #[test]
+    fn merge_stops_at_gap() {
+        let mut stream = buffered_stream(&[(100, b"ab"), (104, b"ef")]);
+        assert_eq!(merge(&mut stream), ">ab");
+        assert_eq!(stream.expected_seq, 102);
+        assert_eq!(stream.segments.len(), 1);
+        assert_eq!(stream.segments[0].seq_num, 104);
+    }
+
+    #[test]
+    fn merge_drops_duplicate_segments() {
+        let mut stream = buffered_stream(&[(98, b"xy"), (100, b"ab"), (100, b"ab"), (101, b"bc")]);
+        assert_eq!(merge(&mut stream), ">abc");
+        assert_eq!(stream.expected_seq, 103);
+        assert!(stream.segments.is_empty());
+    }
 }
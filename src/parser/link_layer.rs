@@ -1,10 +1,55 @@
 // src/parser/link_layer.rs
-use nom::{IResult, Parser, bytes::complete::take, number::complete::be_u16};
+use nom::{
+    IResult, Parser,
+    bytes::complete::take,
+    number::complete::{be_u8, be_u16, be_u32},
+};
+
+/// pcap 链路层类型 (DLT/LINKTYPE)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkType {
+    Null,
+    Ethernet,
+    Raw,
+    LinuxSLL,
+    LinuxSLL2,
+    Unknown(u16),
+}
+
+impl LinkType {
+    pub fn from_dlt(value: u16) -> Self {
+        match value {
+            // DLT_LOOP (108) 与 DLT_NULL 格式相同，但地址族固定为网络字节序
+            0 | 108 => LinkType::Null,
+            1 => LinkType::Ethernet,
+            // DLT_RAW 在不同平台上取值不同 (12/14)，文件中的 LINKTYPE_RAW 为 101
+            12 | 14 | 101 | 228 | 229 => LinkType::Raw,
+            113 => LinkType::LinuxSLL,
+            276 => LinkType::LinuxSLL2,
+            _ => LinkType::Unknown(value),
+        }
+    }
+
+    /// 写入抓包文件时使用的 LINKTYPE 值
+    pub fn to_linktype(&self) -> u32 {
+        match self {
+            LinkType::Null => 0,
+            LinkType::Ethernet => 1,
+            LinkType::Raw => 101,
+            LinkType::LinuxSLL => 113,
+            LinkType::LinuxSLL2 => 276,
+            LinkType::Unknown(value) => *value as u32,
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LinkLayer {
     Ethernet(EthernetFrame),
     SLL(SLLHeader),
+    SLL2(SLL2Header),
+    Raw(RawIpHeader),
+    Loopback(LoopbackHeader),
     // 其他链路层类型可以在此添加
 }
 
@@ -12,9 +57,20 @@ pub enum LinkLayer {
 pub struct EthernetFrame {
     pub dst_mac: [u8; 6],
     pub src_mac: [u8; 6],
+    pub vlan_ids: Vec<u16>,          // 802.1Q/802.1ad 标签，外层在前
+    pub mpls_labels: Vec<MplsLabel>, // MPLS 标签栈，栈顶在前
     pub ethertype: u16,
 }
 
+/// MPLS 标签栈条目 (RFC 3032)
+#[derive(Debug, Clone, PartialEq)]
+pub struct MplsLabel {
+    pub label: u32,
+    pub traffic_class: u8,
+    pub bottom_of_stack: bool,
+    pub ttl: u8,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SLLHeader {
     pub packet_type: u16,
@@ -24,30 +80,141 @@ pub struct SLLHeader {
     pub protocol: u16,
 }
 
-pub fn parse_link_layer(input: &[u8]) -> IResult<&[u8], LinkLayer> {
-    // 检查是否为SLL头 (Linux cooked capture)
-    if input.len() >= 16 && &input[0..2] == &[0x00, 0x00] {
-        return parse_sll(input);
+/// Linux cooked capture v2 头部（协议字段在前，包含接口索引）
+#[derive(Debug, Clone, PartialEq)]
+pub struct SLL2Header {
+    pub protocol: u16,
+    pub interface_index: u32,
+    pub link_layer_addr_type: u16,
+    pub packet_type: u8,
+    pub link_layer_addr_len: u8,
+    pub link_layer_addr: [u8; 8],
+}
+
+/// BSD loopback 封装 (DLT_NULL)，4 字节地址族字段使用抓包主机的字节序
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopbackHeader {
+    pub address_family: u32,
+    pub ethertype: u16,
+}
+
+/// 无链路层头部，直接承载 IP 报文，ethertype 由 IP 版本号推断
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawIpHeader {
+    pub ethertype: u16,
+}
+
+pub fn parse_link_layer(input: &[u8], link_type: LinkType) -> IResult<&[u8], LinkLayer> {
+    match link_type {
+        LinkType::Ethernet => parse_ethernet(input),
+        LinkType::LinuxSLL => parse_sll(input),
+        LinkType::LinuxSLL2 => parse_sll2(input),
+        LinkType::Raw => parse_raw_ip(input),
+        LinkType::Null => parse_loopback(input),
+        LinkType::Unknown(_) => Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Switch,
+        ))),
     }
+}
 
-    // 默认尝试解析以太网帧
-    parse_ethernet(input)
+fn parse_raw_ip(input: &[u8]) -> IResult<&[u8], LinkLayer> {
+    let (input, ethertype) = sniff_ip_version(input)?;
+    Ok((input, LinkLayer::Raw(RawIpHeader { ethertype })))
+}
+
+/// 根据首字节的 IP 版本号推断 ethertype，不消耗输入
+fn sniff_ip_version(input: &[u8]) -> IResult<&[u8], u16> {
+    match input.first().map(|b| b >> 4) {
+        Some(4) => Ok((input, 0x0800)),
+        Some(6) => Ok((input, 0x86DD)),
+        _ => Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        ))),
+    }
+}
+
+fn parse_loopback(input: &[u8]) -> IResult<&[u8], LinkLayer> {
+    let (rest, family) = take(4usize)(input)?;
+    // 地址族取值很小，按小端读取后若高位非零则说明是大端写入
+    let mut address_family = u32::from_le_bytes(family.try_into().unwrap());
+    if address_family > 0xFFFF {
+        address_family = address_family.swap_bytes();
+    }
+
+    let ethertype = match address_family {
+        2 => 0x0800, // AF_INET
+        // AF_INET6 在各平台取值不同：Linux 10，NetBSD/OpenBSD 24，FreeBSD 28，macOS 30
+        10 | 24 | 28 | 30 => 0x86DD,
+        _ => {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Switch,
+            )));
+        }
+    };
+
+    Ok((
+        rest,
+        LinkLayer::Loopback(LoopbackHeader {
+            address_family,
+            ethertype,
+        }),
+    ))
 }
 
 fn parse_ethernet(input: &[u8]) -> IResult<&[u8], LinkLayer> {
-    let (input, (dst_mac, src_mac, ethertype)) =
+    let (mut input, (dst_mac, src_mac, mut ethertype)) =
         (take(6usize), take(6usize), be_u16).parse(input)?;
 
+    // 逐层剥离 VLAN 标签 (802.1Q / 802.1ad QinQ)
+    let mut vlan_ids = Vec::new();
+    while matches!(ethertype, 0x8100 | 0x88A8 | 0x9100) {
+        let (rest, (tci, inner_ethertype)) = (be_u16, be_u16).parse(input)?;
+        vlan_ids.push(tci & 0x0FFF);
+        ethertype = inner_ethertype;
+        input = rest;
+    }
+
+    // 弹出 MPLS 标签栈，内层协议由负载首字节的 IP 版本号推断
+    let mut mpls_labels = Vec::new();
+    if matches!(ethertype, 0x8847 | 0x8848) {
+        (input, mpls_labels) = parse_mpls_labels(input)?;
+        (input, ethertype) = sniff_ip_version(input)?;
+    }
+
     Ok((
         input,
         LinkLayer::Ethernet(EthernetFrame {
             dst_mac: dst_mac.try_into().unwrap(),
             src_mac: src_mac.try_into().unwrap(),
-            ethertype: ethertype,
+            vlan_ids,
+            mpls_labels,
+            ethertype,
         }),
     ))
 }
 
+fn parse_mpls_labels(mut input: &[u8]) -> IResult<&[u8], Vec<MplsLabel>> {
+    let mut labels = Vec::new();
+    loop {
+        let (rest, entry) = be_u32(input)?;
+        input = rest;
+        let label = MplsLabel {
+            label: entry >> 12,
+            traffic_class: ((entry >> 9) & 0x07) as u8,
+            bottom_of_stack: entry & 0x100 != 0,
+            ttl: (entry & 0xFF) as u8,
+        };
+        let bottom_of_stack = label.bottom_of_stack;
+        labels.push(label);
+        if bottom_of_stack {
+            return Ok((input, labels));
+        }
+    }
+}
+
 fn parse_sll(input: &[u8]) -> IResult<&[u8], LinkLayer> {
     let (input, (packet_type, link_layer_addr_type, link_layer_addr_len)) =
         (be_u16, be_u16, be_u16).parse(input)?;
@@ -67,3 +234,50 @@ fn parse_sll(input: &[u8]) -> IResult<&[u8], LinkLayer> {
         }),
     ))
 }
+
+fn parse_sll2(input: &[u8]) -> IResult<&[u8], LinkLayer> {
+    let (input, (protocol, _reserved, interface_index)) = (be_u16, be_u16, be_u32).parse(input)?;
+    let (input, (link_layer_addr_type, packet_type, link_layer_addr_len)) =
+        (be_u16, be_u8, be_u8).parse(input)?;
+    let (input, link_layer_addr) = take(8usize)(input)?;
+
+    Ok((
+        input,
+        LinkLayer::SLL2(SLL2Header {
+            protocol,
+            interface_index,
+            link_layer_addr_type,
+            packet_type,
+            link_layer_addr_len,
+            link_layer_addr: link_layer_addr.try_into().unwrap(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::builder::PacketBuilder;
+
+    #[test]
+    fn ethernet_frame_with_all_zero_macs_is_not_sll() {
+        let mut frame = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .build();
+        // 全零 MAC 的前两个字节与 SLL 的“发往本机”包类型相同，按 DLT 解析时不应被误判
+        frame[..12].fill(0);
+        let (rest, link) = parse_link_layer(&frame, LinkType::Ethernet).unwrap();
+        assert_eq!(
+            link,
+            LinkLayer::Ethernet(EthernetFrame {
+                dst_mac: [0; 6],
+                src_mac: [0; 6],
+                vlan_ids: Vec::new(),
+                mpls_labels: Vec::new(),
+                ethertype: 0x0800,
+            })
+        );
+        assert_eq!(rest, &frame[14..]);
+    }
+}
@@ -1,8 +1,15 @@
 //! 核心解析模块，负责从PCAP文件中解析网络协议和SomeIP消息
 
+pub mod bpf;
+pub mod checksum;
+pub mod chunk_reader;
+pub mod classic_pcap_reader;
+pub mod dlt_reader;
 pub mod flow_control;
 pub mod link_layer;
 pub mod network_layer;
 pub mod pcap_reader;
+pub mod pcapng_reader;
 pub mod someip;
 pub mod transport_layer;
+pub mod tunnel;
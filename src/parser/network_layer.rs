@@ -1,11 +1,9 @@
-use log::debug;
 // src/parser/network_layer.rs
 use nom::{
     IResult,
     bytes::complete::take,
     number::complete::{be_u8, be_u16, be_u32},
 };
-use serde::de;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum NetworkLayer {
@@ -28,6 +26,7 @@ pub struct IPv4PacketInfo {
     pub checksum: u16,
     pub src_ip: [u8; 4],
     pub dst_ip: [u8; 4],
+    pub options: Vec<u8>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -84,6 +83,22 @@ fn parse_ipv4(input: &[u8]) -> IResult<&[u8], NetworkLayer> {
     //     src_ip, dst_ip
     // );
 
+    // 首部长度以 4 字节为单位，最小为 5
+    if ihl < 5 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+    let header_len = ihl as usize * 4;
+
+    // 跳过 IP 选项
+    let (input, options) = take(header_len - 20)(input)?;
+
+    // 按总长度截断，去除以太网填充
+    let payload_len = (total_length as usize).saturating_sub(header_len);
+    let input = &input[..payload_len.min(input.len())];
+
     Ok((
         input,
         NetworkLayer::IPv4(IPv4PacketInfo {
@@ -100,6 +115,7 @@ fn parse_ipv4(input: &[u8]) -> IResult<&[u8], NetworkLayer> {
             checksum,
             src_ip: src_ip.try_into().unwrap(),
             dst_ip: dst_ip.try_into().unwrap(),
+            options: options.to_vec(),
         }),
     ))
 }
@@ -116,6 +132,12 @@ fn parse_ipv6(input: &[u8]) -> IResult<&[u8], NetworkLayer> {
     let (input, src_ip) = take(16usize)(input)?;
     let (input, dst_ip) = take(16usize)(input)?;
 
+    // 按负载长度截断，去除以太网填充（长度为 0 表示巨型帧，不截断）
+    let input = match payload_length as usize {
+        0 => input,
+        payload_len => &input[..payload_len.min(input.len())],
+    };
+
     Ok((
         input,
         NetworkLayer::IPv6(IPv6PacketInfo {
@@ -130,3 +152,61 @@ fn parse_ipv6(input: &[u8]) -> IResult<&[u8], NetworkLayer> {
         }),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IPv4 头部（校验和为 0），`options` 长度须为 4 的倍数
+    fn ipv4_header(options: &[u8], total_length: u16) -> Vec<u8> {
+        let ihl = 5 + (options.len() / 4) as u8;
+        let mut header = vec![0x40 | ihl, 0];
+        header.extend_from_slice(&total_length.to_be_bytes());
+        header.extend_from_slice(&[0, 0, 0x40, 0, 64, 17, 0, 0]);
+        header.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        header.extend_from_slice(options);
+        header
+    }
+
+    fn parse_ipv4_packet(packet: &[u8]) -> (&[u8], IPv4PacketInfo) {
+        match parse_network_layer(packet, 0x0800).unwrap() {
+            (rest, NetworkLayer::IPv4(info)) => (rest, info),
+            other => panic!("expected IPv4, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn router_alert_option_is_skipped() {
+        let router_alert = [0x94, 0x04, 0x00, 0x00];
+        let udp = [0x77, 0x25, 0x77, 0x26, 0x00, 0x08, 0x00, 0x00];
+        let mut packet = ipv4_header(&router_alert, 24 + udp.len() as u16);
+        packet.extend_from_slice(&udp);
+
+        let (rest, info) = parse_ipv4_packet(&packet);
+        assert_eq!(info.header_length, 6);
+        assert_eq!(info.options, router_alert);
+        // 选项之后紧接 UDP 头部
+        assert_eq!(rest, udp);
+    }
+
+    #[test]
+    fn ethernet_padding_is_truncated_to_total_length() {
+        // 28 字节的 IPv4/UDP 报文被填充到以太网最小负载 46 字节
+        let udp = [0x77, 0x25, 0x77, 0x26, 0x00, 0x08, 0x00, 0x00];
+        let mut packet = ipv4_header(&[], 28);
+        packet.extend_from_slice(&udp);
+        packet.resize(46, 0);
+
+        let (rest, info) = parse_ipv4_packet(&packet);
+        assert_eq!(info.total_length, 28);
+        assert!(info.options.is_empty());
+        assert_eq!(rest, udp);
+    }
+
+    #[test]
+    fn header_length_below_minimum_is_rejected() {
+        let mut packet = ipv4_header(&[], 20);
+        packet[0] = 0x44;
+        assert!(parse_network_layer(&packet, 0x0800).is_err());
+    }
+}
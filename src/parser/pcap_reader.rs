@@ -1,46 +1,470 @@
 // src/parser/pcap_reader.rs
-use pcap::{Capture, Packet};
-use std::time::SystemTime;
+use super::chunk_reader::{CaptureInput, ChunkReader};
+use super::classic_pcap_reader::{ClassicPcapReader, is_classic_pcap};
+use super::dlt_reader::{DLT_STORAGE_MAGIC, DltReader};
+use super::link_layer::LinkType;
+use super::pcapng_reader::{PCAPNG_MAGIC, PcapNgReader};
+use crate::error::{Result, SomeIPError};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
 
 #[derive(Debug, Clone)]
 pub struct RawPacket {
     pub timestamp: SystemTime,
-    pub data: Vec<u8>,
+    pub data: Bytes,   // 后续各层解析的负载均为该缓冲区的零拷贝切片
+    pub orig_len: u32, // 帧在线路上的原始长度，抓包时被截断则大于 data.len()
+    pub link_type: LinkType,
+    pub source_file: Option<String>, // 数据包来源文件（多文件输入时）
 }
 
-impl<'a> From<Packet<'a>> for RawPacket {
-    fn from(packet: Packet<'a>) -> Self {
-        RawPacket {
-            timestamp: SystemTime::UNIX_EPOCH
-                .checked_add(std::time::Duration::new(
-                    packet.header.ts.tv_sec as u64,
-                    packet.header.ts.tv_usec as u32 * 1000,
-                ))
-                .unwrap(),
-            data: packet.data.to_vec(),
-        }
-    }
+/// 抓包文件头部中的链路层类型与快照长度
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureInfo {
+    pub link_type: u16,
+    pub snap_len: u32,
+}
+
+/// 输入文件格式，`Auto` 按扩展名与文件头魔数识别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    #[default]
+    Auto,
+    /// 经典 pcap 或 pcapng
+    Pcap,
+    /// AUTOSAR DLT 存储文件中的网络跟踪消息
+    Dlt,
+}
+
+enum CaptureSource {
+    Pcap(ClassicPcapReader),
+    PcapNg(PcapNgReader),
+    Dlt(DltReader),
 }
 
 pub struct PCAPReader {
-    capture: Capture<pcap::Offline>,
+    source: CaptureSource,
+    path: String,
+    format: InputFormat,
+    filter: Option<String>,
+    following: bool, // 跟随模式：读取失败视为文件尾部尚未写完
+    info: Option<CaptureInfo>,
 }
 
+/// 表示从标准输入读取抓包数据的路径
+pub const STDIN_PATH: &str = "-";
+
 impl PCAPReader {
-    pub fn new(pcap_file: &str) -> Result<Self, pcap::Error> {
-        let capture = Capture::from_file(pcap_file)?;
-        Ok(Self { capture })
+    /// 打开抓包文件，路径为 `-` 时从标准输入读取
+    ///
+    /// 标准输入按数据流直接读取，不落盘；格式仍按文件头魔数识别。
+    pub fn new(pcap_file: &str, format: InputFormat) -> Result<Self> {
+        let input: CaptureInput = if pcap_file == STDIN_PATH {
+            Box::new(std::io::stdin())
+        } else {
+            Box::new(File::open(pcap_file)?)
+        };
+        let source = open_source(input, pcap_file, format)?;
+        let info = match &source {
+            CaptureSource::Pcap(reader) => Some(reader.capture_info()),
+            CaptureSource::PcapNg(reader) => reader.capture_info(),
+            CaptureSource::Dlt(_) => None,
+        };
+        Ok(Self {
+            source,
+            path: pcap_file.to_string(),
+            format,
+            filter: None,
+            following: false,
+            info,
+        })
+    }
+
+    /// 文件头部信息（pcapng 取第一个接口描述块），无法识别时为 None
+    pub fn capture_info(&self) -> Option<CaptureInfo> {
+        self.info
+    }
+
+    /// 设置 BPF 过滤表达式，只读取匹配的数据包
+    pub fn set_filter(&mut self, expr: &str) -> Result<()> {
+        let result = match &mut self.source {
+            CaptureSource::Pcap(reader) => reader.set_filter(expr),
+            CaptureSource::PcapNg(reader) => reader.set_filter(expr),
+            CaptureSource::Dlt(reader) => reader.set_filter(expr),
+        };
+        result.map_err(|e| {
+            SomeIPError::ConfigError(format!("Invalid BPF filter '{}': {}", expr, e))
+        })?;
+        self.filter = Some(expr.to_string());
+        Ok(())
+    }
+
+    /// 已读取到的文件偏移
+    fn position(&self) -> u64 {
+        match &self.source {
+            CaptureSource::Pcap(reader) => reader.position(),
+            CaptureSource::PcapNg(reader) => reader.position(),
+            CaptureSource::Dlt(reader) => reader.position(),
+        }
+    }
+
+    /// 跟随模式下检查文件是否被轮转
+    ///
+    /// 追加的数据无需处理，读取器保持文件句柄与偏移，下次读取时继续；
+    /// 文件变得比已读取的偏移还短时视为轮转，重新打开并从头读取。
+    pub fn check_rotation(&mut self) -> Result<()> {
+        if self.path == STDIN_PATH {
+            return Ok(());
+        }
+        let file_len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return Ok(()), // 轮转过程中文件可能暂时不存在
+        };
+        if file_len >= self.position() {
+            return Ok(());
+        }
+
+        log::info!("Capture file {} was rotated, reading from start", self.path);
+        self.source = open_source(Box::new(File::open(&self.path)?), &self.path, self.format)?;
+        if let Some(filter) = self.filter.clone() {
+            self.set_filter(&filter)?;
+        }
+        Ok(())
+    }
+
+    /// 读取下一个数据包，文件结束时返回 None
+    pub fn next_packet(&mut self) -> Result<Option<RawPacket>> {
+        let raw_packet = match &mut self.source {
+            CaptureSource::Pcap(reader) => match reader.next_packet() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => return Ok(None),
+                // 跟随模式下最后一条记录可能尚未写完，已缓冲的部分保留到下次读取
+                Err(e) if self.following => {
+                    log::debug!("Incomplete record at end of {}: {}", self.path, e);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            },
+            CaptureSource::PcapNg(reader) => match reader.next_packet() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => return Ok(None),
+                // 跟随模式下最后一个块可能尚未写完，已缓冲的部分保留到下次读取
+                Err(e) if self.following => {
+                    log::debug!("Incomplete record at end of {}: {}", self.path, e);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            },
+            CaptureSource::Dlt(reader) => match reader.next_packet() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => {
+                    if let Some(skipped) = reader.take_skipped_report() {
+                        log::info!(
+                            "Skipped {} non-network-trace DLT messages in {}",
+                            skipped,
+                            self.path
+                        );
+                    }
+                    return Ok(None);
+                }
+                Err(e) if self.following => {
+                    log::debug!("Incomplete record at end of {}: {}", self.path, e);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        Ok(Some(RawPacket {
+            source_file: Some(self.path.clone()),
+            ..raw_packet
+        }))
+    }
+}
+
+/// 根据扩展名与文件头魔数区分 DLT、pcapng 与经典 pcap 格式
+fn open_source(input: CaptureInput, pcap_file: &str, format: InputFormat) -> Result<CaptureSource> {
+    let mut reader = ChunkReader::new(input);
+    reader.fill(4)?;
+    let magic = reader.buffered().get(..4).unwrap_or_default();
+    let is_dlt = match format {
+        InputFormat::Auto => {
+            magic == DLT_STORAGE_MAGIC
+                || Path::new(pcap_file)
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("dlt"))
+        }
+        InputFormat::Pcap => false,
+        InputFormat::Dlt => true,
+    };
+
+    Ok(if is_dlt {
+        CaptureSource::Dlt(DltReader::new(reader)?)
+    } else if magic == PCAPNG_MAGIC {
+        CaptureSource::PcapNg(PcapNgReader::new(reader)?)
+    } else if is_classic_pcap(magic) {
+        CaptureSource::Pcap(ClassicPcapReader::new(reader)?)
+    } else {
+        return Err(SomeIPError::CaptureFileError(format!(
+            "Unknown capture file format: {}",
+            pcap_file
+        ))
+        .into());
+    })
+}
+
+/// 读取器与处理循环之间通道的背压统计
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelStats {
+    /// 通道中同时排队的最大数据包数
+    pub high_water_mark: usize,
+    /// 因通道已满而等待发送的总时间
+    pub blocked: Duration,
+    /// 通道已满时丢弃的数据包数（仅 `drop_when_full` 模式）
+    pub dropped: usize,
+}
+
+/// 同时读取多个抓包文件，并按时间戳升序合并输出
+pub struct MergingPCAPReader {
+    readers: Vec<PCAPReader>,
+    pending: Vec<Option<RawPacket>>, // 每个文件已读取但尚未输出的数据包
+    heap: BinaryHeap<Reverse<(SystemTime, usize)>>, // (时间戳, 文件索引)
+    follow: Option<Duration>,        // 跟随模式的轮询间隔
+    drop_when_full: bool,            // 通道已满时丢弃数据包而不是等待
+    channel_stats: ChannelStats,
+}
+
+impl MergingPCAPReader {
+    pub fn new(readers: Vec<PCAPReader>) -> Self {
+        let pending = readers.iter().map(|_| None).collect();
+        Self {
+            readers,
+            pending,
+            heap: BinaryHeap::new(),
+            follow: None,
+            drop_when_full: false,
+            channel_stats: ChannelStats::default(),
+        }
+    }
+
+    /// 通道已满时丢弃数据包并计数，避免跟随模式下处理延迟无限增长
+    pub fn set_drop_when_full(&mut self, drop_when_full: bool) {
+        self.drop_when_full = drop_when_full;
+    }
+
+    pub fn channel_stats(&self) -> &ChannelStats {
+        &self.channel_stats
+    }
+
+    /// 启用跟随模式：读到文件末尾后按间隔轮询新追加的数据包（类似 `tail -f`）
+    pub fn set_follow(&mut self, poll_interval: Duration) {
+        self.follow = Some(poll_interval);
+        for reader in &mut self.readers {
+            reader.following = true;
+        }
+    }
+
+    pub fn set_filter(&mut self, expr: &str) -> Result<()> {
+        for reader in &mut self.readers {
+            reader.set_filter(expr)?;
+        }
+        Ok(())
     }
 
-    pub async fn start(&mut self, tx: mpsc::Sender<RawPacket>) -> Result<(), pcap::Error> {
-        while let Ok(packet) = self.capture.next_packet() {
-            let raw_packet = RawPacket::from(packet);
-            if tx.send(raw_packet).await.is_err() {
-                log::warn!("Channel closed, stopping packet processing");
-                break;
+    /// 返回所有文件中时间戳最早的下一个数据包，全部读完时返回 None
+    pub fn next_packet(&mut self) -> Result<Option<RawPacket>> {
+        // 首次调用时为每个文件预读一个数据包
+        if self.heap.is_empty() && self.pending.iter().all(Option::is_none) {
+            for index in 0..self.readers.len() {
+                self.refill(index)?;
             }
         }
+
+        let Some(Reverse((_, index))) = self.heap.pop() else {
+            return Ok(None);
+        };
+        let raw_packet = self.pending[index].take();
+        self.refill(index)?;
+
+        Ok(raw_packet)
+    }
+
+    fn refill(&mut self, index: usize) -> Result<()> {
+        if let Some(raw_packet) = self.readers[index].next_packet()? {
+            self.heap.push(Reverse((raw_packet.timestamp, index)));
+            self.pending[index] = Some(raw_packet);
+        }
         Ok(())
     }
+
+    pub async fn start(&mut self, tx: mpsc::Sender<RawPacket>) -> Result<()> {
+        loop {
+            while let Some(raw_packet) = self.next_packet()? {
+                if !self.send(&tx, raw_packet).await {
+                    log::warn!("Channel closed, stopping packet processing");
+                    return Ok(());
+                }
+            }
+
+            let Some(poll_interval) = self.follow else {
+                return Ok(());
+            };
+            tokio::time::sleep(poll_interval).await;
+            if tx.is_closed() {
+                return Ok(());
+            }
+            for reader in &mut self.readers {
+                reader.check_rotation()?;
+            }
+        }
+    }
+
+    /// 发送数据包并记录背压统计，通道关闭时返回 false
+    async fn send(&mut self, tx: &mpsc::Sender<RawPacket>, raw_packet: RawPacket) -> bool {
+        match tx.try_send(raw_packet) {
+            Ok(()) => {}
+            Err(TrySendError::Closed(_)) => return false,
+            Err(TrySendError::Full(_)) if self.drop_when_full => {
+                self.channel_stats.dropped += 1;
+            }
+            Err(TrySendError::Full(raw_packet)) => {
+                let started = Instant::now();
+                if tx.send(raw_packet).await.is_err() {
+                    return false;
+                }
+                self.channel_stats.blocked += started.elapsed();
+            }
+        }
+        let queued = tx.max_capacity() - tx.capacity();
+        self.channel_stats.high_water_mark = self.channel_stats.high_water_mark.max(queued);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::builder::{PacketBuilder, PcapFileBuilder};
+    use std::time::UNIX_EPOCH;
+
+    fn write_capture(capture: PcapFileBuilder) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        capture.write_to(file.path()).unwrap();
+        file
+    }
+
+    #[test]
+    fn reads_classic_pcap_with_filter() {
+        let udp = PacketBuilder::new().udp(30490, 30490).payload(b"udp");
+        let tcp = PacketBuilder::new().tcp(30509, 40000, 1).payload(b"tcp");
+        let file = write_capture(
+            PcapFileBuilder::new(LinkType::Ethernet)
+                .packet(UNIX_EPOCH, &udp)
+                .packet(UNIX_EPOCH, &tcp)
+                .packet(UNIX_EPOCH, &udp),
+        );
+        let path = file.path().to_str().unwrap();
+
+        let mut reader = PCAPReader::new(path, InputFormat::Auto).unwrap();
+        assert_eq!(reader.capture_info().unwrap().link_type, 1);
+        reader.set_filter("udp").unwrap();
+        let mut packets = Vec::new();
+        while let Some(raw_packet) = reader.next_packet().unwrap() {
+            packets.push(raw_packet);
+        }
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].source_file.as_deref(), Some(path));
+        assert_eq!(&packets[1].data[..], &udp.build()[..]);
+
+        // 无法编译的表达式报告为配置错误，并带上 libpcap 的错误信息
+        let error = reader.set_filter("udp port bogus").unwrap_err();
+        let message = error.to_string();
+        assert!(
+            message.contains("Invalid BPF filter 'udp port bogus'"),
+            "{message}"
+        );
+        assert!(matches!(
+            error.downcast_ref::<SomeIPError>(),
+            Some(SomeIPError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn merges_files_in_timestamp_order() {
+        let packet = PacketBuilder::new().udp(1, 2);
+        let at = |millis| UNIX_EPOCH + Duration::from_millis(millis);
+        let first = write_capture(
+            PcapFileBuilder::new(LinkType::Ethernet)
+                .packet(at(1), &packet)
+                .packet(at(4), &packet),
+        );
+        let second = write_capture(
+            PcapFileBuilder::new(LinkType::Ethernet)
+                .packet(at(2), &packet)
+                .packet(at(3), &packet),
+        );
+        let readers = [&first, &second]
+            .iter()
+            .map(|file| PCAPReader::new(file.path().to_str().unwrap(), InputFormat::Auto).unwrap())
+            .collect();
+
+        let mut reader = MergingPCAPReader::new(readers);
+        let mut timestamps = Vec::new();
+        while let Some(raw_packet) = reader.next_packet().unwrap() {
+            timestamps.push(raw_packet.timestamp);
+        }
+        assert_eq!(timestamps, [at(1), at(2), at(3), at(4)]);
+    }
+
+    #[test]
+    fn follows_appended_and_rotated_file() {
+        use std::io::Write;
+
+        let packet = |byte: u8| PacketBuilder::new().udp(1, 2).payload([byte]);
+        let capture = PcapFileBuilder::new(LinkType::Ethernet)
+            .packet(UNIX_EPOCH, &packet(1))
+            .packet(UNIX_EPOCH, &packet(2))
+            .packet(UNIX_EPOCH, &packet(3))
+            .build();
+        let record_len = (capture.len() - 24) / 3;
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let last_byte = |raw_packet: Option<RawPacket>| *raw_packet.unwrap().data.last().unwrap();
+
+        // 第二条记录只写了一半
+        let split = 24 + record_len + record_len / 2;
+        std::fs::write(path, &capture[..split]).unwrap();
+        let mut reader = PCAPReader::new(path, InputFormat::Auto).unwrap();
+        reader.following = true;
+        assert_eq!(last_byte(reader.next_packet().unwrap()), 1);
+        assert!(reader.next_packet().unwrap().is_none());
+
+        // 追加剩余数据后从原偏移继续读取，不重复输出
+        let mut appender = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        appender.write_all(&capture[split..]).unwrap();
+        reader.check_rotation().unwrap();
+        assert_eq!(last_byte(reader.next_packet().unwrap()), 2);
+        assert_eq!(last_byte(reader.next_packet().unwrap()), 3);
+        assert!(reader.next_packet().unwrap().is_none());
+
+        // 文件被替换为更短的新文件时从头读取
+        std::fs::write(path, &capture[..24 + record_len]).unwrap();
+        reader.check_rotation().unwrap();
+        assert_eq!(last_byte(reader.next_packet().unwrap()), 1);
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"not a capture file").unwrap();
+        assert!(PCAPReader::new(file.path().to_str().unwrap(), InputFormat::Auto).is_err());
+    }
 }
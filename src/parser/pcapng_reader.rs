@@ -0,0 +1,464 @@
+// src/parser/pcapng_reader.rs
+use super::bpf::BpfFilter;
+use super::chunk_reader::{CaptureInput, ChunkReader};
+use super::link_layer::LinkType;
+use super::pcap_reader::{CaptureInfo, RawPacket};
+use crate::error::{Result, SomeIPError};
+use bytes::Bytes;
+use nom::{
+    IResult, Parser,
+    bytes::complete::take,
+    number::{Endianness, complete as number},
+};
+use std::time::{Duration, SystemTime};
+
+pub const PCAPNG_MAGIC: [u8; 4] = [0x0A, 0x0D, 0x0D, 0x0A];
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const OBSOLETE_PACKET_BLOCK: u32 = 0x00000002;
+const SIMPLE_PACKET_BLOCK: u32 = 0x00000003;
+const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+/// 单个块的长度上限，长度字段来自不可信的文件，超出时视为文件损坏而不是按其分配内存
+const MAX_BLOCK_LENGTH: usize = 16 * 1024 * 1024;
+const OPTION_END: u16 = 0;
+const OPTION_IF_TSRESOL: u16 = 9;
+
+#[derive(Debug, Clone, PartialEq)]
+struct InterfaceDescription {
+    link_type: u16,
+    snap_len: u32,
+    ts_resolution: u8,
+}
+
+/// pcapng 文件读取器，每个块是读取缓冲区的零拷贝切片
+pub struct PcapNgReader {
+    reader: ChunkReader<CaptureInput>,
+    endianness: Endianness,
+    interfaces: Vec<InterfaceDescription>,
+    filter: Option<BpfFilter>,
+    info: Option<CaptureInfo>,
+}
+
+impl PcapNgReader {
+    pub fn new(mut reader: ChunkReader<CaptureInput>) -> Result<Self> {
+        let info = peek_capture_info(&mut reader)?;
+        Ok(Self {
+            reader,
+            endianness: Endianness::Little,
+            interfaces: Vec::new(),
+            filter: None,
+            info,
+        })
+    }
+
+    /// 第一个接口描述块中的链路层类型与快照长度，文件开头不是该块时为 None
+    pub fn capture_info(&self) -> Option<CaptureInfo> {
+        self.info
+    }
+
+    /// 设置 BPF 过滤表达式，按数据包所属接口的链路层类型执行
+    /// 已读取到的输入偏移
+    pub fn position(&self) -> u64 {
+        self.reader.position()
+    }
+
+    pub fn set_filter(&mut self, expr: &str) -> std::result::Result<(), pcap::Error> {
+        self.filter = Some(BpfFilter::new(expr)?);
+        Ok(())
+    }
+
+    /// 读取下一个数据包，文件结束时返回 None
+    pub fn next_packet(&mut self) -> Result<Option<RawPacket>> {
+        loop {
+            let Some((block_type, body)) = self.read_block()? else {
+                return Ok(None);
+            };
+
+            match block_type {
+                SECTION_HEADER_BLOCK => {
+                    // 新的 Section 会重置接口列表
+                    self.interfaces.clear();
+                }
+                INTERFACE_DESCRIPTION_BLOCK => {
+                    let (_, interface) = parse_interface_description(&body, self.endianness)
+                        .map_err(|e| invalid_block("interface description", e))?;
+                    self.interfaces.push(interface);
+                }
+                ENHANCED_PACKET_BLOCK => {
+                    let (_, record) = parse_enhanced_packet(&body, self.endianness)
+                        .map_err(|e| invalid_block("enhanced packet", e))?;
+                    if !self.matches_filter(record.interface_id as usize, record.data) {
+                        continue;
+                    }
+                    return self.build_packet(&body, record).map(Some);
+                }
+                OBSOLETE_PACKET_BLOCK => {
+                    let (_, record) = parse_obsolete_packet(&body, self.endianness)
+                        .map_err(|e| invalid_block("packet", e))?;
+                    if !self.matches_filter(record.interface_id as usize, record.data) {
+                        continue;
+                    }
+                    return self.build_packet(&body, record).map(Some);
+                }
+                SIMPLE_PACKET_BLOCK => {
+                    // Simple Packet Block 不带时间戳，固定属于第一个接口
+                    let interface = self.interface(0)?;
+                    let (_, orig_len) = number::u32::<_, nom::error::Error<&[u8]>>(self.endianness)
+                        .parse(&body[..])
+                        .map_err(|e| invalid_block("simple packet", e))?;
+                    let mut cap_len = orig_len.min(body.len() as u32 - 4) as usize;
+                    if interface.snap_len > 0 {
+                        cap_len = cap_len.min(interface.snap_len as usize);
+                    }
+                    if !self.matches_filter(0, &body[4..4 + cap_len]) {
+                        continue;
+                    }
+                    return Ok(Some(RawPacket {
+                        timestamp: SystemTime::UNIX_EPOCH,
+                        data: body.slice(4..4 + cap_len),
+                        orig_len,
+                        link_type: LinkType::from_dlt(interface.link_type),
+                        source_file: None,
+                    }));
+                }
+                _ => {
+                    // 其他块类型（统计、名称解析等）直接跳过
+                    log::trace!("Skipping pcapng block type 0x{:08X}", block_type);
+                }
+            }
+        }
+    }
+
+    /// 由块体 `body` 中解析出的数据包记录构造数据包，数据为 `body` 的零拷贝切片
+    fn build_packet(&self, body: &Bytes, record: PacketRecord) -> Result<RawPacket> {
+        let interface = self.interface(record.interface_id as usize)?;
+        let ticks = ((record.ts_high as u64) << 32) | record.ts_low as u64;
+
+        Ok(RawPacket {
+            timestamp: SystemTime::UNIX_EPOCH
+                .checked_add(ticks_to_duration(ticks, interface.ts_resolution))
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+            data: body.slice_ref(record.data),
+            orig_len: record.orig_len,
+            link_type: LinkType::from_dlt(interface.link_type),
+            source_file: None,
+        })
+    }
+
+    fn matches_filter(&self, interface_id: usize, data: &[u8]) -> bool {
+        match (&self.filter, self.interfaces.get(interface_id)) {
+            (Some(filter), Some(interface)) => filter.matches(interface.link_type, data),
+            _ => true,
+        }
+    }
+
+    fn interface(&self, interface_id: usize) -> Result<&InterfaceDescription> {
+        self.interfaces.get(interface_id).ok_or_else(|| {
+            SomeIPError::CaptureFileError(format!(
+                "pcapng packet references unknown interface {}",
+                interface_id
+            ))
+            .into()
+        })
+    }
+
+    /// 读取一个完整的块，返回块类型和块体（不含首尾的类型/长度字段）
+    ///
+    /// 最后一个块不完整时返回错误，已读取的部分保留在缓冲区中，文件继续增长后可以再次读取。
+    fn read_block(&mut self) -> Result<Option<(u32, Bytes)>> {
+        if !self.reader.fill(8)? {
+            return self.end_of_input();
+        }
+        let block_type = u32::from_be_bytes(self.reader.buffered()[0..4].try_into().unwrap());
+
+        // Section Header Block 的字节序由其 Byte-Order Magic 决定，块类型本身与字节序无关
+        if block_type == SECTION_HEADER_BLOCK {
+            if !self.reader.fill(12)? {
+                return self.end_of_input();
+            }
+            self.endianness = section_endianness(&self.reader.buffered()[8..12])?;
+        }
+
+        let header = &self.reader.buffered()[..8];
+        let block_type = self.read_u32(&header[0..4]);
+        let total_length = self.read_u32(&header[4..8]) as usize;
+        // 块总长度包含：类型(4) + 长度(4) + 块体 + 尾部长度(4)
+        if !(12..=MAX_BLOCK_LENGTH).contains(&total_length) || !total_length.is_multiple_of(4) {
+            return Err(SomeIPError::CaptureFileError(format!(
+                "Invalid pcapng block length: {}",
+                total_length
+            ))
+            .into());
+        }
+
+        if !self.reader.fill(total_length)? {
+            return self.end_of_input();
+        }
+        let block = self.reader.take(total_length);
+        Ok(Some((block_type, block.slice(8..total_length - 4))))
+    }
+
+    fn end_of_input<T>(&self) -> Result<Option<T>> {
+        if self.reader.buffered().is_empty() {
+            Ok(None)
+        } else {
+            Err(SomeIPError::CaptureFileError("Truncated pcapng block".to_string()).into())
+        }
+    }
+
+    fn read_u32(&self, bytes: &[u8]) -> u32 {
+        let bytes: [u8; 4] = bytes.try_into().unwrap();
+        match self.endianness {
+            Endianness::Big => u32::from_be_bytes(bytes),
+            _ => u32::from_le_bytes(bytes),
+        }
+    }
+}
+
+fn section_endianness(magic: &[u8]) -> Result<Endianness> {
+    let magic: [u8; 4] = magic.try_into().unwrap();
+    if u32::from_be_bytes(magic) == BYTE_ORDER_MAGIC {
+        Ok(Endianness::Big)
+    } else if u32::from_le_bytes(magic) == BYTE_ORDER_MAGIC {
+        Ok(Endianness::Little)
+    } else {
+        Err(SomeIPError::CaptureFileError("Invalid pcapng byte-order magic".to_string()).into())
+    }
+}
+
+/// 不消耗数据地读取 Section Header Block 之后紧跟的接口描述块
+fn peek_capture_info(reader: &mut ChunkReader<CaptureInput>) -> Result<Option<CaptureInfo>> {
+    if !reader.fill(12)? {
+        return Ok(None);
+    }
+    let endianness = section_endianness(&reader.buffered()[8..12])?;
+    let u32_at = |bytes: &[u8], offset: usize| {
+        let value: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+        match endianness {
+            Endianness::Big => u32::from_be_bytes(value),
+            _ => u32::from_le_bytes(value),
+        }
+    };
+    let idb = u32_at(reader.buffered(), 4) as usize;
+    if idb > MAX_BLOCK_LENGTH || !reader.fill(idb + 16)? {
+        return Ok(None);
+    }
+    let bytes = reader.buffered();
+    if u32_at(bytes, idb) != INTERFACE_DESCRIPTION_BLOCK {
+        return Ok(None);
+    }
+    let link_type = u32_at(bytes, idb + 8);
+    let link_type = match endianness {
+        Endianness::Big => (link_type >> 16) as u16,
+        _ => link_type as u16,
+    };
+    Ok(Some(CaptureInfo {
+        link_type,
+        snap_len: u32_at(bytes, idb + 12),
+    }))
+}
+
+fn invalid_block(block: &str, e: nom::Err<nom::error::Error<&[u8]>>) -> SomeIPError {
+    SomeIPError::CaptureFileError(format!("Invalid pcapng {} block: {}", block, e))
+}
+
+fn ticks_to_duration(ticks: u64, ts_resolution: u8) -> Duration {
+    if ts_resolution & 0x80 == 0 {
+        // 10 的负幂次
+        let units_per_sec = 10u64.saturating_pow(ts_resolution as u32);
+        let secs = ticks / units_per_sec;
+        let nanos =
+            ((ticks % units_per_sec) as u128 * 1_000_000_000 / units_per_sec as u128) as u32;
+        Duration::new(secs, nanos)
+    } else {
+        // 2 的负幂次
+        let shift = (ts_resolution & 0x7F).min(63);
+        let units_per_sec = 1u64 << shift;
+        let secs = ticks >> shift;
+        let nanos = (((ticks & (units_per_sec - 1)) as u128 * 1_000_000_000) >> shift) as u32;
+        Duration::new(secs, nanos)
+    }
+}
+
+fn parse_interface_description(
+    input: &[u8],
+    endianness: Endianness,
+) -> IResult<&[u8], InterfaceDescription> {
+    let (input, (link_type, _reserved, snap_len)) = (
+        number::u16(endianness),
+        number::u16(endianness),
+        number::u32(endianness),
+    )
+        .parse(input)?;
+
+    // 默认时间戳精度为微秒
+    let mut ts_resolution = 6;
+    let mut options = input;
+    while options.len() >= 4 {
+        let (rest, (code, length)) =
+            (number::u16(endianness), number::u16(endianness)).parse(options)?;
+        if code == OPTION_END {
+            break;
+        }
+        let padded_length = (length as usize).div_ceil(4) * 4;
+        if padded_length > rest.len() {
+            break;
+        }
+        if code == OPTION_IF_TSRESOL && length >= 1 {
+            ts_resolution = rest[0];
+        }
+        options = &rest[padded_length..];
+    }
+
+    Ok((
+        &[],
+        InterfaceDescription {
+            link_type,
+            snap_len,
+            ts_resolution,
+        },
+    ))
+}
+
+/// Enhanced / Obsolete Packet Block 中的数据包记录
+struct PacketRecord<'a> {
+    interface_id: u32,
+    ts_high: u32,
+    ts_low: u32,
+    orig_len: u32,
+    data: &'a [u8],
+}
+
+fn parse_enhanced_packet(input: &[u8], endianness: Endianness) -> IResult<&[u8], PacketRecord<'_>> {
+    let (input, (interface_id, ts_high, ts_low, cap_len, orig_len)) = (
+        number::u32(endianness),
+        number::u32(endianness),
+        number::u32(endianness),
+        number::u32(endianness),
+        number::u32(endianness),
+    )
+        .parse(input)?;
+    let (input, data) = take(cap_len as usize)(input)?;
+
+    Ok((
+        input,
+        PacketRecord {
+            interface_id,
+            ts_high,
+            ts_low,
+            orig_len,
+            data,
+        },
+    ))
+}
+
+fn parse_obsolete_packet(input: &[u8], endianness: Endianness) -> IResult<&[u8], PacketRecord<'_>> {
+    let (input, (interface_id, _drops_count, ts_high, ts_low, cap_len, orig_len)) = (
+        number::u16(endianness),
+        number::u16(endianness),
+        number::u32(endianness),
+        number::u32(endianness),
+        number::u32(endianness),
+        number::u32(endianness),
+    )
+        .parse(input)?;
+    let (input, data) = take(cap_len as usize)(input)?;
+
+    Ok((
+        input,
+        PacketRecord {
+            interface_id: interface_id as u32,
+            ts_high,
+            ts_low,
+            orig_len,
+            data,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let total_length = (12 + body.len()) as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&block_type.to_le_bytes());
+        bytes.extend_from_slice(&total_length.to_le_bytes());
+        bytes.extend_from_slice(body);
+        bytes.extend_from_slice(&total_length.to_le_bytes());
+        bytes
+    }
+
+    fn section_header() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&u64::MAX.to_le_bytes()); // 未指定 Section 长度
+        block(SECTION_HEADER_BLOCK, &body)
+    }
+
+    fn interface_description(link_type: u16) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&link_type.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&65535u32.to_le_bytes());
+        block(INTERFACE_DESCRIPTION_BLOCK, &body)
+    }
+
+    fn enhanced_packet(ticks: u64, data: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&((ticks >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(ticks as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        body.resize(body.len().div_ceil(4) * 4, 0);
+        block(ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    fn reader(bytes: Vec<u8>) -> PcapNgReader {
+        PcapNgReader::new(ChunkReader::new(Box::new(std::io::Cursor::new(bytes)))).unwrap()
+    }
+
+    #[test]
+    fn reads_enhanced_packets() {
+        let mut bytes = section_header();
+        bytes.extend(interface_description(1));
+        bytes.extend(enhanced_packet(1_500_000, b"frame"));
+        let mut reader = reader(bytes);
+        assert_eq!(
+            reader.capture_info(),
+            Some(CaptureInfo {
+                link_type: 1,
+                snap_len: 65535
+            })
+        );
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(&packet.data[..], b"frame");
+        assert_eq!(packet.link_type, LinkType::Ethernet);
+        assert_eq!(
+            packet.timestamp,
+            SystemTime::UNIX_EPOCH + Duration::from_micros(1_500_000)
+        );
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_oversized_block_length_without_allocating() {
+        let mut bytes = section_header();
+        bytes.extend(interface_description(1));
+        // 损坏的块声称长度接近 4 GiB
+        bytes.extend_from_slice(&ENHANCED_PACKET_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 32]);
+        let error = reader(bytes).next_packet().unwrap_err().to_string();
+        assert!(error.contains("Invalid pcapng block length"), "{error}");
+    }
+}
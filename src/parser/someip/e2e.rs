@@ -0,0 +1,367 @@
+// src/parser/someip/e2e.rs
+use crate::error::{Result, SomeIPError};
+use serde::{Deserialize, Serialize};
+
+/// AUTOSAR E2E 保护配置文件（头部位于负载起始位置）
+///
+/// - P01：CRC8 (SAE J1850) + 4 位计数器，数据 ID 不随报文传输
+/// - P02：CRC8H2F + 4 位计数器，数据 ID 由计数器从 DataIDList 中选取
+/// - P04：12 字节头部（长度、计数器、数据 ID、CRC32P4），大端字节序
+/// - P05：CRC16 (CCITT) + 8 位计数器，小端字节序，数据 ID 不随报文传输
+/// - P06：CRC16 (CCITT) + 长度 + 8 位计数器，大端字节序，数据 ID 不随报文传输
+/// - P07：20 字节头部（CRC64、长度、计数器、数据 ID），大端字节序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum E2EProfile {
+    P01,
+    P02,
+    P04,
+    P05,
+    P06,
+    P07,
+}
+
+impl E2EProfile {
+    /// E2E 头部占用的字节数
+    pub fn header_len(self) -> usize {
+        match self {
+            E2EProfile::P01 | E2EProfile::P02 => 2,
+            E2EProfile::P04 => 12,
+            E2EProfile::P05 => 3,
+            E2EProfile::P06 => 5,
+            E2EProfile::P07 => 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct E2EHeader {
+    pub crc: u64,
+    pub counter: u32,
+    /// 仅 P04/P07 在头部中携带数据 ID
+    pub data_id: Option<u32>,
+    /// 仅 P04/P06/P07 在头部中携带长度
+    pub length: Option<u32>,
+}
+
+/// 消息的 E2E 校验结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct E2EStatus {
+    pub crc_valid: bool,
+    pub counter: u32,
+}
+
+/// 解析 E2E 头部，返回头部与其后的实际 PDU 数据
+pub fn parse_e2e_header(profile: E2EProfile, payload: &[u8]) -> Result<(E2EHeader, &[u8])> {
+    let header_len = profile.header_len();
+    if payload.len() < header_len {
+        return Err(SomeIPError::InvalidPacketFormat(format!(
+            "E2E {:?} header requires {} bytes, got {}",
+            profile,
+            header_len,
+            payload.len()
+        ))
+        .into());
+    }
+
+    let header = match profile {
+        E2EProfile::P01 | E2EProfile::P02 => E2EHeader {
+            crc: payload[0] as u64,
+            counter: (payload[1] & 0x0F) as u32,
+            data_id: None,
+            length: None,
+        },
+        E2EProfile::P04 => E2EHeader {
+            crc: u32::from_be_bytes(payload[8..12].try_into().unwrap()) as u64,
+            counter: u16::from_be_bytes([payload[2], payload[3]]) as u32,
+            data_id: Some(u32::from_be_bytes(payload[4..8].try_into().unwrap())),
+            length: Some(u16::from_be_bytes([payload[0], payload[1]]) as u32),
+        },
+        E2EProfile::P05 => E2EHeader {
+            crc: u16::from_le_bytes([payload[0], payload[1]]) as u64,
+            counter: payload[2] as u32,
+            data_id: None,
+            length: None,
+        },
+        E2EProfile::P06 => E2EHeader {
+            crc: u16::from_be_bytes([payload[0], payload[1]]) as u64,
+            counter: payload[4] as u32,
+            data_id: None,
+            length: Some(u16::from_be_bytes([payload[2], payload[3]]) as u32),
+        },
+        E2EProfile::P07 => E2EHeader {
+            crc: u64::from_be_bytes(payload[0..8].try_into().unwrap()),
+            counter: u32::from_be_bytes(payload[12..16].try_into().unwrap()),
+            data_id: Some(u32::from_be_bytes(payload[16..20].try_into().unwrap())),
+            length: Some(u32::from_be_bytes(payload[8..12].try_into().unwrap())),
+        },
+    };
+
+    Ok((header, &payload[header_len..]))
+}
+
+/// 按配置文件重新计算 CRC 并与头部中的值比较
+///
+/// `data_id` 用于数据 ID 不随报文传输的配置文件（P01/P02/P05/P06）；P04/P07 使用头部中的数据 ID。
+/// P02 假定 DataIDList 中的所有条目均为同一个数据 ID。
+pub fn verify_e2e_crc(
+    profile: E2EProfile,
+    header: &E2EHeader,
+    payload: &[u8],
+    data_id: u32,
+) -> bool {
+    // 头部中的长度字段与实际数据长度不一致时无需再计算 CRC
+    if header
+        .length
+        .is_some_and(|len| len as usize != payload.len())
+    {
+        return false;
+    }
+    let expected = match profile {
+        E2EProfile::P01 => {
+            // 数据 ID 两字节（低字节在前）参与计算，随后是除 CRC 字节外的数据
+            let crc = crc8_sae_j1850(0x00, &(data_id as u16).to_le_bytes());
+            crc8_sae_j1850(crc, &payload[1..]) as u64
+        }
+        E2EProfile::P02 => {
+            let crc = crc8h2f(0xFF, &payload[1..]);
+            (crc8h2f(crc, &[data_id as u8]) ^ 0xFF) as u64
+        }
+        E2EProfile::P04 => {
+            let crc = crc32p4(0xFFFF_FFFF, &payload[..8]);
+            (crc32p4(crc, &payload[12..]) ^ 0xFFFF_FFFF) as u64
+        }
+        E2EProfile::P05 => {
+            let crc = crc16_ccitt(0xFFFF, &payload[2..]);
+            crc16_ccitt(crc, &(data_id as u16).to_le_bytes()) as u64
+        }
+        E2EProfile::P06 => {
+            // 数据 ID 高字节在前
+            let crc = crc16_ccitt(0xFFFF, &payload[2..]);
+            crc16_ccitt(crc, &(data_id as u16).to_be_bytes()) as u64
+        }
+        E2EProfile::P07 => crc64_ecma(0xFFFF_FFFF_FFFF_FFFF, &payload[8..]) ^ 0xFFFF_FFFF_FFFF_FFFF,
+    };
+    expected == header.crc
+}
+
+/// CRC8 SAE J1850（多项式 0x1D），调用方负责初值与结果异或
+fn crc8_sae_j1850(mut crc: u8, data: &[u8]) -> u8 {
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x1D
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC8H2F（多项式 0x2F），调用方负责初值与结果异或
+fn crc8h2f(mut crc: u8, data: &[u8]) -> u8 {
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x2F
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC16 CCITT（多项式 0x1021，不反转）
+fn crc16_ccitt(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC64 ECMA-182（多项式 0x42F0E1EBA9EA3693，按位反转计算），调用方负责初值与结果异或
+fn crc64_ecma(mut crc: u64, data: &[u8]) -> u64 {
+    for &byte in data {
+        crc ^= byte as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xC96C_5795_D787_0F42
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC32P4（多项式 0xF4ACFB13，按位反转计算），调用方负责初值与结果异或
+fn crc32p4(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xC8DF_352F
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// AUTOSAR SWS_CRC 中各 CRC 算法的测试数据
+    const CRC_INPUTS: [&[u8]; 8] = [
+        &[0x00, 0x00, 0x00, 0x00],
+        &[0xF2, 0x01, 0x83],
+        &[0x0F, 0xAA, 0x00, 0x55],
+        &[0x00, 0xFF, 0x55, 0x11],
+        &[0x33, 0x22, 0x55, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF],
+        &[0x92, 0x6B, 0x55],
+        &[0xFF, 0xFF, 0xFF, 0xFF],
+        b"123456789",
+    ];
+
+    #[test]
+    fn crc8_sae_j1850_known_answers() {
+        let expected = [0x59, 0x37, 0x79, 0xB8, 0xCB, 0x8C, 0x74, 0x4B];
+        for (input, expected) in CRC_INPUTS.iter().zip(expected) {
+            assert_eq!(crc8_sae_j1850(0xFF, input) ^ 0xFF, expected);
+        }
+    }
+
+    #[test]
+    fn crc8h2f_known_answers() {
+        let expected = [0x12, 0xC2, 0xC6, 0x77, 0x11, 0x33, 0x6C, 0xDF];
+        for (input, expected) in CRC_INPUTS.iter().zip(expected) {
+            assert_eq!(crc8h2f(0xFF, input) ^ 0xFF, expected);
+        }
+    }
+
+    #[test]
+    fn crc16_ccitt_known_answers() {
+        let expected = [
+            0x84C0, 0xD374, 0x2023, 0xB8F9, 0xF53F, 0x0745, 0x1D0F, 0x29B1,
+        ];
+        for (input, expected) in CRC_INPUTS.iter().zip(expected) {
+            assert_eq!(crc16_ccitt(0xFFFF, input), expected);
+        }
+    }
+
+    #[test]
+    fn crc32p4_known_answers() {
+        let expected = [
+            0x6FB3_2240,
+            0x4F72_1A25,
+            0x2066_2DF8,
+            0x9BD7_996E,
+            0xA65A_343D,
+            0xEE68_8A78,
+            0xFFFF_FFFF,
+            0x1697_D06A,
+        ];
+        for (input, expected) in CRC_INPUTS.iter().zip(expected) {
+            assert_eq!(crc32p4(0xFFFF_FFFF, input) ^ 0xFFFF_FFFF, expected);
+        }
+    }
+
+    #[test]
+    fn crc64_ecma_known_answers() {
+        let expected = [
+            0xF4A5_8635_1E1B_9F4B,
+            0x319C_2766_8164_F1C6,
+            0x54C5_D0F7_667C_1575,
+            0xA638_22BE_7E07_04E6,
+            0x701E_CEB2_19A8_E5D5,
+            0x5FAA_96A9_B59F_3E4E,
+            0xFFFF_FFFF_0000_0000,
+            0x995D_C9BB_DF19_39FA,
+        ];
+        for (input, expected) in CRC_INPUTS.iter().zip(expected) {
+            assert_eq!(crc64_ecma(u64::MAX, input) ^ u64::MAX, expected);
+        }
+    }
+
+    /// 校验完整帧，并确认翻转任一数据位后校验失败
+    fn check_frame(profile: E2EProfile, frame: &[u8], data_id: u32, counter: u32) {
+        let (header, data) = parse_e2e_header(profile, frame).unwrap();
+        assert_eq!(header.counter, counter);
+        assert_eq!(data, [0x11, 0x22, 0x33, 0x44]);
+        assert!(verify_e2e_crc(profile, &header, frame, data_id));
+
+        let mut corrupted = frame.to_vec();
+        *corrupted.last_mut().unwrap() ^= 0x01;
+        let (header, _) = parse_e2e_header(profile, &corrupted).unwrap();
+        assert!(!verify_e2e_crc(profile, &header, &corrupted, data_id));
+
+        // 数据 ID 不随报文传输的配置文件，数据 ID 不同时校验失败
+        if header.data_id.is_none() {
+            let (header, _) = parse_e2e_header(profile, frame).unwrap();
+            assert!(!verify_e2e_crc(profile, &header, frame, data_id + 1));
+        }
+    }
+
+    #[test]
+    fn profile_01_frame() {
+        let frame = [0x5D, 0x01, 0x11, 0x22, 0x33, 0x44];
+        check_frame(E2EProfile::P01, &frame, 0x0123, 1);
+    }
+
+    #[test]
+    fn profile_02_frame() {
+        let frame = [0x48, 0x01, 0x11, 0x22, 0x33, 0x44];
+        check_frame(E2EProfile::P02, &frame, 0x0123, 1);
+    }
+
+    #[test]
+    fn profile_04_frame() {
+        let frame = [
+            0x00, 0x10, 0x01, 0x02, 0x0A, 0x0B, 0x0C, 0x0D, 0x42, 0xEF, 0x5E, 0xDC, 0x11, 0x22,
+            0x33, 0x44,
+        ];
+        check_frame(E2EProfile::P04, &frame, 0, 0x0102);
+    }
+
+    #[test]
+    fn profile_05_frame() {
+        let frame = [0x00, 0x16, 0x05, 0x11, 0x22, 0x33, 0x44];
+        check_frame(E2EProfile::P05, &frame, 0x1234, 5);
+    }
+
+    #[test]
+    fn profile_06_frame() {
+        let frame = [0x4E, 0x51, 0x00, 0x09, 0x05, 0x11, 0x22, 0x33, 0x44];
+        check_frame(E2EProfile::P06, &frame, 0x1234, 5);
+    }
+
+    #[test]
+    fn profile_07_frame() {
+        let frame = [
+            0x82, 0x0B, 0x25, 0x0E, 0xE3, 0xDE, 0xD7, 0x04, 0x00, 0x00, 0x00, 0x18, 0x01, 0x02,
+            0x03, 0x04, 0x0A, 0x0B, 0x0C, 0x0D, 0x11, 0x22, 0x33, 0x44,
+        ];
+        check_frame(E2EProfile::P07, &frame, 0, 0x0102_0304);
+    }
+
+    #[test]
+    fn length_field_mismatch_fails() {
+        let mut frame = vec![0x4E, 0x51, 0x00, 0x09, 0x05, 0x11, 0x22, 0x33, 0x44];
+        frame.push(0x55);
+        let (header, _) = parse_e2e_header(E2EProfile::P06, &frame).unwrap();
+        assert!(!verify_e2e_crc(E2EProfile::P06, &header, &frame, 0x1234));
+    }
+}
@@ -1,10 +1,13 @@
 // src/parser/someip/header.rs
+use crate::error::{Result, SomeIPError};
 use nom::{
     IResult, Parser,
     number::complete::{be_u8, be_u16, be_u32},
 };
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SomeIPHeader {
     pub service_id: u16,
     pub method_id: u16,
@@ -13,11 +16,19 @@ pub struct SomeIPHeader {
     pub session_id: u16,
     pub protocol_version: u8,
     pub interface_version: u8,
+    /// 消息类型中的 TP 标志位（0x20），`message_type` 只保存去掉该位后的基础类型
+    pub is_tp: bool,
     pub message_type: MessageType,
     pub return_code: ReturnCode,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// 消息类型字节中表示 SomeIP-TP 分段的标志位
+pub const TP_FLAG: u8 = 0x20;
+
+/// SomeIP 规范定义的协议版本
+pub const PROTOCOL_VERSION: u8 = 0x01;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum MessageType {
     Request = 0x00,
@@ -36,13 +47,120 @@ pub enum MessageType {
 impl MessageType {
     pub fn as_u8(&self) -> u8 {
         match self {
+            MessageType::Request => 0x00,
+            MessageType::RequestNoReturn => 0x01,
+            MessageType::Notification => 0x02,
+            MessageType::RequestACK => 0x40,
+            MessageType::RequestNoReturnACK => 0x41,
+            MessageType::NotificationACK => 0x42,
+            MessageType::Response => 0x80,
+            MessageType::Error => 0x81,
+            MessageType::ResponseACK => 0xC0,
+            MessageType::ErrorACK => 0xC1,
             MessageType::Unknown(value) => *value,
-            _ => unsafe { std::mem::transmute_copy(self) },
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// 按 SomeIP 规范中的名称输出，未知值输出十六进制
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MessageType::Request => "REQUEST",
+            MessageType::RequestNoReturn => "REQUEST_NO_RETURN",
+            MessageType::Notification => "NOTIFICATION",
+            MessageType::RequestACK => "REQUEST_ACK",
+            MessageType::RequestNoReturnACK => "REQUEST_NO_RETURN_ACK",
+            MessageType::NotificationACK => "NOTIFICATION_ACK",
+            MessageType::Response => "RESPONSE",
+            MessageType::Error => "ERROR",
+            MessageType::ResponseACK => "RESPONSE_ACK",
+            MessageType::ErrorACK => "ERROR_ACK",
+            MessageType::Unknown(value) => return write!(f, "0x{:02X}", value),
+        };
+        f.write_str(name)
+    }
+}
+
+impl SomeIPHeader {
+    /// 按大端网络字节序编码 16 字节 SomeIP 头部
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[0..2].copy_from_slice(&self.service_id.to_be_bytes());
+        bytes[2..4].copy_from_slice(&self.method_id.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.length.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.client_id.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.session_id.to_be_bytes());
+        bytes[12] = self.protocol_version;
+        bytes[13] = self.interface_version;
+        bytes[14] = if self.is_tp {
+            self.message_type.as_u8() | TP_FLAG
+        } else {
+            self.message_type.as_u8()
+        };
+        bytes[15] = self.return_code.as_u8();
+        bytes
+    }
+
+    /// 粗略判断头部是否来自 SomeIP 消息，用于区分与 SomeIP 共用端口的其他协议
+    ///
+    /// 要求协议版本为 1 且消息类型已定义；length 字段由 `length_fits` 单独检查。
+    pub fn is_plausible(&self) -> bool {
+        self.protocol_version == PROTOCOL_VERSION
+            && !matches!(self.message_type, MessageType::Unknown(_))
+    }
+
+    /// length 字段（从 client_id 开始计算，至少 8）描述的消息能否放进 `available` 字节
+    pub fn length_fits(&self, available: usize) -> bool {
+        self.length >= 8 && 16 + self.payload_len() <= available
+    }
+
+    /// 16 字节头部之后的负载长度：length 字段覆盖 client_id 到负载末尾，即 length - 8
+    pub fn payload_len(&self) -> usize {
+        (self.length as usize).saturating_sub(8)
+    }
+}
+
+/// 单行摘要，如 `SVC:0x1234/MTH:0x0001 [REQUEST] rc=E_OK sess=0001:0002`
+impl fmt::Display for SomeIPHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SVC:0x{:04X}/MTH:0x{:04X} [{}{}] rc={} sess={:04X}:{:04X}",
+            self.service_id,
+            self.method_id,
+            self.message_type,
+            if self.is_tp { "+TP" } else { "" },
+            self.return_code,
+            self.client_id,
+            self.session_id
+        )
+    }
+}
+
+/// 编码完整的 SomeIP 消息（头部 + 负载），负载长度必须与头部 length 字段一致
+pub fn encode_someip_message(header: &SomeIPHeader, payload: &[u8]) -> Result<Vec<u8>> {
+    let expected = (header.length as usize).checked_sub(8).ok_or_else(|| {
+        SomeIPError::InvalidPacketFormat(format!(
+            "SomeIP length field {} is shorter than the 8-byte header remainder",
+            header.length
+        ))
+    })?;
+    if payload.len() != expected {
+        return Err(SomeIPError::InvalidPacketFormat(format!(
+            "payload length {} does not match SomeIP length field {}",
+            payload.len(),
+            header.length
+        ))
+        .into());
+    }
+    let mut bytes = Vec::with_capacity(16 + payload.len());
+    bytes.extend_from_slice(&header.to_bytes());
+    bytes.extend_from_slice(payload);
+    Ok(bytes)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ReturnCode {
     Ok,
     NotOk,
@@ -58,6 +176,46 @@ pub enum ReturnCode {
     Unknown(u8),
 }
 
+impl ReturnCode {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            ReturnCode::Ok => 0x00,
+            ReturnCode::NotOk => 0x01,
+            ReturnCode::UnknownService => 0x02,
+            ReturnCode::UnknownMethod => 0x03,
+            ReturnCode::NotReady => 0x04,
+            ReturnCode::NotReachable => 0x05,
+            ReturnCode::Timeout => 0x06,
+            ReturnCode::WrongProtocolVersion => 0x07,
+            ReturnCode::WrongInterfaceVersion => 0x08,
+            ReturnCode::MalformedMessage => 0x09,
+            ReturnCode::WrongMessageType => 0x0A,
+            ReturnCode::Unknown(value) => *value,
+        }
+    }
+}
+
+/// 按 SomeIP 规范中的名称输出，未知值输出十六进制
+impl fmt::Display for ReturnCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ReturnCode::Ok => "E_OK",
+            ReturnCode::NotOk => "E_NOT_OK",
+            ReturnCode::UnknownService => "E_UNKNOWN_SERVICE",
+            ReturnCode::UnknownMethod => "E_UNKNOWN_METHOD",
+            ReturnCode::NotReady => "E_NOT_READY",
+            ReturnCode::NotReachable => "E_NOT_REACHABLE",
+            ReturnCode::Timeout => "E_TIMEOUT",
+            ReturnCode::WrongProtocolVersion => "E_WRONG_PROTOCOL_VERSION",
+            ReturnCode::WrongInterfaceVersion => "E_WRONG_INTERFACE_VERSION",
+            ReturnCode::MalformedMessage => "E_MALFORMED_MESSAGE",
+            ReturnCode::WrongMessageType => "E_WRONG_MESSAGE_TYPE",
+            ReturnCode::Unknown(value) => return write!(f, "0x{:02X}", value),
+        };
+        f.write_str(name)
+    }
+}
+
 pub fn parse_someip_header(input: &[u8]) -> IResult<&[u8], SomeIPHeader> {
     let (input, (service_id, method_id, length, client_id, session_id)) =
         (be_u16, be_u16, be_u32, be_u16, be_u16).parse(input)?;
@@ -66,7 +224,7 @@ pub fn parse_someip_header(input: &[u8]) -> IResult<&[u8], SomeIPHeader> {
         (be_u8, be_u8, be_u8, be_u8).parse(input)?;
 
     Ok((
-        &input[..],
+        input,
         SomeIPHeader {
             service_id,
             method_id,
@@ -75,14 +233,16 @@ pub fn parse_someip_header(input: &[u8]) -> IResult<&[u8], SomeIPHeader> {
             session_id,
             protocol_version,
             interface_version,
+            is_tp: message_type & TP_FLAG != 0,
             message_type: parse_message_type(message_type),
             return_code: parse_return_code(return_code),
         },
     ))
 }
 
-fn parse_message_type(value: u8) -> MessageType {
-    match value {
+/// 解析基础消息类型，TP 标志位会被忽略
+pub fn parse_message_type(value: u8) -> MessageType {
+    match value & !TP_FLAG {
         0x00 => MessageType::Request,
         0x01 => MessageType::RequestNoReturn,
         0x02 => MessageType::Notification,
@@ -93,7 +253,7 @@ fn parse_message_type(value: u8) -> MessageType {
         0x81 => MessageType::Error,
         0xC0 => MessageType::ResponseACK,
         0xC1 => MessageType::ErrorACK,
-        _ => MessageType::Unknown(value),
+        other => MessageType::Unknown(other),
     }
 }
 
@@ -113,3 +273,194 @@ pub fn parse_return_code(value: u8) -> ReturnCode {
         _ => ReturnCode::Unknown(value),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(length: u32) -> SomeIPHeader {
+        SomeIPHeader {
+            service_id: 0x1234,
+            method_id: 0x8001,
+            length,
+            client_id: 0x0001,
+            session_id: 0x0002,
+            protocol_version: 1,
+            interface_version: 3,
+            is_tp: false,
+            message_type: MessageType::Notification,
+            return_code: ReturnCode::Ok,
+        }
+    }
+
+    #[test]
+    fn encode_then_parse_round_trip() {
+        let payload = [0xDE, 0xAD, 0xBE, 0xEF];
+        let message_types = [
+            MessageType::Request,
+            MessageType::RequestNoReturn,
+            MessageType::Notification,
+            MessageType::RequestACK,
+            MessageType::RequestNoReturnACK,
+            MessageType::NotificationACK,
+            MessageType::Response,
+            MessageType::Error,
+            MessageType::ResponseACK,
+            MessageType::ErrorACK,
+            MessageType::Unknown(0x03),
+            MessageType::Unknown(0xDF),
+        ];
+        // 每种消息类型都分别以普通消息与 TP 分段编码
+        for message_type in message_types {
+            for is_tp in [false, true] {
+                let original = SomeIPHeader {
+                    message_type: message_type.clone(),
+                    is_tp,
+                    ..header(12)
+                };
+                let bytes = encode_someip_message(&original, &payload).unwrap();
+                let (rest, parsed) = parse_someip_header(&bytes).unwrap();
+                assert_eq!(parsed, original, "{message_type:?} tp={is_tp}");
+                assert_eq!(rest, payload);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_tp_flag() {
+        let mut tp = header(8);
+        tp.is_tp = true;
+        let bytes = encode_someip_message(&tp, &[]).unwrap();
+        assert_eq!(bytes[14], 0x22);
+        assert_eq!(parse_someip_header(&bytes).unwrap().1, tp);
+    }
+
+    #[test]
+    fn payload_len_excludes_header_remainder() {
+        assert_eq!(header(12).payload_len(), 4);
+        assert_eq!(header(8).payload_len(), 0);
+        assert_eq!(header(3).payload_len(), 0);
+    }
+
+    #[test]
+    fn length_fits_counts_from_client_id() {
+        assert!(header(12).length_fits(20));
+        assert!(!header(13).length_fits(20));
+        assert!(!header(0xFFFF).length_fits(20));
+        assert!(!header(7).length_fits(20));
+    }
+
+    #[test]
+    fn rejects_length_shorter_than_header() {
+        assert!(encode_someip_message(&header(7), &[]).is_err());
+    }
+
+    #[test]
+    fn rejects_payload_length_mismatch() {
+        assert!(encode_someip_message(&header(12), &[0; 3]).is_err());
+    }
+
+    #[test]
+    fn request_header_to_bytes_round_trip() {
+        let request = SomeIPHeader {
+            method_id: 0x0001,
+            message_type: MessageType::Request,
+            ..header(8)
+        };
+        let bytes = request.to_bytes();
+        assert_eq!(
+            bytes,
+            [
+                0x12, 0x34, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x01, 0x00, 0x02, 0x01, 0x03,
+                0x00, 0x00
+            ]
+        );
+        let (rest, parsed) = parse_someip_header(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed, request);
+    }
+
+    #[test]
+    fn message_type_as_u8_round_trips() {
+        let known = [
+            (MessageType::Request, 0x00),
+            (MessageType::RequestNoReturn, 0x01),
+            (MessageType::Notification, 0x02),
+            (MessageType::RequestACK, 0x40),
+            (MessageType::RequestNoReturnACK, 0x41),
+            (MessageType::NotificationACK, 0x42),
+            (MessageType::Response, 0x80),
+            (MessageType::Error, 0x81),
+            (MessageType::ResponseACK, 0xC0),
+            (MessageType::ErrorACK, 0xC1),
+        ];
+        for (message_type, value) in known {
+            assert_eq!(message_type.as_u8(), value);
+            assert_eq!(parse_message_type(value), message_type);
+        }
+        // 包括未知值在内，不带 TP 标志的每个值都能还原
+        for value in (0..=u8::MAX).filter(|value| value & TP_FLAG == 0) {
+            assert_eq!(parse_message_type(value).as_u8(), value);
+        }
+        assert_eq!(parse_message_type(0x03), MessageType::Unknown(0x03));
+        assert_eq!(
+            parse_message_type(0x02 | TP_FLAG),
+            MessageType::Notification
+        );
+    }
+
+    #[test]
+    fn tp_message_types_decode_to_base_type() {
+        for (byte, base) in [(0x20, MessageType::Request), (0xA0, MessageType::Response)] {
+            let mut bytes = header(8).to_bytes();
+            bytes[14] = byte;
+            let (_, parsed) = parse_someip_header(&bytes).unwrap();
+            assert!(parsed.is_tp);
+            assert_eq!(parsed.message_type, base);
+            // 重新编码时 TP 标志位与基础类型合并还原
+            assert_eq!(parsed.to_bytes()[14], byte);
+        }
+    }
+
+    #[test]
+    fn display_uses_spec_names() {
+        assert_eq!(MessageType::Request.to_string(), "REQUEST");
+        assert_eq!(
+            MessageType::RequestNoReturn.to_string(),
+            "REQUEST_NO_RETURN"
+        );
+        assert_eq!(MessageType::ErrorACK.to_string(), "ERROR_ACK");
+        assert_eq!(MessageType::Unknown(0x03).to_string(), "0x03");
+        assert_eq!(ReturnCode::Ok.to_string(), "E_OK");
+        assert_eq!(ReturnCode::UnknownService.to_string(), "E_UNKNOWN_SERVICE");
+        assert_eq!(
+            ReturnCode::WrongInterfaceVersion.to_string(),
+            "E_WRONG_INTERFACE_VERSION"
+        );
+        assert_eq!(ReturnCode::Unknown(0x11).to_string(), "0x11");
+    }
+
+    #[test]
+    fn header_display_is_one_line_summary() {
+        let request = SomeIPHeader {
+            method_id: 0x0001,
+            message_type: MessageType::Request,
+            ..header(8)
+        };
+        assert_eq!(
+            request.to_string(),
+            "SVC:0x1234/MTH:0x0001 [REQUEST] rc=E_OK sess=0001:0002"
+        );
+
+        let tp_error = SomeIPHeader {
+            is_tp: true,
+            message_type: MessageType::Error,
+            return_code: ReturnCode::Unknown(0x2A),
+            ..header(8)
+        };
+        assert_eq!(
+            tp_error.to_string(),
+            "SVC:0x1234/MTH:0x8001 [ERROR+TP] rc=0x2A sess=0001:0002"
+        );
+    }
+}
@@ -3,6 +3,7 @@ use crate::error::{Result, SomeIPError};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
+use std::hash::Hash;
 use std::io::Read;
 use std::net::IpAddr;
 use std::path::Path;
@@ -43,8 +44,13 @@ pub struct Element {
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ServiceInstance {
+    /// 引用的服务接口（SHORT-NAME 或以 `/` 分隔的路径）
     #[serde(rename = "SERVICE-REF")]
     pub service_ref: String,
+
+    #[serde(rename = "SOMEIP-INSTANCE-ID")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -75,6 +81,11 @@ pub struct Event {
 
     #[serde(rename = "SOMEIP-EVENT-ID")]
     pub event_id: String,
+
+    /// 负载中按顺序排列的参数，用于 `Matrix::decode_payload`
+    #[serde(rename = "SOMEIP-PARAMETERS")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<Parameter>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -87,6 +98,86 @@ pub struct Method {
 
     #[serde(rename = "SOMEIP-METHOD-TYPE")]
     pub method_type: String,
+
+    #[serde(rename = "SOMEIP-PARAMETERS")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<Parameter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Parameter {
+    #[serde(rename = "SHORT-NAME")]
+    pub short_name: String,
+
+    #[serde(rename = "TYPE")]
+    pub data_type: DataType,
+}
+
+/// 参数的基本数据类型，按 SomeIP 规范以大端序编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataType {
+    /// 单字节，0 为 false，1 为 true
+    Boolean,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Sint8,
+    Sint16,
+    Sint32,
+    Sint64,
+    /// IEEE-754 单精度浮点数
+    Float32,
+    /// IEEE-754 双精度浮点数
+    Float64,
+}
+
+impl DataType {
+    /// 编码后的字节数
+    pub fn size(self) -> usize {
+        match self {
+            DataType::Boolean | DataType::Uint8 | DataType::Sint8 => 1,
+            DataType::Uint16 | DataType::Sint16 => 2,
+            DataType::Uint32 | DataType::Sint32 | DataType::Float32 => 4,
+            DataType::Uint64 | DataType::Sint64 | DataType::Float64 => 8,
+        }
+    }
+
+    /// 从 `input` 开头解码一个值，返回值与剩余字节；长度不足或布尔值不是 0/1 时返回 None
+    pub fn decode(self, input: &[u8]) -> Option<(serde_json::Value, &[u8])> {
+        use serde_json::Value;
+
+        if input.len() < self.size() {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(self.size());
+        let value = match self {
+            DataType::Boolean => match bytes[0] {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                _ => return None,
+            },
+            DataType::Uint8 => Value::from(bytes[0]),
+            DataType::Uint16 => Value::from(u16::from_be_bytes(bytes.try_into().ok()?)),
+            DataType::Uint32 => Value::from(u32::from_be_bytes(bytes.try_into().ok()?)),
+            DataType::Uint64 => Value::from(u64::from_be_bytes(bytes.try_into().ok()?)),
+            DataType::Sint8 => Value::from(bytes[0] as i8),
+            DataType::Sint16 => Value::from(i16::from_be_bytes(bytes.try_into().ok()?)),
+            DataType::Sint32 => Value::from(i32::from_be_bytes(bytes.try_into().ok()?)),
+            DataType::Sint64 => Value::from(i64::from_be_bytes(bytes.try_into().ok()?)),
+            DataType::Float32 => float_value(f32::from_be_bytes(bytes.try_into().ok()?) as f64),
+            DataType::Float64 => float_value(f64::from_be_bytes(bytes.try_into().ok()?)),
+        };
+        Some((value, rest))
+    }
+}
+
+/// JSON 无法表示 NaN 与无穷大，这些值输出为字符串（"NaN"、"inf"、"-inf"）
+fn float_value(value: f64) -> serde_json::Value {
+    serde_json::Number::from_f64(value)
+        .map(serde_json::Value::Number)
+        .unwrap_or_else(|| serde_json::Value::String(value.to_string()))
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -96,12 +187,110 @@ pub struct Field {
 
     #[serde(rename = "SOMEIP-FIELD-ID")]
     pub field_id: String,
+
+    /// 显式指定的 getter/setter/notifier ID，优先于按 `FieldIdScheme` 推导的值
+    #[serde(rename = "SOMEIP-GETTER-ID")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub getter_id: Option<String>,
+
+    #[serde(rename = "SOMEIP-SETTER-ID")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub setter_id: Option<String>,
+
+    #[serde(rename = "SOMEIP-NOTIFIER-ID")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notifier_id: Option<String>,
+}
+
+/// 由字段 ID 推导 getter/setter/notifier 方法 ID 的规则
+///
+/// SomeIP 规范中 getter/setter 是方法（0x0000-0x7FFF），notifier 是事件（0x8000-0xFFFF），
+/// 但各协议栈对字段 ID 的编码约定不同，可通过矩阵中的显式 ID 覆盖。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldIdScheme {
+    /// GET = id，SET = id | 0x4000，NOTIFIER = id | 0x8000（notifier 位于事件范围）
+    #[default]
+    EventRange,
+    /// 旧版规则：GET = id，SET = id | 0x8000，NOTIFIER = id | 0x4000
+    Legacy,
+}
+
+impl FieldIdScheme {
+    /// 返回 (getter, setter, notifier) 方法 ID
+    pub fn derive(self, field_id: u16) -> (u16, u16, u16) {
+        match self {
+            FieldIdScheme::EventRange => (field_id, field_id | 0x4000, field_id | 0x8000),
+            FieldIdScheme::Legacy => (field_id, field_id | 0x8000, field_id | 0x4000),
+        }
+    }
+}
+
+/// 合并多个矩阵文件时，同一 ID 在不同文件中名称不同的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatrixConflictPolicy {
+    /// 报错并停止加载
+    #[default]
+    Error,
+    /// 使用后加载文件中的名称
+    Override,
+    /// 保留先加载文件中的名称
+    Skip,
+}
+
+/// 方法 ID 对应的接口成员类型，对应 CSV 矩阵的 `type` 列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MethodKind {
+    Method,
+    Event,
+    FieldGet,
+    FieldSet,
+    FieldNotifier,
+}
+
+/// CSV 矩阵中的一行，方法列为空时只定义服务名称
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    service_id_hex: String,
+    service_name: String,
+    #[serde(default)]
+    method_id_hex: String,
+    #[serde(default)]
+    method_name: String,
+    #[serde(rename = "type", default)]
+    kind: Option<MethodKind>,
 }
 
+/// 矩阵文件定义内容的统计，用于 `--validate-only`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatrixSummary {
+    pub services: usize,
+    pub methods: usize,
+    pub events: usize,
+    pub fields: usize,
+    pub instances: usize,
+    pub invalid_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Matrix {
     service_id_to_name: HashMap<u16, String>,
     method_id_to_name: HashMap<(u16, u16), String>,
+    method_kinds: HashMap<(u16, u16), MethodKind>,
+    parameters: HashMap<(u16, u16), Vec<Parameter>>, // (服务ID, 方法ID) -> 负载参数
+    instance_id_to_name: HashMap<(u16, u16), String>, // (服务ID, 实例ID) -> 实例（ECU）名称
+    invalid_ids: Vec<String>,                        // 无法解析的 ID 及其所在位置
     ip_to_name: HashMap<IpAddr, String>,
+    field_id_scheme: FieldIdScheme,
+    conflict_policy: MatrixConflictPolicy,
+}
+
+impl Default for Matrix {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Matrix {
@@ -109,11 +298,145 @@ impl Matrix {
         Self {
             service_id_to_name: HashMap::new(),
             method_id_to_name: HashMap::new(),
+            method_kinds: HashMap::new(),
+            parameters: HashMap::new(),
+            instance_id_to_name: HashMap::new(),
+            invalid_ids: Vec::new(),
             ip_to_name: HashMap::new(),
+            field_id_scheme: FieldIdScheme::default(),
+            conflict_policy: MatrixConflictPolicy::default(),
+        }
+    }
+
+    /// 设置字段方法 ID 的推导规则，需在加载矩阵文件之前调用
+    pub fn set_field_id_scheme(&mut self, scheme: FieldIdScheme) {
+        self.field_id_scheme = scheme;
+    }
+
+    /// 设置 `merge` 遇到名称冲突时的处理方式
+    pub fn set_conflict_policy(&mut self, policy: MatrixConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    /// 已定义名称的服务数
+    pub fn len(&self) -> usize {
+        self.service_id_to_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.service_id_to_name.is_empty()
+    }
+
+    /// 将另一个矩阵的全部映射合并到当前矩阵
+    ///
+    /// 同一服务、方法、实例或 IP 在两个矩阵中名称不同时按冲突策略处理：
+    /// `Error` 时不做任何修改并返回错误，`Override` 使用 `other` 中的名称，`Skip` 保留当前名称。
+    pub fn merge(&mut self, other: Matrix) -> Result<()> {
+        let conflicts = self.conflicts_with(&other);
+        if !conflicts.is_empty() {
+            match self.conflict_policy {
+                MatrixConflictPolicy::Error => {
+                    return Err(SomeIPError::MatrixFileError(format!(
+                        "{} conflicting entries, first conflict at {}",
+                        conflicts.len(),
+                        conflicts[0]
+                    ))
+                    .into());
+                }
+                policy => log::warn!(
+                    "Merging matrix with {} conflicting entries ({:?}), first conflict at {}",
+                    conflicts.len(),
+                    policy,
+                    conflicts[0]
+                ),
+            }
+        }
+
+        let keep_existing = self.conflict_policy == MatrixConflictPolicy::Skip;
+        merge_map(
+            &mut self.service_id_to_name,
+            other.service_id_to_name,
+            keep_existing,
+        );
+        merge_map(
+            &mut self.method_id_to_name,
+            other.method_id_to_name,
+            keep_existing,
+        );
+        merge_map(&mut self.method_kinds, other.method_kinds, keep_existing);
+        merge_map(&mut self.parameters, other.parameters, keep_existing);
+        merge_map(
+            &mut self.instance_id_to_name,
+            other.instance_id_to_name,
+            keep_existing,
+        );
+        merge_map(&mut self.ip_to_name, other.ip_to_name, keep_existing);
+        self.invalid_ids.extend(other.invalid_ids);
+        Ok(())
+    }
+
+    /// 两个矩阵中名称不同的同一 ID，按 ID 排序
+    fn conflicts_with(&self, other: &Matrix) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        let describe = |what: String, ours: &str, theirs: &str| {
+            format!("{} ('{}' vs '{}')", what, ours, theirs)
+        };
+        let mut services: Vec<_> = other.service_id_to_name.iter().collect();
+        services.sort();
+        for (id, name) in services {
+            if let Some(existing) = self.service_id_to_name.get(id)
+                && existing != name
+            {
+                conflicts.push(describe(format!("service 0x{:04X}", id), existing, name));
+            }
         }
+        let mut methods: Vec<_> = other.method_id_to_name.iter().collect();
+        methods.sort();
+        for ((service_id, method_id), name) in methods {
+            if let Some(existing) = self.method_id_to_name.get(&(*service_id, *method_id))
+                && existing != name
+            {
+                conflicts.push(describe(
+                    format!("method 0x{:04X}:0x{:04X}", service_id, method_id),
+                    existing,
+                    name,
+                ));
+            }
+        }
+        let mut instances: Vec<_> = other.instance_id_to_name.iter().collect();
+        instances.sort();
+        for ((service_id, instance_id), name) in instances {
+            if let Some(existing) = self.instance_id_to_name.get(&(*service_id, *instance_id))
+                && existing != name
+            {
+                conflicts.push(describe(
+                    format!("instance 0x{:04X}/0x{:04X}", service_id, instance_id),
+                    existing,
+                    name,
+                ));
+            }
+        }
+        let mut ips: Vec<_> = other.ip_to_name.iter().collect();
+        ips.sort();
+        for (ip, name) in ips {
+            if let Some(existing) = self.ip_to_name.get(ip)
+                && existing != name
+            {
+                conflicts.push(describe(format!("IP {}", ip), existing, name));
+            }
+        }
+        conflicts
     }
 
     pub fn load_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let is_csv = path
+            .as_ref()
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        if is_csv {
+            return self.load_csv(path);
+        }
+
         let mut file = File::open(path)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
@@ -133,56 +456,219 @@ impl Matrix {
         Err(SomeIPError::MatrixFileError("Unsupported matrix file format".to_string()).into())
     }
 
+    /// 读取 CSV 矩阵，列为 `service_id_hex,service_name,method_id_hex,method_name,type`
+    pub fn load_csv<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let mut reader = csv::Reader::from_path(path)
+            .map_err(|e| SomeIPError::MatrixFileError(format!("Cannot open CSV matrix: {}", e)))?;
+
+        for (index, row) in reader.deserialize::<CsvRow>().enumerate() {
+            let row_number = index + 2; // 第 1 行为表头
+            let row = row.map_err(|e| {
+                SomeIPError::MatrixFileError(format!("Invalid CSV row {}: {}", row_number, e))
+            })?;
+
+            let service_id = parse_matrix_id(&row.service_id_hex).ok_or_else(|| {
+                SomeIPError::MatrixFileError(format!(
+                    "Invalid service ID '{}' at row {}",
+                    row.service_id_hex, row_number
+                ))
+            })?;
+            self.service_id_to_name
+                .insert(service_id, row.service_name.clone());
+
+            if row.method_id_hex.trim().is_empty() {
+                continue;
+            }
+            let method_id = parse_matrix_id(&row.method_id_hex).ok_or_else(|| {
+                SomeIPError::MatrixFileError(format!(
+                    "Invalid method ID '{}' at row {}",
+                    row.method_id_hex, row_number
+                ))
+            })?;
+            let kind = row.kind.ok_or_else(|| {
+                SomeIPError::MatrixFileError(format!("Missing type at row {}", row_number))
+            })?;
+            self.add_method(service_id, method_id, &row.method_name, kind);
+        }
+        Ok(())
+    }
+
+    /// 将当前的服务与方法映射导出为 CSV，可由 `load_csv` 重新读取
+    pub fn export_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = csv::Writer::from_path(path).map_err(|e| {
+            SomeIPError::MatrixFileError(format!("Cannot create CSV matrix: {}", e))
+        })?;
+
+        let mut methods: Vec<_> = self
+            .method_id_to_name
+            .iter()
+            .map(|(&key, name)| (key, name))
+            .collect();
+        methods.sort_by_key(|(key, _)| *key);
+        let mut service_ids: Vec<_> = self.service_id_to_name.keys().copied().collect();
+        service_ids.sort();
+
+        for service_id in service_ids {
+            let service_name = &self.service_id_to_name[&service_id];
+            let mut rows = methods
+                .iter()
+                .filter(|((id, _), _)| *id == service_id)
+                .peekable();
+            if rows.peek().is_none() {
+                writer.serialize(CsvRow {
+                    service_id_hex: format!("0x{:04X}", service_id),
+                    service_name: service_name.clone(),
+                    method_id_hex: String::new(),
+                    method_name: String::new(),
+                    kind: None,
+                })?;
+            }
+            for &((_, method_id), method_name) in rows {
+                writer.serialize(CsvRow {
+                    service_id_hex: format!("0x{:04X}", service_id),
+                    service_name: service_name.clone(),
+                    method_id_hex: format!("0x{:04X}", method_id),
+                    method_name: method_name.to_string(),
+                    kind: Some(
+                        self.method_kinds
+                            .get(&(service_id, method_id))
+                            .copied()
+                            .unwrap_or(MethodKind::Method),
+                    ),
+                })?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// 解析矩阵中的 ID，失败时记录到 `invalid_ids` 而不是静默跳过
+    fn parse_id(&mut self, value: &str, context: impl FnOnce() -> String) -> Option<u16> {
+        let id = parse_matrix_id(value);
+        if id.is_none() {
+            let invalid = format!("{}: '{}'", context(), value);
+            log::warn!("Ignoring matrix entry with unparseable ID: {}", invalid);
+            self.invalid_ids.push(invalid);
+        }
+        id
+    }
+
+    fn add_method(&mut self, service_id: u16, method_id: u16, name: &str, kind: MethodKind) {
+        self.method_id_to_name
+            .insert((service_id, method_id), name.to_string());
+        self.method_kinds.insert((service_id, method_id), kind);
+    }
+
+    fn add_parameters(&mut self, service_id: u16, method_id: u16, parameters: &[Parameter]) {
+        if !parameters.is_empty() {
+            self.parameters
+                .insert((service_id, method_id), parameters.to_vec());
+        }
+    }
+
     fn parse_xml_matrix(&mut self, matrix: MatrixFile) {
         // 解析服务和方法
+        let mut instances = Vec::new();
         for package in &matrix.ar_packages {
-            self.parse_package(package);
+            self.parse_package(package, &mut instances);
+        }
+
+        // 服务实例可能引用后面才定义的接口，全部接口解析完成后再解析引用
+        for (name, instance) in instances {
+            let interface = instance
+                .service_ref
+                .rsplit('/')
+                .next()
+                .unwrap_or(&instance.service_ref);
+            let service_id = self
+                .service_id_to_name
+                .iter()
+                .find(|(_, service_name)| service_name.as_str() == interface)
+                .map(|(id, _)| *id);
+            let instance_id = instance
+                .instance_id
+                .as_deref()
+                .and_then(|id| self.parse_id(id, || format!("service instance {}", name)));
+            if let (Some(service_id), Some(instance_id)) = (service_id, instance_id) {
+                self.add_instance_mapping(service_id, instance_id, &name);
+            }
         }
     }
 
-    fn parse_package(&mut self, package: &ArPackage) {
+    fn parse_package(
+        &mut self,
+        package: &ArPackage,
+        instances: &mut Vec<(String, ServiceInstance)>,
+    ) {
         // 解析服务接口
         for element in &package.elements {
+            if let Some(service_instance) = &element.service_instance {
+                instances.push((element.short_name.clone(), service_instance.clone()));
+            }
+
             if let Some(service_interface) = &element.service_interface {
-                if let Some(service_id_str) = &service_interface.service_id {
-                    if let Ok(service_id) = u16::from_str_radix(service_id_str, 16) {
-                        self.service_id_to_name
-                            .insert(service_id, service_interface.short_name.clone());
-
-                        // 解析方法
-                        for method in &service_interface.methods {
-                            if let Ok(method_id) = u16::from_str_radix(&method.method_id, 16) {
-                                self.method_id_to_name
-                                    .insert((service_id, method_id), method.short_name.clone());
-                            }
-                        }
-
-                        // 解析事件
-                        for event in &service_interface.events {
-                            if let Ok(event_id) = u16::from_str_radix(&event.event_id, 16) {
-                                self.method_id_to_name
-                                    .insert((service_id, event_id), event.short_name.clone());
-                            }
-                        }
-
-                        // 解析字段
-                        for field in &service_interface.fields {
-                            if let Ok(field_id) = u16::from_str_radix(&field.field_id, 16) {
-                                // 字段有GET、SET和NOTIFIER方法
-                                self.method_id_to_name.insert(
-                                    (service_id, field_id),
-                                    format!("{}_GET", field.short_name),
-                                );
-                                self.method_id_to_name.insert(
-                                    (service_id, field_id | 0x8000),
-                                    format!("{}_SET", field.short_name),
-                                );
-                                self.method_id_to_name.insert(
-                                    (service_id, field_id | 0x4000),
-                                    format!("{}_NOTIFIER", field.short_name),
-                                );
-                            }
-                        }
+                let interface = &service_interface.short_name;
+                let Some(service_id) = service_interface.service_id.as_deref().and_then(|id| {
+                    self.parse_id(id, || format!("service interface {}", interface))
+                }) else {
+                    continue;
+                };
+                self.service_id_to_name
+                    .insert(service_id, interface.clone());
+
+                // 解析方法
+                for method in &service_interface.methods {
+                    let context = || format!("method {}.{}", interface, method.short_name);
+                    if let Some(method_id) = self.parse_id(&method.method_id, context) {
+                        self.add_method(
+                            service_id,
+                            method_id,
+                            &method.short_name,
+                            MethodKind::Method,
+                        );
+                        self.add_parameters(service_id, method_id, &method.parameters);
+                    }
+                }
+
+                // 解析事件
+                for event in &service_interface.events {
+                    let context = || format!("event {}.{}", interface, event.short_name);
+                    if let Some(event_id) = self.parse_id(&event.event_id, context) {
+                        self.add_method(service_id, event_id, &event.short_name, MethodKind::Event);
+                        self.add_parameters(service_id, event_id, &event.parameters);
+                    }
+                }
+
+                // 解析字段
+                for field in &service_interface.fields {
+                    let context = || format!("field {}.{}", interface, field.short_name);
+                    let Some(field_id) = self.parse_id(&field.field_id, context) else {
+                        continue;
+                    };
+                    // 字段有GET、SET和NOTIFIER方法，显式 ID 优先于推导值
+                    let (getter, setter, notifier) = self.field_id_scheme.derive(field_id);
+                    for (explicit, derived, suffix, kind) in [
+                        (&field.getter_id, getter, "GET", MethodKind::FieldGet),
+                        (&field.setter_id, setter, "SET", MethodKind::FieldSet),
+                        (
+                            &field.notifier_id,
+                            notifier,
+                            "NOTIFIER",
+                            MethodKind::FieldNotifier,
+                        ),
+                    ] {
+                        let method_id = explicit
+                            .as_deref()
+                            .and_then(|id| {
+                                self.parse_id(id, || format!("{} {}", context(), suffix))
+                            })
+                            .unwrap_or(derived);
+                        self.add_method(
+                            service_id,
+                            method_id,
+                            &format!("{}_{}", field.short_name, suffix),
+                            kind,
+                        );
                     }
                 }
             }
@@ -190,7 +676,7 @@ impl Matrix {
 
         // 递归解析子包
         for sub_package in &package.sub_packages {
-            self.parse_package(sub_package);
+            self.parse_package(sub_package, instances);
         }
     }
 
@@ -198,6 +684,11 @@ impl Matrix {
         self.ip_to_name.insert(*ip, name.to_string());
     }
 
+    pub fn add_instance_mapping(&mut self, service_id: u16, instance_id: u16, name: &str) {
+        self.instance_id_to_name
+            .insert((service_id, instance_id), name.to_string());
+    }
+
     pub fn get_service_name(&self, service_id: u16) -> Option<&str> {
         self.service_id_to_name.get(&service_id).map(|s| s.as_str())
     }
@@ -208,7 +699,351 @@ impl Matrix {
             .map(|s| s.as_str())
     }
 
+    /// 按矩阵中定义的参数解码负载，结果为参数名到值的 JSON 对象；
+    /// 该方法未定义参数、负载长度不足或值不合法时返回 None，多余的尾部字节被忽略
+    pub fn decode_payload(
+        &self,
+        service_id: u16,
+        method_id: u16,
+        payload: &[u8],
+    ) -> Option<serde_json::Value> {
+        let parameters = self.parameters.get(&(service_id, method_id))?;
+        let mut values = serde_json::Map::new();
+        let mut rest = payload;
+        for parameter in parameters {
+            let (value, remaining) = parameter.data_type.decode(rest)?;
+            values.insert(parameter.short_name.clone(), value);
+            rest = remaining;
+        }
+        Some(serde_json::Value::Object(values))
+    }
+
+    pub fn get_instance_name(&self, service_id: u16, instance_id: u16) -> Option<&str> {
+        self.instance_id_to_name
+            .get(&(service_id, instance_id))
+            .map(|s| s.as_str())
+    }
+
+    pub fn summary(&self) -> MatrixSummary {
+        let count = |kind: MethodKind| self.method_kinds.values().filter(|&&k| k == kind).count();
+        MatrixSummary {
+            services: self.service_id_to_name.len(),
+            methods: count(MethodKind::Method),
+            events: count(MethodKind::Event),
+            // 每个字段恰好有一个 getter
+            fields: count(MethodKind::FieldGet),
+            instances: self.instance_id_to_name.len(),
+            invalid_ids: self.invalid_ids.clone(),
+        }
+    }
+
     pub fn get_ip_name(&self, ip: &IpAddr) -> Option<&str> {
         self.ip_to_name.get(ip).map(|s| s.as_str())
     }
 }
+
+/// 合并映射表，`keep_existing` 为 true 时不覆盖已有的键
+fn merge_map<K: Eq + Hash, V>(
+    target: &mut HashMap<K, V>,
+    source: HashMap<K, V>,
+    keep_existing: bool,
+) {
+    for (key, value) in source {
+        if keep_existing {
+            target.entry(key).or_insert(value);
+        } else {
+            target.insert(key, value);
+        }
+    }
+}
+
+/// 矩阵文件中的 ID 一律按十六进制解析，`0x` 前缀可选，
+/// 与旧版矩阵及 CSV 导出中不带前缀的 `{:04X}` 写法保持一致
+fn parse_matrix_id(value: &str) -> Option<u16> {
+    let value = value.trim();
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+pub fn format_matrix_summary(summary: &MatrixSummary) -> String {
+    let mut output = format!(
+        "Matrix summary\nServices: {}\nMethods: {}\nEvents: {}\nFields: {}\nInstances: {}\n",
+        summary.services, summary.methods, summary.events, summary.fields, summary.instances
+    );
+    for invalid in &summary.invalid_ids {
+        output.push_str(&format!("Invalid ID: {}\n", invalid));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_hex_with_optional_prefix() {
+        assert_eq!(parse_matrix_id("0x1000"), Some(0x1000));
+        assert_eq!(parse_matrix_id(" 0XABCD "), Some(0xABCD));
+        assert_eq!(parse_matrix_id("1234"), Some(0x1234));
+        assert_eq!(parse_matrix_id("abcd"), Some(0xABCD));
+        assert_eq!(parse_matrix_id("0x10000"), None);
+        assert_eq!(parse_matrix_id("0x"), None);
+        assert_eq!(parse_matrix_id("12G4"), None);
+    }
+
+    #[test]
+    fn prefixed_ids_in_matrix_file_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("matrix.json");
+        std::fs::write(
+            &path,
+            r#"{"AR-PACKAGES": [{"SHORT-NAME": "Services", "ELEMENTS": [
+                {"SHORT-NAME": "Vehicle", "SOMEIP-SERVICE-INTERFACE": {
+                    "SHORT-NAME": "Vehicle", "SOMEIP-SERVICE-ID": "0x1234",
+                    "SOMEIP-METHODS": [{"SHORT-NAME": "GetSpeed",
+                        "SOMEIP-METHOD-ID": "0x0001", "SOMEIP-METHOD-TYPE": "REQUEST"}],
+                    "SOMEIP-EVENTS": [{"SHORT-NAME": "SpeedChanged", "SOMEIP-EVENT-ID": "8001"}]}}
+            ]}]}"#,
+        )
+        .unwrap();
+        let mut matrix = Matrix::new();
+        matrix.load_from_file(&path).unwrap();
+        assert_eq!(matrix.get_service_name(0x1234), Some("Vehicle"));
+        assert_eq!(matrix.get_method_name(0x1234, 0x0001), Some("GetSpeed"));
+        assert_eq!(matrix.get_method_name(0x1234, 0x8001), Some("SpeedChanged"));
+        assert!(matrix.summary().invalid_ids.is_empty());
+    }
+
+    fn typed_matrix(parameters: &str) -> Matrix {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("matrix.json");
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"AR-PACKAGES": [{{"SHORT-NAME": "Services", "ELEMENTS": [
+                    {{"SHORT-NAME": "Vehicle", "SOMEIP-SERVICE-INTERFACE": {{
+                        "SHORT-NAME": "Vehicle", "SOMEIP-SERVICE-ID": "0x1234",
+                        "SOMEIP-EVENTS": [{{"SHORT-NAME": "SpeedChanged",
+                            "SOMEIP-EVENT-ID": "0x8001", "SOMEIP-PARAMETERS": {}}}]}}}}
+                ]}}]}}"#,
+                parameters
+            ),
+        )
+        .unwrap();
+        let mut matrix = Matrix::new();
+        matrix.load_from_file(&path).unwrap();
+        matrix
+    }
+
+    #[test]
+    fn decodes_float32_and_boolean_parameters() {
+        let matrix = typed_matrix(
+            r#"[{"SHORT-NAME": "Speed", "TYPE": "float32"},
+                {"SHORT-NAME": "Valid", "TYPE": "boolean"}]"#,
+        );
+        let payload = [0x3F, 0xC0, 0x00, 0x00, 0x01];
+        assert_eq!(
+            matrix.decode_payload(0x1234, 0x8001, &payload),
+            Some(serde_json::json!({"Speed": 1.5, "Valid": true}))
+        );
+        // 未定义参数的方法不解码
+        assert_eq!(matrix.decode_payload(0x1234, 0x0001, &payload), None);
+        // 负载长度不足
+        assert_eq!(matrix.decode_payload(0x1234, 0x8001, &payload[..4]), None);
+    }
+
+    #[test]
+    fn non_finite_floats_and_invalid_booleans() {
+        let matrix = typed_matrix(
+            r#"[{"SHORT-NAME": "A", "TYPE": "float64"},
+                {"SHORT-NAME": "B", "TYPE": "float32"},
+                {"SHORT-NAME": "C", "TYPE": "float32"}]"#,
+        );
+        let mut payload = f64::NAN.to_be_bytes().to_vec();
+        payload.extend(f32::INFINITY.to_be_bytes());
+        payload.extend(f32::NEG_INFINITY.to_be_bytes());
+        assert_eq!(
+            matrix.decode_payload(0x1234, 0x8001, &payload),
+            Some(serde_json::json!({"A": "NaN", "B": "inf", "C": "-inf"}))
+        );
+
+        assert_eq!(DataType::Boolean.decode(&[0x00]).unwrap().0, false);
+        assert_eq!(DataType::Boolean.decode(&[0x02]), None);
+        assert_eq!(
+            DataType::Float64
+                .decode(&(-0.25f64).to_be_bytes())
+                .unwrap()
+                .0,
+            -0.25
+        );
+    }
+
+    /// 服务 0x1234 只有一个字段 `Speed`，字段 ID 为 0x0010
+    fn field_matrix(scheme: FieldIdScheme, notifier_id: Option<&str>) -> Matrix {
+        let interface = ServiceInterface {
+            short_name: "Vehicle".to_string(),
+            service_id: Some("0x1234".to_string()),
+            fields: vec![Field {
+                short_name: "Speed".to_string(),
+                field_id: "0x0010".to_string(),
+                notifier_id: notifier_id.map(str::to_string),
+                ..Field::default()
+            }],
+            ..ServiceInterface::default()
+        };
+        let mut matrix = Matrix::new();
+        matrix.set_field_id_scheme(scheme);
+        matrix.parse_xml_matrix(MatrixFile {
+            ar_packages: vec![ArPackage {
+                short_name: "Services".to_string(),
+                elements: vec![Element {
+                    short_name: "Vehicle".to_string(),
+                    service_interface: Some(interface),
+                    ..Element::default()
+                }],
+                sub_packages: Vec::new(),
+            }],
+        });
+        matrix
+    }
+
+    /// 观察到的通知消息在输出中显示的方法名
+    fn notification_method(matrix: &Matrix, method_id: u16) -> String {
+        use crate::output::formatter::{FormatOptions, convert_to_formatted};
+        use crate::parser::someip::header::MessageType;
+        use crate::testing::message::someip_message;
+
+        let notification = someip_message(0x1234, method_id, 1, MessageType::Notification);
+        convert_to_formatted(&notification, matrix, &FormatOptions::default()).method
+    }
+
+    #[test]
+    fn field_notifier_derived_in_event_range() {
+        let matrix = field_matrix(FieldIdScheme::EventRange, None);
+        assert_eq!(notification_method(&matrix, 0x8010), "Speed_NOTIFIER");
+        assert_eq!(matrix.get_method_name(0x1234, 0x0010), Some("Speed_GET"));
+        assert_eq!(matrix.get_method_name(0x1234, 0x4010), Some("Speed_SET"));
+    }
+
+    #[test]
+    fn legacy_scheme_and_explicit_notifier_id() {
+        let legacy = field_matrix(FieldIdScheme::Legacy, None);
+        assert_eq!(notification_method(&legacy, 0x4010), "Speed_NOTIFIER");
+        assert_eq!(legacy.get_method_name(0x1234, 0x8010), Some("Speed_SET"));
+
+        let explicit = field_matrix(FieldIdScheme::EventRange, Some("0x8123"));
+        assert_eq!(notification_method(&explicit, 0x8123), "Speed_NOTIFIER");
+        assert_eq!(explicit.get_method_name(0x1234, 0x8010), None);
+    }
+
+    #[test]
+    fn csv_round_trip_keeps_services_and_methods() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("matrix.csv");
+        std::fs::write(
+            &source,
+            "service_id_hex,service_name,method_id_hex,method_name,type\n\
+             0x1234,Vehicle,0x0001,GetSpeed,method\n\
+             0x1234,Vehicle,0x8001,SpeedChanged,event\n\
+             0x1234,Vehicle,0x0010,Speed_GET,field_get\n\
+             0x5678,Diagnostics,,,\n",
+        )
+        .unwrap();
+        let mut matrix = Matrix::new();
+        matrix.load_from_file(&source).unwrap();
+        assert_eq!(matrix.get_service_name(0x5678), Some("Diagnostics"));
+        assert_eq!(matrix.get_method_name(0x1234, 0x8001), Some("SpeedChanged"));
+
+        let exported = dir.path().join("exported.csv");
+        matrix.export_csv(&exported).unwrap();
+        let mut reloaded = Matrix::new();
+        reloaded.load_csv(&exported).unwrap();
+        assert_eq!(reloaded.service_id_to_name, matrix.service_id_to_name);
+        assert_eq!(reloaded.method_id_to_name, matrix.method_id_to_name);
+        assert_eq!(reloaded.method_kinds, matrix.method_kinds);
+        // 导出结果与再次导出完全一致
+        let again = dir.path().join("again.csv");
+        reloaded.export_csv(&again).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&exported).unwrap(),
+            std::fs::read_to_string(&again).unwrap()
+        );
+    }
+
+    #[test]
+    fn csv_reports_row_of_invalid_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("matrix.csv");
+        std::fs::write(
+            &path,
+            "service_id_hex,service_name,method_id_hex,method_name,type\n\
+             0x1234,Vehicle,0x0001,GetSpeed,method\n\
+             0x1234,Vehicle,0xZZ01,Broken,method\n",
+        )
+        .unwrap();
+        let error = Matrix::new().load_csv(&path).unwrap_err().to_string();
+        assert!(error.contains("'0xZZ01' at row 3"), "{error}");
+    }
+
+    /// 服务 0x1234 只有一个方法 0x0001 的矩阵
+    fn single_service(service_name: &str, method_name: &str) -> Matrix {
+        let mut matrix = Matrix::new();
+        matrix
+            .service_id_to_name
+            .insert(0x1234, service_name.to_string());
+        matrix.add_method(0x1234, 0x0001, method_name, MethodKind::Method);
+        matrix
+    }
+
+    #[test]
+    fn merge_combines_disjoint_and_identical_entries() {
+        let mut matrix = single_service("Vehicle", "GetSpeed");
+        let mut other = single_service("Vehicle", "GetSpeed");
+        other
+            .service_id_to_name
+            .insert(0x5678, "Diagnostics".to_string());
+        other.add_ip_mapping(&"10.0.0.1".parse().unwrap(), "Gateway");
+        matrix.merge(other).unwrap();
+
+        assert_eq!(matrix.len(), 2);
+        assert_eq!(matrix.get_service_name(0x5678), Some("Diagnostics"));
+        assert_eq!(matrix.get_method_name(0x1234, 0x0001), Some("GetSpeed"));
+        assert_eq!(
+            matrix.get_ip_name(&"10.0.0.1".parse().unwrap()),
+            Some("Gateway")
+        );
+    }
+
+    #[test]
+    fn merge_conflict_follows_policy() {
+        let mut matrix = single_service("Vehicle", "GetSpeed");
+        let error = matrix
+            .merge(single_service("Chassis", "GetSpeed"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("conflict at service 0x1234 ('Vehicle' vs 'Chassis')"),
+            "{error}"
+        );
+        // 报错时不做任何修改
+        assert_eq!(matrix.get_service_name(0x1234), Some("Vehicle"));
+
+        matrix.set_conflict_policy(MatrixConflictPolicy::Skip);
+        matrix
+            .merge(single_service("Chassis", "ReadSpeed"))
+            .unwrap();
+        assert_eq!(matrix.get_service_name(0x1234), Some("Vehicle"));
+        assert_eq!(matrix.get_method_name(0x1234, 0x0001), Some("GetSpeed"));
+
+        matrix.set_conflict_policy(MatrixConflictPolicy::Override);
+        matrix
+            .merge(single_service("Chassis", "ReadSpeed"))
+            .unwrap();
+        assert_eq!(matrix.get_service_name(0x1234), Some("Chassis"));
+        assert_eq!(matrix.get_method_name(0x1234, 0x0001), Some("ReadSpeed"));
+        assert_eq!(matrix.len(), 1);
+    }
+}
@@ -1,8 +1,13 @@
 //! SomeIP协议解析子模块，包含头部、SD、TP、MSI等解析逻辑
 
+pub mod e2e;
 pub mod header;
 pub mod matrix;
 pub mod msi_parser;
+pub mod sd_offers;
 pub mod sd_parser;
+pub mod sd_reboot;
+pub mod sd_subscription;
 pub mod session;
+pub mod stream_decoder;
 pub mod tp_parser;
@@ -22,17 +22,19 @@ pub fn parse_msi_packet(payload: &[u8]) -> Result<MSIPacket> {
         // 解析头部
         let (header, _consumed) = parse_someip_header_wrapper(remaining)?;
 
-        // 计算消息总长度（包括头部）
-        let message_length = header.length as usize;
-
-        // 确保有足够的数据
-        if message_length > remaining.len() {
-            return Err(SomeIPError::InvalidPacketFormat(
-                "MSI packet truncated: insufficient data for claimed length".to_string(),
-            )
+        // 确保有足够的数据（length 字段从 client_id 开始计算，至少为 8）
+        if !header.length_fits(remaining.len()) {
+            return Err(SomeIPError::InvalidPacketFormat(format!(
+                "MSI packet truncated or malformed: length {} with {} bytes left",
+                header.length,
+                remaining.len()
+            ))
             .into());
         }
 
+        // 计算消息总长度（包括头部）
+        let message_length = 16 + header.payload_len();
+
         // 提取消息数据
         let message_data = &remaining[..message_length];
 
@@ -0,0 +1,268 @@
+// src/parser/someip/sd_offers.rs
+use super::matrix::Matrix;
+use super::sd_parser::{SDEntry, SDOption, SDPacket, TransportProtocol};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// 提供服务的单播端点
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OfferedEndpoint {
+    pub ip: IpAddr,
+    pub protocol: String,
+    pub port: u16,
+    /// 端点地址与发送 OfferService 的 SD 地址不一致（代理、NAT 或伪造的报文）
+    pub source_mismatch: bool,
+}
+
+/// 端点地址与 SD 报文源地址不一致的服务提供
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointMismatch {
+    pub service_id: u16,
+    pub instance_id: u16,
+    pub provider: IpAddr,
+    pub endpoint: OfferedEndpoint,
+}
+
+/// 最近一次 OfferService 描述的服务实例
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OfferedService {
+    /// 发送 OfferService 的 SD 端点地址
+    pub provider: IpAddr,
+    /// SD 报文的 unicast 标志：提供方能否接收单播 SD 消息（如单播的 Subscribe）
+    pub unicast: bool,
+    pub major_version: u8,
+    pub minor_version: u32,
+    pub endpoints: Vec<OfferedEndpoint>,
+    /// 负载均衡选项 (优先级, 权重)，未携带时为空
+    pub load_balancing: Option<(u16, u16)>,
+}
+
+/// 跟踪当前有效的服务提供，用于输出服务拓扑
+///
+/// TTL 为 0 的 OfferService（StopOffer）会移除对应实例。
+pub struct OfferTracker {
+    offers: BTreeMap<(u16, u16), OfferedService>, // (服务ID, 实例ID) -> 服务提供
+}
+
+impl Default for OfferTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OfferTracker {
+    pub fn new() -> Self {
+        Self {
+            offers: BTreeMap::new(),
+        }
+    }
+
+    /// 记录 SD 报文中的服务提供，返回新出现的端点地址不一致
+    /// （同一实例重复提供相同的不一致端点时只返回一次）
+    pub fn record(&mut self, provider: IpAddr, sd_packet: &SDPacket) -> Vec<EndpointMismatch> {
+        let mut mismatches = Vec::new();
+        for entry in &sd_packet.entries {
+            let SDEntry::OfferService(offer) = entry else {
+                continue;
+            };
+            let key = (offer.service_id, offer.instance_id);
+            if entry.is_stop() {
+                self.offers.remove(&key);
+                continue;
+            }
+
+            let mut endpoints = Vec::new();
+            let mut load_balancing = None;
+            for option in sd_packet.entry_options(entry) {
+                match option {
+                    SDOption::Ipv4Endpoint(opt) => endpoints.push(OfferedEndpoint::new(
+                        IpAddr::V4(Ipv4Addr::from(opt.ip_address)),
+                        &opt.transport_protocol,
+                        opt.port,
+                        provider,
+                    )),
+                    SDOption::Ipv6Endpoint(opt) => endpoints.push(OfferedEndpoint::new(
+                        IpAddr::V6(Ipv6Addr::from(opt.ip_address)),
+                        &opt.transport_protocol,
+                        opt.port,
+                        provider,
+                    )),
+                    SDOption::LoadBalancing(opt) => {
+                        load_balancing = Some((opt.priority, opt.weight));
+                    }
+                    _ => {}
+                }
+            }
+
+            let previous = self.offers.get(&key);
+            for endpoint in endpoints.iter().filter(|endpoint| endpoint.source_mismatch) {
+                let known = previous.is_some_and(|offer| {
+                    offer.provider == provider && offer.endpoints.contains(endpoint)
+                });
+                if !known {
+                    mismatches.push(EndpointMismatch {
+                        service_id: offer.service_id,
+                        instance_id: offer.instance_id,
+                        provider,
+                        endpoint: endpoint.clone(),
+                    });
+                }
+            }
+
+            self.offers.insert(
+                key,
+                OfferedService {
+                    provider,
+                    unicast: sd_packet.flags.unicast,
+                    major_version: offer.major_version,
+                    minor_version: offer.minor_version,
+                    endpoints,
+                    load_balancing,
+                },
+            );
+        }
+        mismatches
+    }
+
+    pub fn offers(&self) -> impl Iterator<Item = (&(u16, u16), &OfferedService)> {
+        self.offers.iter()
+    }
+}
+
+impl OfferedEndpoint {
+    fn new(ip: IpAddr, protocol: &TransportProtocol, port: u16, provider: IpAddr) -> Self {
+        Self {
+            ip,
+            protocol: protocol_name(protocol),
+            port,
+            source_mismatch: ip != provider,
+        }
+    }
+}
+
+fn protocol_name(protocol: &TransportProtocol) -> String {
+    match protocol {
+        TransportProtocol::TCP => "TCP".to_string(),
+        TransportProtocol::UDP => "UDP".to_string(),
+        TransportProtocol::Unknown(value) => format!("0x{:02X}", value),
+    }
+}
+
+pub fn format_sd_offers(tracker: &OfferTracker, matrix: &Matrix) -> String {
+    let mut output = String::from("SD offer topology\n");
+    for (&(service_id, instance_id), offer) in tracker.offers() {
+        let service = matrix
+            .get_service_name(service_id)
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("0x{:04X}", service_id));
+        let load_balancing = offer
+            .load_balancing
+            .map(|(priority, weight)| format!(" priority {} weight {}", priority, weight))
+            .unwrap_or_default();
+        output.push_str(&format!(
+            "Offer: {}/0x{:04X} v{}.{} from {}{}{}\n",
+            service,
+            instance_id,
+            offer.major_version,
+            offer.minor_version,
+            offer.provider,
+            if offer.unicast { "" } else { " multicast-only" },
+            // 没有端点时负载均衡信息附在服务提供行上
+            if offer.endpoints.is_empty() {
+                load_balancing.as_str()
+            } else {
+                ""
+            }
+        ));
+        for endpoint in &offer.endpoints {
+            output.push_str(&format!(
+                "  Endpoint: {} {}:{}{}{}\n",
+                endpoint.protocol,
+                endpoint.ip,
+                endpoint.port,
+                load_balancing,
+                if endpoint.source_mismatch {
+                    " [source mismatch]"
+                } else {
+                    ""
+                }
+            ));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::someip::sd_parser::LoadBalancingOption;
+    use crate::testing::sd::{offer, sd_packet, udp_endpoint};
+
+    #[test]
+    fn offer_shows_load_balancing_per_endpoint() {
+        let mut entry = offer(0x1234, 0x0001, 3, 0);
+        if let SDEntry::OfferService(offer) = &mut entry {
+            offer.number_of_first_options = 2;
+        }
+        let packet = sd_packet(
+            1,
+            true,
+            true,
+            vec![entry],
+            vec![
+                udp_endpoint([10, 0, 0, 1], 30509),
+                SDOption::LoadBalancing(LoadBalancingOption {
+                    strategy: 0,
+                    priority: 2,
+                    weight: 50,
+                }),
+            ],
+        );
+        let mut tracker = OfferTracker::new();
+        tracker.record(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), &packet);
+
+        let (_, offered) = tracker.offers().next().unwrap();
+        assert_eq!(offered.load_balancing, Some((2, 50)));
+        let output = format_sd_offers(&tracker, &Matrix::new());
+        assert!(
+            output.contains("  Endpoint: UDP 10.0.0.1:30509 priority 2 weight 50\n"),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn endpoint_not_matching_source_is_flagged_once() {
+        let provider = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let packet = |session_id| {
+            sd_packet(
+                session_id,
+                true,
+                true,
+                vec![offer(0x1234, 0x0001, 3, 0), offer(0x5678, 0x0001, 3, 1)],
+                vec![
+                    udp_endpoint([192, 168, 1, 9], 30509),
+                    udp_endpoint([10, 0, 0, 1], 30510),
+                ],
+            )
+        };
+        let mut tracker = OfferTracker::new();
+        let mismatches = tracker.record(provider, &packet(1));
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].service_id, 0x1234);
+        assert_eq!(mismatches[0].provider, provider);
+        assert_eq!(mismatches[0].endpoint.ip.to_string(), "192.168.1.9");
+        // 重复的服务提供不再报告
+        assert!(tracker.record(provider, &packet(2)).is_empty());
+
+        let output = format_sd_offers(&tracker, &Matrix::new());
+        assert!(
+            output.contains("  Endpoint: UDP 192.168.1.9:30509 [source mismatch]\n"),
+            "{output}"
+        );
+        assert!(
+            output.contains("  Endpoint: UDP 10.0.0.1:30510\n"),
+            "{output}"
+        );
+    }
+}
@@ -1,14 +1,15 @@
 // src/parser/someip/sd_parser.rs
-use super::header::parse_return_code;
 use super::header::*;
+use crate::error::{Result, SomeIPError};
 use nom::{
     IResult, Parser,
     bytes::complete::take,
-    multi::{count, many0},
+    multi::count,
     number::complete::{be_u8, be_u16, be_u24, be_u32},
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SDPacket {
     pub header: SomeIPHeader,
     pub flags: SDFlags,
@@ -16,14 +17,14 @@ pub struct SDPacket {
     pub options: Vec<SDOption>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SDFlags {
     pub reboot: bool,
     pub unicast: bool,
     pub explicit_initial_data_control: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SDEntry {
     FindService(FindServiceEntry),
     OfferService(OfferServiceEntry),
@@ -32,7 +33,7 @@ pub enum SDEntry {
     Unknown { entry_type: u8, data: Vec<u8> },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FindServiceEntry {
     pub service_id: u16,
     pub instance_id: u16,
@@ -45,7 +46,7 @@ pub struct FindServiceEntry {
     pub number_of_second_options: u8,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OfferServiceEntry {
     pub service_id: u16,
     pub instance_id: u16,
@@ -58,7 +59,7 @@ pub struct OfferServiceEntry {
     pub number_of_second_options: u8,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SubscribeEventgroupEntry {
     pub service_id: u16,
     pub instance_id: u16,
@@ -72,7 +73,7 @@ pub struct SubscribeEventgroupEntry {
     pub number_of_second_options: u8,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SubscribeEventgroupAckEntry {
     pub service_id: u16,
     pub instance_id: u16,
@@ -84,10 +85,9 @@ pub struct SubscribeEventgroupAckEntry {
     pub number_of_first_options: u8,
     pub second_options_index: u8,
     pub number_of_second_options: u8,
-    pub return_code: ReturnCode,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SDOption {
     Configuration(ConfigurationOption),
     LoadBalancing(LoadBalancingOption),
@@ -100,68 +100,214 @@ pub enum SDOption {
     Unknown { option_type: u8, data: Vec<u8> },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// 配置选项，每一项为 `key=value` 或仅有 `key`，按出现顺序保存
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConfigurationOption {
-    pub items: Vec<String>,
+    pub items: Vec<(String, Option<String>)>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl ConfigurationOption {
+    /// 以原始字符串形式返回各配置项
+    pub fn raw_items(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("{}={}", key, value),
+                None => key.clone(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LoadBalancingOption {
     pub strategy: u8,
     pub priority: u16,
     pub weight: u16,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ipv4EndpointOption {
     pub ip_address: [u8; 4],
     pub transport_protocol: TransportProtocol,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ipv6EndpointOption {
     pub ip_address: [u8; 16],
     pub transport_protocol: TransportProtocol,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ipv4MulticastOption {
     pub ip_address: [u8; 4],
     pub transport_protocol: TransportProtocol,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ipv6MulticastOption {
     pub ip_address: [u8; 16],
     pub transport_protocol: TransportProtocol,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ipv4SDEndpointOption {
     pub ip_address: [u8; 4],
     pub transport_protocol: TransportProtocol,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ipv6SDEndpointOption {
     pub ip_address: [u8; 16],
     pub transport_protocol: TransportProtocol,
     pub port: u16,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TransportProtocol {
     TCP,
     UDP,
     Unknown(u8),
 }
 
-pub fn parse_sd_packet(input: &[u8], header: SomeIPHeader) -> IResult<&[u8], SDPacket> {
+impl SDEntry {
+    /// 条目所属的服务 ID（未知类型的条目返回 None）
+    pub fn service_id(&self) -> Option<u16> {
+        match self {
+            SDEntry::FindService(e) => Some(e.service_id),
+            SDEntry::OfferService(e) => Some(e.service_id),
+            SDEntry::SubscribeEventgroup(e) => Some(e.service_id),
+            SDEntry::SubscribeEventgroupAck(e) => Some(e.service_id),
+            SDEntry::Unknown { .. } => None,
+        }
+    }
+
+    /// TTL 为 0 的 OfferService / SubscribeEventgroup 表示 StopOffer / StopSubscribe
+    pub fn is_stop(&self) -> bool {
+        match self {
+            SDEntry::OfferService(e) => e.ttl == 0,
+            SDEntry::SubscribeEventgroup(e) => e.ttl == 0,
+            _ => false,
+        }
+    }
+
+    /// 两组选项引用：(第一组起始索引, 数量, 第二组起始索引, 数量)
+    fn option_runs(&self) -> (u8, u8, u8, u8) {
+        macro_rules! runs {
+            ($e:expr) => {
+                (
+                    $e.first_options_index,
+                    $e.number_of_first_options,
+                    $e.second_options_index,
+                    $e.number_of_second_options,
+                )
+            };
+        }
+        match self {
+            SDEntry::FindService(e) => runs!(e),
+            SDEntry::OfferService(e) => runs!(e),
+            SDEntry::SubscribeEventgroup(e) => runs!(e),
+            SDEntry::SubscribeEventgroupAck(e) => runs!(e),
+            SDEntry::Unknown { .. } => (0, 0, 0, 0),
+        }
+    }
+}
+
+impl SDPacket {
+    /// 解析条目引用的两组选项，超出选项数组范围的引用会被截断
+    pub fn resolve_options(&self, entry: &SDEntry) -> (Vec<&SDOption>, Vec<&SDOption>) {
+        let (first_index, first_count, second_index, second_count) = entry.option_runs();
+        let run = |index: u8, count: u8| -> Vec<&SDOption> {
+            let start = (index as usize).min(self.options.len());
+            let end = (start + count as usize).min(self.options.len());
+            if end - start < count as usize {
+                log::debug!(
+                    "SD entry references options {}..{} but only {} options present",
+                    index,
+                    index as usize + count as usize,
+                    self.options.len()
+                );
+            }
+            self.options[start..end].iter().collect()
+        };
+        (
+            run(first_index, first_count),
+            run(second_index, second_count),
+        )
+    }
+
+    /// 条目引用的全部选项：第一组选项在前，第二组选项在后
+    pub fn entry_options(&self, entry: &SDEntry) -> Vec<&SDOption> {
+        let (first_options, second_options) = self.resolve_options(entry);
+        first_options.into_iter().chain(second_options).collect()
+    }
+
+    /// 编码为完整的 SomeIP-SD 消息（与 `decode_sd_packet` 的格式对应），头部 length 字段按实际长度填写
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+
+        let mut flags_byte = 0u8;
+        if self.flags.reboot {
+            flags_byte |= 0x80;
+        }
+        if self.flags.unicast {
+            flags_byte |= 0x40;
+        }
+        if self.flags.explicit_initial_data_control {
+            flags_byte |= 0x20;
+        }
+        body.push(flags_byte);
+        body.extend_from_slice(&[0u8; 3]); // 保留字段
+
+        let entries: Vec<u8> = self.entries.iter().flat_map(encode_sd_entry).collect();
+        body.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        body.extend_from_slice(&entries);
+
+        let options: Vec<u8> = self.options.iter().flat_map(encode_sd_option).collect();
+        body.extend_from_slice(&(options.len() as u32).to_be_bytes());
+        body.extend_from_slice(&options);
+
+        let mut header = self.header.clone();
+        header.length = body.len() as u32 + 8;
+        encode_someip_message(&header, &body).expect("length field is computed from the payload")
+    }
+}
+
+/// 解析 SD 包，先校验条目数组长度，解析失败时返回可读的错误信息
+pub fn decode_sd_packet(input: &[u8], header: SomeIPHeader) -> Result<SDPacket> {
+    if input.len() >= 8 {
+        let entries_length = u32::from_be_bytes(input[4..8].try_into().unwrap()) as usize;
+        let remaining = input.len() - 8;
+        if !entries_length.is_multiple_of(16) {
+            return Err(SomeIPError::InvalidPacketFormat(format!(
+                "SD entries length {} is not a multiple of 16",
+                entries_length
+            ))
+            .into());
+        }
+        if entries_length > remaining {
+            return Err(SomeIPError::InvalidPacketFormat(format!(
+                "SD entries length {} exceeds remaining {} bytes",
+                entries_length, remaining
+            ))
+            .into());
+        }
+    }
+
+    parse_sd_packet(input, header)
+        .map(|(_, packet)| packet)
+        .map_err(|e| {
+            SomeIPError::InvalidPacketFormat(format!("Failed to parse SD packet: {}", e)).into()
+        })
+}
+
+/// 解析 SD 包主体，条目数组长度已由 `decode_sd_packet` 校验
+fn parse_sd_packet(input: &[u8], header: SomeIPHeader) -> IResult<&[u8], SDPacket> {
     let (input, flags_byte) = be_u8(input)?;
     let flags = SDFlags {
         reboot: (flags_byte & 0x80) != 0,
@@ -266,9 +412,8 @@ fn parse_sd_entry(input: &[u8]) -> IResult<&[u8], SDEntry> {
             ))
         }
         0x07 => {
-            // SubscribeEventgroupAck
+            // SubscribeEventgroupAck（与 SubscribeEventgroup 布局相同，共 16 字节）
             let (input, (reserved, eventgroup_id)) = (be_u16, be_u16).parse(input)?;
-            let (input, return_code) = be_u8(input)?;
             Ok((
                 input,
                 SDEntry::SubscribeEventgroupAck(SubscribeEventgroupAckEntry {
@@ -282,7 +427,6 @@ fn parse_sd_entry(input: &[u8]) -> IResult<&[u8], SDEntry> {
                     number_of_first_options,
                     second_options_index,
                     number_of_second_options,
-                    return_code: parse_return_code(return_code),
                 }),
             ))
         }
@@ -343,7 +487,21 @@ fn parse_sd_options(input: &[u8], length: usize) -> IResult<&[u8], Vec<SDOption>
 }
 
 fn parse_configuration_option(input: &[u8]) -> IResult<&[u8], SDOption> {
-    let (input, items) = many0(parse_configuration_item).parse(input)?;
+    let mut items = Vec::new();
+    let mut input = input;
+    while !input.is_empty() {
+        let (rest, item) = parse_configuration_item(input)?;
+        input = rest;
+        // 长度为 0 的项表示配置字符串结束
+        if item.is_empty() {
+            break;
+        }
+        // 按第一个 '=' 拆分键值，没有 '=' 的项只有键
+        items.push(match item.split_once('=') {
+            Some((key, value)) => (key.to_string(), Some(value.to_string())),
+            None => (item, None),
+        });
+    }
     Ok((
         input,
         SDOption::Configuration(ConfigurationOption { items }),
@@ -519,3 +677,323 @@ fn parse_ipv6_sd_endpoint_option(input: &[u8]) -> IResult<&[u8], SDOption> {
         }),
     ))
 }
+
+fn encode_sd_entry(entry: &SDEntry) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(16);
+    // 条目公共部分：类型、选项索引、选项数量、服务/实例 ID、主版本号、TTL
+    macro_rules! encode_common {
+        ($entry_type:expr, $e:expr) => {{
+            bytes.extend_from_slice(&[
+                $entry_type,
+                $e.first_options_index,
+                $e.second_options_index,
+                ($e.number_of_first_options & 0x0F) | ($e.number_of_second_options << 4),
+            ]);
+            bytes.extend_from_slice(&$e.service_id.to_be_bytes());
+            bytes.extend_from_slice(&$e.instance_id.to_be_bytes());
+            bytes.push($e.major_version);
+            bytes.extend_from_slice(&$e.ttl.to_be_bytes()[1..]);
+        }};
+    }
+
+    match entry {
+        SDEntry::FindService(e) => {
+            encode_common!(0x00, e);
+            bytes.extend_from_slice(&e.minor_version.to_be_bytes());
+        }
+        SDEntry::OfferService(e) => {
+            encode_common!(0x01, e);
+            bytes.extend_from_slice(&e.minor_version.to_be_bytes());
+        }
+        SDEntry::SubscribeEventgroup(e) => {
+            encode_common!(0x06, e);
+            bytes.extend_from_slice(&e.reserved.to_be_bytes());
+            bytes.extend_from_slice(&e.eventgroup_id.to_be_bytes());
+        }
+        SDEntry::SubscribeEventgroupAck(e) => {
+            encode_common!(0x07, e);
+            bytes.extend_from_slice(&e.reserved.to_be_bytes());
+            bytes.extend_from_slice(&e.eventgroup_id.to_be_bytes());
+        }
+        SDEntry::Unknown { entry_type, data } => {
+            // 未知条目只保留了类型与末尾数据，公共部分补零
+            bytes.push(*entry_type);
+            bytes.extend_from_slice(&[0u8; 11]);
+            bytes.extend_from_slice(data);
+        }
+    }
+    bytes
+}
+
+fn encode_sd_option(option: &SDOption) -> Vec<u8> {
+    let (option_type, data) = match option {
+        SDOption::Configuration(opt) => {
+            let mut data = Vec::new();
+            for item in opt.raw_items() {
+                data.extend_from_slice(&(item.len() as u16).to_be_bytes());
+                data.extend_from_slice(item.as_bytes());
+            }
+            (0x01, data)
+        }
+        SDOption::LoadBalancing(opt) => {
+            let mut data = vec![opt.strategy];
+            data.extend_from_slice(&opt.priority.to_be_bytes());
+            data.extend_from_slice(&opt.weight.to_be_bytes());
+            (0x02, data)
+        }
+        SDOption::Ipv4Endpoint(opt) => (
+            0x04,
+            encode_endpoint(&opt.ip_address, &opt.transport_protocol, opt.port),
+        ),
+        SDOption::Ipv6Endpoint(opt) => (
+            0x06,
+            encode_endpoint(&opt.ip_address, &opt.transport_protocol, opt.port),
+        ),
+        SDOption::Ipv4Multicast(opt) => (
+            0x14,
+            encode_endpoint(&opt.ip_address, &opt.transport_protocol, opt.port),
+        ),
+        SDOption::Ipv6Multicast(opt) => (
+            0x16,
+            encode_endpoint(&opt.ip_address, &opt.transport_protocol, opt.port),
+        ),
+        SDOption::Ipv4SDEndpoint(opt) => (
+            0x24,
+            encode_endpoint(&opt.ip_address, &opt.transport_protocol, opt.port),
+        ),
+        SDOption::Ipv6SDEndpoint(opt) => (
+            0x26,
+            encode_endpoint(&opt.ip_address, &opt.transport_protocol, opt.port),
+        ),
+        SDOption::Unknown { option_type, data } => (*option_type, data.clone()),
+    };
+
+    // 选项长度包含 4 字节选项头（长度、类型、保留字段）
+    let mut bytes = Vec::with_capacity(4 + data.len());
+    bytes.extend_from_slice(&(data.len() as u16 + 4).to_be_bytes());
+    bytes.push(option_type);
+    bytes.push(0); // 保留字段
+    bytes.extend_from_slice(&data);
+    bytes
+}
+
+fn encode_endpoint(ip_address: &[u8], protocol: &TransportProtocol, port: u16) -> Vec<u8> {
+    let mut data = ip_address.to_vec();
+    data.push(match protocol {
+        TransportProtocol::TCP => 0x06,
+        TransportProtocol::UDP => 0x11,
+        TransportProtocol::Unknown(value) => *value,
+    });
+    data.extend_from_slice(&port.to_be_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::someip::header::parse_someip_header;
+    use crate::testing::sd::{offer, sd_packet, subscribe_ack, udp_endpoint};
+
+    /// 编码包含一个 OfferService 条目的 SD 消息，返回 SD 主体与头部
+    fn encoded() -> (Vec<u8>, SomeIPHeader) {
+        let bytes = sd_packet(
+            1,
+            true,
+            true,
+            vec![offer(0x1234, 1, 3, 0)],
+            vec![udp_endpoint([10, 0, 0, 1], 30501)],
+        )
+        .to_bytes();
+        let (payload, header) = parse_someip_header(&bytes).unwrap();
+        (payload.to_vec(), header)
+    }
+
+    fn set_entries_length(body: &mut [u8], length: u32) {
+        body[4..8].copy_from_slice(&length.to_be_bytes());
+    }
+
+    #[test]
+    fn decodes_encoded_packet() {
+        let (body, header) = encoded();
+        let packet = decode_sd_packet(&body, header).unwrap();
+        assert_eq!(packet.entries.len(), 1);
+        assert_eq!(packet.options.len(), 1);
+    }
+
+    #[test]
+    fn rejects_entries_length_not_multiple_of_16() {
+        let (mut body, header) = encoded();
+        set_entries_length(&mut body, 15);
+        let error = decode_sd_packet(&body, header).unwrap_err().to_string();
+        assert!(
+            error.contains("SD entries length 15 is not a multiple of 16"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_entries_length() {
+        let (mut body, header) = encoded();
+        let remaining = body.len() - 8;
+        set_entries_length(&mut body, 64);
+        let error = decode_sd_packet(&body, header).unwrap_err().to_string();
+        assert!(
+            error.contains(&format!(
+                "SD entries length 64 exceeds remaining {} bytes",
+                remaining
+            )),
+            "{}",
+            error
+        );
+    }
+
+    /// 编码后再解析，头部 length 字段以编码结果为准
+    fn round_trip(packet: &SDPacket) -> (SDPacket, SDPacket) {
+        let bytes = packet.to_bytes();
+        let (payload, header) = parse_someip_header(&bytes).unwrap();
+        let mut expected = packet.clone();
+        expected.header.length = bytes.len() as u32 - 8;
+        (decode_sd_packet(payload, header).unwrap(), expected)
+    }
+
+    #[test]
+    fn offer_with_ipv4_endpoint_round_trip() {
+        let packet = sd_packet(
+            7,
+            true,
+            false,
+            vec![offer(0x1234, 1, 3, 0)],
+            vec![udp_endpoint([192, 168, 1, 10], 30501)],
+        );
+        let bytes = packet.to_bytes();
+        // 标志字节与 3 字节保留字段，之后是条目数组长度
+        assert_eq!(bytes[16..24], [0x80, 0, 0, 0, 0, 0, 0, 16]);
+        let (decoded, expected) = round_trip(&packet);
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn subscribe_ack_with_return_code_round_trip() {
+        let mut packet = sd_packet(
+            8,
+            false,
+            true,
+            vec![subscribe_ack(0x1234, 1, 0x10, 0)],
+            Vec::new(),
+        );
+        packet.header.return_code = ReturnCode::NotOk;
+        let (decoded, expected) = round_trip(&packet);
+        assert_eq!(decoded, expected);
+        assert_eq!(decoded.header.return_code, ReturnCode::NotOk);
+    }
+
+    /// 按 2 字节长度前缀拼接配置项
+    fn configuration_data(items: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for item in items {
+            data.extend_from_slice(&(item.len() as u16).to_be_bytes());
+            data.extend_from_slice(item.as_bytes());
+        }
+        data
+    }
+
+    fn configuration_items(data: &[u8]) -> Vec<(String, Option<String>)> {
+        match parse_configuration_option(data).unwrap().1 {
+            SDOption::Configuration(option) => option.items,
+            other => panic!("expected configuration option, got {:?}", other),
+        }
+    }
+
+    fn item(key: &str, value: Option<&str>) -> (String, Option<String>) {
+        (key.to_string(), value.map(str::to_string))
+    }
+
+    #[test]
+    fn configuration_items_split_on_first_equals() {
+        let items = configuration_items(&configuration_data(&["hostname=ecu1", "path=/a=b"]));
+        assert_eq!(
+            items,
+            [item("hostname", Some("ecu1")), item("path", Some("/a=b"))]
+        );
+    }
+
+    #[test]
+    fn configuration_key_without_equals_has_no_value() {
+        let items = configuration_items(&configuration_data(&["keyonly", "empty="]));
+        assert_eq!(items, [item("keyonly", None), item("empty", Some(""))]);
+    }
+
+    #[test]
+    fn empty_configuration_item_ends_the_list() {
+        // 空项之后的内容不再按配置项解析
+        let items = configuration_items(&configuration_data(&["a=1", "", "b=2"]));
+        assert_eq!(items, [item("a", Some("1"))]);
+
+        // 末尾的结束项不产生配置项，原始字符串保持不变
+        let data = configuration_data(&["a=1", "flag", ""]);
+        let (rest, option) = parse_configuration_option(&data).unwrap();
+        assert!(rest.is_empty());
+        let SDOption::Configuration(option) = option else {
+            panic!("expected configuration option");
+        };
+        assert_eq!(option.raw_items(), ["a=1", "flag"]);
+    }
+
+    #[test]
+    fn truncated_configuration_item_is_an_error() {
+        let mut data = configuration_data(&["hostname=ecu1"]);
+        data.truncate(data.len() - 1);
+        assert!(parse_configuration_option(&data).is_err());
+    }
+
+    /// OfferService 条目：第一组选项从 `first` 开始，第二组选项从 `second` 开始
+    fn offer_with_runs(first: (u8, u8), second: (u8, u8)) -> SDEntry {
+        let SDEntry::OfferService(mut entry) = offer(0x1234, 1, 3, 0) else {
+            unreachable!();
+        };
+        (entry.first_options_index, entry.number_of_first_options) = first;
+        (entry.second_options_index, entry.number_of_second_options) = second;
+        SDEntry::OfferService(entry)
+    }
+
+    #[test]
+    fn resolves_first_and_second_option_runs() {
+        let options = vec![
+            udp_endpoint([10, 0, 0, 1], 30501),
+            udp_endpoint([10, 0, 0, 1], 30502),
+            udp_endpoint([10, 0, 0, 1], 30503),
+        ];
+        let packet = sd_packet(1, false, true, Vec::new(), options.clone());
+
+        let both = offer_with_runs((0, 2), (2, 1));
+        let (first, second) = packet.resolve_options(&both);
+        assert_eq!(first, [&options[0], &options[1]]);
+        assert_eq!(second, [&options[2]]);
+        assert_eq!(
+            packet.entry_options(&both),
+            [&options[0], &options[1], &options[2]]
+        );
+
+        // 两组选项可以引用同一个选项
+        let (first, second) = packet.resolve_options(&offer_with_runs((1, 1), (1, 1)));
+        assert_eq!((first, second), (vec![&options[1]], vec![&options[1]]));
+    }
+
+    #[test]
+    fn out_of_range_option_references_are_truncated() {
+        let options = vec![
+            udp_endpoint([10, 0, 0, 1], 30501),
+            udp_endpoint([10, 0, 0, 1], 30502),
+        ];
+        let packet = sd_packet(1, false, true, Vec::new(), options.clone());
+
+        // 第一组越过末尾只保留存在的选项，第二组完全越界则为空
+        let (first, second) = packet.resolve_options(&offer_with_runs((1, 3), (5, 2)));
+        assert_eq!(first, [&options[1]]);
+        assert!(second.is_empty());
+
+        let (first, second) = packet.resolve_options(&offer_with_runs((255, 255), (0, 0)));
+        assert!(first.is_empty() && second.is_empty());
+    }
+}
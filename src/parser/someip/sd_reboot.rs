@@ -0,0 +1,164 @@
+// src/parser/someip/sd_reboot.rs
+use super::sd_parser::SDPacket;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::SystemTime;
+
+/// 检测到的 ECU 重启事件
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RebootDetected {
+    pub timestamp: SystemTime,
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub previous_session_id: u16,
+    pub session_id: u16,
+}
+
+/// SD 会话 ID 的计数范围：多播与发往每个对端的单播各自独立计数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SdChannel {
+    Multicast,
+    Unicast(IpAddr),
+}
+
+impl SdChannel {
+    fn of(dst_ip: IpAddr) -> Self {
+        let multicast = match dst_ip {
+            IpAddr::V4(ip) => ip.is_multicast() || ip.is_broadcast(),
+            IpAddr::V6(ip) => ip.is_multicast(),
+        };
+        if multicast {
+            SdChannel::Multicast
+        } else {
+            SdChannel::Unicast(dst_ip)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SdSessionState {
+    session_id: u16,
+    reboot: bool,
+}
+
+/// 按发送端端点跟踪 SD 会话 ID 与重启标志，检测 ECU 重启
+///
+/// 多播与单播 SD 报文的会话 ID 分别计数，因此按 (发送端, 多播/单播目的地址) 分别跟踪。
+///
+/// 满足以下任一条件即判定为重启：
+/// - 重启标志由 0 变为 1
+/// - 重启标志保持为 1，但会话 ID 没有递增
+pub struct RebootTracker {
+    endpoints: HashMap<(IpAddr, u16, SdChannel), SdSessionState>,
+}
+
+impl Default for RebootTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RebootTracker {
+    pub fn new() -> Self {
+        Self {
+            endpoints: HashMap::new(),
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        timestamp: SystemTime,
+        src_ip: IpAddr,
+        src_port: u16,
+        dst_ip: IpAddr,
+        sd_packet: &SDPacket,
+    ) -> Option<RebootDetected> {
+        let current = SdSessionState {
+            session_id: sd_packet.header.session_id,
+            reboot: sd_packet.flags.reboot,
+        };
+        let previous = self
+            .endpoints
+            .insert((src_ip, src_port, SdChannel::of(dst_ip)), current)?;
+
+        let rebooted =
+            current.reboot && (!previous.reboot || previous.session_id >= current.session_id);
+        rebooted.then_some(RebootDetected {
+            timestamp,
+            src_ip,
+            src_port,
+            previous_session_id: previous.session_id,
+            session_id: current.session_id,
+        })
+    }
+}
+
+pub fn format_sd_reboots(events: &[RebootDetected]) -> String {
+    let mut output = String::from("SD reboot report\n");
+    for event in events {
+        let timestamp = event
+            .timestamp
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| format!("{:.6}", d.as_secs_f64()))
+            .unwrap_or_else(|_| "-".to_string());
+        output.push_str(&format!(
+            "[{}] Reboot detected: {}:{} session 0x{:04X} -> 0x{:04X}\n",
+            timestamp, event.src_ip, event.src_port, event.previous_session_id, event.session_id
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::sd::sd_packet;
+    use std::time::UNIX_EPOCH;
+
+    const ECU: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+    const MULTICAST: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(224, 224, 224, 245));
+    const PEER: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2));
+
+    fn record(tracker: &mut RebootTracker, dst_ip: IpAddr, session_id: u16) -> bool {
+        let packet = sd_packet(session_id, true, true, Vec::new(), Vec::new());
+        tracker
+            .record(UNIX_EPOCH, ECU, 30490, dst_ip, &packet)
+            .is_some()
+    }
+
+    #[test]
+    fn interleaved_multicast_and_unicast_is_not_a_reboot() {
+        let mut tracker = RebootTracker::new();
+        let sequence = [
+            (MULTICAST, 1),
+            (PEER, 1),
+            (MULTICAST, 2),
+            (PEER, 2),
+            (MULTICAST, 3),
+            (PEER, 3),
+        ];
+        for (dst_ip, session_id) in sequence {
+            assert!(!record(&mut tracker, dst_ip, session_id));
+        }
+    }
+
+    #[test]
+    fn detects_reboot_per_channel() {
+        let mut tracker = RebootTracker::new();
+        assert!(!record(&mut tracker, MULTICAST, 5));
+        assert!(!record(&mut tracker, PEER, 1));
+        // 多播会话 ID 回退到 1：重启
+        assert!(record(&mut tracker, MULTICAST, 1));
+
+        // 重启标志由 0 变为 1
+        let mut tracker = RebootTracker::new();
+        let cleared = sd_packet(7, false, true, Vec::new(), Vec::new());
+        assert!(
+            tracker
+                .record(UNIX_EPOCH, ECU, 30490, PEER, &cleared)
+                .is_none()
+        );
+        assert!(record(&mut tracker, PEER, 8));
+    }
+}
@@ -0,0 +1,593 @@
+// src/parser/someip/sd_subscription.rs
+use super::header::ReturnCode;
+use super::matrix::Matrix;
+use super::sd_parser::{SDEntry, SDOption, SDPacket};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, SystemTime};
+
+/// TTL 为 0xFFFFFF 表示订阅一直有效
+const TTL_INFINITE: u32 = 0xFF_FFFF;
+
+/// (服务ID, 实例ID, 事件组ID)
+pub type SubscriptionKey = (u16, u16, u16);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub subscriber_ip: IpAddr,
+    pub subscriber_port: u16,
+    pub ttl: u32,
+    pub start_time: SystemTime,
+    pub last_renewed: SystemTime,
+    pub ack_received: bool,
+    /// 订阅方 SD 报文的 unicast 标志：置位时应答以单播发回订阅方，否则应答可能以组播发送
+    pub unicast: bool,
+}
+
+/// 通知消息所属的事件组订阅
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubscriptionRef {
+    pub eventgroup_id: u16,
+    pub subscriber_ip: IpAddr,
+    pub subscriber_port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionEvent {
+    Subscribe {
+        timestamp: SystemTime,
+        key: SubscriptionKey,
+    },
+    Ack {
+        timestamp: SystemTime,
+        key: SubscriptionKey,
+    },
+    /// 订阅被拒绝（TTL 为 0 的 SubscribeEventgroupAck，或 SD 消息返回码不为 E_OK）
+    Nack {
+        timestamp: SystemTime,
+        key: SubscriptionKey,
+    },
+    Stop {
+        timestamp: SystemTime,
+        key: SubscriptionKey,
+    },
+    Expire {
+        timestamp: SystemTime,
+        key: SubscriptionKey,
+    },
+}
+
+/// 一次 SubscribeEventgroup 与应答它的 SubscribeEventgroupAck 的配对结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionAck {
+    pub key: SubscriptionKey,
+    /// 发送订阅的 SD 端点地址
+    pub subscriber_ip: IpAddr,
+    /// 应答订阅的 SD 端点地址
+    pub server_ip: IpAddr,
+    pub subscribe_time: SystemTime,
+    pub ack_time: SystemTime,
+    /// 从订阅到应答的时间
+    pub latency: Duration,
+    /// 应答条目的 TTL，为 0 表示否定应答
+    pub ttl: u32,
+    /// SD 消息的返回码
+    pub return_code: ReturnCode,
+    pub rejected: bool,
+}
+
+/// 跟踪事件组订阅的生命周期：订阅 -> 确认 -> 停止/过期
+pub struct SubscriptionTracker {
+    /// (订阅键, 订阅方 SD 地址) -> 订阅，同一事件组的多个订阅方分别跟踪
+    subscriptions: BTreeMap<(SubscriptionKey, IpAddr), Subscription>,
+    events: Vec<SubscriptionEvent>,
+    /// 尚未应答的订阅：(订阅键, 订阅方地址, 服务方地址) -> (首次未应答订阅的时间, 订阅方 unicast 标志)
+    pending_acks: HashMap<(SubscriptionKey, IpAddr, IpAddr), (SystemTime, bool)>,
+    acks: Vec<SubscriptionAck>,
+}
+
+impl Default for SubscriptionTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubscriptionTracker {
+    pub fn new() -> Self {
+        Self {
+            subscriptions: BTreeMap::new(),
+            events: Vec::new(),
+            pending_acks: HashMap::new(),
+            acks: Vec::new(),
+        }
+    }
+
+    /// 记录一个 SD 数据包，返回其中被拒绝的订阅应答
+    pub fn record(
+        &mut self,
+        timestamp: SystemTime,
+        src_ip: IpAddr,
+        src_port: u16,
+        dst_ip: IpAddr,
+        sd_packet: &SDPacket,
+    ) -> Vec<SubscriptionAck> {
+        self.expire(timestamp);
+        let mut rejections = Vec::new();
+        let unicast = sd_packet.flags.unicast;
+
+        for entry in &sd_packet.entries {
+            match entry {
+                SDEntry::SubscribeEventgroup(sub) => {
+                    let key = (sub.service_id, sub.instance_id, sub.eventgroup_id);
+                    if entry.is_stop() {
+                        self.pending_acks.remove(&(key, src_ip, dst_ip));
+                        if self.subscriptions.remove(&(key, src_ip)).is_some() {
+                            self.events.push(SubscriptionEvent::Stop { timestamp, key });
+                        }
+                        continue;
+                    }
+
+                    // 订阅者端点取自条目引用的端点选项，缺省时使用数据包源地址
+                    let (subscriber_ip, subscriber_port) =
+                        subscriber_endpoint(sd_packet, entry).unwrap_or((src_ip, src_port));
+                    // 续订未应答时保留首次订阅时间，确认延迟从最早的未应答订阅算起；
+                    // unicast 标志取最近一次订阅的值，应答按它决定以单播还是组播配对
+                    self.pending_acks
+                        .entry((key, src_ip, dst_ip))
+                        .and_modify(|(_, pending_unicast)| *pending_unicast = unicast)
+                        .or_insert((timestamp, unicast));
+                    match self.subscriptions.get_mut(&(key, src_ip)) {
+                        Some(existing) => {
+                            // 续订：刷新 TTL，保留首次订阅时间与确认状态
+                            existing.subscriber_ip = subscriber_ip;
+                            existing.subscriber_port = subscriber_port;
+                            existing.ttl = sub.ttl;
+                            existing.last_renewed = timestamp;
+                            existing.unicast = unicast;
+                        }
+                        None => {
+                            self.subscriptions.insert(
+                                (key, src_ip),
+                                Subscription {
+                                    subscriber_ip,
+                                    subscriber_port,
+                                    ttl: sub.ttl,
+                                    start_time: timestamp,
+                                    last_renewed: timestamp,
+                                    ack_received: false,
+                                    unicast,
+                                },
+                            );
+                            self.events
+                                .push(SubscriptionEvent::Subscribe { timestamp, key });
+                        }
+                    }
+                }
+                SDEntry::SubscribeEventgroupAck(ack) => {
+                    let key = (ack.service_id, ack.instance_id, ack.eventgroup_id);
+                    let return_code = sd_packet.header.return_code.clone();
+                    let rejected = ack.ttl == 0 || return_code != ReturnCode::Ok;
+
+                    // 应答由服务方发给订阅方，与反方向的订阅配对；订阅方不支持单播时
+                    // 应答以组播发送，与该服务方所有不支持单播的订阅方的订阅配对
+                    let subscribers: Vec<IpAddr> = if dst_ip.is_multicast() {
+                        self.pending_acks
+                            .iter()
+                            .filter(|&(&(pending_key, _, server_ip), &(_, unicast))| {
+                                pending_key == key && server_ip == src_ip && !unicast
+                            })
+                            .map(|(&(_, subscriber_ip, _), _)| subscriber_ip)
+                            .collect()
+                    } else {
+                        vec![dst_ip]
+                    };
+                    for subscriber_ip in subscribers {
+                        // 应答只作用于该订阅方的订阅，否定应答不影响同一事件组的其他订阅方
+                        if rejected {
+                            if self.subscriptions.remove(&(key, subscriber_ip)).is_some() {
+                                self.events.push(SubscriptionEvent::Nack { timestamp, key });
+                            }
+                        } else if let Some(subscription) =
+                            self.subscriptions.get_mut(&(key, subscriber_ip))
+                            && !subscription.ack_received
+                        {
+                            subscription.ack_received = true;
+                            self.events.push(SubscriptionEvent::Ack { timestamp, key });
+                        }
+
+                        let Some((subscribe_time, _)) =
+                            self.pending_acks.remove(&(key, subscriber_ip, src_ip))
+                        else {
+                            continue;
+                        };
+                        let correlation = SubscriptionAck {
+                            key,
+                            subscriber_ip,
+                            server_ip: src_ip,
+                            subscribe_time,
+                            ack_time: timestamp,
+                            latency: timestamp.duration_since(subscribe_time).unwrap_or_default(),
+                            ttl: ack.ttl,
+                            return_code: return_code.clone(),
+                            rejected,
+                        };
+                        if rejected {
+                            rejections.push(correlation.clone());
+                        }
+                        self.acks.push(correlation);
+                    }
+                }
+                _ => {}
+            }
+        }
+        rejections
+    }
+
+    /// 移除 TTL 已到期的订阅
+    pub fn expire(&mut self, now: SystemTime) {
+        let expired: Vec<(SubscriptionKey, IpAddr)> = self
+            .subscriptions
+            .iter()
+            .filter(|(_, sub)| sub.is_expired(now))
+            .map(|(key, _)| *key)
+            .collect();
+
+        for (key, subscriber_ip) in expired {
+            self.subscriptions.remove(&(key, subscriber_ip));
+            self.events.push(SubscriptionEvent::Expire {
+                timestamp: now,
+                key,
+            });
+        }
+    }
+
+    pub fn active_subscriptions(&self) -> impl Iterator<Item = (&SubscriptionKey, &Subscription)> {
+        self.subscriptions.iter().map(|((key, _), sub)| (key, sub))
+    }
+
+    pub fn subscription_events(&self) -> &[SubscriptionEvent] {
+        &self.events
+    }
+
+    /// 已与订阅配对的全部应答（含拒绝）
+    pub fn ack_correlations(&self) -> &[SubscriptionAck] {
+        &self.acks
+    }
+
+    /// 被拒绝的订阅
+    pub fn rejected_subscriptions(&self) -> impl Iterator<Item = &SubscriptionAck> {
+        self.acks.iter().filter(|ack| ack.rejected)
+    }
+
+    /// 查找通知消息对应的已确认订阅
+    ///
+    /// 优先匹配订阅者端点与通知目的地址一致的订阅；目的地址不匹配任何订阅者
+    /// （例如组播发送）时，仅当该服务只有一个有效订阅才将其归属于该订阅。
+    pub fn resolve_notification(
+        &self,
+        timestamp: SystemTime,
+        service_id: u16,
+        instance_id: Option<u16>,
+        dst_ip: IpAddr,
+        dst_port: u16,
+    ) -> Option<SubscriptionRef> {
+        let candidates: Vec<(&SubscriptionKey, &Subscription)> = self
+            .active_subscriptions()
+            .filter(|((svc, inst, _), sub)| {
+                *svc == service_id
+                    && instance_id.is_none_or(|id| id == *inst)
+                    && sub.ack_received
+                    && !sub.is_expired(timestamp)
+            })
+            .collect();
+
+        let &(&(_, _, eventgroup_id), sub) = candidates
+            .iter()
+            .find(|(_, sub)| sub.subscriber_ip == dst_ip && sub.subscriber_port == dst_port)
+            .or(match candidates.as_slice() {
+                [only] => Some(only),
+                _ => None,
+            })?;
+        Some(SubscriptionRef {
+            eventgroup_id,
+            subscriber_ip: sub.subscriber_ip,
+            subscriber_port: sub.subscriber_port,
+        })
+    }
+}
+
+impl Subscription {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.ttl != TTL_INFINITE
+            && now
+                .duration_since(self.last_renewed)
+                .is_ok_and(|elapsed| elapsed > Duration::from_secs(self.ttl as u64))
+    }
+}
+
+fn subscriber_endpoint(sd_packet: &SDPacket, entry: &SDEntry) -> Option<(IpAddr, u16)> {
+    sd_packet
+        .entry_options(entry)
+        .into_iter()
+        .find_map(|option| match option {
+            SDOption::Ipv4Endpoint(opt) => Some((Ipv4Addr::from(opt.ip_address).into(), opt.port)),
+            SDOption::Ipv6Endpoint(opt) => Some((Ipv6Addr::from(opt.ip_address).into(), opt.port)),
+            _ => None,
+        })
+}
+
+fn service_name(matrix: &Matrix, service_id: u16) -> String {
+    matrix
+        .get_service_name(service_id)
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("0x{:04X}", service_id))
+}
+
+/// 单行描述一次被拒绝的订阅
+pub fn format_rejected_subscription(ack: &SubscriptionAck, matrix: &Matrix) -> String {
+    let (service_id, instance_id, eventgroup_id) = ack.key;
+    format!(
+        "Rejected: {}/0x{:04X} eventgroup 0x{:04X} {} -> {} ({}, ttl {}) after {:.3} ms",
+        service_name(matrix, service_id),
+        instance_id,
+        eventgroup_id,
+        ack.subscriber_ip,
+        ack.server_ip,
+        ack.return_code,
+        ack.ttl,
+        ack.latency.as_secs_f64() * 1000.0
+    )
+}
+
+pub fn format_subscription_summary(tracker: &SubscriptionTracker, matrix: &Matrix) -> String {
+    let mut counts = [0usize; 5];
+    for event in tracker.subscription_events() {
+        let index = match event {
+            SubscriptionEvent::Subscribe { .. } => 0,
+            SubscriptionEvent::Ack { .. } => 1,
+            SubscriptionEvent::Nack { .. } => 2,
+            SubscriptionEvent::Stop { .. } => 3,
+            SubscriptionEvent::Expire { .. } => 4,
+        };
+        counts[index] += 1;
+    }
+
+    let mut output = format!(
+        "Subscription summary: {} subscribed, {} acknowledged, {} rejected, {} stopped, {} expired\n",
+        counts[0], counts[1], counts[2], counts[3], counts[4]
+    );
+
+    let latencies: Vec<f64> = tracker
+        .ack_correlations()
+        .iter()
+        .map(|ack| ack.latency.as_secs_f64() * 1000.0)
+        .collect();
+    if !latencies.is_empty() {
+        output.push_str(&format!(
+            "Ack latency: {} acks, avg {:.3} ms, max {:.3} ms\n",
+            latencies.len(),
+            latencies.iter().sum::<f64>() / latencies.len() as f64,
+            latencies.iter().cloned().fold(0.0, f64::max)
+        ));
+    }
+    for ack in tracker.rejected_subscriptions() {
+        output.push_str(&format_rejected_subscription(ack, matrix));
+        output.push('\n');
+    }
+
+    for (&(service_id, instance_id, eventgroup_id), sub) in tracker.active_subscriptions() {
+        output.push_str(&format!(
+            "Active: {}/0x{:04X} eventgroup 0x{:04X} <- {}:{} ttl {}{}{}\n",
+            service_name(matrix, service_id),
+            instance_id,
+            eventgroup_id,
+            sub.subscriber_ip,
+            sub.subscriber_port,
+            sub.ttl,
+            if sub.unicast { "" } else { " multicast-only" },
+            if sub.ack_received {
+                ""
+            } else {
+                " (not acknowledged)"
+            }
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::someip::header::parse_someip_header;
+    use crate::parser::someip::sd_parser::decode_sd_packet;
+    use crate::testing::sd::{sd_packet, subscribe, subscribe_ack, udp_endpoint};
+    use std::time::UNIX_EPOCH;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+    const MULTICAST: IpAddr = IpAddr::V4(Ipv4Addr::new(239, 0, 0, 1));
+
+    fn at(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn subscribe_packet(session_id: u16, unicast: bool) -> SDPacket {
+        sd_packet(
+            session_id,
+            false,
+            unicast,
+            vec![subscribe(0x1234, 1, 0x10, 3, 0)],
+            vec![udp_endpoint([10, 0, 0, 1], 40000)],
+        )
+    }
+
+    fn ack_packet(session_id: u16) -> SDPacket {
+        sd_packet(
+            session_id,
+            false,
+            true,
+            vec![subscribe_ack(0x1234, 1, 0x10, 3)],
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn subscribe_ack_stop_lifecycle() {
+        let key = (0x1234, 1, 0x10);
+        let mut tracker = SubscriptionTracker::new();
+        tracker.record(at(0), CLIENT, 30490, SERVER, &subscribe_packet(1, true));
+        let (_, subscription) = tracker.active_subscriptions().next().unwrap();
+        assert!(!subscription.ack_received);
+        assert_eq!(
+            (subscription.subscriber_ip, subscription.subscriber_port),
+            (CLIENT, 40000)
+        );
+
+        tracker.record(at(20), SERVER, 30490, CLIENT, &ack_packet(1));
+        let (_, subscription) = tracker.active_subscriptions().next().unwrap();
+        assert!(subscription.ack_received);
+        assert_eq!(subscription.start_time, at(0));
+
+        let stop = sd_packet(
+            2,
+            false,
+            true,
+            vec![subscribe(0x1234, 1, 0x10, 0, 0)],
+            vec![udp_endpoint([10, 0, 0, 1], 40000)],
+        );
+        tracker.record(at(40), CLIENT, 30490, SERVER, &stop);
+        assert_eq!(tracker.active_subscriptions().count(), 0);
+        assert_eq!(
+            tracker.subscription_events(),
+            [
+                SubscriptionEvent::Subscribe {
+                    timestamp: at(0),
+                    key
+                },
+                SubscriptionEvent::Ack {
+                    timestamp: at(20),
+                    key
+                },
+                SubscriptionEvent::Stop {
+                    timestamp: at(40),
+                    key
+                },
+            ]
+        );
+        // 停止之后的通知不再归属该订阅
+        assert_eq!(
+            tracker.resolve_notification(at(50), 0x1234, Some(1), CLIENT, 40000),
+            None
+        );
+    }
+
+    #[test]
+    fn unicast_flag_is_decoded_and_surfaced() {
+        for unicast in [true, false] {
+            let bytes = subscribe_packet(1, unicast).to_bytes();
+            let (payload, header) = parse_someip_header(&bytes).unwrap();
+            let decoded = decode_sd_packet(payload, header).unwrap();
+            assert_eq!(decoded.flags.unicast, unicast);
+
+            let mut tracker = SubscriptionTracker::new();
+            tracker.record(at(0), CLIENT, 30490, SERVER, &decoded);
+            let (_, subscription) = tracker.active_subscriptions().next().unwrap();
+            assert_eq!(subscription.unicast, unicast);
+            let summary = format_subscription_summary(&tracker, &Matrix::new());
+            assert_eq!(summary.contains(" multicast-only"), !unicast);
+        }
+    }
+
+    #[test]
+    fn renewal_updates_unicast_flag_for_ack_pairing() {
+        let mut tracker = SubscriptionTracker::new();
+        tracker.record(at(0), CLIENT, 30490, SERVER, &subscribe_packet(1, true));
+        // 续订时订阅方不再支持单播，服务方以组播应答
+        tracker.record(at(100), CLIENT, 30490, SERVER, &subscribe_packet(2, false));
+        tracker.record(at(150), SERVER, 30490, MULTICAST, &ack_packet(1));
+
+        let acks = tracker.ack_correlations();
+        assert_eq!(acks.len(), 1);
+        assert_eq!(acks[0].subscriber_ip, CLIENT);
+        // 延迟从首次未应答的订阅算起
+        assert_eq!(acks[0].latency, Duration::from_millis(150));
+    }
+
+    #[test]
+    fn multicast_ack_ignores_unicast_subscribers() {
+        let mut tracker = SubscriptionTracker::new();
+        tracker.record(at(0), CLIENT, 30490, SERVER, &subscribe_packet(1, true));
+        tracker.record(at(50), SERVER, 30490, MULTICAST, &ack_packet(1));
+        assert!(tracker.ack_correlations().is_empty());
+        tracker.record(at(60), SERVER, 30490, CLIENT, &ack_packet(2));
+        assert_eq!(tracker.ack_correlations().len(), 1);
+    }
+
+    #[test]
+    fn nack_removes_only_that_subscribers_subscription() {
+        const OTHER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+        let mut tracker = SubscriptionTracker::new();
+        tracker.record(at(0), CLIENT, 30490, SERVER, &subscribe_packet(1, true));
+        tracker.record(at(10), OTHER, 30490, SERVER, &subscribe_packet(1, true));
+        tracker.record(at(20), SERVER, 30490, CLIENT, &ack_packet(1));
+        let mut nack = ack_packet(2);
+        let SDEntry::SubscribeEventgroupAck(entry) = &mut nack.entries[0] else {
+            unreachable!()
+        };
+        entry.ttl = 0;
+        let rejections = tracker.record(at(30), SERVER, 30490, OTHER, &nack);
+
+        assert_eq!(rejections.len(), 1);
+        assert_eq!(rejections[0].subscriber_ip, OTHER);
+        let active: Vec<_> = tracker.active_subscriptions().collect();
+        assert_eq!(active.len(), 1);
+        assert!(active[0].1.ack_received);
+        assert!(matches!(
+            tracker.subscription_events().last(),
+            Some(SubscriptionEvent::Nack { .. })
+        ));
+    }
+
+    #[test]
+    fn acknowledged_subscription_attributes_notifications() {
+        let mut tracker = SubscriptionTracker::new();
+        tracker.record(at(0), CLIENT, 30490, SERVER, &subscribe_packet(1, true));
+        // 应答之前的通知不归属任何订阅
+        assert_eq!(
+            tracker.resolve_notification(at(10), 0x1234, Some(1), CLIENT, 40000),
+            None
+        );
+
+        tracker.record(at(20), SERVER, 30490, CLIENT, &ack_packet(1));
+        let expected = SubscriptionRef {
+            eventgroup_id: 0x10,
+            subscriber_ip: CLIENT,
+            subscriber_port: 40000,
+        };
+        for millis in [30, 40, 50] {
+            assert_eq!(
+                tracker.resolve_notification(at(millis), 0x1234, Some(1), CLIENT, 40000),
+                Some(expected.clone())
+            );
+        }
+        // 唯一的订阅也接收组播发送的通知；其他服务与实例不匹配
+        assert_eq!(
+            tracker.resolve_notification(at(60), 0x1234, None, MULTICAST, 40000),
+            Some(expected)
+        );
+        assert_eq!(
+            tracker.resolve_notification(at(60), 0x1234, Some(2), CLIENT, 40000),
+            None
+        );
+        assert_eq!(
+            tracker.resolve_notification(at(60), 0x5678, Some(1), CLIENT, 40000),
+            None
+        );
+        // TTL 为 3 秒，过期后不再归属
+        assert_eq!(
+            tracker.resolve_notification(at(3_100), 0x1234, Some(1), CLIENT, 40000),
+            None
+        );
+    }
+}
@@ -1,79 +1,149 @@
 // src/parser/someip/session.rs
+use super::e2e::E2EStatus;
 use super::header::*;
+use super::sd_subscription::SubscriptionRef;
 use crate::error::{Result, SomeIPError};
-use std::collections::{HashMap, VecDeque};
-use std::net::IpAddr;
-use std::time::{Duration, Instant, SystemTime};
+use crate::parser::tunnel::TunnelInfo;
+use crate::utils::timestamp::format_timestamp;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RequestResponsePair {
     pub request: SomeIPMessage,
     pub response: Option<SomeIPMessage>,
-    pub timeout: Instant,
+    pub timeout: SystemTime, // 按抓包时间计算的超时时刻
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SomeIPMessage {
     pub timestamp: SystemTime,
     pub header: SomeIPHeader,
+    #[serde(with = "hex::serde")]
     pub payload: Vec<u8>,
     pub src_ip: IpAddr,
     pub dst_ip: IpAddr,
     pub src_port: u16,
     pub dst_port: u16,
+    pub vlan_id: Option<u16>,
+    pub tunnel: Option<TunnelInfo>,
+    /// 根据 SD 提供的端口推断出的服务实例 ID
+    pub instance_id: Option<u16>,
+    /// 响应相对于对应请求的往返时间（毫秒），仅已配对的响应消息有值
+    pub latency_ms: Option<f64>,
+    /// 通知消息所属的事件组订阅，无法从 SD 订阅表中确定时为空
+    pub subscription: Option<SubscriptionRef>,
+    /// 开启 `--e2e-profile` 时负载起始处 E2E 头部的校验结果，头部已从 `payload` 中剥离
+    #[serde(default)]
+    pub e2e: Option<E2EStatus>,
+    /// TCP 字节流失去同步后重新扫描头部找到的第一条消息，之前可能有消息丢失
+    #[serde(default)]
+    pub resynced: bool,
 }
 
+/// 单行摘要，用于日志：时间、端点、头部摘要与负载长度
+impl fmt::Display for SomeIPMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} → {} {} payload={} bytes",
+            format_timestamp(&self.timestamp),
+            SocketAddr::new(self.src_ip, self.src_port),
+            SocketAddr::new(self.dst_ip, self.dst_port),
+            self.header,
+            self.payload.len()
+        )
+    }
+}
+
+/// 会话表已满时淘汰待响应请求的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvictionPolicy {
+    /// 淘汰最早加入的请求
+    #[default]
+    Fifo,
+    /// 淘汰最久未被访问的请求，同一会话（服务ID, 客户端ID, 会话ID）的请求再次出现时刷新
+    Lru,
+}
+
+#[derive(Debug, Clone)]
 pub struct SessionManager {
-    sessions: HashMap<(u16, u16, u16), RequestResponsePair>, // (服务ID, 客户端ID, 会话ID) -> 会话
+    /// 等待响应的请求，(服务ID, 客户端ID, 会话ID) -> 会话；
+    /// 淘汰顺序由链表维护：FIFO 下为加入顺序，LRU 下为访问顺序
+    sessions: LruCache<(u16, u16, u16), RequestResponsePair>,
     timeout: Duration,
     max_pairs: usize,
-    pending_responses: VecDeque<(u16, u16, u16)>, // 等待响应的请求
+    history: VecDeque<RequestResponsePair>, // 最近完成的请求/响应对，最旧的在前
+    history_size: usize,
+    eviction_policy: EvictionPolicy,
 }
 
 impl SessionManager {
-    pub fn new(timeout: Duration, max_pairs: usize) -> Self {
+    /// `history_size` 为保留的已完成请求/响应对数量，为 0 时不保留
+    pub fn new(timeout: Duration, max_pairs: usize, history_size: usize) -> Self {
+        Self {
+            history: VecDeque::with_capacity(history_size),
+            history_size,
+            ..Self::new_with_policy(timeout, max_pairs, EvictionPolicy::default())
+        }
+    }
+
+    /// 使用指定淘汰策略创建会话管理器，不保留已完成的请求/响应对
+    pub fn new_with_policy(timeout: Duration, max_pairs: usize, policy: EvictionPolicy) -> Self {
         Self {
-            sessions: HashMap::new(),
+            sessions: LruCache::unbounded(),
             timeout,
             max_pairs,
-            pending_responses: VecDeque::new(),
+            history: VecDeque::new(),
+            history_size: 0,
+            eviction_policy: policy,
         }
     }
 
-    pub fn add_request(&mut self, message: SomeIPMessage) -> Result<()> {
-        // 检查是否需要移除最旧的会话
-        if self.sessions.len() >= self.max_pairs {
-            // 移除最旧的待响应请求
-            while let Some(key) = self.pending_responses.pop_front() {
-                if self.sessions.contains_key(&key) {
-                    self.sessions.remove(&key);
-                    break;
-                }
-            }
-        }
+    /// 设置会话表已满时的淘汰策略（默认 FIFO）
+    pub fn set_eviction_policy(&mut self, policy: EvictionPolicy) {
+        self.eviction_policy = policy;
+    }
 
+    pub fn add_request(&mut self, message: SomeIPMessage) -> Result<()> {
         let key = (
             message.header.service_id,
             message.header.client_id,
             message.header.session_id,
         );
+        // 超时时刻以请求的抓包时间为基准
+        let timeout = message.timestamp + self.timeout;
+        let pair = RequestResponsePair {
+            request: message,
+            response: None,
+            timeout,
+        };
 
-        // 添加新会话
-        self.sessions.insert(
-            key,
-            RequestResponsePair {
-                request: message,
-                response: None,
-                timeout: Instant::now() + self.timeout,
-            },
-        );
+        // 同一会话的请求再次出现时覆盖原会话，不需要淘汰；
+        // FIFO 保持其原有位置，LRU 将其移到最近访问的一端
+        if let Some(existing) = self.sessions.peek_mut(&key) {
+            *existing = pair;
+            if self.eviction_policy == EvictionPolicy::Lru {
+                self.sessions.promote(&key);
+            }
+            return Ok(());
+        }
 
-        // 添加到待响应队列
-        self.pending_responses.push_back(key);
+        // 会话表已满时移除链表头部的请求：FIFO 为最早加入的，LRU 为最久未访问的
+        if self.sessions.len() >= self.max_pairs {
+            self.sessions.pop_lru();
+        }
+        self.sessions.put(key, pair);
 
         Ok(())
     }
 
+    /// 将响应与等待中的请求配对；请求已完成时（如重复或迟到的错误响应）与历史中的请求配对
     pub fn add_response(&mut self, message: SomeIPMessage) -> Result<Option<RequestResponsePair>> {
         let key = (
             message.header.service_id,
@@ -81,45 +151,94 @@ impl SessionManager {
             message.header.session_id,
         );
 
+        // 检查是否是响应消息类型
+        if !matches!(
+            message.header.message_type,
+            MessageType::Response | MessageType::Error
+        ) {
+            return Err(SomeIPError::InvalidPacketFormat(
+                "Expected response message type".to_string(),
+            )
+            .into());
+        }
+
         // 查找对应的请求
-        if let Some(pair) = self.sessions.get_mut(&key) {
-            // 检查是否是响应消息类型
-            match message.header.message_type {
-                MessageType::Response | MessageType::Error => {
-                    // 添加响应
-                    pair.response = Some(message);
-
-                    // 从待响应队列中移除
-                    if let Some(pos) = self.pending_responses.iter().position(|&k| k == key) {
-                        self.pending_responses.remove(pos);
-                    }
-
-                    return Ok(Some(pair.clone()));
-                }
-                _ => {
-                    return Err(SomeIPError::InvalidPacketFormat(
-                        "Expected response message type".to_string(),
-                    )
-                    .into());
-                }
+        let pair = if let Some(pair) = self.sessions.pop(&key) {
+            pair
+        } else if let Some(pair) = self.history.iter().rev().find(|pair| {
+            (
+                pair.request.header.service_id,
+                pair.request.header.client_id,
+                pair.request.header.session_id,
+            ) == key
+        }) {
+            pair.clone()
+        } else {
+            // 没有找到对应的请求
+            log::warn!("Response received without matching request: {:?}", key);
+            return Ok(None);
+        };
+
+        // 计算往返时间，响应早于请求（时钟偏差或乱序）时记为 0
+        let mut message = message;
+        let latency = match message.timestamp.duration_since(pair.request.timestamp) {
+            Ok(latency) => latency,
+            Err(e) => {
+                log::warn!(
+                    "Response for {:?} precedes its request by {:?}, clamping latency to 0",
+                    key,
+                    e.duration()
+                );
+                Duration::ZERO
             }
+        };
+        message.latency_ms = Some(latency.as_secs_f64() * 1000.0);
+
+        let pair = RequestResponsePair {
+            response: Some(message),
+            ..pair
+        };
+        self.push_history(pair.clone());
+        Ok(Some(pair))
+    }
+
+    fn push_history(&mut self, pair: RequestResponsePair) {
+        if self.history_size == 0 {
+            return;
         }
+        if self.history.len() >= self.history_size {
+            self.history.pop_front();
+        }
+        self.history.push_back(pair);
+    }
+
+    /// 最近完成的请求/响应对，按完成顺序从旧到新
+    pub fn history(&self) -> impl Iterator<Item = &RequestResponsePair> {
+        self.history.iter()
+    }
 
-        // 没有找到对应的请求
-        log::warn!("Response received without matching request: {:?}", key);
-        Ok(None)
+    /// 历史中指定服务/方法的请求/响应对
+    pub fn find_in_history(&self, service_id: u16, method_id: u16) -> Vec<&RequestResponsePair> {
+        self.history
+            .iter()
+            .filter(|pair| {
+                pair.request.header.service_id == service_id
+                    && pair.request.header.method_id == method_id
+            })
+            .collect()
     }
 
-    pub fn get_pending_requests(&self) -> Vec<RequestResponsePair> {
+    pub fn get_pending_requests(&self, now: SystemTime) -> Vec<RequestResponsePair> {
         self.sessions
-            .values()
-            .filter(|pair| pair.response.is_none() && pair.timeout > Instant::now())
+            .iter()
+            .map(|(_, pair)| pair)
+            .filter(|pair| pair.response.is_none() && pair.timeout > now)
             .cloned()
             .collect()
     }
 
-    pub fn cleanup_expired_sessions(&mut self) -> Vec<RequestResponsePair> {
-        let now = Instant::now();
+    /// 移除在抓包时间 `now` 之前已超时且未收到响应的会话，按请求时间排序返回
+    pub fn cleanup_expired_sessions(&mut self, now: SystemTime) -> Vec<RequestResponsePair> {
         let expired: Vec<_> = self
             .sessions
             .iter()
@@ -130,15 +249,224 @@ impl SessionManager {
         let mut result = Vec::new();
 
         for key in expired {
-            if let Some(pair) = self.sessions.remove(&key) {
-                // 从待响应队列中移除
-                if let Some(pos) = self.pending_responses.iter().position(|&k| k == key) {
-                    self.pending_responses.remove(pos);
-                }
+            if let Some(pair) = self.sessions.pop(&key) {
+                result.push(pair);
+            }
+        }
+
+        result.sort_by_key(|pair| pair.request.timestamp);
+        result
+    }
+
+    /// 移除与某个节点相关（请求的源或目的地址为 `ip`）且未收到响应的会话，按请求时间排序返回；
+    /// 该节点的已完成请求/响应对也从历史中移除
+    ///
+    /// 节点重启后其会话 ID 重新计数，旧请求不会再收到响应，保留会导致与新会话错误配对
+    pub fn evict_node(&mut self, ip: IpAddr) -> Vec<RequestResponsePair> {
+        let evicted: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|(_, pair)| {
+                pair.response.is_none() && (pair.request.src_ip == ip || pair.request.dst_ip == ip)
+            })
+            .map(|(&key, _)| key)
+            .collect();
+
+        let mut result = Vec::new();
+        for key in evicted {
+            if let Some(pair) = self.sessions.pop(&key) {
                 result.push(pair);
             }
         }
 
+        self.history
+            .retain(|pair| pair.request.src_ip != ip && pair.request.dst_ip != ip);
+
+        result.sort_by_key(|pair| pair.request.timestamp);
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::message::someip_message;
+    use std::time::UNIX_EPOCH;
+
+    fn request(session_id: u16) -> SomeIPMessage {
+        someip_message(0x1234, 0x0001, session_id, MessageType::Request)
+    }
+
+    /// 与 `request` 方向相反的响应
+    fn response(session_id: u16) -> SomeIPMessage {
+        let mut message = someip_message(0x1234, 0x0001, session_id, MessageType::Response);
+        std::mem::swap(&mut message.src_ip, &mut message.dst_ip);
+        std::mem::swap(&mut message.src_port, &mut message.dst_port);
+        message
+    }
+
+    fn manager() -> SessionManager {
+        SessionManager::new(Duration::from_secs(5), 100, 10)
+    }
+
+    /// 会话表容量为 3，依次加入会话 1、2、3 后再次发送会话 1，最后加入会话 4 触发淘汰
+    fn pending_after_overflow(policy: EvictionPolicy) -> Vec<u16> {
+        let mut sessions = SessionManager::new_with_policy(Duration::from_secs(5), 3, policy);
+        for session_id in [1, 2, 3, 1, 4] {
+            sessions.add_request(request(session_id)).unwrap();
+        }
+        let mut pending: Vec<_> = sessions
+            .get_pending_requests(UNIX_EPOCH)
+            .iter()
+            .map(|pair| pair.request.header.session_id)
+            .collect();
+        pending.sort();
+        pending
+    }
+
+    #[test]
+    fn message_display_is_one_line_summary() {
+        let mut message = request(3);
+        message.timestamp = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        message.payload = vec![0; 4];
+        assert_eq!(
+            message.to_string(),
+            "2023-11-14 22:13:20.250 10.0.0.1:30501 → 10.0.0.2:30502 \
+             SVC:0x1234/MTH:0x0001 [REQUEST] rc=E_OK sess=0001:0003 payload=4 bytes"
+        );
+    }
+
+    #[test]
+    fn fifo_evicts_oldest_request() {
+        assert_eq!(pending_after_overflow(EvictionPolicy::Fifo), [2, 3, 4]);
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_seen_request() {
+        assert_eq!(pending_after_overflow(EvictionPolicy::Lru), [1, 3, 4]);
+    }
+
+    #[test]
+    fn resent_request_does_not_grow_session_table() {
+        for policy in [EvictionPolicy::Fifo, EvictionPolicy::Lru] {
+            let mut sessions = SessionManager::new_with_policy(Duration::from_secs(5), 3, policy);
+            // 会话处理未启用时会话 ID 恒为 0，同一请求反复出现
+            for _ in 0..100 {
+                sessions.add_request(request(0)).unwrap();
+            }
+            sessions.add_request(request(1)).unwrap();
+            assert_eq!(sessions.get_pending_requests(UNIX_EPOCH).len(), 2);
+            assert!(sessions.add_response(response(0)).unwrap().is_some());
+            assert!(sessions.add_response(response(0)).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn pairs_response_with_request() {
+        let mut sessions = manager();
+        sessions.add_request(request(1)).unwrap();
+        let pair = sessions.add_response(response(1)).unwrap().unwrap();
+        assert_eq!(pair.request.header.session_id, 1);
+        assert_eq!(pair.response.unwrap().latency_ms, Some(0.0));
+        assert!(sessions.add_response(response(2)).unwrap().is_none());
+    }
+
+    #[test]
+    fn latency_is_response_minus_request_time() {
+        let mut sessions = manager();
+        sessions.add_request(request(1)).unwrap();
+        let mut late = response(1);
+        late.timestamp = UNIX_EPOCH + Duration::from_micros(12_500);
+        let pair = sessions.add_response(late).unwrap().unwrap();
+        assert_eq!(pair.response.unwrap().latency_ms, Some(12.5));
+    }
+
+    #[test]
+    fn response_before_request_clamps_latency_to_zero() {
+        let mut sessions = manager();
+        let mut request = request(1);
+        request.timestamp = UNIX_EPOCH + Duration::from_secs(1);
+        sessions.add_request(request).unwrap();
+        let pair = sessions.add_response(response(1)).unwrap().unwrap();
+        assert_eq!(pair.response.unwrap().latency_ms, Some(0.0));
+    }
+
+    #[test]
+    fn response_after_reboot_does_not_pair_with_old_request() {
+        let mut sessions = manager();
+        let server = response(1).src_ip;
+        sessions.add_request(request(1)).unwrap();
+
+        let evicted = sessions.evict_node(server);
+        assert_eq!(evicted.len(), 1);
+        assert!(sessions.add_response(response(1)).unwrap().is_none());
+    }
+
+    #[test]
+    fn reboot_clears_completed_pairs_from_history() {
+        let mut sessions = manager();
+        let server = response(1).src_ip;
+        sessions.add_request(request(1)).unwrap();
+        sessions.add_response(response(1)).unwrap().unwrap();
+        // 重复的响应与历史中的请求配对
+        assert!(sessions.add_response(response(1)).unwrap().is_some());
+
+        assert!(sessions.evict_node(server).is_empty());
+        assert_eq!(sessions.history().count(), 0);
+        // 重启后会话 ID 重新计数，同一会话 ID 的响应不属于旧请求
+        assert!(sessions.add_response(response(1)).unwrap().is_none());
+    }
+
+    #[test]
+    fn late_error_pairs_with_request_from_history() {
+        let mut sessions = manager();
+        let mut request = request(1);
+        request.payload = vec![0x42];
+        sessions.add_request(request).unwrap();
+        sessions.add_response(response(1)).unwrap().unwrap();
+
+        let mut error = response(1);
+        error.header.message_type = MessageType::Error;
+        error.timestamp = UNIX_EPOCH + Duration::from_millis(250);
+        let pair = sessions.add_response(error).unwrap().unwrap();
+        assert_eq!(pair.request.payload, [0x42]);
+        assert_eq!(pair.response.unwrap().latency_ms, Some(250.0));
+    }
+
+    #[test]
+    fn history_evicts_oldest_pairs_past_capacity() {
+        let mut sessions = SessionManager::new(Duration::from_secs(5), 100, 3);
+        for session_id in 1..=5 {
+            sessions.add_request(request(session_id)).unwrap();
+            sessions
+                .add_response(response(session_id))
+                .unwrap()
+                .unwrap();
+        }
+        let kept: Vec<u16> = sessions
+            .history()
+            .map(|pair| pair.request.header.session_id)
+            .collect();
+        assert_eq!(kept, [3, 4, 5]);
+        // 已移出历史的请求不再参与配对
+        assert!(sessions.add_response(response(1)).unwrap().is_none());
+    }
+
+    #[test]
+    fn find_in_history_matches_service_and_method() {
+        let mut sessions = manager();
+        sessions.add_request(request(1)).unwrap();
+        sessions.add_response(response(1)).unwrap().unwrap();
+        let mut other = someip_message(0x1234, 0x0002, 2, MessageType::Request);
+        sessions.add_request(other.clone()).unwrap();
+        other.header.message_type = MessageType::Response;
+        std::mem::swap(&mut other.src_ip, &mut other.dst_ip);
+        std::mem::swap(&mut other.src_port, &mut other.dst_port);
+        sessions.add_response(other).unwrap().unwrap();
+
+        let found = sessions.find_in_history(0x1234, 0x0002);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].request.header.session_id, 2);
+        assert!(sessions.find_in_history(0x5678, 0x0001).is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+// src/parser/someip/stream_decoder.rs
+use super::header::{SomeIPHeader, parse_someip_header};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+/// 单向 TCP 连接：(源地址, 源端口, 目的地址, 目的端口)
+pub type StreamKey = (IpAddr, u16, IpAddr, u16);
+
+/// 从字节流中切分出的一条 SomeIP 消息
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamMessage {
+    pub header: SomeIPHeader,
+    pub payload: Vec<u8>,
+    /// 失去同步后重新扫描找到的第一条消息
+    pub resynced: bool,
+}
+
+/// 从 TCP 字节流中切分 SomeIP 消息
+///
+/// 每个方向缓存尚未构成完整消息的字节，下一个分段到达后继续拼接，
+/// 因此跨分段（包括头部被分段边界截断）的消息不会丢失。
+/// 连接建立（SYN）或终止（FIN/RST）时调用 `reset` 重新同步。
+///
+/// 头部不像 SomeIP（协议版本、消息类型或长度不符）时字节流已失去同步，
+/// 逐字节向后扫描协议版本为 1、消息类型已定义且长度不超过 `resync_max_length`
+/// 的头部，从那里继续切分。
+#[derive(Debug)]
+pub struct SomeIpStreamDecoder {
+    buffers: HashMap<StreamKey, Vec<u8>>,
+    max_length: u32, // 长度字段上限，超出时视为失去同步，避免为无效长度无限缓存
+    resync_max_length: u32, // 重新扫描时候选头部的长度字段上限，比 `max_length` 更严格以减少误判
+    scanning: HashMap<StreamKey, usize>, // 正在扫描下一个有效头部的方向 -> 已跳过的字节数
+    resumed: HashSet<StreamKey>, // 已找到有效头部、下一条消息需要标记的方向
+    resyncs: u64,
+    resync_skipped_bytes: u64,
+}
+
+impl SomeIpStreamDecoder {
+    pub fn new(max_length: u32) -> Self {
+        Self {
+            buffers: HashMap::new(),
+            max_length,
+            resync_max_length: 64 * 1024,
+            scanning: HashMap::new(),
+            resumed: HashSet::new(),
+            resyncs: 0,
+            resync_skipped_bytes: 0,
+        }
+    }
+
+    /// 设置重新扫描时候选头部的长度字段上限（默认 64 KiB）
+    pub fn set_resync_max_length(&mut self, max_length: u32) {
+        self.resync_max_length = max_length;
+    }
+
+    /// 追加按序重组后的数据，返回其中所有完整的消息（头部与负载）
+    pub fn push(&mut self, key: StreamKey, data: &[u8]) -> Vec<StreamMessage> {
+        let mut buffer = self.buffers.remove(&key).unwrap_or_default();
+        buffer.extend_from_slice(data);
+
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while buffer.len() - offset >= 16 {
+            if let Some(skipped) = self.scanning.get_mut(&key) {
+                let found = Self::find_header(
+                    &buffer[offset..],
+                    self.resync_max_length.min(self.max_length),
+                );
+                // 找不到时保留末尾不足一个头部的字节，头部可能被分段边界截断
+                let skip = found.unwrap_or(buffer.len() - offset - 15);
+                *skipped += skip;
+                self.resync_skipped_bytes += skip as u64;
+                offset += skip;
+                if found.is_none() {
+                    break;
+                }
+                log::warn!(
+                    "TCP stream {}:{} -> {}:{} resynchronised SomeIP framing after skipping {} bytes",
+                    key.0,
+                    key.1,
+                    key.2,
+                    key.3,
+                    skipped
+                );
+                self.scanning.remove(&key);
+                self.resumed.insert(key);
+            }
+
+            let header = match parse_someip_header(&buffer[offset..]) {
+                Ok((_, header))
+                    if header.is_plausible() && (8..=self.max_length).contains(&header.length) =>
+                {
+                    header
+                }
+                result => {
+                    log::warn!(
+                        "TCP stream {}:{} -> {}:{} lost SomeIP framing (length {:?}), scanning for the next header",
+                        key.0,
+                        key.1,
+                        key.2,
+                        key.3,
+                        result.map(|(_, header)| header.length).ok()
+                    );
+                    self.resyncs += 1;
+                    self.scanning.insert(key, 1);
+                    self.resumed.remove(&key);
+                    // 当前位置不是有效头部，从下一个字节开始扫描
+                    self.resync_skipped_bytes += 1;
+                    offset += 1;
+                    continue;
+                }
+            };
+            // length 字段从 client_id 开始计算，整条消息为 16 字节头部加 length - 8 字节负载
+            let msg_len = 16 + header.payload_len();
+            if buffer.len() - offset < msg_len {
+                break;
+            }
+            messages.push(StreamMessage {
+                header,
+                payload: buffer[offset + 16..offset + msg_len].to_vec(),
+                resynced: self.resumed.remove(&key),
+            });
+            offset += msg_len;
+        }
+        buffer.drain(..offset);
+        if !buffer.is_empty() {
+            self.buffers.insert(key, buffer);
+        }
+        messages
+    }
+
+    /// 在 `data` 中查找下一个可信的 SomeIP 头部（长度字段不超过 `max_length`），返回其偏移
+    fn find_header(data: &[u8], max_length: u32) -> Option<usize> {
+        (0..=data.len().saturating_sub(16)).find(|&pos| {
+            parse_someip_header(&data[pos..]).is_ok_and(|(_, header)| {
+                header.is_plausible() && (8..=max_length).contains(&header.length)
+            })
+        })
+    }
+
+    /// 字节流出现缺口（例如跳过了 TCP 缺口）后调用：丢弃缓存的不完整消息，
+    /// 并从之后的数据中重新扫描头部，返回丢弃的字节数
+    pub fn resync(&mut self, key: &StreamKey) -> usize {
+        let discarded = self.reset(key);
+        self.resyncs += 1;
+        self.resync_skipped_bytes += discarded as u64;
+        self.scanning.insert(*key, discarded);
+        discarded
+    }
+
+    /// 丢弃某个方向缓存的不完整消息，返回丢弃的字节数
+    pub fn reset(&mut self, key: &StreamKey) -> usize {
+        self.scanning.remove(key);
+        self.resumed.remove(key);
+        self.buffers.remove(key).map_or(0, |buffer| buffer.len())
+    }
+
+    /// 某个方向是否缓存着尚未完整的消息
+    pub fn has_partial(&self, key: &StreamKey) -> bool {
+        self.buffers.contains_key(key)
+    }
+
+    /// 失去同步后重新扫描头部的次数
+    pub fn resync_count(&self) -> u64 {
+        self.resyncs
+    }
+
+    /// 重新扫描头部时跳过的字节数
+    pub fn resync_skipped_bytes(&self) -> u64 {
+        self.resync_skipped_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::someip::header::{MessageType, ReturnCode, encode_someip_message};
+    use std::net::Ipv4Addr;
+
+    const KEY: StreamKey = (
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        30501,
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+        30502,
+    );
+
+    /// 负载为 `payload_len` 个 `session_id` 字节的完整消息
+    fn message(session_id: u16, payload_len: usize) -> Vec<u8> {
+        let header = SomeIPHeader {
+            service_id: 0x1234,
+            method_id: 0x8001,
+            length: payload_len as u32 + 8,
+            client_id: 0,
+            session_id,
+            protocol_version: 1,
+            interface_version: 1,
+            is_tp: false,
+            message_type: MessageType::Notification,
+            return_code: ReturnCode::Ok,
+        };
+        encode_someip_message(&header, &vec![session_id as u8; payload_len]).unwrap()
+    }
+
+    fn sessions(messages: &[StreamMessage]) -> Vec<u16> {
+        messages.iter().map(|m| m.header.session_id).collect()
+    }
+
+    #[test]
+    fn message_split_across_four_segments() {
+        let mut decoder = SomeIpStreamDecoder::new(1 << 20);
+        let bytes = message(1, 5000);
+        // 第一个分段在头部中间结束
+        let segments = [
+            &bytes[..10],
+            &bytes[10..2000],
+            &bytes[2000..4000],
+            &bytes[4000..],
+        ];
+        for segment in &segments[..3] {
+            assert!(decoder.push(KEY, segment).is_empty());
+            assert!(decoder.has_partial(&KEY));
+        }
+        let messages = decoder.push(KEY, segments[3]);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].payload.len(), 5000);
+        assert!(!messages[0].resynced);
+        assert!(!decoder.has_partial(&KEY));
+    }
+
+    #[test]
+    fn back_to_back_messages_in_one_segment() {
+        let mut decoder = SomeIpStreamDecoder::new(1 << 20);
+        let mut bytes = message(1, 4);
+        bytes.extend(message(2, 0));
+        bytes.extend(&message(3, 8)[..20]);
+        assert_eq!(sessions(&decoder.push(KEY, &bytes)), [1, 2]);
+        assert_eq!(sessions(&decoder.push(KEY, &message(3, 8)[20..])), [3]);
+    }
+
+    #[test]
+    fn reset_discards_partial_message() {
+        let mut decoder = SomeIpStreamDecoder::new(1 << 20);
+        let partial = message(1, 100);
+        decoder.push(KEY, &partial[..50]);
+        assert_eq!(decoder.reset(&KEY), 50);
+        assert!(!decoder.has_partial(&KEY));
+        // 重置后新消息从头部开始切分，不与旧数据拼接
+        let messages = decoder.push(KEY, &message(2, 4));
+        assert_eq!(sessions(&messages), [2]);
+        assert!(!messages[0].resynced);
+    }
+
+    #[test]
+    fn resyncs_after_garbage_prefix() {
+        let mut decoder = SomeIpStreamDecoder::new(1 << 20);
+        let mut bytes = vec![0xFF; 7];
+        bytes.extend(message(1, 4));
+        bytes.extend(message(2, 4));
+        let messages = decoder.push(KEY, &bytes);
+        assert_eq!(sessions(&messages), [1, 2]);
+        // 只有失去同步后的第一条消息被标记
+        assert!(messages[0].resynced);
+        assert!(!messages[1].resynced);
+        assert_eq!(decoder.resync_count(), 1);
+        assert_eq!(decoder.resync_skipped_bytes(), 7);
+    }
+
+    #[test]
+    fn resync_finds_header_split_across_segments() {
+        let mut decoder = SomeIpStreamDecoder::new(1 << 20);
+        let mut bytes = vec![0xFF; 20];
+        bytes.extend(message(1, 4));
+        bytes.extend(message(2, 4));
+        // 第一个分段在有效头部的中间结束
+        let split = 20 + 6;
+        assert!(decoder.push(KEY, &bytes[..split]).is_empty());
+        let messages = decoder.push(KEY, &bytes[split..]);
+        assert_eq!(sessions(&messages), [1, 2]);
+        assert!(messages[0].resynced);
+        assert!(!messages[1].resynced);
+        assert_eq!(decoder.resync_skipped_bytes(), 20);
+    }
+
+    #[test]
+    fn resync_after_gap_marks_next_message() {
+        let mut decoder = SomeIpStreamDecoder::new(1 << 20);
+        decoder.push(KEY, &message(1, 100)[..40]);
+        assert_eq!(decoder.resync(&KEY), 40);
+        // 缺口之后的数据从另一条消息的中间开始
+        let mut bytes = message(2, 30)[25..].to_vec();
+        let skipped = bytes.len();
+        bytes.extend(message(3, 4));
+        let messages = decoder.push(KEY, &bytes);
+        assert_eq!(sessions(&messages), [3]);
+        assert!(messages[0].resynced);
+        assert_eq!(decoder.resync_skipped_bytes(), 40 + skipped as u64);
+    }
+}
@@ -14,6 +14,36 @@ pub struct TPSegment {
     pub payload: Bytes,
 }
 
+impl TPSegment {
+    /// 编码为完整的 SomeIP-TP 消息（与 `parse_tp_segment` 的分段头格式对应）
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut tp_payload = Vec::with_capacity(4 + self.payload.len());
+        if self.is_first {
+            // 第一个分段：标志位 + 3 字节偏移量
+            let mut flags = 0x80 | ((self.offset >> 16) as u8 & 0x3F);
+            if self.is_last {
+                flags |= 0x40;
+            }
+            tp_payload.push(flags);
+            tp_payload.extend_from_slice(&(self.offset as u16).to_be_bytes());
+        } else {
+            // 后续分段：4 字节偏移量，最后一个分段置位 0x40
+            let mut offset = self.offset;
+            if self.is_last {
+                offset |= 0x4000_0000;
+            }
+            tp_payload.extend_from_slice(&offset.to_be_bytes());
+        }
+        tp_payload.extend_from_slice(&self.payload);
+
+        let mut header = self.header.clone();
+        header.length = tp_payload.len() as u32 + 8;
+        header.is_tp = true;
+        encode_someip_message(&header, &tp_payload)
+            .expect("length field is computed from the payload")
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ReassembledMessage {
     pub header: SomeIPHeader,
@@ -25,13 +55,39 @@ struct PendingMessage {
     header: SomeIPHeader,
     segments: HashMap<u32, Bytes>, // 偏移量 -> 数据
     expected_offset: u32,
-    total_size: Option<u32>,
+    total_size: Option<u32>, // 收到标记为最后的分段后确定
     last_updated: Instant,
 }
 
+/// 分段偏移量与期望值不一致（非重传）时记录的缺口事件
+#[derive(Debug, Clone, PartialEq)]
+pub struct TpGapEvent {
+    pub key: (u16, u16, u16), // (服务ID, 客户端ID, 会话ID)
+    pub expected_offset: u32,
+    pub received_offset: u32,
+    pub timestamp: Instant,
+}
+
+/// 重组统计
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TpStats {
+    /// 收到首个分段的消息数
+    pub total_messages: usize,
+    /// 重组完成的消息数
+    pub complete: usize,
+    /// 超时丢弃的消息数
+    pub timeout: usize,
+    /// 检测到的缺口事件总数（不受 `drain_gap_events` 影响）
+    pub gap_events: usize,
+    /// 位于最后一个分段之后而被丢弃的分段数
+    pub late_segments: usize,
+}
+
 pub struct TPParser {
     pending_messages: HashMap<(u16, u16, u16), PendingMessage>, // (服务ID, 客户端ID, 会话ID) -> 待重组消息
     timeout: Duration,
+    gap_events: Vec<TpGapEvent>,
+    stats: TpStats,
 }
 
 impl TPParser {
@@ -39,9 +95,25 @@ impl TPParser {
         Self {
             pending_messages: HashMap::new(),
             timeout,
+            gap_events: Vec::new(),
+            stats: TpStats::default(),
         }
     }
 
+    /// 取出自上次调用以来记录的缺口事件
+    pub fn drain_gap_events(&mut self) -> Vec<TpGapEvent> {
+        std::mem::take(&mut self.gap_events)
+    }
+
+    /// (服务ID, 客户端ID, 会话ID) 对应的消息是否仍在等待后续分段
+    pub fn is_pending(&self, key: &(u16, u16, u16)) -> bool {
+        self.pending_messages.contains_key(key)
+    }
+
+    pub fn reassembly_stats(&self) -> TpStats {
+        self.stats.clone()
+    }
+
     pub fn process_segment(&mut self, segment: TPSegment) -> Result<Option<ReassembledMessage>> {
         let key = (
             segment.header.service_id,
@@ -54,33 +126,32 @@ impl TPParser {
 
         // 处理第一个分段
         if segment.is_first {
-            let total_size = if segment.is_last {
-                // 单段消息
-                segment.offset + segment.payload.len() as u32
-            } else {
-                // 多段消息，第一个分段包含完整长度
-                segment.header.length - 8 // 减去头部大小
-            };
+            self.stats.total_messages += 1;
 
+            // 如果是单段消息，直接返回
+            if segment.is_last {
+                self.pending_messages.remove(&key);
+                self.stats.complete += 1;
+                return Ok(Some(ReassembledMessage {
+                    header: SomeIPHeader {
+                        is_tp: false,
+                        ..segment.header
+                    },
+                    payload: segment.payload.to_vec(),
+                }));
+            }
+
+            // 多段消息的总长度在收到最后一个分段后才能确定
             self.pending_messages.insert(
                 key,
                 PendingMessage {
                     header: segment.header.clone(),
                     segments: HashMap::from([(segment.offset, segment.payload.clone())]),
                     expected_offset: segment.offset + segment.payload.len() as u32,
-                    total_size: Some(total_size),
+                    total_size: None,
                     last_updated: Instant::now(),
                 },
             );
-
-            // 如果是单段消息，直接返回
-            if segment.is_last {
-                return Ok(Some(ReassembledMessage {
-                    header: segment.header,
-                    payload: segment.payload.to_vec(),
-                }));
-            }
-
             return Ok(None);
         }
 
@@ -96,66 +167,107 @@ impl TPParser {
         // 更新最后更新时间
         pending_msg.last_updated = Instant::now();
 
-        // 检查偏移量是否符合预期
-        if segment.offset != pending_msg.expected_offset {
-            // 乱序分段，先缓存
-            pending_msg.segments.insert(segment.offset, segment.payload);
+        // 最后一个分段之后的分段：消息长度已确定，丢弃
+        let end = segment.offset + segment.payload.len() as u32;
+        if !segment.is_last
+            && pending_msg
+                .total_size
+                .is_some_and(|total_size| segment.offset >= total_size)
+        {
+            log::debug!(
+                "Dropping TP segment at offset {} after last segment for {:?}",
+                segment.offset,
+                key
+            );
+            self.stats.late_segments += 1;
             return Ok(None);
         }
 
-        // 正常顺序的分段
-        let payload = segment.payload.clone();
-        pending_msg.segments.insert(segment.offset, payload.clone());
-        pending_msg.expected_offset += payload.len() as u32;
+        // 检查偏移量是否符合预期：已收到过相同偏移量的分段视为重传，否则记录缺口
+        if segment.offset != pending_msg.expected_offset
+            && !pending_msg.segments.contains_key(&segment.offset)
+        {
+            self.gap_events.push(TpGapEvent {
+                key,
+                expected_offset: pending_msg.expected_offset,
+                received_offset: segment.offset,
+                timestamp: pending_msg.last_updated,
+            });
+            self.stats.gap_events += 1;
+        }
+        pending_msg.segments.insert(segment.offset, segment.payload);
 
-        // 检查是否是最后一个分段
         if segment.is_last {
-            pending_msg.total_size = Some(segment.offset + segment.payload.len() as u32);
+            // 乱序到达的多个最后分段取最大的结束位置，总长度只增不减
+            let total_size = pending_msg.total_size.map_or(end, |size| size.max(end));
+            pending_msg.total_size = Some(total_size);
+
+            // 先于最后分段到达、位于其后的分段同样丢弃
+            let before = pending_msg.segments.len();
+            pending_msg
+                .segments
+                .retain(|&offset, _| offset < total_size);
+            self.stats.late_segments += before - pending_msg.segments.len();
         }
 
-        // 检查是否可以重组完整消息
-        if let Some(total_size) = pending_msg.total_size {
-            if pending_msg.expected_offset >= total_size {
-                // 所有分段都已收到，进行重组
-                let pending_msg_clone = pending_msg.clone();
-                self.pending_messages.remove(&key);
-                let reassembled = self.reassemble_message(&pending_msg_clone)?;
-                return Ok(Some(reassembled));
+        // 按已缓存的连续分段推进期望偏移量
+        while let Some(data) = pending_msg.segments.get(&pending_msg.expected_offset) {
+            if data.is_empty() {
+                break;
             }
+            pending_msg.expected_offset += data.len() as u32;
+        }
+
+        // 检查是否可以重组完整消息
+        if let Some(total_size) = pending_msg.total_size
+            && pending_msg.expected_offset >= total_size
+        {
+            // 所有分段都已收到，进行重组
+            let pending_msg_clone = pending_msg.clone();
+            self.pending_messages.remove(&key);
+            let reassembled = self.reassemble_message(&pending_msg_clone)?;
+            self.stats.complete += 1;
+            return Ok(Some(reassembled));
         }
 
         Ok(None)
     }
 
     fn reassemble_message(&self, pending_msg: &PendingMessage) -> Result<ReassembledMessage> {
-        let mut offset = 0;
         let total_size = pending_msg.total_size.ok_or_else(|| {
             SomeIPError::TPSegmentError("Missing total size when reassembling message".to_string())
         })? as usize;
 
         let mut payload = vec![0; total_size];
 
-        // 按偏移量顺序组装数据
+        // 按偏移量顺序组装数据，超出总长度的部分截断
         let mut segments: Vec<_> = pending_msg.segments.iter().collect();
         segments.sort_by_key(|(off, _)| *off);
 
         for (seg_offset, data) in segments {
-            let len = data.len();
-            payload[*seg_offset as usize..(*seg_offset as usize + len)]
-                .copy_from_slice(&data[..len]);
-            offset += len as u32;
+            let start = *seg_offset as usize;
+            let end = (start + data.len()).min(total_size);
+            if start < end {
+                payload[start..end].copy_from_slice(&data[..end - start]);
+            }
         }
 
+        // 重组后的消息不再是分段消息
         Ok(ReassembledMessage {
-            header: pending_msg.header.clone(),
+            header: SomeIPHeader {
+                is_tp: false,
+                ..pending_msg.header.clone()
+            },
             payload,
         })
     }
 
     fn cleanup_expired_messages(&mut self) {
         let now = Instant::now();
+        let before = self.pending_messages.len();
         self.pending_messages
             .retain(|_, msg| now.duration_since(msg.last_updated) <= self.timeout);
+        self.stats.timeout += before - self.pending_messages.len();
     }
 }
 
@@ -176,8 +288,8 @@ pub fn parse_tp_segment(payload: &[u8], header: SomeIPHeader) -> Result<TPSegmen
         // 第一个分段使用3字节偏移量
         ((first_byte & 0x3F) as u32) << 16 | (payload[1] as u32) << 8 | payload[2] as u32
     } else {
-        // 后续分段使用4字节偏移量
-        (payload[0] as u32) << 24
+        // 后续分段使用4字节偏移量，最高两位为标志位
+        ((payload[0] & 0x3F) as u32) << 24
             | (payload[1] as u32) << 16
             | (payload[2] as u32) << 8
             | payload[3] as u32
@@ -195,3 +307,163 @@ pub fn parse_tp_segment(payload: &[u8], header: SomeIPHeader) -> Result<TPSegmen
         payload: Bytes::copy_from_slice(data),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::message::someip_message;
+
+    const KEY: (u16, u16, u16) = (0x1234, 0x0001, 7);
+
+    fn segment(offset: u32, is_first: bool, is_last: bool, data: &[u8]) -> TPSegment {
+        let mut header = someip_message(KEY.0, 0x0001, KEY.2, MessageType::Notification).header;
+        header.is_tp = true;
+        TPSegment {
+            header,
+            is_first,
+            is_last,
+            offset,
+            payload: Bytes::copy_from_slice(data),
+        }
+    }
+
+    #[test]
+    fn in_order_segments_reassemble_without_gaps() {
+        let mut parser = TPParser::new(Duration::from_secs(5));
+        assert!(
+            parser
+                .process_segment(segment(0, true, false, &[1; 16]))
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            parser
+                .process_segment(segment(16, false, false, &[2; 16]))
+                .unwrap()
+                .is_none()
+        );
+        let message = parser
+            .process_segment(segment(32, false, true, &[3; 4]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.payload.len(), 36);
+        assert_eq!(&message.payload[14..18], [1, 1, 2, 2]);
+        assert!(!message.header.is_tp);
+        assert!(parser.drain_gap_events().is_empty());
+        assert_eq!(
+            parser.reassembly_stats(),
+            TpStats {
+                total_messages: 1,
+                complete: 1,
+                ..TpStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn out_of_order_segment_records_one_gap() {
+        let mut parser = TPParser::new(Duration::from_secs(5));
+        parser
+            .process_segment(segment(0, true, false, &[1; 16]))
+            .unwrap();
+        // 跳过偏移 16 的分段
+        parser
+            .process_segment(segment(32, false, true, &[3; 16]))
+            .unwrap();
+        let message = parser
+            .process_segment(segment(16, false, false, &[2; 16]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.payload.len(), 48);
+        assert_eq!(&message.payload[30..34], [2, 2, 3, 3]);
+
+        let events = parser.drain_gap_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].key, KEY);
+        assert_eq!(events[0].expected_offset, 16);
+        assert_eq!(events[0].received_offset, 32);
+        // 取出后事件清空，统计中的计数保留
+        assert!(parser.drain_gap_events().is_empty());
+        assert_eq!(parser.reassembly_stats().gap_events, 1);
+        assert_eq!(parser.reassembly_stats().complete, 1);
+    }
+
+    #[test]
+    fn incomplete_message_times_out() {
+        let mut parser = TPParser::new(Duration::ZERO);
+        parser
+            .process_segment(segment(0, true, false, &[1; 16]))
+            .unwrap();
+        assert!(parser.is_pending(&KEY));
+        std::thread::sleep(Duration::from_millis(2));
+        // 下一个分段到达时先清理超时消息，之后的分段找不到首个分段而被丢弃
+        assert!(
+            parser
+                .process_segment(segment(16, false, true, &[2; 16]))
+                .unwrap()
+                .is_none()
+        );
+        assert!(!parser.is_pending(&KEY));
+        assert_eq!(
+            parser.reassembly_stats(),
+            TpStats {
+                total_messages: 1,
+                timeout: 1,
+                ..TpStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn stray_segment_after_last_is_dropped() {
+        let mut parser = TPParser::new(Duration::from_secs(5));
+        parser
+            .process_segment(segment(0, true, false, &[1; 16]))
+            .unwrap();
+        // 最后分段先于中间分段到达，之后又收到偏移更大的多余分段
+        parser
+            .process_segment(segment(32, false, true, &[3; 16]))
+            .unwrap();
+        assert!(
+            parser
+                .process_segment(segment(48, false, false, &[4; 16]))
+                .unwrap()
+                .is_none()
+        );
+        assert!(parser.is_pending(&KEY));
+        let message = parser
+            .process_segment(segment(16, false, false, &[2; 16]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.payload.len(), 48);
+        assert!(!message.payload.contains(&4));
+        assert_eq!(parser.reassembly_stats().late_segments, 1);
+    }
+
+    #[test]
+    fn earlier_last_segment_does_not_shrink_total_size() {
+        let mut parser = TPParser::new(Duration::from_secs(5));
+        parser
+            .process_segment(segment(0, true, false, &[1; 16]))
+            .unwrap();
+        parser
+            .process_segment(segment(32, false, true, &[3; 16]))
+            .unwrap();
+        // 另一个标记为最后、但结束位置更靠前的分段不会提前完成重组
+        assert!(
+            parser
+                .process_segment(segment(16, false, true, &[2; 8]))
+                .unwrap()
+                .is_none()
+        );
+        let message = parser
+            .process_segment(segment(24, false, false, &[2; 8]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.payload.len(), 48);
+        assert_eq!(
+            &message.payload[14..34],
+            [1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3]
+        );
+    }
+}
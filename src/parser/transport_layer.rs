@@ -1,8 +1,9 @@
 // src/parser/transport_layer.rs
+use bytes::Bytes;
 use nom::{
     IResult, Parser,
     bytes::complete::take,
-    number::complete::{be_u16, be_u32},
+    number::complete::{be_u8, be_u16, be_u32},
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,7 +18,14 @@ pub struct UDPPacketInfo {
     pub dst_port: u16,
     pub length: u16,
     pub checksum: u16,
-    pub payload: Vec<u8>,
+    pub payload: Bytes,
+}
+
+impl UDPPacketInfo {
+    /// 长度字段是否与抓包数据一致：不小于头部长度，且抓包中包含其声明的全部负载
+    pub fn length_fits_capture(&self) -> bool {
+        (self.length as usize).checked_sub(8) == Some(self.payload.len())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -32,8 +40,17 @@ pub struct TCPPacketInfo {
     pub window_size: u16,
     pub checksum: u16,
     pub urgent_ptr: u16,
-    pub options: Vec<u8>,
-    pub payload: Vec<u8>,
+    pub options: TcpOptions,
+    pub payload: Bytes,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TcpOptions {
+    pub mss: Option<u16>,
+    pub window_scale: Option<u8>,
+    pub sack_permitted: bool,
+    pub sack_blocks: Vec<(u32, u32)>,
+    pub timestamps: Option<(u32, u32)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -49,7 +66,8 @@ pub struct TCPFlags {
     pub fin: bool,
 }
 
-pub fn parse_transport_layer(input: &[u8], protocol: u8) -> IResult<&[u8], TransportLayer> {
+/// 解析 UDP/TCP 头部，负载是 `input` 的零拷贝切片
+pub fn parse_transport_layer(input: &Bytes, protocol: u8) -> IResult<&[u8], TransportLayer> {
     match protocol {
         17 => parse_udp(input),
         6 => parse_tcp(input),
@@ -60,11 +78,17 @@ pub fn parse_transport_layer(input: &[u8], protocol: u8) -> IResult<&[u8], Trans
     }
 }
 
-fn parse_udp(input: &[u8]) -> IResult<&[u8], TransportLayer> {
+fn parse_udp(packet: &Bytes) -> IResult<&[u8], TransportLayer> {
     let (input, (src_port, dst_port, length, checksum)) =
-        (be_u16, be_u16, be_u16, be_u16).parse(input)?;
+        (be_u16, be_u16, be_u16, be_u16).parse(packet.as_ref())?;
 
-    let payload = input.to_vec();
+    // 按 UDP 长度字段截断负载，去掉以太网填充等尾部字节；
+    // 长度字段非法（小于头部长度或超出抓包数据）时保留全部剩余数据，由 `length_fits_capture` 检查
+    let payload_len = match (length as usize).checked_sub(8) {
+        Some(len) => len.min(input.len()),
+        None => input.len(),
+    };
+    let payload = packet.slice_ref(&input[..payload_len]);
 
     Ok((
         &[],
@@ -78,36 +102,45 @@ fn parse_udp(input: &[u8]) -> IResult<&[u8], TransportLayer> {
     ))
 }
 
-fn parse_tcp(input: &[u8]) -> IResult<&[u8], TransportLayer> {
+fn parse_tcp(packet: &Bytes) -> IResult<&[u8], TransportLayer> {
     let (input, (src_port, dst_port, seq_num, ack_num)) =
-        (be_u16, be_u16, be_u32, be_u32).parse(input)?;
+        (be_u16, be_u16, be_u32, be_u32).parse(packet.as_ref())?;
 
     let (input, data_offset_reserved_flags) = be_u16(input)?;
     let data_offset = ((data_offset_reserved_flags >> 12) & 0x0F) as u8;
-    let reserved = ((data_offset_reserved_flags >> 6) & 0x3F) as u8;
+    let reserved = ((data_offset_reserved_flags >> 9) & 0x07) as u8;
 
+    // 低 9 位为标志位：NS | CWR | ECE | URG | ACK | PSH | RST | SYN | FIN
     let flags = TCPFlags {
-        ns: (data_offset_reserved_flags & 0x0020) != 0,
-        cwr: (data_offset_reserved_flags & 0x0010) != 0,
-        ece: (data_offset_reserved_flags & 0x0008) != 0,
-        urg: (data_offset_reserved_flags & 0x0004) != 0,
-        ack: (data_offset_reserved_flags & 0x0002) != 0,
-        psh: (data_offset_reserved_flags & 0x0001) != 0,
-        rst: (data_offset_reserved_flags & 0x0000) != 0,
-        syn: (data_offset_reserved_flags & 0x0000) != 0,
-        fin: (data_offset_reserved_flags & 0x0000) != 0,
+        ns: (data_offset_reserved_flags & 0x0100) != 0,
+        cwr: (data_offset_reserved_flags & 0x0080) != 0,
+        ece: (data_offset_reserved_flags & 0x0040) != 0,
+        urg: (data_offset_reserved_flags & 0x0020) != 0,
+        ack: (data_offset_reserved_flags & 0x0010) != 0,
+        psh: (data_offset_reserved_flags & 0x0008) != 0,
+        rst: (data_offset_reserved_flags & 0x0004) != 0,
+        syn: (data_offset_reserved_flags & 0x0002) != 0,
+        fin: (data_offset_reserved_flags & 0x0001) != 0,
     };
 
     let (input, (window_size, checksum, urgent_ptr)) = (be_u16, be_u16, be_u16).parse(input)?;
 
-    let options_size = (data_offset * 4 - 20) as usize;
+    // 数据偏移以 4 字节为单位，小于 5 时连固定头部都放不下
+    let Some(options_size) = (usize::from(data_offset) * 4).checked_sub(20) else {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    };
     let (input, options) = if options_size > 0 {
-        take(options_size)(input)?
+        let (input, options_data) = take(options_size)(input)?;
+        let (_, options) = parse_tcp_options(options_data)?;
+        (input, options)
     } else {
-        (&input[..0], &[][..])
+        (input, TcpOptions::default())
     };
 
-    let payload = input.to_vec();
+    let payload = packet.slice_ref(input);
 
     Ok((
         &[],
@@ -122,8 +155,158 @@ fn parse_tcp(input: &[u8]) -> IResult<&[u8], TransportLayer> {
             window_size,
             checksum,
             urgent_ptr,
-            options: options.to_vec(),
+            options,
             payload,
         }),
     ))
 }
+
+pub fn parse_tcp_options(input: &[u8]) -> IResult<&[u8], TcpOptions> {
+    let mut options = TcpOptions::default();
+    let mut remaining = input;
+
+    while !remaining.is_empty() {
+        let (rest, kind) = be_u8(remaining)?;
+        match kind {
+            // End of Option List
+            0 => return Ok((&[], options)),
+            // No-Operation
+            1 => {
+                remaining = rest;
+                continue;
+            }
+            _ => {}
+        }
+
+        let (rest, length) = be_u8(rest)?;
+        let length = length as usize;
+        if length < 2 || length - 2 > rest.len() {
+            // 长度非法，停止解析剩余选项
+            break;
+        }
+        let (rest, value) = take(length - 2)(rest)?;
+
+        match (kind, value.len()) {
+            // Maximum Segment Size
+            (2, 2) => {
+                let (_, mss) = be_u16(value)?;
+                options.mss = Some(mss);
+            }
+            // Window Scale
+            (3, 1) => {
+                options.window_scale = Some(value[0]);
+            }
+            // SACK Permitted
+            (4, 0) => {
+                options.sack_permitted = true;
+            }
+            // SACK (左右边界对)
+            (5, len) if len % 8 == 0 => {
+                let (_, blocks) = nom::multi::count((be_u32, be_u32), len / 8).parse(value)?;
+                options.sack_blocks = blocks;
+            }
+            // Timestamps
+            (8, 8) => {
+                let (_, (ts_val, ts_ecr)) = (be_u32, be_u32).parse(value)?;
+                options.timestamps = Some((ts_val, ts_ecr));
+            }
+            _ => {}
+        }
+
+        remaining = rest;
+    }
+
+    Ok((remaining, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::builder::{PacketBuilder, TCP_ACK, TCP_FIN, TCP_RST, TCP_SYN};
+
+    /// 以以太网 + IPv4 帧构造 TCP 分段，返回解析出的 TCP 头部
+    fn parse_segment(flags: u8, payload: &[u8]) -> TCPPacketInfo {
+        let frame = Bytes::from(
+            PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .tcp(40000, 30501, 1000)
+                .tcp_flags(flags)
+                .payload(payload)
+                .build(),
+        );
+        // 以太网头部 14 字节 + IPv4 头部 20 字节
+        match parse_transport_layer(&frame.slice(34..), 6).unwrap().1 {
+            TransportLayer::TCP(tcp) => tcp,
+            other => panic!("expected TCP, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_syn() {
+        let tcp = parse_segment(TCP_SYN, &[]);
+        assert!(tcp.flags.syn);
+        assert!(!tcp.flags.ack && !tcp.flags.fin && !tcp.flags.rst && !tcp.flags.psh);
+        assert_eq!(
+            (tcp.src_port, tcp.dst_port, tcp.seq_num),
+            (40000, 30501, 1000)
+        );
+        assert!(tcp.payload.is_empty());
+    }
+
+    #[test]
+    fn parses_fin_ack_with_payload() {
+        let tcp = parse_segment(TCP_FIN | TCP_ACK, b"ab");
+        assert!(tcp.flags.fin && tcp.flags.ack);
+        assert!(!tcp.flags.syn && !tcp.flags.rst);
+        assert_eq!(&tcp.payload[..], b"ab");
+    }
+
+    #[test]
+    fn parses_rst() {
+        let tcp = parse_segment(TCP_RST, &[]);
+        assert!(tcp.flags.rst);
+        assert!(!tcp.flags.syn && !tcp.flags.fin && !tcp.flags.ack);
+    }
+
+    #[test]
+    fn data_offset_below_header_size_is_rejected() {
+        let mut header = vec![0u8; 20];
+        header[12] = 4 << 4;
+        assert!(parse_transport_layer(&Bytes::from(header), 6).is_err());
+    }
+
+    #[test]
+    fn parses_syn_options() {
+        let options = [
+            2, 4, 0x05, 0xB4, // MSS 1460
+            1,    // NOP
+            3, 3, 7, // Window Scale 7
+            4, 2, // SACK Permitted
+            8, 10, 0, 0, 0, 1, 0, 0, 0, 2, // Timestamps
+        ];
+        let (_, parsed) = parse_tcp_options(&options).unwrap();
+        assert_eq!(parsed.mss, Some(1460));
+        assert_eq!(parsed.window_scale, Some(7));
+        assert!(parsed.sack_permitted);
+        assert_eq!(parsed.timestamps, Some((1, 2)));
+        assert!(parsed.sack_blocks.is_empty());
+    }
+
+    #[test]
+    fn end_of_option_list_stops_parsing() {
+        // NOP 填充后的 EOL 之后的字节不再按选项解析
+        let (_, parsed) = parse_tcp_options(&[1, 1, 0, 2, 4, 0x05, 0xB4]).unwrap();
+        assert_eq!(parsed, TcpOptions::default());
+    }
+
+    #[test]
+    fn truncated_option_keeps_earlier_options() {
+        // 时间戳选项声明 10 字节，实际只剩 4 字节
+        let (_, parsed) = parse_tcp_options(&[2, 4, 0x05, 0xB4, 8, 10, 0, 0, 0, 1]).unwrap();
+        assert_eq!(parsed.mss, Some(1460));
+        assert_eq!(parsed.timestamps, None);
+
+        // 只剩类型字节，缺少长度字段
+        assert!(parse_tcp_options(&[2]).is_err());
+    }
+}
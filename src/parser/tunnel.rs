@@ -0,0 +1,107 @@
+// src/parser/tunnel.rs
+use nom::{
+    IResult,
+    bytes::complete::take,
+    number::complete::{be_u8, be_u16, be_u24, be_u32},
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// VXLAN 默认 UDP 端口（IANA 分配）
+pub const VXLAN_DEFAULT_PORT: u16 = 4789;
+
+/// GRE 的 IP 协议号
+pub const IP_PROTOCOL_GRE: u8 = 47;
+
+/// GRE 承载以太网帧时使用的协议类型（Transparent Ethernet Bridging）
+pub const GRE_PROTO_TRANSPARENT_ETHERNET: u16 = 0x6558;
+
+/// 最大隧道嵌套层数，防止构造的数据包导致无限递归
+pub const MAX_TUNNEL_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TunnelKind {
+    Vxlan { vni: u32 },
+    Gre,
+}
+
+/// 隧道外层信息，随解封装后的消息一起保留
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TunnelInfo {
+    pub kind: TunnelKind,
+    pub outer_src_ip: IpAddr,
+    pub outer_dst_ip: IpAddr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VxlanHeader {
+    pub flags: u8,
+    pub vni: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreHeader {
+    pub flags: u16,
+    pub protocol_type: u16,
+    pub key: Option<u32>,
+    pub sequence: Option<u32>,
+}
+
+/// 解析 VXLAN 头部，返回内层以太网帧
+pub fn parse_vxlan(input: &[u8]) -> IResult<&[u8], VxlanHeader> {
+    let (input, flags) = be_u8(input)?;
+    // I 标志位必须置位，否则 VNI 无效
+    if flags & 0x08 == 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+    let (input, _) = take(3usize)(input)?; // 保留字段
+    let (input, vni) = be_u24(input)?;
+    let (input, _) = take(1usize)(input)?; // 保留字段
+
+    Ok((input, VxlanHeader { flags, vni }))
+}
+
+/// 解析 GRE 头部（RFC 2784/2890），返回内层负载
+pub fn parse_gre(input: &[u8]) -> IResult<&[u8], GreHeader> {
+    let (input, flags) = be_u16(input)?;
+    // 仅支持版本 0（版本 1 为 PPTP 增强 GRE）
+    if flags & 0x0007 != 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+    let (input, protocol_type) = be_u16(input)?;
+
+    // 校验和存在时后跟 2 字节校验和与 2 字节保留字段
+    let input = if flags & 0x8000 != 0 {
+        take(4usize)(input)?.0
+    } else {
+        input
+    };
+    let (input, key) = if flags & 0x2000 != 0 {
+        let (input, key) = be_u32(input)?;
+        (input, Some(key))
+    } else {
+        (input, None)
+    };
+    let (input, sequence) = if flags & 0x1000 != 0 {
+        let (input, seq) = be_u32(input)?;
+        (input, Some(seq))
+    } else {
+        (input, None)
+    };
+
+    Ok((
+        input,
+        GreHeader {
+            flags,
+            protocol_type,
+            key,
+            sequence,
+        },
+    ))
+}
@@ -0,0 +1,504 @@
+// src/testing/builder.rs
+use crate::parser::checksum::internet_checksum;
+use crate::parser::link_layer::LinkType;
+use crate::parser::someip::header::{MessageType, ReturnCode, SomeIPHeader, encode_someip_message};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+enum Link {
+    Ethernet {
+        vlan_ids: Vec<u16>,
+        mpls_labels: Vec<u32>,
+    },
+    Sll,
+}
+
+#[derive(Debug, Clone)]
+enum Transport {
+    Udp {
+        src_port: u16,
+        dst_port: u16,
+    },
+    Tcp {
+        src_port: u16,
+        dst_port: u16,
+        seq_num: u32,
+        flags: u8,
+    },
+}
+
+/// TCP 标志位，可组合后传给 `PacketBuilder::tcp_flags`
+pub const TCP_FIN: u8 = 0x01;
+pub const TCP_SYN: u8 = 0x02;
+pub const TCP_RST: u8 = 0x04;
+pub const TCP_PSH: u8 = 0x08;
+pub const TCP_ACK: u8 = 0x10;
+
+/// 链式构造单个数据包：链路层 -> IP -> UDP/TCP -> SomeIP
+#[derive(Debug, Clone)]
+pub struct PacketBuilder {
+    link: Link,
+    addresses: Option<(IpAddr, IpAddr)>,
+    transport: Option<Transport>,
+    someip_header: Option<SomeIPHeader>,
+    payload: Vec<u8>,
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PacketBuilder {
+    /// 默认使用以太网链路层
+    pub fn new() -> Self {
+        Self {
+            link: Link::Ethernet {
+                vlan_ids: Vec::new(),
+                mpls_labels: Vec::new(),
+            },
+            addresses: None,
+            transport: None,
+            someip_header: None,
+            payload: Vec::new(),
+        }
+    }
+
+    pub fn ethernet(mut self) -> Self {
+        self.link = Link::Ethernet {
+            vlan_ids: Vec::new(),
+            mpls_labels: Vec::new(),
+        };
+        self
+    }
+
+    /// Linux cooked capture (SLL) 链路层
+    pub fn sll(mut self) -> Self {
+        self.link = Link::Sll;
+        self
+    }
+
+    /// 添加一层 802.1Q 标签（多次调用构造 QinQ，外层在前）
+    pub fn vlan(mut self, id: u16) -> Self {
+        if let Link::Ethernet { vlan_ids, .. } = &mut self.link {
+            vlan_ids.push(id & 0x0FFF);
+        }
+        self
+    }
+
+    /// 压入一层 MPLS 标签（多次调用构造标签栈，栈顶在前）
+    pub fn mpls(mut self, label: u32) -> Self {
+        if let Link::Ethernet { mpls_labels, .. } = &mut self.link {
+            mpls_labels.push(label & 0xF_FFFF);
+        }
+        self
+    }
+
+    pub fn ipv4(
+        mut self,
+        src: impl Into<std::net::Ipv4Addr>,
+        dst: impl Into<std::net::Ipv4Addr>,
+    ) -> Self {
+        self.addresses = Some((IpAddr::V4(src.into()), IpAddr::V4(dst.into())));
+        self
+    }
+
+    pub fn ipv6(
+        mut self,
+        src: impl Into<std::net::Ipv6Addr>,
+        dst: impl Into<std::net::Ipv6Addr>,
+    ) -> Self {
+        self.addresses = Some((IpAddr::V6(src.into()), IpAddr::V6(dst.into())));
+        self
+    }
+
+    pub fn udp(mut self, src_port: u16, dst_port: u16) -> Self {
+        self.transport = Some(Transport::Udp { src_port, dst_port });
+        self
+    }
+
+    /// TCP 分段，标志位默认为 PSH | ACK
+    pub fn tcp(mut self, src_port: u16, dst_port: u16, seq_num: u32) -> Self {
+        self.transport = Some(Transport::Tcp {
+            src_port,
+            dst_port,
+            seq_num,
+            flags: TCP_PSH | TCP_ACK,
+        });
+        self
+    }
+
+    /// 设置 TCP 标志位，需在 `tcp` 之后调用
+    pub fn tcp_flags(mut self, value: u8) -> Self {
+        if let Some(Transport::Tcp { flags, .. }) = &mut self.transport {
+            *flags = value;
+        }
+        self
+    }
+
+    /// 设置 SomeIP 头部，length 字段在构造时按负载长度自动填写
+    pub fn someip_header(
+        mut self,
+        service_id: u16,
+        method_id: u16,
+        client_id: u16,
+        session_id: u16,
+        message_type: MessageType,
+    ) -> Self {
+        self.someip_header = Some(SomeIPHeader {
+            service_id,
+            method_id,
+            length: 8,
+            client_id,
+            session_id,
+            protocol_version: 1,
+            interface_version: 1,
+            is_tp: false,
+            message_type,
+            return_code: ReturnCode::Ok,
+        });
+        self
+    }
+
+    pub fn payload(mut self, bytes: impl AsRef<[u8]>) -> Self {
+        self.payload = bytes.as_ref().to_vec();
+        self
+    }
+
+    /// 生成完整的链路层帧，可直接作为 `RawPacket::data`
+    pub fn build(&self) -> Vec<u8> {
+        // SomeIP 层
+        let mut data = match &self.someip_header {
+            Some(header) => {
+                let mut header = header.clone();
+                header.length = self.payload.len() as u32 + 8;
+                encode_someip_message(&header, &self.payload)
+                    .expect("length field is computed from the payload")
+            }
+            None => self.payload.clone(),
+        };
+
+        // 传输层
+        let (src_ip, dst_ip) = self.addresses.unwrap_or((
+            IpAddr::V4([127, 0, 0, 1].into()),
+            IpAddr::V4([127, 0, 0, 1].into()),
+        ));
+        let protocol = match self.transport {
+            Some(Transport::Udp { src_port, dst_port }) => {
+                let mut segment = Vec::with_capacity(8 + data.len());
+                segment.extend_from_slice(&src_port.to_be_bytes());
+                segment.extend_from_slice(&dst_port.to_be_bytes());
+                segment.extend_from_slice(&(8 + data.len() as u16).to_be_bytes());
+                segment.extend_from_slice(&[0, 0]);
+                segment.extend_from_slice(&data);
+                let checksum = match transport_checksum(&src_ip, &dst_ip, 17, &segment) {
+                    0 => 0xFFFF, // UDP 中 0 表示未计算校验和
+                    checksum => checksum,
+                };
+                segment[6..8].copy_from_slice(&checksum.to_be_bytes());
+                data = segment;
+                17
+            }
+            Some(Transport::Tcp {
+                src_port,
+                dst_port,
+                seq_num,
+                flags,
+            }) => {
+                let mut segment = Vec::with_capacity(20 + data.len());
+                segment.extend_from_slice(&src_port.to_be_bytes());
+                segment.extend_from_slice(&dst_port.to_be_bytes());
+                segment.extend_from_slice(&seq_num.to_be_bytes());
+                segment.extend_from_slice(&0u32.to_be_bytes()); // 确认号
+                segment.push(5 << 4); // 数据偏移：20 字节
+                segment.push(flags);
+                segment.extend_from_slice(&u16::MAX.to_be_bytes()); // 窗口
+                segment.extend_from_slice(&[0, 0, 0, 0]); // 校验和、紧急指针
+                segment.extend_from_slice(&data);
+                let checksum = transport_checksum(&src_ip, &dst_ip, 6, &segment);
+                segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+                data = segment;
+                6
+            }
+            None => 17,
+        };
+
+        // 网络层
+        let (ethertype, packet) = match (src_ip, dst_ip) {
+            (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                let mut packet = Vec::with_capacity(40 + data.len());
+                packet.extend_from_slice(&0x6000_0000u32.to_be_bytes());
+                packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                packet.push(protocol);
+                packet.push(64); // 跳数限制
+                packet.extend_from_slice(&src.octets());
+                packet.extend_from_slice(&dst.octets());
+                packet.extend_from_slice(&data);
+                (0x86DDu16, packet)
+            }
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                let mut header = Vec::with_capacity(20);
+                header.extend_from_slice(&[0x45, 0]);
+                header.extend_from_slice(&(20 + data.len() as u16).to_be_bytes());
+                header.extend_from_slice(&[0, 0, 0x40, 0]); // 标识、DF 标志
+                header.extend_from_slice(&[64, protocol, 0, 0]);
+                header.extend_from_slice(&src.octets());
+                header.extend_from_slice(&dst.octets());
+                let checksum = internet_checksum(&[&header]);
+                header[10..12].copy_from_slice(&checksum.to_be_bytes());
+                header.extend_from_slice(&data);
+                (0x0800u16, header)
+            }
+            _ => panic!("IPv4 and IPv6 addresses cannot be mixed"),
+        };
+
+        // 链路层
+        let mut frame = Vec::with_capacity(packet.len() + 22);
+        match &self.link {
+            Link::Ethernet {
+                vlan_ids,
+                mpls_labels,
+            } => {
+                frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x02]); // 目的 MAC
+                frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]); // 源 MAC
+                for vlan_id in vlan_ids {
+                    frame.extend_from_slice(&0x8100u16.to_be_bytes());
+                    frame.extend_from_slice(&vlan_id.to_be_bytes());
+                }
+                if mpls_labels.is_empty() {
+                    frame.extend_from_slice(&ethertype.to_be_bytes());
+                } else {
+                    frame.extend_from_slice(&0x8847u16.to_be_bytes());
+                    for (index, label) in mpls_labels.iter().enumerate() {
+                        let bottom_of_stack = (index + 1 == mpls_labels.len()) as u32;
+                        let entry = label << 12 | bottom_of_stack << 8 | 64; // TTL 64
+                        frame.extend_from_slice(&entry.to_be_bytes());
+                    }
+                }
+            }
+            Link::Sll => {
+                frame.extend_from_slice(&0u16.to_be_bytes()); // 发往本机
+                frame.extend_from_slice(&1u16.to_be_bytes()); // ARPHRD_ETHER
+                frame.extend_from_slice(&6u16.to_be_bytes());
+                frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01, 0, 0]);
+                frame.extend_from_slice(&ethertype.to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&packet);
+        frame
+    }
+}
+
+fn transport_checksum(src_ip: &IpAddr, dst_ip: &IpAddr, protocol: u8, segment: &[u8]) -> u16 {
+    let length = segment.len() as u32;
+    match (src_ip, dst_ip) {
+        (IpAddr::V6(src), IpAddr::V6(dst)) => internet_checksum(&[
+            &src.octets(),
+            &dst.octets(),
+            &length.to_be_bytes(),
+            &[0, 0, 0, protocol],
+            segment,
+        ]),
+        (IpAddr::V4(src), IpAddr::V4(dst)) => internet_checksum(&[
+            &src.octets(),
+            &dst.octets(),
+            &[0, protocol],
+            &(length as u16).to_be_bytes(),
+            segment,
+        ]),
+        _ => 0,
+    }
+}
+
+/// 构造经典 PCAP 文件（微秒精度，小端字节序）
+pub struct PcapFileBuilder {
+    link_type: LinkType,
+    packets: Vec<(SystemTime, Vec<u8>)>,
+}
+
+impl PcapFileBuilder {
+    pub fn new(link_type: LinkType) -> Self {
+        Self {
+            link_type,
+            packets: Vec::new(),
+        }
+    }
+
+    pub fn packet(mut self, timestamp: SystemTime, packet: &PacketBuilder) -> Self {
+        self.packets.push((timestamp, packet.build()));
+        self
+    }
+
+    pub fn raw_packet(mut self, timestamp: SystemTime, data: Vec<u8>) -> Self {
+        self.packets.push((timestamp, data));
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let network: u32 = match self.link_type {
+            LinkType::Null => 0,
+            LinkType::Ethernet => 1,
+            LinkType::Raw => 101,
+            LinkType::LinuxSLL => 113,
+            LinkType::LinuxSLL2 => 276,
+            LinkType::Unknown(value) => value as u32,
+        };
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0xA1B2_C3D4u32.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&4u16.to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes()); // 时区偏移
+        bytes.extend_from_slice(&0u32.to_le_bytes()); // 时间戳精度
+        bytes.extend_from_slice(&65535u32.to_le_bytes()); // snaplen
+        bytes.extend_from_slice(&network.to_le_bytes());
+
+        for (timestamp, data) in &self.packets {
+            let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+            bytes.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+            bytes.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::File::create(path)?.write_all(&self.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::checksum::ChecksumVerifier;
+    use crate::parser::link_layer::{LinkLayer, parse_link_layer};
+    use crate::parser::network_layer::{NetworkLayer, parse_network_layer};
+    use crate::parser::someip::header::parse_someip_header;
+    use crate::parser::transport_layer::{TransportLayer, parse_transport_layer};
+    use bytes::Bytes;
+
+    /// 逐层解析构造的帧，返回链路层、传输层与 SomeIP 负载
+    fn decode(frame: &[u8], link_type: LinkType) -> (LinkLayer, TransportLayer, Vec<u8>) {
+        let (rest, link) = parse_link_layer(frame, link_type).unwrap();
+        let ethertype = match &link {
+            LinkLayer::Ethernet(frame) => frame.ethertype,
+            LinkLayer::SLL(header) => header.protocol,
+            other => panic!("unexpected link layer {other:?}"),
+        };
+        let (payload, network) = parse_network_layer(rest, ethertype).unwrap();
+        let protocol = match network {
+            NetworkLayer::IPv4(ip) => ip.protocol,
+            NetworkLayer::IPv6(ip) => ip.next_header,
+        };
+        let (_, transport) =
+            parse_transport_layer(&Bytes::copy_from_slice(payload), protocol).unwrap();
+        let someip = match &transport {
+            TransportLayer::UDP(udp) => udp.payload.to_vec(),
+            TransportLayer::TCP(tcp) => tcp.payload.to_vec(),
+        };
+        (link, transport, someip)
+    }
+
+    #[test]
+    fn udp_frame_round_trips_through_parsers() {
+        let frame = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, 0x8001, 0, 7, MessageType::Notification)
+            .payload([0xDE, 0xAD])
+            .build();
+
+        let (_, transport, someip) = decode(&frame, LinkType::Ethernet);
+        let TransportLayer::UDP(udp) = transport else {
+            panic!("expected UDP");
+        };
+        assert_eq!((udp.src_port, udp.dst_port), (30501, 30502));
+        let (payload, header) = parse_someip_header(&someip).unwrap();
+        assert_eq!(header.service_id, 0x1234);
+        assert_eq!(header.method_id, 0x8001);
+        assert_eq!(header.session_id, 7);
+        assert_eq!(header.length, 10);
+        assert_eq!(header.message_type, MessageType::Notification);
+        assert_eq!(payload, [0xDE, 0xAD]);
+    }
+
+    #[test]
+    fn tcp_frame_carries_sequence_number() {
+        let frame = PacketBuilder::new()
+            .ipv6(std::net::Ipv6Addr::LOCALHOST, std::net::Ipv6Addr::LOCALHOST)
+            .tcp(30509, 40000, 0xFFFF_FF00)
+            .someip_header(0x1234, 0x0001, 1, 1, MessageType::Request)
+            .build();
+
+        let (_, transport, someip) = decode(&frame, LinkType::Ethernet);
+        let TransportLayer::TCP(tcp) = transport else {
+            panic!("expected TCP");
+        };
+        assert_eq!(tcp.seq_num, 0xFFFF_FF00);
+        assert!(tcp.flags.psh && tcp.flags.ack);
+        assert_eq!(someip.len(), 16);
+    }
+
+    #[test]
+    fn vlan_mpls_and_sll_link_layers() {
+        let packet = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(1, 2)
+            .payload(b"x");
+
+        let (link, _, payload) = decode(
+            &packet.clone().vlan(5).mpls(100).build(),
+            LinkType::Ethernet,
+        );
+        let LinkLayer::Ethernet(ethernet) = link else {
+            panic!("expected Ethernet");
+        };
+        assert_eq!(ethernet.vlan_ids, [5]);
+        assert_eq!(ethernet.mpls_labels.len(), 1);
+        assert_eq!(ethernet.mpls_labels[0].label, 100);
+        assert_eq!(payload, b"x");
+
+        let (link, _, payload) = decode(&packet.sll().build(), LinkType::LinuxSLL);
+        assert!(matches!(link, LinkLayer::SLL(_)));
+        assert_eq!(payload, b"x");
+    }
+
+    #[test]
+    fn checksums_are_valid() {
+        let frame = PacketBuilder::new()
+            .ipv4([192, 168, 0, 1], [192, 168, 0, 2])
+            .udp(30490, 30490)
+            .payload([1, 2, 3])
+            .build();
+        let ip = &frame[14..];
+        let mut verifier = ChecksumVerifier::new(false);
+        assert!(verifier.check_ipv4_header(&ip[..20]));
+        let src = IpAddr::V4([192, 168, 0, 1].into());
+        let dst = IpAddr::V4([192, 168, 0, 2].into());
+        assert!(verifier.check_transport(&src, &dst, 17, &ip[20..]));
+        assert_eq!(verifier.ipv4_failures + verifier.udp_failures, 0);
+    }
+
+    #[test]
+    fn pcap_file_has_global_and_record_headers() {
+        let packet = PacketBuilder::new().udp(1, 2).payload(b"abc");
+        let frame_len = packet.build().len();
+        let timestamp = UNIX_EPOCH + std::time::Duration::new(5, 250_000_000);
+        let bytes = PcapFileBuilder::new(LinkType::Ethernet)
+            .packet(timestamp, &packet)
+            .build();
+
+        assert_eq!(bytes.len(), 24 + 16 + frame_len);
+        assert_eq!(bytes[..4], 0xA1B2_C3D4u32.to_le_bytes());
+        assert_eq!(bytes[20..24], 1u32.to_le_bytes());
+        assert_eq!(bytes[24..28], 5u32.to_le_bytes());
+        assert_eq!(bytes[28..32], 250_000u32.to_le_bytes());
+        assert_eq!(bytes[32..36], (frame_len as u32).to_le_bytes());
+    }
+}
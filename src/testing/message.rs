@@ -0,0 +1,73 @@
+// src/testing/message.rs
+use crate::parser::someip::header::{MessageType, ReturnCode, SomeIPHeader};
+use crate::parser::someip::session::SomeIPMessage;
+use crate::parser::transport_layer::{TCPFlags, TCPPacketInfo, TcpOptions};
+use bytes::Bytes;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::UNIX_EPOCH;
+
+/// 构造已解析的 SomeIP 消息：空负载，时间戳为 UNIX 纪元，
+/// 端点为 10.0.0.1:30501 -> 10.0.0.2:30502，其余字段可直接修改
+pub fn someip_message(
+    service_id: u16,
+    method_id: u16,
+    session_id: u16,
+    message_type: MessageType,
+) -> SomeIPMessage {
+    SomeIPMessage {
+        timestamp: UNIX_EPOCH,
+        header: SomeIPHeader {
+            service_id,
+            method_id,
+            length: 8,
+            client_id: 0x0001,
+            session_id,
+            protocol_version: 1,
+            interface_version: 1,
+            is_tp: false,
+            message_type,
+            return_code: ReturnCode::Ok,
+        },
+        payload: Vec::new(),
+        src_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+        src_port: 30501,
+        dst_port: 30502,
+        vlan_id: None,
+        tunnel: None,
+        instance_id: None,
+        latency_ms: None,
+        subscription: None,
+        e2e: None,
+        resynced: false,
+    }
+}
+
+/// 构造已解析的 TCP 头部：目的端口 30502，只带 ACK 标志，窗口 65535，无选项；
+/// 负载单独传给 `TcpFlowController`，标志与选项可直接修改
+pub fn tcp_segment(src_port: u16, seq_num: u32) -> TCPPacketInfo {
+    TCPPacketInfo {
+        src_port,
+        dst_port: 30502,
+        seq_num,
+        ack_num: 0,
+        data_offset: 5,
+        reserved: 0,
+        flags: TCPFlags {
+            ns: false,
+            cwr: false,
+            ece: false,
+            urg: false,
+            ack: true,
+            psh: false,
+            rst: false,
+            syn: false,
+            fin: false,
+        },
+        window_size: 0xFFFF,
+        checksum: 0,
+        urgent_ptr: 0,
+        options: TcpOptions::default(),
+        payload: Bytes::new(),
+    }
+}
@@ -0,0 +1,5 @@
+//! 测试辅助模块，用于构造合成数据包、PCAP 文件、SD 消息与已解析的消息（需启用 `testing` 特性，本 crate 的测试中总是可用）
+
+pub mod builder;
+pub mod message;
+pub mod sd;
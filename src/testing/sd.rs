@@ -0,0 +1,99 @@
+// src/testing/sd.rs
+use crate::parser::someip::header::{MessageType, ReturnCode, SomeIPHeader};
+use crate::parser::someip::sd_parser::{
+    Ipv4EndpointOption, OfferServiceEntry, SDEntry, SDFlags, SDOption, SDPacket,
+    SubscribeEventgroupAckEntry, SubscribeEventgroupEntry, TransportProtocol,
+};
+
+/// 构造 SD 消息，条目与选项按给定顺序排列
+pub fn sd_packet(
+    session_id: u16,
+    reboot: bool,
+    unicast: bool,
+    entries: Vec<SDEntry>,
+    options: Vec<SDOption>,
+) -> SDPacket {
+    SDPacket {
+        header: SomeIPHeader {
+            service_id: 0xFFFF,
+            method_id: 0x8100,
+            length: 8,
+            client_id: 0,
+            session_id,
+            protocol_version: 1,
+            interface_version: 1,
+            is_tp: false,
+            message_type: MessageType::Notification,
+            return_code: ReturnCode::Ok,
+        },
+        flags: SDFlags {
+            reboot,
+            unicast,
+            explicit_initial_data_control: false,
+        },
+        entries,
+        options,
+    }
+}
+
+/// OfferService 条目，引用从 `option_index` 开始的一个选项；TTL 为 0 表示 StopOffer
+pub fn offer(service_id: u16, instance_id: u16, ttl: u32, option_index: u8) -> SDEntry {
+    SDEntry::OfferService(OfferServiceEntry {
+        service_id,
+        instance_id,
+        major_version: 1,
+        ttl,
+        minor_version: 0,
+        first_options_index: option_index,
+        number_of_first_options: 1,
+        second_options_index: 0,
+        number_of_second_options: 0,
+    })
+}
+
+/// SubscribeEventgroup 条目，引用从 `option_index` 开始的一个选项；TTL 为 0 表示 StopSubscribe
+pub fn subscribe(
+    service_id: u16,
+    instance_id: u16,
+    eventgroup_id: u16,
+    ttl: u32,
+    option_index: u8,
+) -> SDEntry {
+    SDEntry::SubscribeEventgroup(SubscribeEventgroupEntry {
+        service_id,
+        instance_id,
+        major_version: 1,
+        ttl,
+        eventgroup_id,
+        reserved: 0,
+        first_options_index: option_index,
+        number_of_first_options: 1,
+        second_options_index: 0,
+        number_of_second_options: 0,
+    })
+}
+
+/// SubscribeEventgroupAck 条目，不引用选项；TTL 为 0 表示 NACK
+pub fn subscribe_ack(service_id: u16, instance_id: u16, eventgroup_id: u16, ttl: u32) -> SDEntry {
+    SDEntry::SubscribeEventgroupAck(SubscribeEventgroupAckEntry {
+        service_id,
+        instance_id,
+        major_version: 1,
+        ttl,
+        eventgroup_id,
+        reserved: 0,
+        first_options_index: 0,
+        number_of_first_options: 0,
+        second_options_index: 0,
+        number_of_second_options: 0,
+    })
+}
+
+/// IPv4 UDP 端点选项
+pub fn udp_endpoint(ip_address: [u8; 4], port: u16) -> SDOption {
+    SDOption::Ipv4Endpoint(Ipv4EndpointOption {
+        ip_address,
+        transport_protocol: TransportProtocol::UDP,
+        port,
+    })
+}
@@ -0,0 +1,53 @@
+// src/utils/hexdump.rs
+
+/// 按 `hexdump -C` 的格式输出：每行 16 字节，依次为偏移、十六进制（8 字节一组）与可打印 ASCII
+///
+/// 每行末尾带换行符，空数据返回空字符串。
+pub fn hexdump(data: &[u8]) -> String {
+    let mut output = String::new();
+    for (line, chunk) in data.chunks(16).enumerate() {
+        let mut hex = String::with_capacity(49);
+        for (index, byte) in chunk.iter().enumerate() {
+            if index == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", byte));
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        output.push_str(&format!("{:08x}  {:<49} |{}|\n", line * 16, hex, ascii));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn twenty_bytes_span_two_lines() {
+        let data: Vec<u8> = (0x41..0x55).collect();
+        assert_eq!(
+            hexdump(&data),
+            "00000000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50  |ABCDEFGHIJKLMNOP|\n\
+             00000010  51 52 53 54                                       |QRST|\n"
+        );
+    }
+
+    #[test]
+    fn non_printable_bytes_shown_as_dots() {
+        assert_eq!(
+            hexdump(&[0x00, b' ', 0x7F, b'~']),
+            "00000000  00 20 7f 7e                                       |. .~|\n"
+        );
+        assert_eq!(hexdump(&[]), "");
+    }
+}
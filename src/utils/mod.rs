@@ -1,3 +1,4 @@
 //! 工具模块，提供时间戳处理和TCP流控等辅助功能
 
+pub mod hexdump;
 pub mod timestamp;
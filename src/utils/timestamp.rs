@@ -2,20 +2,59 @@
 use chrono::{DateTime, TimeZone, Utc};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn system_time_to_utc(time: SystemTime) -> DateTime<Utc> {
-    let duration = time
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
+/// 无法表示为 UTC 时间的时间戳的输出占位符
+pub const INVALID_TIMESTAMP: &str = "<invalid timestamp>";
 
-    Utc.timestamp_opt(duration.as_secs() as i64, duration.subsec_nanos())
-        .unwrap()
+/// 早于 1970 年或超出 chrono 可表示范围时返回 None
+pub fn system_time_to_utc(time: SystemTime) -> Option<DateTime<Utc>> {
+    let duration = time.duration_since(UNIX_EPOCH).ok()?;
+    let secs = i64::try_from(duration.as_secs()).ok()?;
+    Utc.timestamp_opt(secs, duration.subsec_nanos()).single()
 }
 
+/// 格式化为 UTC 时间（毫秒精度），无法表示的时间戳输出占位符，而不是中断导出
 pub fn format_timestamp(time: &SystemTime) -> String {
-    let utc = system_time_to_utc(*time);
-    utc.format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+    match system_time_to_utc(*time) {
+        Some(utc) => utc.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        None => INVALID_TIMESTAMP.to_string(),
+    }
 }
 
 pub fn format_duration_ms(duration: std::time::Duration) -> String {
     format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
 }
+
+/// 解析 RFC3339 时间（如 2024-01-01T08:00:00+08:00）或 Unix 纪元秒数（可带小数）
+pub fn parse_timestamp(value: &str) -> Result<SystemTime, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.into());
+    }
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(|secs| UNIX_EPOCH + std::time::Duration::from_secs_f64(secs))
+        .ok_or_else(|| format!("无效的时间（需要 RFC3339 或 Unix 秒数）: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_millisecond_utc_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        assert_eq!(format_timestamp(&time), "2023-11-14 22:13:20.250");
+    }
+
+    #[test]
+    fn pre_epoch_and_out_of_range_times_use_placeholder() {
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(1);
+        assert_eq!(system_time_to_utc(before_epoch), None);
+        assert_eq!(format_timestamp(&before_epoch), INVALID_TIMESTAMP);
+
+        let far_future = UNIX_EPOCH + Duration::from_secs(i64::MAX as u64);
+        assert_eq!(format_timestamp(&far_future), INVALID_TIMESTAMP);
+    }
+}
@@ -0,0 +1,1393 @@
+//! 以子进程方式运行命令行程序的端到端测试
+use someip_parser::parser::link_layer::LinkType;
+use someip_parser::parser::pcap_reader::{InputFormat, PCAPReader};
+use someip_parser::parser::someip::header::{
+    MessageType, ReturnCode, SomeIPHeader, encode_someip_message,
+};
+use someip_parser::parser::someip::tp_parser::TPSegment;
+use someip_parser::testing::builder::{
+    PacketBuilder, PcapFileBuilder, TCP_ACK, TCP_FIN, TCP_PSH, TCP_RST, TCP_SYN,
+};
+use someip_parser::testing::sd::{offer, sd_packet, subscribe, udp_endpoint};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// 运行程序，`stdin` 非空时写入其标准输入
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_someip-parser"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// 两个 SomeIP 通知组成的抓包文件，目的端口 30502 需作为 `--sd-port` 传入才会按 SomeIP 解析
+fn notifications() -> Vec<u8> {
+    let packet = |session_id| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, 0x8001, 0, session_id, MessageType::Notification)
+            .payload([0xAB])
+    };
+    PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH + Duration::from_secs(1), &packet(1))
+        .packet(UNIX_EPOCH + Duration::from_secs(2), &packet(2))
+        .build()
+}
+
+#[test]
+fn reads_capture_from_stdin() {
+    let output = run(
+        &["-p", "-", "--sd-port", "30502", "--output-format", "json"],
+        &notifications(),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let messages = messages.as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["service"], "0x1234");
+    assert_eq!(messages[1]["payload"], "ab");
+}
+
+/// 从 `ip` 发出的 SD 多播报文，在 `ip:30501` 上提供服务 0x1234 的实例 `instance_id`
+fn offer_from(ip: [u8; 4], instance_id: u16) -> PacketBuilder {
+    let sd = sd_packet(
+        1,
+        true,
+        true,
+        vec![offer(0x1234, instance_id, 3, 0)],
+        vec![udp_endpoint(ip, 30501)],
+    );
+    PacketBuilder::new()
+        .ipv4(ip, [224, 224, 224, 245])
+        .udp(30490, 30490)
+        .payload(sd.to_bytes())
+}
+
+fn notification_from(ip: [u8; 4]) -> PacketBuilder {
+    PacketBuilder::new()
+        .ipv4(ip, [10, 0, 0, 2])
+        .udp(30501, 30502)
+        .someip_header(0x1234, 0x8001, 0, 1, MessageType::Notification)
+}
+
+#[test]
+fn resolves_instance_by_provider_address() {
+    // 两个 ECU 在同一端口上提供同一服务的不同实例
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH, &offer_from([10, 0, 0, 1], 1))
+        .packet(UNIX_EPOCH, &offer_from([10, 0, 0, 3], 2))
+        .packet(UNIX_EPOCH, &notification_from([10, 0, 0, 3]))
+        .packet(UNIX_EPOCH, &notification_from([10, 0, 0, 1]))
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--service-id",
+            "0x1234",
+            "--output-format",
+            "json",
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let instances: Vec<_> = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|message| (message["sender"].clone(), message["instance"].clone()))
+        .collect();
+    assert_eq!(
+        instances,
+        [
+            ("10.0.0.3".into(), "0x0002".into()),
+            ("10.0.0.1".into(), "0x0001".into())
+        ]
+    );
+}
+
+#[test]
+fn id_filters_read_bare_values_as_hex() {
+    let packet = |service_id, method_id| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(service_id, method_id, 0, 1, MessageType::Notification)
+    };
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH, &packet(0x1234, 0x8001))
+        .packet(UNIX_EPOCH, &packet(1234, 0x8001))
+        .packet(UNIX_EPOCH, &packet(0x1234, 0x8002))
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "--service-id",
+            "1234",
+            "--method-id",
+            "8001",
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let messages = messages.as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["raw"]["header"]["service_id"], 0x1234);
+    assert_eq!(messages[0]["raw"]["header"]["method_id"], 0x8001);
+}
+
+#[test]
+fn stop_offer_withdraws_learned_port() {
+    let stop_offer = PacketBuilder::new()
+        .ipv4([10, 0, 0, 1], [224, 224, 224, 245])
+        .udp(30490, 30490)
+        .payload(
+            sd_packet(
+                2,
+                true,
+                true,
+                vec![offer(0x1234, 1, 0, 0)],
+                vec![udp_endpoint([10, 0, 0, 1], 30501)],
+            )
+            .to_bytes(),
+        );
+    let notification = |session_id| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, 0x8001, 0, session_id, MessageType::Notification)
+    };
+    // 端口 30501 只在 Offer 与 StopOffer 之间被当作 SomeIP 端口
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH, &notification(1))
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(1),
+            &offer_from([10, 0, 0, 1], 1),
+        )
+        .packet(UNIX_EPOCH + Duration::from_secs(2), &notification(2))
+        .packet(UNIX_EPOCH + Duration::from_secs(3), &stop_offer)
+        .packet(UNIX_EPOCH + Duration::from_secs(4), &notification(3))
+        .build();
+    let output = run(
+        &["-p", "-", "--service-id", "1234", "--output-format", "json"],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let sessions: Vec<_> = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|msg| msg["raw"]["header"]["session_id"].clone())
+        .collect();
+    assert_eq!(sessions, [2]);
+}
+
+#[test]
+fn timeout_sweep_emits_expired_request_before_later_packets() {
+    let request = PacketBuilder::new()
+        .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+        .udp(30501, 30502)
+        .someip_header(0x1234, 0x0001, 0, 1, MessageType::Request);
+    // 请求在抓包时间 5 秒时超时，6 秒时才有下一个数据包
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH, &request)
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(6),
+            &notification_from([10, 0, 0, 1]),
+        )
+        .build();
+    let message_types = |extra: &[&str]| {
+        let mut args = vec!["-p", "-", "--sd-port", "30502", "--output-format", "json"];
+        args.extend_from_slice(extra);
+        let output = run(&args, &capture);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        messages
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|msg| msg["message_type"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        message_types(&["--timeout-sweep-interval", "1"]),
+        ["REQUEST", "NOTIFICATION"]
+    );
+    // 不定期检查时超时的请求在文件末尾才输出
+    assert_eq!(message_types(&[]), ["NOTIFICATION", "REQUEST"]);
+}
+
+#[test]
+fn parse_depth_stops_at_requested_layer() {
+    let flows = |depth: &str| {
+        let output = run(
+            &[
+                "-p",
+                "-",
+                "--sd-port",
+                "30502",
+                "--output-format",
+                "json",
+                "--parse-depth",
+                depth,
+            ],
+            &notifications(),
+        );
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let flows: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let flows = flows.as_array().unwrap().clone();
+        assert_eq!(flows.len(), 1, "{depth}: {flows:?}");
+        // 只输出流记录，不包含 SomeIP 消息字段
+        assert!(flows[0].get("raw").is_none() && flows[0].get("service").is_none());
+        assert_eq!(flows[0]["packets"], 2);
+        let field = |name: &str| flows[0][name].clone();
+        (
+            field("protocol"),
+            field("src"),
+            field("dst"),
+            field("bytes"),
+        )
+    };
+
+    // 每个数据包：以太网 14 + IPv4 20 + UDP 8 + SomeIP 头部 16 + 负载 1 字节
+    assert_eq!(
+        flows("link"),
+        (
+            "0x0800".into(),
+            "02:00:00:00:00:01".into(),
+            "02:00:00:00:00:02".into(),
+            (2 * 59).into()
+        )
+    );
+    assert_eq!(
+        flows("network"),
+        (
+            "UDP".into(),
+            "10.0.0.1".into(),
+            "10.0.0.2".into(),
+            (2 * 45).into()
+        )
+    );
+    assert_eq!(
+        flows("transport"),
+        (
+            "UDP".into(),
+            "10.0.0.1:30501".into(),
+            "10.0.0.2:30502".into(),
+            (2 * 17).into()
+        )
+    );
+}
+
+fn header(service_id: u16, session_id: u16) -> SomeIPHeader {
+    SomeIPHeader {
+        service_id,
+        method_id: 0x8001,
+        length: 8,
+        client_id: 0,
+        session_id,
+        protocol_version: 1,
+        interface_version: 1,
+        is_tp: false,
+        message_type: MessageType::Notification,
+        return_code: ReturnCode::Ok,
+    }
+}
+
+/// 完整的 SomeIP 消息字节，length 字段按负载填写
+fn someip_bytes(service_id: u16, payload_len: usize) -> Vec<u8> {
+    let mut header = header(service_id, 1);
+    header.length = payload_len as u32 + 8;
+    encode_someip_message(&header, &vec![0x5A; payload_len]).unwrap()
+}
+
+/// 以 `--write-pcap` 运行并读回导出的帧数据
+fn write_pcap(capture: &[u8], service_id: &str) -> Vec<Vec<u8>> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.pcap");
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--service-id",
+            service_id,
+            "--write-pcap",
+            path.to_str().unwrap(),
+        ],
+        capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut reader = PCAPReader::new(path.to_str().unwrap(), InputFormat::Auto).unwrap();
+    let mut frames = Vec::new();
+    while let Some(packet) = reader.next_packet().unwrap() {
+        assert_eq!(packet.orig_len as usize, packet.data.len());
+        frames.push(packet.data.to_vec());
+    }
+    frames
+}
+
+#[test]
+fn write_pcap_exports_every_segment_of_a_tcp_message() {
+    // 消息 A 跨三个分段，第三个分段同时携带消息 B 的开头
+    let mut stream = someip_bytes(0x1234, 2984);
+    stream.extend(someip_bytes(0x5678, 20));
+    let bounds = [0, 1000, 2000, 3010, stream.len()];
+    let segments: Vec<_> = bounds
+        .windows(2)
+        .map(|range| {
+            PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .tcp(40000, 30502, 1000 + range[0] as u32)
+                .payload(&stream[range[0]..range[1]])
+        })
+        .collect();
+    let mut capture = PcapFileBuilder::new(LinkType::Ethernet);
+    for (index, segment) in segments.iter().enumerate() {
+        capture = capture.packet(UNIX_EPOCH + Duration::from_millis(index as u64), segment);
+    }
+    let capture = capture.build();
+    let frames: Vec<_> = segments.iter().map(PacketBuilder::build).collect();
+
+    assert_eq!(write_pcap(&capture, "0x1234"), frames[..3]);
+    assert_eq!(write_pcap(&capture, "0x5678"), frames[2..]);
+}
+
+#[test]
+fn write_pcap_exports_every_tp_fragment() {
+    let fragment = |service_id, offset: u32, is_last| {
+        let segment = TPSegment {
+            header: SomeIPHeader {
+                is_tp: true,
+                ..header(service_id, 7)
+            },
+            is_first: offset == 0,
+            is_last,
+            offset,
+            payload: vec![0xA5; 16].into(),
+        };
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(40000, 30502)
+            .payload(segment.to_bytes())
+    };
+    let packets = [
+        fragment(0x1234, 0, false),
+        fragment(0x5678, 0, false),
+        fragment(0x1234, 16, true),
+        fragment(0x5678, 16, true),
+    ];
+    let mut capture = PcapFileBuilder::new(LinkType::Ethernet);
+    for packet in &packets {
+        capture = capture.packet(UNIX_EPOCH, packet);
+    }
+
+    assert_eq!(
+        write_pcap(&capture.build(), "0x1234"),
+        [packets[0].build(), packets[2].build()]
+    );
+}
+
+/// 以 `--strict` 解析单个帧，返回 (是否没有解析错误, 输出的消息数)
+fn parse_strict(frame: Vec<u8>) -> (bool, usize) {
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .raw_packet(UNIX_EPOCH, frame)
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--strict",
+            "--output-format",
+            "json",
+        ],
+        &capture,
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    (output.status.success(), messages.as_array().unwrap().len())
+}
+
+/// 以太网 + 20 字节 IPv4 头部之后 UDP 长度字段的偏移
+const UDP_LENGTH_OFFSET: usize = 14 + 20 + 4;
+
+#[test]
+fn udp_length_check_accepts_ethernet_padding() {
+    let mut frame = notification_from([10, 0, 0, 1]).build();
+    frame.extend_from_slice(&[0; 6]); // 以太网最小帧长填充
+    assert_eq!(parse_strict(frame), (true, 1));
+}
+
+#[test]
+fn udp_length_check_rejects_lying_length_on_someip_port() {
+    let mut frame = notification_from([10, 0, 0, 1]).build();
+    let length = u16::from_be_bytes([frame[UDP_LENGTH_OFFSET], frame[UDP_LENGTH_OFFSET + 1]]);
+    frame[UDP_LENGTH_OFFSET..UDP_LENGTH_OFFSET + 2].copy_from_slice(&(length + 100).to_be_bytes());
+    assert_eq!(parse_strict(frame), (false, 0));
+}
+
+#[test]
+fn udp_length_check_ignores_other_ports() {
+    let mut frame = PacketBuilder::new()
+        .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+        .udp(5353, 5353)
+        .payload([0; 32])
+        .build();
+    frame[UDP_LENGTH_OFFSET..UDP_LENGTH_OFFSET + 2].copy_from_slice(&1000u16.to_be_bytes());
+    assert_eq!(parse_strict(frame), (true, 0));
+}
+
+#[test]
+fn exit_code_reflects_parse_errors() {
+    let mut broken = notification_from([10, 0, 0, 1]).build();
+    let length = u16::from_be_bytes([broken[UDP_LENGTH_OFFSET], broken[UDP_LENGTH_OFFSET + 1]]);
+    broken[UDP_LENGTH_OFFSET..UDP_LENGTH_OFFSET + 2].copy_from_slice(&(length + 100).to_be_bytes());
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .raw_packet(UNIX_EPOCH, notification_from([10, 0, 0, 1]).build())
+        .raw_packet(UNIX_EPOCH + Duration::from_secs(1), broken.clone())
+        .raw_packet(UNIX_EPOCH + Duration::from_secs(2), broken)
+        .build();
+    let exit_code = |extra: &[&str]| {
+        let mut args = vec!["-p", "-", "--sd-port", "30502"];
+        args.extend_from_slice(extra);
+        run(&args, &capture).status.code()
+    };
+
+    assert_eq!(exit_code(&[]), Some(0));
+    assert_eq!(exit_code(&["--strict"]), Some(2));
+    assert_eq!(exit_code(&["--strict", "--error-exit-code", "7"]), Some(7));
+    assert_eq!(exit_code(&["--max-errors", "2"]), Some(0));
+    assert_eq!(exit_code(&["--max-errors", "1"]), Some(2));
+}
+
+/// 以太网 + IPv4 + UDP 头部之后 SomeIP 长度字段的偏移
+const SOMEIP_LENGTH_OFFSET: usize = 14 + 20 + 8 + 4;
+
+#[test]
+fn absurd_someip_length_is_rejected_early() {
+    let mut frame = notification_from([10, 0, 0, 1]).payload([0; 20]).build();
+    frame[SOMEIP_LENGTH_OFFSET..SOMEIP_LENGTH_OFFSET + 4]
+        .copy_from_slice(&0x1000_0000u32.to_be_bytes());
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .raw_packet(UNIX_EPOCH, frame)
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--strict",
+            "--verbose",
+            "--verbose",
+        ],
+        &capture,
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("SomeIP 长度字段 268435456 超过上限 65536"),
+        "{stderr}"
+    );
+
+    // 上限可以调低：长度正确但超过上限的消息同样被拒绝
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .raw_packet(
+            UNIX_EPOCH,
+            notification_from([10, 0, 0, 1]).payload([0; 20]).build(),
+        )
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--strict",
+            "--verbose",
+            "--verbose",
+            "--max-someip-length",
+            "16",
+        ],
+        &capture,
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("SomeIP 长度字段 28 超过上限 16"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn non_someip_packet_on_shared_port_is_skipped() {
+    // 同一端口上的其他协议：协议版本与消息类型都不是 SomeIP 的取值
+    let other = PacketBuilder::new()
+        .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+        .udp(30501, 30502)
+        .payload(*b"GET /status HTTP/1.0\r\n\r\n");
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH, &other)
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(1),
+            &notification_from([10, 0, 0, 1]).payload([0xAB]),
+        )
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--strict",
+            "--summary",
+            "--output-format",
+            "json",
+        ],
+        &capture,
+    );
+    // 跳过不算解析失败
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["payload"], "ab");
+    // 统计按输出格式以 JSON 写到标准错误
+    let summary: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(summary["skipped_non_someip"], 1);
+    assert_eq!(summary["parse_errors"], 0);
+}
+
+#[test]
+fn oversized_length_over_short_payload_is_malformed() {
+    // 16 字节头部 + 4 字节负载，length 字段却声称 0xFFFF
+    let mut frame = notification_from([10, 0, 0, 1]).payload([0; 4]).build();
+    frame[SOMEIP_LENGTH_OFFSET..SOMEIP_LENGTH_OFFSET + 4].copy_from_slice(&0xFFFFu32.to_be_bytes());
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .raw_packet(UNIX_EPOCH, frame)
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--strict",
+            "--verbose",
+            "--verbose",
+        ],
+        &capture,
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "{stderr}");
+    assert!(
+        stderr.contains("SomeIP 长度字段 65535 与 UDP 负载 20 字节不符（应在 8 到 12 之间）"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn non_first_ipv4_fragment_is_skipped() {
+    let mut frame = notification_from([10, 0, 0, 1]).build();
+    // 片偏移 185（1480 字节），其负载从 UDP 数据报中间开始
+    frame[14 + 6..14 + 8].copy_from_slice(&185u16.to_be_bytes());
+    assert_eq!(parse_strict(frame), (true, 0));
+}
+
+#[test]
+fn output_filter_keeps_request_for_pairing() {
+    let packet = |src, dst, src_port, dst_port, message_type| {
+        PacketBuilder::new()
+            .ipv4(src, dst)
+            .udp(src_port, dst_port)
+            .someip_header(0x1234, 0x0001, 1, 7, message_type)
+            .payload([0xAB])
+    };
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(1),
+            &packet(
+                [10, 0, 0, 1],
+                [10, 0, 0, 2],
+                30501,
+                30502,
+                MessageType::Request,
+            ),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_millis(1500),
+            &packet(
+                [10, 0, 0, 2],
+                [10, 0, 0, 1],
+                30502,
+                30501,
+                MessageType::Response,
+            ),
+        )
+        .build();
+    // 只输出响应：请求被过滤掉，但仍需参与配对
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "--message-type",
+            "0x80",
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let messages = messages.as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["latency_ms"], 500.0);
+}
+
+#[test]
+fn e2e_checked_only_for_configured_messages() {
+    // 负载为 P05 保护的数据（数据 ID 0x1234，计数器 5）
+    let packet = |method_id| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, method_id, 0, 1, MessageType::Notification)
+            .payload([0x00, 0x16, 0x05, 0x11, 0x22, 0x33, 0x44])
+    };
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH + Duration::from_secs(1), &packet(0x8001))
+        .packet(UNIX_EPOCH + Duration::from_secs(2), &packet(0x8002))
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "--e2e-profile",
+            "p05",
+            "--e2e-data-id",
+            "0x1234",
+            "--e2e-id",
+            "0x1234:0x8001",
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(messages[0]["e2e_crc_valid"], true);
+    assert_eq!(messages[0]["payload"], "11223344");
+    assert!(messages[1].get("e2e_crc_valid").is_none());
+    assert_eq!(messages[1]["payload"], "00160511223344");
+}
+
+#[test]
+fn payload_filter_sees_e2e_stripped_payload() {
+    let packet = |method_id| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, method_id, 0, 1, MessageType::Notification)
+            .payload([0x00, 0x16, 0x05, 0x11, 0x22, 0x33, 0x44])
+    };
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH + Duration::from_secs(1), &packet(0x8001))
+        .packet(UNIX_EPOCH + Duration::from_secs(2), &packet(0x8002))
+        .build();
+    let dir = tempfile::tempdir().unwrap();
+    let pcap = dir.path().join("out.pcap");
+    // 模式只出现在 E2E 头部中：受保护的消息剥离后不再匹配，导出的帧也不应包含它
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "--e2e-profile",
+            "p05",
+            "--e2e-data-id",
+            "0x1234",
+            "--e2e-id",
+            "0x1234:0x8001",
+            "--payload-pattern",
+            "00160511",
+            "--write-pcap",
+            pcap.to_str().unwrap(),
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let messages = messages.as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["raw"]["header"]["method_id"], 0x8002);
+
+    let mut reader = PCAPReader::new(pcap.to_str().unwrap(), InputFormat::Auto).unwrap();
+    let mut frames = 0;
+    while reader.next_packet().unwrap().is_some() {
+        frames += 1;
+    }
+    assert_eq!(frames, 1);
+}
+
+/// 从 10.0.0.1:40000 发往 10.0.0.2:30502 的 TCP 分段 (序列号, 标志位, 数据)，返回输出消息的负载长度
+fn tcp_payload_lengths(segments: &[(u32, u8, Vec<u8>)]) -> Vec<u64> {
+    let mut capture = PcapFileBuilder::new(LinkType::Ethernet);
+    for (index, (seq_num, flags, data)) in segments.iter().enumerate() {
+        let packet = PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .tcp(40000, 30502, *seq_num)
+            .tcp_flags(*flags)
+            .payload(data);
+        capture = capture.packet(UNIX_EPOCH + Duration::from_millis(index as u64), &packet);
+    }
+    let output = run(
+        &["-p", "-", "--sd-port", "30502", "--output-format", "json"],
+        &capture.build(),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|msg| msg["payload_length"].as_u64().unwrap())
+        .collect()
+}
+
+#[test]
+fn tcp_syn_discards_partial_message() {
+    let partial = someip_bytes(0x1234, 100)[..30].to_vec();
+    let complete = someip_bytes(0x1234, 4);
+    // 不重置时新消息会被拼接在未完成的消息之后，两者都无法输出
+    let interrupted = [
+        (1000, TCP_PSH | TCP_ACK, partial.clone()),
+        (1030, TCP_PSH | TCP_ACK, complete.clone()),
+    ];
+    assert!(tcp_payload_lengths(&interrupted).is_empty());
+
+    let restarted = [
+        (1000, TCP_PSH | TCP_ACK, partial),
+        (5000, TCP_SYN, Vec::new()),
+        (5001, TCP_PSH | TCP_ACK, complete),
+    ];
+    assert_eq!(tcp_payload_lengths(&restarted), [4]);
+}
+
+#[test]
+fn tcp_rst_discards_partial_message() {
+    let segments = [
+        (
+            1000,
+            TCP_PSH | TCP_ACK,
+            someip_bytes(0x1234, 100)[..30].to_vec(),
+        ),
+        (1030, TCP_RST, Vec::new()),
+        (1030, TCP_PSH | TCP_ACK, someip_bytes(0x1234, 4)),
+    ];
+    assert_eq!(tcp_payload_lengths(&segments), [4]);
+}
+
+#[test]
+fn tcp_fin_discards_partial_message() {
+    // FIN 之后同一四元组上中途接续的数据从新的消息边界开始
+    let segments = [
+        (
+            1000,
+            TCP_PSH | TCP_ACK,
+            someip_bytes(0x1234, 100)[..30].to_vec(),
+        ),
+        (1030, TCP_FIN | TCP_ACK, Vec::new()),
+        (1030, TCP_PSH | TCP_ACK, someip_bytes(0x1234, 4)),
+    ];
+    assert_eq!(tcp_payload_lengths(&segments), [4]);
+}
+
+#[test]
+fn udp_datagram_with_back_to_back_messages() {
+    let mut first = header(0x1234, 1);
+    first.length = 8 + 3;
+    let mut second = header(0x5678, 2);
+    second.length = 8 + 2;
+    let mut datagram = encode_someip_message(&first, &[0x01, 0x02, 0x03]).unwrap();
+    datagram.extend(encode_someip_message(&second, &[0x04, 0x05]).unwrap());
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(
+            UNIX_EPOCH,
+            &PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .udp(30501, 30502)
+                .payload(datagram),
+        )
+        .build();
+    let output = run(
+        &["-p", "-", "--sd-port", "30502", "--output-format", "json"],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let messages = messages.as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["service"], "0x1234");
+    assert_eq!(messages[0]["payload"], "010203");
+    assert_eq!(messages[1]["service"], "0x5678");
+    assert_eq!(messages[1]["payload"], "0405");
+}
+
+#[test]
+fn udp_datagram_with_two_notifications_emits_both() {
+    let mut datagram = Vec::new();
+    for session_id in [1, 2] {
+        let mut notification = header(0x1234, session_id);
+        notification.length = 8 + 1;
+        datagram.extend(encode_someip_message(&notification, &[session_id as u8]).unwrap());
+    }
+    // 末尾不足一个头部的字节不是消息，也不算解析失败
+    datagram.extend([0xEE; 4]);
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(
+            UNIX_EPOCH,
+            &PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .udp(30501, 30502)
+                .payload(datagram),
+        )
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--strict",
+            "--output-format",
+            "json",
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let notifications: Vec<_> = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|msg| {
+            (
+                msg["message_type"].clone(),
+                msg["raw"]["header"]["session_id"].clone(),
+                msg["payload"].clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        notifications,
+        [
+            ("NOTIFICATION".into(), 1.into(), "01".into()),
+            ("NOTIFICATION".into(), 2.into(), "02".into())
+        ]
+    );
+}
+
+#[test]
+fn service_id_filter_skips_before_session_tracking() {
+    // 0x5678 的请求没有响应，若进入会话表会在结束时作为超时请求输出
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(
+            UNIX_EPOCH,
+            &PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .udp(30501, 30502)
+                .someip_header(0x5678, 0x0001, 0, 1, MessageType::Request)
+                .payload([0x01]),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(1),
+            &PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .udp(30501, 30502)
+                .someip_header(0x1234, 0x8001, 0, 2, MessageType::Notification)
+                .payload([0x02]),
+        )
+        .build();
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "--service-id",
+            "0x1234",
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let messages = messages.as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["service"], "0x1234");
+}
+
+#[test]
+fn summary_counts_messages_sessions_and_methods() {
+    let packet = |service_id, method_id, session_id, message_type| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(service_id, method_id, 0, session_id, message_type)
+            .payload([0x00])
+    };
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH, &packet(0x1234, 0x0001, 1, MessageType::Request))
+        .packet(
+            UNIX_EPOCH + Duration::from_millis(500),
+            &packet(0x1234, 0x0001, 1, MessageType::Response),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(1),
+            &packet(0x1234, 0x0001, 2, MessageType::Request),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(2),
+            &packet(0x5678, 0x8001, 3, MessageType::Notification),
+        )
+        .build();
+    let dir = tempfile::tempdir().unwrap();
+    let output_file = dir.path().join("messages.json");
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "-o",
+            output_file.to_str().unwrap(),
+            // --stats 是 --summary 的别名
+            "--stats",
+        ],
+        &capture,
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(summary["total_packets"], 4);
+    assert_eq!(summary["messages_by_type"]["REQUEST"], 2);
+    assert_eq!(summary["messages_by_type"]["RESPONSE"], 1);
+    assert_eq!(summary["messages_by_type"]["NOTIFICATION"], 1);
+    assert_eq!(summary["unmatched_requests"], 1);
+    assert_eq!(summary["timed_out_sessions"], 0);
+    assert_eq!(summary["tp_reassemblies"], 0);
+    assert_eq!(summary["tcp_streams"], 0);
+    let methods = summary["methods"].as_array().unwrap();
+    assert_eq!(methods.len(), 2);
+    assert_eq!(methods[0]["service_id"], 0x1234);
+    assert_eq!(methods[0]["method_id"], 0x0001);
+    assert_eq!(methods[0]["count"], 3);
+    assert_eq!(methods[1]["count"], 1);
+    assert_eq!(summary["services"]["4660"]["request_count"], 2);
+}
+
+#[test]
+fn summary_keeps_json_stdout_a_single_document() {
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "--summary",
+        ],
+        &notifications(),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(messages.as_array().unwrap().len(), 2);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("\"total_packets\": 2"), "{}", stderr);
+}
+
+#[test]
+fn subscription_expires_at_end_of_quiet_capture() {
+    let sd = sd_packet(
+        1,
+        false,
+        true,
+        vec![subscribe(0x1234, 1, 0x10, 3, 0)],
+        vec![udp_endpoint([10, 0, 0, 1], 40000)],
+    );
+    // 订阅之后没有其他 SD 报文，只有一个非 SomeIP 数据包推进抓包时间
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(
+            UNIX_EPOCH,
+            &PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .udp(30490, 30490)
+                .payload(sd.to_bytes()),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(10),
+            &PacketBuilder::new()
+                .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+                .udp(5000, 5000)
+                .payload([0x00]),
+        )
+        .build();
+    let output = run(&["-p", "-", "--verbose"], &capture);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 subscribed, 0 acknowledged, 0 rejected, 0 stopped, 1 expired"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn validate_only_reports_unparseable_service_id() {
+    let dir = tempfile::tempdir().unwrap();
+    let matrix = dir.path().join("matrix.json");
+    std::fs::write(
+        &matrix,
+        r#"{"AR-PACKAGES": [{"SHORT-NAME": "Services", "ELEMENTS": [
+            {"SHORT-NAME": "Vehicle", "SOMEIP-SERVICE-INTERFACE": {
+                "SHORT-NAME": "Vehicle", "SOMEIP-SERVICE-ID": "0x1234",
+                "SOMEIP-EVENTS": [{"SHORT-NAME": "SpeedChanged", "SOMEIP-EVENT-ID": "0x8001"}]}},
+            {"SHORT-NAME": "Broken", "SOMEIP-SERVICE-INTERFACE": {
+                "SHORT-NAME": "Broken", "SOMEIP-SERVICE-ID": "0x12G4"}}
+        ]}]}"#,
+    )
+    .unwrap();
+
+    let output = run(&["--validate-only", "-m", matrix.to_str().unwrap()], &[]);
+    // 存在无法解析的 ID 时以失败退出，但仍输出统计
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Services: 1\n"), "{stdout}");
+    assert!(stdout.contains("Events: 1\n"), "{stdout}");
+    assert!(
+        stdout.contains("Invalid ID:") && stdout.contains("'0x12G4'"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn deduplicate_drops_mirrored_notification() {
+    let packet = |session_id| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, 0x8001, 0, session_id, MessageType::Notification)
+            .payload([0xAB])
+    };
+    // 会话 1 被镜像端口重复抓到，会话 2 只出现一次
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(UNIX_EPOCH + Duration::from_secs(1), &packet(1))
+        .packet(UNIX_EPOCH + Duration::from_millis(1001), &packet(1))
+        .packet(UNIX_EPOCH + Duration::from_secs(2), &packet(2))
+        .build();
+    let sessions = |extra: &[&str]| {
+        let mut args = vec!["-p", "-", "--sd-port", "30502", "--output-format", "json"];
+        args.extend_from_slice(extra);
+        let output = run(&args, &capture);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let messages: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+        messages
+            .iter()
+            .map(|msg| msg["raw"]["header"]["session_id"].as_u64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sessions(&[]), [1, 1, 2]);
+    assert_eq!(sessions(&["--deduplicate"]), [1, 2]);
+
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--deduplicate",
+            "--summary",
+        ],
+        &capture,
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Duplicates dropped: 1\n"), "{stderr}");
+}
+
+#[test]
+fn endpoint_filters_select_by_address_and_port() {
+    let packet = |src: [u8; 4], src_port| {
+        PacketBuilder::new()
+            .ipv4(src, [10, 0, 0, 2])
+            .udp(src_port, 30502)
+            .someip_header(0x1234, 0x8001, 0, 1, MessageType::Notification)
+            .payload([0xAB])
+    };
+    let capture = PcapFileBuilder::new(LinkType::Ethernet)
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(1),
+            &packet([10, 0, 0, 1], 30501),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(2),
+            &packet([10, 0, 0, 7], 30501),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(3),
+            &packet([10, 0, 1, 1], 30501),
+        )
+        .packet(
+            UNIX_EPOCH + Duration::from_secs(4),
+            &packet([10, 0, 0, 1], 30509),
+        )
+        .build();
+    let senders = |extra: &[&str]| {
+        let mut args = vec!["-p", "-", "--sd-port", "30502", "--output-format", "json"];
+        args.extend_from_slice(extra);
+        let output = run(&args, &capture);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let messages: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+        messages
+            .iter()
+            .map(|msg| {
+                format!(
+                    "{}:{}",
+                    msg["sender"].as_str().unwrap(),
+                    msg["raw"]["src_port"]
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(senders(&[]).len(), 4);
+    assert_eq!(senders(&["--src-ip", "10.0.0.7"]), ["10.0.0.7:30501"]);
+    assert_eq!(
+        senders(&["--src-ip", "10.0.0.0/24"]),
+        ["10.0.0.1:30501", "10.0.0.7:30501", "10.0.0.1:30509"]
+    );
+    // 源端口不匹配的报文被丢弃
+    assert_eq!(
+        senders(&["--src-ip", "10.0.0.1", "--src-port", "30509"]),
+        ["10.0.0.1:30509"]
+    );
+    assert!(senders(&["--dst-port", "30501"]).is_empty());
+}
+
+#[test]
+fn time_window_keeps_packets_from_start_until_end() {
+    let packet = |session_id| {
+        PacketBuilder::new()
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2])
+            .udp(30501, 30502)
+            .someip_header(0x1234, 0x8001, 0, session_id, MessageType::Notification)
+            .payload([0xAB])
+    };
+    let mut capture = PcapFileBuilder::new(LinkType::Ethernet);
+    for second in 1..=5 {
+        capture = capture.packet(
+            UNIX_EPOCH + Duration::from_secs(second),
+            &packet(second as u16),
+        );
+    }
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "--start-time",
+            "2",
+            "--end-time",
+            "1970-01-01T00:00:04Z",
+        ],
+        &capture.build(),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let messages: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let sessions: Vec<_> = messages
+        .iter()
+        .map(|msg| msg["raw"]["header"]["session_id"].as_u64().unwrap())
+        .collect();
+    // 1 早于窗口，2 位于起始边界（包含），4 位于结束边界（不包含），5 晚于窗口
+    assert_eq!(sessions, [2, 3]);
+}
+
+#[test]
+fn manifest_lists_every_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    let (messages, pcap, stats, manifest) = (
+        path("messages.json"),
+        path("messages.pcap"),
+        path("stats.json"),
+        path("manifest.json"),
+    );
+    let output = run(
+        &[
+            "-p",
+            "-",
+            "--sd-port",
+            "30502",
+            "--output-format",
+            "json",
+            "-o",
+            &messages,
+            "--write-pcap",
+            &pcap,
+            "--stats-file",
+            &stats,
+            "--manifest",
+            &manifest,
+        ],
+        &notifications(),
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&manifest).unwrap()).unwrap();
+    let mut files: Vec<_> = manifest["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| {
+            let path = file["path"].as_str().unwrap().to_string();
+            // 清单中的大小与磁盘上的文件一致
+            assert_eq!(
+                file["bytes"].as_u64().unwrap(),
+                std::fs::metadata(&path).unwrap().len()
+            );
+            (
+                path,
+                file["format"].as_str().unwrap().to_string(),
+                file["records"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            (messages, "json".to_string(), 2),
+            (pcap, "pcap".to_string(), 2),
+            (stats, "json".to_string(), 1),
+        ]
+    );
+}